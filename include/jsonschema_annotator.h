@@ -0,0 +1,39 @@
+/* C interface of jsonschema-annotator, built with the `ffi` feature */
+#ifndef JSONSCHEMA_ANNOTATOR_H
+#define JSONSCHEMA_ANNOTATOR_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The document was annotated */
+#define JSONSCHEMA_ANNOTATOR_OK 0
+/* An argument was null, not UTF-8 or named an unknown format */
+#define JSONSCHEMA_ANNOTATOR_INVALID_ARGUMENT 1
+/* The schema isn't valid JSON or not a usable schema */
+#define JSONSCHEMA_ANNOTATOR_INVALID_SCHEMA 2
+/* The target document couldn't be parsed */
+#define JSONSCHEMA_ANNOTATOR_PARSE_ERROR 3
+/* Annotation failed for another reason, including a panic */
+#define JSONSCHEMA_ANNOTATOR_ERROR 4
+
+/*
+ * Annotate `target`, a document in `format` ("toml" or "yaml"), with the
+ * comments of the JSON Schema in `schema_json`.
+ *
+ * On success `*out` receives the annotated document and
+ * JSONSCHEMA_ANNOTATOR_OK is returned. Otherwise an error code is returned and
+ * `*out` receives an error message (or NULL). Free `*out` with
+ * jsonschema_annotator_free_string.
+ */
+int jsonschema_annotator_annotate(const char *schema_json, const char *target,
+                                  const char *format, char **out);
+
+/* Free a string returned by jsonschema_annotator_annotate */
+void jsonschema_annotator_free_string(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* JSONSCHEMA_ANNOTATOR_H */
@@ -0,0 +1,69 @@
+/// Check whether a dot-separated path matches a glob pattern
+///
+/// Patterns are matched segment by segment: `*` matches any run of characters
+/// within a single segment (e.g., `server.*` or `log_*`), `?` matches a single
+/// character, and a `**` segment matches zero or more whole segments
+/// (e.g., `server.**` matches everything below `server`).
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('.').collect();
+    let path: Vec<&str> = if path.is_empty() {
+        Vec::new()
+    } else {
+        path.split('.').collect()
+    };
+    segments_match(&pattern, &path)
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((segment_pattern, rest)) => match path.split_first() {
+            Some((segment, path_rest)) => {
+                wildcard_match(segment_pattern.as_bytes(), segment.as_bytes())
+                    && segments_match(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| wildcard_match(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && wildcard_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && wildcard_match(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_match() {
+        assert!(path_matches("server.port", "server.port"));
+        assert!(!path_matches("server.port", "server.host"));
+        assert!(!path_matches("server", "server.port"));
+    }
+
+    #[test]
+    fn test_single_segment_wildcard() {
+        assert!(path_matches("server.*", "server.port"));
+        assert!(!path_matches("server.*", "server"));
+        assert!(!path_matches("server.*", "server.tls.cert"));
+        assert!(path_matches("*.port", "server.port"));
+        assert!(path_matches("log_*", "log_level"));
+        assert!(path_matches("por?", "port"));
+    }
+
+    #[test]
+    fn test_recursive_wildcard() {
+        assert!(path_matches("server.**", "server.port"));
+        assert!(path_matches("server.**", "server.tls.cert"));
+        assert!(path_matches("server.**", "server"));
+        assert!(path_matches("**.port", "database.connection.port"));
+        assert!(!path_matches("**.port", "database.connection.host"));
+    }
+}
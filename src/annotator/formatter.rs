@@ -0,0 +1,470 @@
+use super::literal::{render_key, render_literal};
+use super::text::{markdown_to_plain, strip_html, wrap_line, wrap_text};
+use super::{AnnotatorConfig, SectionStyle};
+use crate::format::TargetFormat;
+use crate::schema::Annotation;
+
+/// Context passed to a [`CommentFormatter`] for a single annotated field
+#[derive(Debug, Clone, Copy)]
+pub struct CommentContext<'a> {
+    /// Dot-separated path of the field being annotated (e.g., "server.port")
+    pub path: &'a str,
+    /// Indentation of the field in the target document, in columns
+    pub indent: usize,
+    /// Whether the field is a section (TOML table, YAML mapping) rather than a leaf value
+    pub is_section: bool,
+    /// Whether the field holds an empty map or, in YAML, no value at all
+    pub is_empty: bool,
+    /// Format of the target document
+    pub format: TargetFormat,
+    /// Configuration of the annotator doing the rendering
+    pub config: &'a AnnotatorConfig,
+}
+
+/// Renders an [`Annotation`] into comment lines
+///
+/// Implement this to fully customize how annotations appear in the output.
+/// Returned lines must include the comment marker (e.g., `# `) but not the
+/// indentation, which is applied by the annotator. Returning no lines leaves
+/// the field unannotated.
+pub trait CommentFormatter: std::fmt::Debug + Send + Sync {
+    /// Format an annotation as comment lines
+    fn format(&self, annotation: &Annotation, context: &CommentContext<'_>) -> Vec<String>;
+}
+
+/// The built-in formatter: title, description, array item and content hints
+/// (all wrapped to the maximum line width), secret note, version introduced,
+/// default value, environment variable, then examples and the example entry
+/// of an empty map
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultCommentFormatter;
+
+impl CommentFormatter for DefaultCommentFormatter {
+    fn format(&self, annotation: &Annotation, context: &CommentContext<'_>) -> Vec<String> {
+        let config = context.config;
+        let mut lines = Vec::new();
+        let width = match config.max_line_width {
+            Some(width) => width.saturating_sub(context.indent + 2),
+            None => usize::MAX,
+        };
+        let push_wrapped = |lines: &mut Vec<String>, text: &str| {
+            lines.extend(wrap_line(text, width).into_iter().map(|line| format!("# {}", line)));
+        };
+
+        if config.include_title {
+            if let Some(title) = &annotation.title {
+                let key = context.path.rsplit('.').next().unwrap_or_default();
+                if !(config.deduplicate && is_key_restatement(title, key)) {
+                    match config.section_style {
+                        SectionStyle::Plain => push_wrapped(&mut lines, title),
+                        _ if !context.is_section => push_wrapped(&mut lines, title),
+                        style => lines.push(banner(title, style, context)),
+                    }
+                }
+            }
+        }
+
+        if config.include_description {
+            if let Some(desc) = &annotation.description {
+                let mut desc = desc.clone();
+                if config.strip_html {
+                    desc = strip_html(&desc);
+                }
+                if config.convert_markdown {
+                    desc = markdown_to_plain(&desc);
+                }
+                for line in wrap_text(&desc, width) {
+                    lines.push(format!("# {}", line).trim_end().to_string());
+                }
+            }
+            if let Some(items) = &annotation.items {
+                push_wrapped(&mut lines, items);
+            }
+            if let Some(hint) = annotation.content_hint() {
+                push_wrapped(&mut lines, &hint);
+            }
+        }
+
+        if config.mark_secrets && annotation.secret {
+            lines.push("# SECRET — do not commit real values".to_string());
+        }
+        let redacted = config.redact_secrets && annotation.secret;
+
+        let since = config.since_keyword.as_ref().and_then(|keyword| annotation.extensions.get(keyword));
+        match since {
+            Some(serde_json::Value::String(version)) => lines.push(format!("# Since: {}", version)),
+            Some(version @ serde_json::Value::Number(_)) => lines.push(format!("# Since: {}", version)),
+            _ => {}
+        }
+
+        if config.include_default && !redacted {
+            let default = annotation
+                .default_value
+                .as_ref()
+                .and_then(|value| {
+                    render_literal(value, context.format, Some(annotation))
+                })
+                .or_else(|| annotation.default.clone());
+            if let Some(default) = default {
+                lines.push(format!("# Default: {}", default));
+            }
+        }
+
+        if let Some(env_vars) = &config.env_vars {
+            if !context.is_section {
+                lines.push(format!("# Env: {}", env_vars.var_name(context.path)));
+            }
+        }
+
+        if config.include_examples && !context.is_section && !redacted {
+            let key = context.path.rsplit('.').next().unwrap_or_default();
+            for example in &annotation.examples {
+                let (Some(key), Some(value)) = (
+                    render_key(key, context.format),
+                    render_literal(example, context.format, Some(annotation)),
+                ) else {
+                    continue;
+                };
+                match context.format {
+                    TargetFormat::Toml => lines.push(format!("# {} = {}", key, value)),
+                    _ => lines.push(format!("# {}: {}", key, value)),
+                }
+            }
+        }
+
+        if config.example_entries && context.is_empty && !redacted {
+            let entry = annotation.example_entry.as_ref().and_then(|(key, value)| {
+                Some((render_key(key, context.format)?, render_literal(value, context.format, None)?))
+            });
+            match (entry, context.format) {
+                (Some((key, value)), TargetFormat::Toml) => {
+                    lines.push(format!("# Example entry: {} = {}", key, value));
+                }
+                (Some((key, value)), _) => lines.push(format!("# Example entry: {}: {}", key, value)),
+                (None, _) => {}
+            }
+        }
+
+        lines
+    }
+}
+
+/// Render a section title as a banner line spanning the available width
+fn banner(title: &str, style: SectionStyle, context: &CommentContext<'_>) -> String {
+    let width = context
+        .config
+        .max_line_width
+        .unwrap_or(78)
+        .saturating_sub(context.indent);
+    let label = format!(" {} ", title);
+    match style {
+        SectionStyle::Rule => {
+            let fill = width.saturating_sub(label.chars().count() + 2);
+            let left = fill / 2;
+            format!("# {}{}{}", "─".repeat(left.max(3)), label, "─".repeat((fill - left).max(3)))
+        }
+        _ => {
+            let fill = width.saturating_sub(label.chars().count());
+            let left = fill / 2;
+            format!("{}{}{}", "#".repeat(left.max(3)), label, "#".repeat((fill - left).max(3)))
+        }
+    }
+}
+
+/// Check whether a title is just a case/separator variation of the key name
+/// (e.g., key `max_connections`, title "Max Connections")
+fn is_key_restatement(title: &str, key: &str) -> bool {
+    let normalize = |s: &str| -> String {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    normalize(title) == normalize(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotator::EnvVarStyle;
+
+    fn context(config: &AnnotatorConfig, indent: usize) -> CommentContext<'_> {
+        CommentContext {
+            path: "test",
+            indent,
+            is_section: false,
+            is_empty: false,
+            format: TargetFormat::Yaml,
+            config,
+        }
+    }
+
+    #[test]
+    fn test_default_formatter() {
+        let config = AnnotatorConfig {
+            include_default: true,
+            ..Default::default()
+        };
+        let ann = Annotation::new("test")
+            .with_title("Title")
+            .with_description("Description")
+            .with_default("42");
+
+        let lines = DefaultCommentFormatter.format(&ann, &context(&config, 0));
+        assert_eq!(lines, vec!["# Title", "# Description", "# Default: 42"]);
+    }
+
+    #[test]
+    fn test_default_formatter_dedupes_key_titles() {
+        let config = AnnotatorConfig {
+            deduplicate: true,
+            ..Default::default()
+        };
+        let restated = Annotation::new("max_connections")
+            .with_title("Max Connections")
+            .with_description("Upper bound on open connections");
+        let distinct = Annotation::new("port").with_title("Listen Port");
+
+        let mut ctx = context(&config, 0);
+        ctx.path = "pool.max_connections";
+        assert_eq!(
+            DefaultCommentFormatter.format(&restated, &ctx),
+            vec!["# Upper bound on open connections"]
+        );
+        ctx.path = "port";
+        assert_eq!(DefaultCommentFormatter.format(&distinct, &ctx), vec!["# Listen Port"]);
+    }
+
+    #[test]
+    fn test_default_formatter_env_vars() {
+        let config = AnnotatorConfig {
+            env_vars: Some(EnvVarStyle::new("APP", "__")),
+            ..Default::default()
+        };
+        let ann = Annotation::new("server.max-connections").with_title("Limit");
+
+        let mut ctx = context(&config, 0);
+        ctx.path = "server.max-connections";
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &ctx),
+            vec!["# Limit", "# Env: APP__SERVER__MAX_CONNECTIONS"]
+        );
+        // Sections can't be set from a single variable
+        ctx.is_section = true;
+        assert_eq!(DefaultCommentFormatter.format(&ann, &ctx), vec!["# Limit"]);
+
+        assert_eq!(EnvVarStyle::new("", "_").var_name("server.port"), "SERVER_PORT");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_default_formatter_examples() {
+        let config = AnnotatorConfig {
+            include_examples: true,
+            ..Default::default()
+        };
+        let ann = Annotation::new("server.host")
+            .with_title("Host")
+            .with_examples([serde_json::json!("0.0.0.0"), serde_json::json!(null)]);
+
+        let mut ctx = context(&config, 0);
+        ctx.path = "server.host";
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &ctx),
+            vec!["# Host", "# host: 0.0.0.0", "# host: null"]
+        );
+        // TOML has no null
+        ctx.format = TargetFormat::Toml;
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &ctx),
+            vec!["# Host", "# host = \"0.0.0.0\""]
+        );
+
+        let ann = Annotation::new("log level").with_examples([serde_json::json!(["a", 1])]);
+        ctx.path = "log level";
+        assert_eq!(DefaultCommentFormatter.format(&ann, &ctx), vec!["# \"log level\" = [\"a\", 1]"]);
+        ctx.format = TargetFormat::Yaml;
+        assert_eq!(DefaultCommentFormatter.format(&ann, &ctx), vec!["# log level: [a, 1]"]);
+    }
+
+    #[test]
+    fn test_default_formatter_secrets() {
+        let ann = Annotation::new("test")
+            .with_title("Token")
+            .with_default("\"dev\"")
+            .with_examples([serde_json::json!("abc")])
+            .with_secret(true);
+        let marked = AnnotatorConfig {
+            include_default: true,
+            include_examples: true,
+            mark_secrets: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &context(&marked, 0)),
+            vec!["# Token", "# SECRET — do not commit real values", "# Default: \"dev\"", "# test: abc"]
+        );
+
+        let redacted = AnnotatorConfig {
+            redact_secrets: true,
+            ..marked
+        };
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &context(&redacted, 0)),
+            vec!["# Token", "# SECRET — do not commit real values"]
+        );
+    }
+
+    #[test]
+    fn test_default_formatter_since() {
+        let ann = Annotation::new("test")
+            .with_title("Retries")
+            .with_extension("x-since", serde_json::json!("v2.3"))
+            .with_extension("x-added-in", serde_json::json!(4));
+
+        let config = AnnotatorConfig::default();
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &context(&config, 0)),
+            vec!["# Retries", "# Since: v2.3"]
+        );
+
+        let config = AnnotatorConfig {
+            since_keyword: Some("x-added-in".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &context(&config, 0)),
+            vec!["# Retries", "# Since: 4"]
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_default_formatter_native_defaults() {
+        let config = AnnotatorConfig {
+            include_default: true,
+            ..Default::default()
+        };
+        let ann = Annotation::new("started")
+            .with_default_value(serde_json::json!("1979-05-27T07:32:00Z"))
+            .with_format("date-time");
+
+        let mut ctx = context(&config, 0);
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &ctx),
+            vec!["# Default: 1979-05-27T07:32:00Z"]
+        );
+        ctx.format = TargetFormat::Toml;
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &ctx),
+            vec!["# Default: 1979-05-27T07:32:00Z"]
+        );
+
+        let ann = Annotation::new("tags").with_default_value(serde_json::json!(["web", "yes"]));
+        assert_eq!(DefaultCommentFormatter.format(&ann, &ctx), vec!["# Default: [\"web\", \"yes\"]"]);
+        ctx.format = TargetFormat::Yaml;
+        assert_eq!(DefaultCommentFormatter.format(&ann, &ctx), vec!["# Default: [web, \"yes\"]"]);
+    }
+
+    #[test]
+    fn test_default_formatter_section_banners() {
+        let ann = Annotation::new("server")
+            .with_title("Server")
+            .with_description("HTTP settings");
+
+        let config = AnnotatorConfig {
+            section_style: SectionStyle::Rule,
+            max_line_width: Some(30),
+            ..Default::default()
+        };
+        let mut ctx = context(&config, 0);
+        ctx.is_section = true;
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &ctx),
+            vec!["# ────────── Server ──────────", "# HTTP settings"]
+        );
+
+        let config = AnnotatorConfig {
+            section_style: SectionStyle::Hash,
+            max_line_width: Some(30),
+            ..Default::default()
+        };
+        let mut ctx = context(&config, 0);
+        ctx.is_section = true;
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &ctx)[0],
+            "########### Server ###########"
+        );
+
+        // Leaf values keep the plain style
+        ctx.is_section = false;
+        assert_eq!(DefaultCommentFormatter.format(&ann, &ctx)[0], "# Server");
+    }
+
+    #[test]
+    fn test_default_formatter_converts_markdown() {
+        let config = AnnotatorConfig {
+            include_title: false,
+            convert_markdown: true,
+            ..Default::default()
+        };
+        let ann = Annotation::new("test")
+            .with_description("Use `https` for [TLS](https://example.com/tls).\n\n- fast\n- safe");
+
+        let lines = DefaultCommentFormatter.format(&ann, &context(&config, 0));
+        assert_eq!(
+            lines,
+            vec![
+                "# Use https for TLS (https://example.com/tls).",
+                "#",
+                "# - fast",
+                "# - safe"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_formatter_wraps_within_indent() {
+        let config = AnnotatorConfig {
+            max_line_width: Some(30),
+            ..Default::default()
+        };
+        let ann = Annotation::new("test")
+            .with_description("This is a very long description that should be wrapped");
+
+        let lines = DefaultCommentFormatter.format(&ann, &context(&config, 4));
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(line.len() + 4 <= 30);
+        }
+
+        let config = AnnotatorConfig {
+            max_line_width: None,
+            ..Default::default()
+        };
+        let lines = DefaultCommentFormatter.format(&ann, &context(&config, 4));
+        assert_eq!(lines, vec!["# This is a very long description that should be wrapped"]);
+    }
+
+    #[test]
+    fn test_default_formatter_wraps_titles() {
+        let config = AnnotatorConfig {
+            max_line_width: Some(30),
+            ..Default::default()
+        };
+        let ann = Annotation::new("test")
+            .with_title("Maximum number of concurrent connections per worker")
+            .with_items("Items: one of \"fast\", \"balanced\", \"thorough\"");
+
+        assert_eq!(
+            DefaultCommentFormatter.format(&ann, &context(&config, 2)),
+            vec![
+                "# Maximum number of",
+                "#   concurrent connections",
+                "#   per worker",
+                "# Items: one of \"fast\",",
+                "#   \"balanced\", \"thorough\"",
+            ]
+        );
+    }
+}
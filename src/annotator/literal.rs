@@ -0,0 +1,217 @@
+use serde_json::Value;
+
+use crate::format::TargetFormat;
+use crate::schema::Annotation;
+
+/// Render a value the way it would be written in a document of the given
+/// format, or `None` if the format can't express it (TOML has no null or
+/// integers beyond 64 bits) or isn't a built-in one
+///
+/// `annotation` is that of the value's schema, if any. TOML writes strings in
+/// the `date`, `date-time` and `time` formats as bare date-times, the
+/// `Infinity` and `NaN` strings of `number` values as `inf` and `nan`, and
+/// whole numbers of `integer` values as integers.
+#[cfg_attr(not(feature = "toml"), allow(unused_variables))]
+pub(crate) fn render_literal(
+    value: &Value,
+    format: TargetFormat,
+    annotation: Option<&Annotation>,
+) -> Option<String> {
+    match format {
+        #[cfg(feature = "toml")]
+        TargetFormat::Toml => toml_literal(value, annotation),
+        TargetFormat::Yaml => Some(yaml_literal(value, false)),
+        _ => None,
+    }
+}
+
+/// Render a key for an assignment, quoting it if it can't be written bare
+pub(crate) fn render_key(key: &str, format: TargetFormat) -> Option<String> {
+    match format {
+        #[cfg(feature = "toml")]
+        TargetFormat::Toml => Some(toml_edit::Key::new(key).to_string()),
+        TargetFormat::Yaml => Some(yaml_string(key, false)),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "toml")]
+fn toml_literal(value: &Value, annotation: Option<&Annotation>) -> Option<String> {
+    let schema_format = annotation.and_then(|annotation| annotation.format.as_deref());
+    let has_type = |name: &str| annotation.is_some_and(|annotation| annotation.types.iter().any(|t| t == name));
+    match value {
+        Value::String(s) if matches!(schema_format, Some("date" | "date-time" | "time")) => {
+            if let Ok(datetime) = s.parse::<toml_edit::Datetime>() {
+                return Some(datetime.to_string());
+            }
+        }
+        // JSON has no infinite or NaN numbers, so schemas spell them as strings
+        Value::String(s) if has_type("number") => {
+            if let Some(float) = non_finite(s) {
+                return Some(float.to_string());
+            }
+        }
+        Value::Number(n) => return toml_number(n, has_type("integer") && !has_type("number")),
+        _ => {}
+    }
+    let value = crate::generate::toml_value(value)?;
+    Some(value.to_string().trim().to_string())
+}
+
+/// Write a number as a TOML integer or float
+///
+/// Floats keep a fractional part or exponent, so they read back as floats,
+/// and large or small ones are written with an exponent. Whole floats are
+/// written as integers when `integer` is set.
+#[cfg(feature = "toml")]
+fn toml_number(n: &serde_json::Number, integer: bool) -> Option<String> {
+    if let Some(i) = n.as_i64() {
+        return Some(i.to_string());
+    }
+    if n.is_u64() {
+        return None;
+    }
+    let float = n.as_f64()?;
+    if integer && float.fract() == 0.0 && float.abs() < i64::MAX as f64 {
+        return Some((float as i64).to_string());
+    }
+    // Debug formatting switches to an exponent for large and small magnitudes
+    Some(format!("{:?}", float))
+}
+
+/// The TOML spelling of a non-finite float written as a string, such as the
+/// `Infinity` and `NaN` of JSON encoders or YAML's `.inf`
+#[cfg(feature = "toml")]
+fn non_finite(s: &str) -> Option<&'static str> {
+    match s.strip_prefix('+').unwrap_or(s).to_ascii_lowercase().as_str() {
+        "inf" | "infinity" | ".inf" => Some("inf"),
+        "-inf" | "-infinity" | "-.inf" => Some("-inf"),
+        "nan" | ".nan" => Some("nan"),
+        _ => None,
+    }
+}
+
+/// Render a value as a YAML scalar or flow collection
+fn yaml_literal(value: &Value, flow: bool) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => yaml_string(s, flow),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(|v| yaml_literal(v, true)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let entries: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{}: {}", yaml_string(k, true), yaml_literal(v, true)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+    }
+}
+
+/// Write a string plain when YAML would read it back as the same string,
+/// double-quoted otherwise
+fn yaml_string(s: &str, flow: bool) -> String {
+    if is_plain_yaml_string(s, flow) {
+        s.to_string()
+    } else {
+        // JSON strings are valid double-quoted YAML scalars
+        serde_json::to_string(s).unwrap_or_default()
+    }
+}
+
+fn is_plain_yaml_string(s: &str, flow: bool) -> bool {
+    let Some(first) = s.chars().next() else {
+        return false;
+    };
+    if s.trim() != s
+        || "-?:,[]{}#&*!|>'\"%@`".contains(first)
+        || s.contains(": ")
+        || s.contains(" #")
+        || s.ends_with(':')
+        || s.chars().any(char::is_control)
+        || (flow && s.contains([',', '[', ']', '{', '}']))
+    {
+        return false;
+    }
+    // Words YAML 1.1 or 1.2 parsers resolve to null, booleans or numbers
+    let lower = s.to_lowercase();
+    let keyword = matches!(
+        lower.as_str(),
+        "null" | "~" | "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n"
+            | ".inf" | "+.inf" | "-.inf" | ".nan"
+    );
+    let number = s.parse::<f64>().is_ok()
+        || lower.starts_with("0x")
+        || lower.starts_with("0o")
+        || lower.starts_with("+.")
+        || lower.starts_with("-.");
+    !keyword && !number
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_yaml_literals() {
+        let yaml = |value: Value| render_literal(&value, TargetFormat::Yaml, None).unwrap();
+        assert_eq!(yaml(json!("localhost")), "localhost");
+        assert_eq!(yaml(json!("0.0.0.0")), "0.0.0.0");
+        assert_eq!(yaml(json!("8080")), "\"8080\"");
+        assert_eq!(yaml(json!("yes")), "\"yes\"");
+        assert_eq!(yaml(json!("")), "\"\"");
+        assert_eq!(yaml(json!("a: b")), "\"a: b\"");
+        assert_eq!(yaml(json!("*.log")), "\"*.log\"");
+        assert_eq!(yaml(json!(["web", "a,b", 1])), "[web, \"a,b\", 1]");
+        assert_eq!(yaml(json!({"level": "info", "on": true})), "{level: info, \"on\": true}");
+        assert_eq!(yaml(json!(null)), "null");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_literals() {
+        let toml = |value: Value, format: Option<&str>| {
+            let annotation = format.map(|format| Annotation::new("value").with_format(format));
+            render_literal(&value, TargetFormat::Toml, annotation.as_ref())
+        };
+        assert_eq!(toml(json!("localhost"), None).unwrap(), "\"localhost\"");
+        assert_eq!(toml(json!(["web", 1]), None).unwrap(), "[\"web\", 1]");
+        assert_eq!(toml(json!({"a": 1}), None).unwrap(), "{ a = 1 }");
+        assert_eq!(toml(json!("1979-05-27"), Some("date")).unwrap(), "1979-05-27");
+        assert_eq!(toml(json!("1979-05-27"), None).unwrap(), "\"1979-05-27\"");
+        assert_eq!(toml(json!("soon"), Some("date")).unwrap(), "\"soon\"");
+        assert_eq!(toml(json!("1979-05-27t07:32:00z"), Some("date-time")).unwrap(), "1979-05-27T07:32:00Z");
+        assert_eq!(
+            toml(json!("1979-05-27T00:32:00.999999-07:00"), Some("date-time")).unwrap(),
+            "1979-05-27T00:32:00.999999-07:00"
+        );
+        assert_eq!(toml(json!(null), None), None);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_numbers() {
+        let toml = |value: Value, types: &[&str]| {
+            let annotation = Annotation::new("value").with_types(types.iter().copied());
+            render_literal(&value, TargetFormat::Toml, Some(&annotation))
+        };
+        assert_eq!(toml(json!(8080), &["integer"]).unwrap(), "8080");
+        assert_eq!(toml(json!(-1), &[]).unwrap(), "-1");
+        assert_eq!(toml(json!(3.0), &["integer"]).unwrap(), "3");
+        assert_eq!(toml(json!(3.0), &["number"]).unwrap(), "3.0");
+        assert_eq!(toml(json!(0.1), &["number"]).unwrap(), "0.1");
+        assert_eq!(toml(json!(-0.0), &["number"]).unwrap(), "-0.0");
+        assert_eq!(toml(json!(1e300), &["number"]).unwrap(), "1e300");
+        assert_eq!(toml(json!(1.5e-10), &[]).unwrap(), "1.5e-10");
+        assert_eq!(toml(json!(u64::MAX), &["integer"]), None);
+        assert_eq!(toml(json!("Infinity"), &["number"]).unwrap(), "inf");
+        assert_eq!(toml(json!("-inf"), &["number", "null"]).unwrap(), "-inf");
+        assert_eq!(toml(json!("NaN"), &["number"]).unwrap(), "nan");
+        assert_eq!(toml(json!("NaN"), &["string"]).unwrap(), "\"NaN\"");
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Hashes of the comments generated for each path of a document, so
+/// generated comments can be told from hand-written ones without marker
+/// lines
+///
+/// Give the lock recorded for a document in
+/// [`AnnotatorConfig::lock`](super::AnnotatorConfig::lock) and
+/// [`ExistingCommentBehavior::Update`](super::ExistingCommentBehavior::Update)
+/// replaces the comment above a key while it's unchanged since it was
+/// generated, leaving hand-edited ones alone. Annotating in that mode
+/// records the new lock in [`AnnotationReport::lock`](super::AnnotationReport::lock).
+/// It serializes as an object from path to entry, for keeping in a lock
+/// file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AnnotationLock {
+    entries: BTreeMap<String, LockEntry>,
+}
+
+/// The comment generated for a path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockEntry {
+    /// Number of comment lines
+    pub lines: usize,
+    /// Hash of the lines, ignoring their indentation
+    pub hash: String,
+}
+
+impl AnnotationLock {
+    /// Create an empty lock
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the entry of a path
+    pub fn get(&self, path: &str) -> Option<&LockEntry> {
+        self.entries.get(path)
+    }
+
+    /// Record the comment lines generated for a path
+    pub fn record(&mut self, path: &str, comment: &[impl AsRef<str>]) {
+        let entry = LockEntry {
+            lines: comment.len(),
+            hash: hash_lines(comment),
+        };
+        self.entries.insert(path.to_string(), entry);
+    }
+
+    /// Iterate over the entries by path
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &LockEntry)> {
+        self.entries.iter()
+    }
+
+    /// Check if the lock has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of lines at the end of the comment block `above` a key that
+    /// are the comment generated for its path, unedited, if any
+    pub(crate) fn generated_lines(&self, path: &str, above: &[impl AsRef<str>]) -> Option<usize> {
+        let entry = self.entries.get(path)?;
+        let start = above.len().checked_sub(entry.lines)?;
+        let block = &above[start..];
+        let is_comment = |line: &str| line.trim_start().starts_with('#');
+        (block.iter().all(|line| is_comment(line.as_ref())) && hash_lines(block) == entry.hash)
+            .then_some(entry.lines)
+    }
+}
+
+/// 64-bit FNV-1a of the trimmed lines, stable across Rust versions unlike
+/// the standard library's hasher
+fn hash_lines(lines: &[impl AsRef<str>]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (i, line) in lines.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "\n" };
+        for byte in separator.bytes().chain(line.as_ref().trim().bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_lines() {
+        let mut lock = AnnotationLock::new();
+        lock.record("port", &["# Port", "# Listen port"]);
+
+        let above = ["# Hand-written", "  # Port", "  # Listen port"];
+        assert_eq!(lock.generated_lines("port", &above), Some(2));
+        assert_eq!(lock.generated_lines("port", &["# Port", "# Edited"]), None);
+        assert_eq!(lock.generated_lines("port", &["# Listen port"]), None);
+        assert_eq!(lock.generated_lines("host", &above), None);
+    }
+}
@@ -1,11 +1,39 @@
+mod filter;
+mod formatter;
+mod literal;
+mod lock;
+mod observer;
+mod options;
+mod text;
+#[cfg(feature = "toml")]
 mod toml;
+#[cfg(feature = "yaml")]
 mod yaml;
 
+pub use self::formatter::{CommentContext, CommentFormatter, DefaultCommentFormatter};
+pub use self::lock::{AnnotationLock, LockEntry};
+pub use self::observer::{AnnotationDecision, AnnotationEvent, AnnotationObserver};
+pub use self::options::{Include, ParseOptionError};
+pub(crate) use self::literal::render_literal;
+pub(crate) use self::observer::notify;
+pub(crate) use self::text::wrap_text;
+#[cfg(feature = "toml")]
 pub use self::toml::TomlAnnotator;
-pub use self::yaml::YamlAnnotator;
+#[cfg(feature = "toml")]
+pub(crate) use self::toml::{key_comments as toml_key_comments, key_line_map as toml_key_line_map};
+#[cfg(feature = "yaml")]
+pub use self::yaml::{YamlAnnotator, YamlDocument};
+#[cfg(feature = "yaml")]
+pub(crate) use self::yaml::{key_comments as yaml_key_comments, key_line_map as yaml_key_line_map};
 
-use crate::error::AnnotatorError;
-use crate::schema::AnnotationMap;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::error::{AnnotatorError, AnnotatorErrorKind, Error};
+use crate::schema::{Annotation, AnnotationMap};
 
 /// How to handle fields that already have comments
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -19,6 +47,190 @@ pub enum ExistingCommentBehavior {
     Append,
     /// Replace existing comment with annotation
     Replace,
+    /// Replace only comments previously generated in this mode, keeping
+    /// hand-written comments
+    ///
+    /// Generated blocks are introduced by a marker line (see
+    /// [`AnnotatorConfig::generated_marker`]) and run up to the key, so
+    /// re-running refreshes stale annotations in place. With a
+    /// [`lock`](AnnotatorConfig::lock) no markers are written, and the lines
+    /// above a key are replaced while they hash to what the lock recorded.
+    Update,
+}
+
+/// What to do about values that are the same as their schema default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RedundantDefaults {
+    /// Don't look for them
+    #[default]
+    Ignore,
+    /// List them in [`AnnotationReport::redundant_defaults`]
+    Report,
+    /// List them and add a `# (same as default)` comment to each
+    Mark,
+}
+
+/// Which occurrence of a key that appears more than once in the same YAML
+/// mapping is annotated
+///
+/// The policy applies to the repeated keys themselves; keys nested below each
+/// occurrence are annotated as usual. TOML documents can't repeat keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Annotate every occurrence
+    #[default]
+    All,
+    /// Annotate only the first occurrence
+    First,
+    /// Annotate only the last occurrence, the one YAML parsers that accept
+    /// duplicates keep
+    Last,
+    /// Fail with a parse error naming the key
+    Error,
+}
+
+/// Which kinds of nodes receive annotations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NodeFilter {
+    /// Annotate both sections and leaf values
+    #[default]
+    All,
+    /// Only annotate leaf values (keys holding a scalar or array)
+    LeavesOnly,
+    /// Only annotate sections (TOML tables, YAML mappings)
+    SectionsOnly,
+}
+
+impl NodeFilter {
+    /// Check whether a node of the given kind should be annotated
+    pub fn includes(&self, is_section: bool) -> bool {
+        match self {
+            NodeFilter::All => true,
+            NodeFilter::LeavesOnly => !is_section,
+            NodeFilter::SectionsOnly => is_section,
+        }
+    }
+}
+
+/// Spacing between generated comments and the hand-written comments they're
+/// added next to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommentSpacing {
+    /// Attach generated comments directly to existing ones
+    #[default]
+    Tight,
+    /// Leave a blank line between generated and existing comments
+    BlankLine,
+}
+
+/// How section (TOML table, YAML mapping) titles are rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SectionStyle {
+    /// Same as leaf comments: `# Server`
+    #[default]
+    Plain,
+    /// A horizontal rule banner: `# ───── Server ─────`
+    Rule,
+    /// A hash banner: `##### Server #####`
+    Hash,
+}
+
+/// How plain YAML keys are read when matching them with the schema's
+/// property names
+///
+/// Quoted keys are always strings. Plain keys that are booleans or integers
+/// in the chosen version are named by their value, as `true`, `false` or a
+/// decimal number, like the data an application loads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum YamlVersion {
+    /// YAML 1.1, where `yes`, `no`, `on`, `off`, `y` and `n` are booleans
+    /// too and integers may be written in binary, with a leading zero for
+    /// octal or with `_` separators
+    V1_1,
+    /// YAML 1.2 core schema: `true` and `false` are the only booleans, in
+    /// any case, and integers are decimal, `0o` octal or `0x` hexadecimal;
+    /// `on:` in a GitHub Actions workflow stays `on`
+    #[default]
+    V1_2,
+}
+
+/// Line ending used for inserted comments and the final output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Unix line endings (`\n`)
+    Lf,
+    /// Windows line endings (`\r\n`)
+    Crlf,
+    /// Use the line ending of the first line of the input
+    #[default]
+    Preserve,
+}
+
+impl LineEnding {
+    /// Resolve [`LineEnding::Preserve`] against the input document
+    pub fn resolve(self, input: &str) -> LineEnding {
+        match self {
+            LineEnding::Preserve => match input.find('\n') {
+                Some(pos) if input[..pos].ends_with('\r') => LineEnding::Crlf,
+                _ => LineEnding::Lf,
+            },
+            other => other,
+        }
+    }
+
+    /// Normalize all line endings in `output`, resolving
+    /// [`LineEnding::Preserve`] against the original `input`
+    pub fn apply(self, input: &str, output: String) -> String {
+        let normalized = if output.contains('\r') {
+            output.replace("\r\n", "\n")
+        } else {
+            output
+        };
+        match self.resolve(input) {
+            LineEnding::Crlf => normalized.replace('\n', "\r\n"),
+            _ => normalized,
+        }
+    }
+}
+
+/// How environment variable names are derived from paths
+///
+/// Mirrors how config-rs and figment overlay environment variables: the
+/// prefix and each path segment are uppercased and joined with the separator,
+/// so `server.port` becomes `APP__SERVER__PORT` with prefix `APP` and
+/// separator `__`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVarStyle {
+    /// Prefix of every variable, omitted when empty
+    pub prefix: String,
+    /// Separator between the prefix and path segments
+    pub separator: String,
+}
+
+impl EnvVarStyle {
+    pub fn new(prefix: impl Into<String>, separator: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            separator: separator.into(),
+        }
+    }
+
+    /// The variable overriding the value at a dot-separated path
+    ///
+    /// Characters that can't appear in a variable name become underscores.
+    pub fn var_name(&self, path: &str) -> String {
+        let segment = |s: &str| -> String {
+            s.chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+                .collect()
+        };
+        std::iter::once(self.prefix.as_str())
+            .filter(|prefix| !prefix.is_empty())
+            .chain(path.split('.'))
+            .map(segment)
+            .collect::<Vec<_>>()
+            .join(&self.separator)
+    }
 }
 
 /// Configuration for annotation behavior
@@ -30,10 +242,132 @@ pub struct AnnotatorConfig {
     pub include_description: bool,
     /// Include default value in comments
     pub include_default: bool,
-    /// Maximum line width for wrapping descriptions (None = no wrap)
+    /// Add the schema's `examples` of leaf values as commented-out
+    /// assignments at the end of their comment, ready to uncomment
+    pub include_examples: bool,
+    /// Add an example entry to the comment of maps without entries, such as
+    /// `env: {}`, derived from their `additionalProperties` (see
+    /// [`Annotation::example_entry`])
+    ///
+    /// YAML documents annotated with
+    /// [`YamlAnnotator::annotate_stream`](super::YamlAnnotator::annotate_stream)
+    /// aren't parsed as a whole, so their empty maps aren't known and get none.
+    pub example_entries: bool,
+    /// Add a `# SECRET — do not commit real values` line to the comment of
+    /// secrets (see [`Annotation::secret`])
+    pub mark_secrets: bool,
+    /// Schema keyword giving the version that introduced a property, shown
+    /// as a `# Since: v2.3` line (None = no such line)
+    pub since_keyword: Option<String>,
+    /// Keep the values of secrets out of generated documents: the `Default:`
+    /// and example lines of their comments are left out, and
+    /// [`generate_document`](crate::generate_document) gives them a
+    /// placeholder for their type instead of their default
+    pub redact_secrets: bool,
+    /// Have [`generate_document`](crate::generate_document) leave only the
+    /// properties the schema requires active, commenting out the optional
+    /// ones, for a minimal starter config that still shows every option
+    pub required_only: bool,
+    /// Add an `Env:` line naming the environment variable that overrides
+    /// each leaf value (None = no such line)
+    pub env_vars: Option<EnvVarStyle>,
+    /// Maximum line width for wrapping titles and descriptions (None = no wrap)
     pub max_line_width: Option<usize>,
+    /// Convert Markdown in descriptions to plain text before wrapping
+    pub convert_markdown: bool,
+    /// Strip inline HTML tags from descriptions before wrapping
+    pub strip_html: bool,
     /// How to handle fields that already have comments
     pub existing_comments: ExistingCommentBehavior,
+    /// Whether comments prepended or appended to existing ones attach
+    /// tightly or leave a blank line between them
+    ///
+    /// Blank lines above a key's comments, such as those separating
+    /// sections, are kept either way.
+    pub comment_spacing: CommentSpacing,
+    /// Text of the marker comment introducing generated blocks in
+    /// [`ExistingCommentBehavior::Update`] mode
+    pub generated_marker: String,
+    /// Hashes of the comments an earlier [`ExistingCommentBehavior::Update`]
+    /// run generated for the document, identifying them instead of marker
+    /// lines
+    pub lock: Option<Arc<AnnotationLock>>,
+    /// Schema reference to declare at the top of the document, as Taplo's
+    /// `#:schema` directive in TOML and a `yaml-language-server` modeline in
+    /// YAML, so editors validate against the same schema (None = leave the
+    /// document's header alone)
+    ///
+    /// Documents whose leading comments already declare a schema are left
+    /// alone.
+    pub schema_directive: Option<String>,
+    /// Only annotate paths matching one of these globs (empty = all paths)
+    pub include_paths: Vec<String>,
+    /// Never annotate paths matching any of these globs
+    pub exclude_paths: Vec<String>,
+    /// Schema keyword that leaves a property and the properties below it
+    /// unannotated when set to `false`, such as `"x-annotate": false` on an
+    /// internal option (None = annotate regardless)
+    ///
+    /// Must be an extension keyword, starting with `x-`.
+    pub opt_out_keyword: Option<String>,
+    /// Only annotate paths up to this many levels deep (None = unlimited)
+    pub max_depth: Option<usize>,
+    /// Which kinds of nodes (sections, leaves) to annotate
+    pub node_filter: NodeFilter,
+    /// How section titles are rendered
+    pub section_style: SectionStyle,
+    /// Open each group of keys sharing a schema `x-category` with a
+    /// `# ── Category ──` banner, added above the first key of the group
+    /// whenever the category changes within a mapping
+    pub category_banners: bool,
+    /// Line ending for inserted comments and the final output
+    pub line_ending: LineEnding,
+    /// How plain YAML keys like `on`, `yes` or `0x10` are read
+    pub yaml_version: YamlVersion,
+    /// Omit titles that merely restate the key name and skip annotations
+    /// identical to their parent's
+    pub deduplicate: bool,
+    /// Mark keys the schema doesn't define with a
+    /// `# WARNING: unknown key (not in schema)` comment
+    ///
+    /// See [`AnnotationMap::is_unknown`] for which keys count as unknown.
+    pub warn_unknown_keys: bool,
+    /// Find values equal to the default the schema gives them, which could
+    /// be removed from the document without changing its meaning
+    pub redundant_defaults: RedundantDefaults,
+    /// Which occurrence of a key repeated within a YAML mapping is annotated
+    ///
+    /// Repeated keys are listed in [`AnnotationReport::duplicate_keys`].
+    pub duplicate_keys: DuplicateKeys,
+    /// Fail when the schema annotates paths that don't appear in the document
+    ///
+    /// Catches typos in keys and drift between a schema and its configs. Only
+    /// paths selected by the path filters are checked.
+    pub strict: bool,
+    /// Fail when keys the schema annotates are left unannotated because the
+    /// annotator can't place comments in their construct, such as inline
+    /// tables, flow mappings or mappings inside sequences
+    ///
+    /// The paths are listed in [`AnnotationReport::unsupported`] either way.
+    pub fail_on_unsupported: bool,
+    /// Re-parse the annotated output and fail if its data differs from the
+    /// input's, guarding against the annotator ever corrupting a document
+    pub verify_round_trip: bool,
+    /// Also tidy the formatting of the annotated document
+    ///
+    /// TOML keys, table headers and comments start their line, with ` = `
+    /// between keys and values. YAML keys are followed by `: ` and comments
+    /// are lined up with the line below them, leaving the indentation that
+    /// gives the document its structure alone. Trailing whitespace and runs
+    /// of blank lines are removed and the document ends with a single
+    /// newline. Multi-line strings are left as they are. Off by default,
+    /// keeping everything but the added comments byte for byte, and not
+    /// supported by [`YamlAnnotator::annotate_stream`](super::YamlAnnotator::annotate_stream).
+    pub normalize: bool,
+    /// Renders annotations into comment lines
+    pub formatter: Arc<dyn CommentFormatter>,
+    /// Called for each path with the decision made for it
+    pub observer: Option<Arc<dyn AnnotationObserver>>,
 }
 
 impl Default for AnnotatorConfig {
@@ -42,8 +376,40 @@ impl Default for AnnotatorConfig {
             include_title: true,
             include_description: true,
             include_default: false,
+            include_examples: false,
+            example_entries: false,
+            mark_secrets: false,
+            redact_secrets: false,
+            required_only: false,
+            since_keyword: Some("x-since".to_string()),
+            env_vars: None,
             max_line_width: Some(80),
+            convert_markdown: false,
+            strip_html: false,
             existing_comments: ExistingCommentBehavior::default(),
+            comment_spacing: CommentSpacing::default(),
+            generated_marker: "[jsonschema-annotator]".to_string(),
+            lock: None,
+            schema_directive: None,
+            include_paths: Vec::new(),
+            exclude_paths: Vec::new(),
+            opt_out_keyword: Some("x-annotate".to_string()),
+            max_depth: None,
+            node_filter: NodeFilter::default(),
+            section_style: SectionStyle::default(),
+            category_banners: false,
+            line_ending: LineEnding::default(),
+            yaml_version: YamlVersion::default(),
+            deduplicate: false,
+            warn_unknown_keys: false,
+            redundant_defaults: RedundantDefaults::Ignore,
+            duplicate_keys: DuplicateKeys::default(),
+            strict: false,
+            fail_on_unsupported: false,
+            verify_round_trip: false,
+            normalize: false,
+            formatter: Arc::new(DefaultCommentFormatter),
+            observer: None,
         }
     }
 }
@@ -66,6 +432,345 @@ impl AnnotatorConfig {
             ..Default::default()
         }
     }
+
+    /// Check whether a path passes the depth limit and include/exclude filters
+    ///
+    /// See [`max_depth`](Self::max_depth), [`include_paths`](Self::include_paths)
+    /// and [`exclude_paths`](Self::exclude_paths). Globs match dot-separated
+    /// paths: `*` matches within a single segment and `**` matches any number
+    /// of segments (e.g., `server.*`, `database.**`, `**.password`).
+    pub fn is_path_selected(&self, path: &str) -> bool {
+        if let Some(max_depth) = self.max_depth {
+            if path.split('.').count() > max_depth {
+                return false;
+            }
+        }
+        let included = self.include_paths.is_empty()
+            || self.include_paths.iter().any(|p| filter::path_matches(p, path));
+        included && !self.exclude_paths.iter().any(|p| filter::path_matches(p, path))
+    }
+
+    /// Check whether the schema opts a path out of annotation with the
+    /// [`opt_out_keyword`](Self::opt_out_keyword)
+    pub fn is_opted_out(&self, annotations: &AnnotationMap, path: &str) -> bool {
+        self.opt_out_keyword
+            .as_deref()
+            .is_some_and(|keyword| annotations.is_disabled(path, keyword))
+    }
+
+    /// Use a custom formatter to render comments
+    pub fn with_formatter(mut self, formatter: impl CommentFormatter + 'static) -> Self {
+        self.formatter = Arc::new(formatter);
+        self
+    }
+
+    /// Watch, veto or rewrite the annotation of each path
+    pub fn with_observer(mut self, observer: impl AnnotationObserver + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+}
+
+/// Look up the annotation for a path, applying the config's selection rules
+pub(crate) fn select_annotation<'a>(
+    config: &AnnotatorConfig,
+    annotations: &'a AnnotationMap,
+    path: &str,
+    is_section: bool,
+    line: Option<usize>,
+    report: &mut AnnotationReport,
+) -> Option<&'a Annotation> {
+    let excluded = |report: &mut AnnotationReport| {
+        report.excluded.push(path.to_string());
+        let event = AnnotationEvent {
+            path,
+            line,
+            decision: AnnotationDecision::Excluded,
+            annotation: annotations.get(path),
+        };
+        notify(config, event, &mut Vec::new());
+    };
+
+    if !config.is_path_selected(path) {
+        tracing::debug!(path, "skipped: excluded by path filters");
+        excluded(report);
+        return None;
+    }
+    if config.is_opted_out(annotations, path) {
+        tracing::debug!(path, "skipped: opted out by the schema");
+        excluded(report);
+        return None;
+    }
+    if !config.node_filter.includes(is_section) {
+        tracing::debug!(path, is_section, "skipped: excluded by node filter");
+        excluded(report);
+        return None;
+    }
+    let Some(annotation) = annotations.get(path) else {
+        tracing::debug!(path, "skipped: no annotation in schema");
+        report.unmatched.push(path.to_string());
+        let event = AnnotationEvent {
+            path,
+            line,
+            decision: AnnotationDecision::Unmatched,
+            annotation: None,
+        };
+        notify(config, event, &mut Vec::new());
+        return None;
+    };
+
+    if config.deduplicate {
+        if let Some((parent_path, _)) = path.rsplit_once('.') {
+            if let Some(parent) = annotations.get(parent_path) {
+                if parent.title == annotation.title && parent.description == annotation.description {
+                    tracing::debug!(path, "skipped: duplicates the parent annotation");
+                    excluded(report);
+                    return None;
+                }
+            }
+        }
+    }
+
+    tracing::trace!(path, "annotating");
+    Some(annotation)
+}
+
+/// The banner to add above a key opening a new category, given the category
+/// of the key annotated before it in the same mapping, which is updated
+pub(crate) fn category_banner(
+    config: &AnnotatorConfig,
+    annotation: &Annotation,
+    previous: &mut Option<String>,
+) -> Option<String> {
+    if !config.category_banners {
+        return None;
+    }
+    let opens = annotation.category.is_some() && annotation.category != *previous;
+    previous.clone_from(&annotation.category);
+    opens.then(|| format!("# ── {} ──", annotation.category.as_deref().unwrap_or_default()))
+}
+
+/// Record the final decision for an annotated path and tell the observer,
+/// which may rewrite or veto the comment of an applied annotation
+///
+/// An empty `comment` means the formatter rendered nothing. Returns whether
+/// the observer changed the comment.
+pub(crate) fn decide(
+    config: &AnnotatorConfig,
+    annotation: &Annotation,
+    line: Option<usize>,
+    applied: bool,
+    comment: &mut Vec<String>,
+    report: &mut AnnotationReport,
+) -> bool {
+    let decision = if comment.is_empty() {
+        AnnotationDecision::Excluded
+    } else if applied {
+        AnnotationDecision::Apply
+    } else {
+        AnnotationDecision::SkipExisting
+    };
+    let event = AnnotationEvent {
+        path: &annotation.path,
+        line,
+        decision,
+        annotation: Some(annotation),
+    };
+    let rewritten = notify(config, event, comment);
+    if config.existing_comments == ExistingCommentBehavior::Update && !comment.is_empty() {
+        report.lock.record(&annotation.path, comment);
+    }
+
+    let bucket = match decision {
+        AnnotationDecision::Apply if comment.is_empty() => &mut report.excluded,
+        AnnotationDecision::Apply => &mut report.applied,
+        AnnotationDecision::SkipExisting => &mut report.skipped_existing,
+        _ => &mut report.excluded,
+    };
+    bucket.push(annotation.path.clone());
+    rewritten
+}
+
+/// Record the schema's annotated paths absent from the document, failing in
+/// [`strict`](AnnotatorConfig::strict) mode
+pub(crate) fn check_missing(
+    config: &AnnotatorConfig,
+    annotations: &AnnotationMap,
+    document_paths: &HashSet<String>,
+    report: &mut AnnotationReport,
+) -> Result<(), AnnotatorError> {
+    let mut paths: Vec<&String> = annotations.iter().map(|(path, _)| path).collect();
+    paths.sort();
+    let mut missing = HashSet::new();
+    for path in paths {
+        // The root annotation describes the document itself
+        if path.is_empty()
+            || document_paths.contains(path)
+            || !config.is_path_selected(path)
+            || config.is_opted_out(annotations, path)
+        {
+            continue;
+        }
+        let below_missing = path
+            .match_indices('.')
+            .any(|(end, _)| missing.contains(&path[..end]));
+        if !below_missing {
+            tracing::debug!(path, "annotated path missing from document");
+            missing.insert(path.as_str());
+            report.missing.push(path.clone());
+        }
+    }
+
+    if config.strict && !report.missing.is_empty() {
+        return Err(Error::new(AnnotatorErrorKind::UnmatchedAnnotations)
+            .add_context(format!("missing paths: {}", report.missing.join(", "))));
+    }
+    Ok(())
+}
+
+/// Record the document's annotated paths that annotation never reached,
+/// failing in [`fail_on_unsupported`](AnnotatorConfig::fail_on_unsupported)
+/// mode
+///
+/// `complex_keys` are paths of keys that no schema path can address, which
+/// are always unsupported.
+pub(crate) fn check_unsupported(
+    config: &AnnotatorConfig,
+    annotations: &AnnotationMap,
+    document_paths: &HashSet<String>,
+    complex_keys: Vec<String>,
+    report: &mut AnnotationReport,
+) -> Result<(), AnnotatorError> {
+    let reached: HashSet<&String> = report
+        .applied
+        .iter()
+        .chain(&report.skipped_existing)
+        .chain(&report.excluded)
+        .chain(&report.unmatched)
+        .collect();
+    let mut unsupported: Vec<String> = document_paths
+        .iter()
+        .filter(|path| annotations.get(path).is_some() && config.is_path_selected(path))
+        .filter(|path| !reached.contains(path))
+        .cloned()
+        .chain(complex_keys)
+        .collect();
+    unsupported.sort();
+    for path in &unsupported {
+        tracing::debug!(path, "skipped: construct can't be annotated");
+    }
+    report.unsupported = unsupported;
+
+    if config.fail_on_unsupported && !report.unsupported.is_empty() {
+        return Err(Error::new(AnnotatorErrorKind::Unsupported)
+            .add_context(format!("unsupported paths: {}", report.unsupported.join(", "))));
+    }
+    Ok(())
+}
+
+/// Join the lines of a normalized document
+///
+/// Lines are given with whether to keep them verbatim, being inside a
+/// multi-line string. The others lose their trailing whitespace, runs of
+/// blank lines among them are collapsed into one and dropped at the start
+/// and end, and the document ends with a single newline.
+#[cfg_attr(not(any(feature = "toml", feature = "yaml")), allow(dead_code))]
+pub(crate) fn join_normalized(lines: Vec<(String, bool)>, newline: &str) -> String {
+    let mut joined: Vec<String> = Vec::with_capacity(lines.len());
+    // Starting as if after a blank line drops leading ones
+    let mut after_blank = true;
+    for (line, verbatim) in lines {
+        let line = if verbatim { line } else { line.trim_end().to_string() };
+        let blank = !verbatim && line.is_empty();
+        if blank && after_blank {
+            continue;
+        }
+        after_blank = blank;
+        joined.push(line);
+    }
+    if after_blank {
+        joined.pop();
+    }
+    let mut output = joined.join(newline);
+    if !output.is_empty() {
+        output.push_str(newline);
+    }
+    output
+}
+
+/// Check that the annotated output parsed to the same data as the input
+pub(crate) fn check_round_trip<T: PartialEq, E>(
+    original: &T,
+    reparsed: Result<T, E>,
+) -> Result<(), AnnotatorError>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    match reparsed {
+        Ok(reparsed) if reparsed == *original => Ok(()),
+        Ok(_) => Err(Error::new(AnnotatorErrorKind::RoundTrip)
+            .add_context("annotated output has different values than the input")),
+        Err(e) => Err(Error::new(AnnotatorErrorKind::RoundTrip)
+            .add_context("annotated output no longer parses")
+            .with_source(e)),
+    }
+}
+
+/// What happened to each path of a document during annotation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AnnotationReport {
+    /// Paths that received a comment
+    pub applied: Vec<String>,
+    /// Paths left alone because of their existing comments
+    pub skipped_existing: Vec<String>,
+    /// Paths excluded by the path filters, the schema's opt-out keyword, the
+    /// node filter or deduplication, or for which the formatter produced no
+    /// comment
+    pub excluded: Vec<String>,
+    /// Paths in the document without an annotation in the schema
+    pub unmatched: Vec<String>,
+    /// Paths in the document the schema doesn't define, when marking them
+    /// with [`warn_unknown_keys`](AnnotatorConfig::warn_unknown_keys)
+    pub unknown: Vec<String>,
+    /// Paths whose value is the same as the schema default, when looking for
+    /// them with [`redundant_defaults`](AnnotatorConfig::redundant_defaults)
+    pub redundant_defaults: Vec<String>,
+    /// Paths of keys that appear more than once in the same YAML mapping
+    pub duplicate_keys: Vec<String>,
+    /// Annotated paths in the schema that don't appear in the document
+    ///
+    /// Paths below a missing path are left out.
+    pub missing: Vec<String>,
+    /// Annotated paths in the document that couldn't be annotated, such as
+    /// keys of inline tables, flow mappings and mappings inside sequences
+    pub unsupported: Vec<String>,
+    /// The comments generated in [`ExistingCommentBehavior::Update`] mode,
+    /// to give as the [`lock`](AnnotatorConfig::lock) of the next run
+    #[serde(skip)]
+    pub lock: AnnotationLock,
+}
+
+/// The result of annotating a document, telling whether anything changed
+///
+/// Callers can skip rewriting documents that are already up to date, and
+/// checks can fail on the first document that isn't.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotateOutcome<'a> {
+    /// The annotated document, borrowing the input when nothing changed
+    pub text: Cow<'a, str>,
+    /// Whether annotating changed the document
+    pub changed: bool,
+    /// What happened to each path
+    pub report: AnnotationReport,
+}
+
+impl<'a> AnnotateOutcome<'a> {
+    /// Compare an annotated document with its input
+    pub fn new(input: &'a str, annotated: String, report: AnnotationReport) -> Self {
+        let changed = annotated != input;
+        let text = if changed { Cow::Owned(annotated) } else { Cow::Borrowed(input) };
+        Self { text, changed, report }
+    }
 }
 
 /// Common interface for format-specific annotators
@@ -76,4 +781,15 @@ pub trait Annotator {
         content: &str,
         annotations: &AnnotationMap,
     ) -> Result<String, AnnotatorError>;
+
+    /// Annotate a document, also reporting what happened to each path
+    ///
+    /// The default implementation reports nothing.
+    fn annotate_with_report(
+        &self,
+        content: &str,
+        annotations: &AnnotationMap,
+    ) -> Result<(String, AnnotationReport), AnnotatorError> {
+        Ok((self.annotate(content, annotations)?, AnnotationReport::default()))
+    }
 }
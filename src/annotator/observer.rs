@@ -0,0 +1,62 @@
+use super::AnnotatorConfig;
+use crate::schema::Annotation;
+
+/// What happens to a path of the document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationDecision {
+    /// A comment is written above the key
+    Apply,
+    /// Left alone because of its existing comments
+    SkipExisting,
+    /// Excluded by the path filters, node filter or deduplication, or the
+    /// formatter produced no comment
+    Excluded,
+    /// The schema has no annotation for the path
+    Unmatched,
+}
+
+/// A path visited during annotation, passed to an [`AnnotationObserver`]
+#[derive(Debug, Clone, Copy)]
+pub struct AnnotationEvent<'a> {
+    /// Dot-separated path of the key (e.g., "server.port")
+    pub path: &'a str,
+    /// One-based line of the key in the input, if known
+    pub line: Option<usize>,
+    /// What the annotator decided to do with the path
+    pub decision: AnnotationDecision,
+    /// The schema's annotation for the path, if any
+    pub annotation: Option<&'a Annotation>,
+}
+
+/// Watches, and can veto or rewrite, the annotation of each path
+///
+/// Called once for every key in the document, in document order.
+pub trait AnnotationObserver: std::fmt::Debug + Send + Sync {
+    /// Observe the decision for a path
+    ///
+    /// For [`AnnotationDecision::Apply`], `comment` holds the lines about to
+    /// be written, as returned by the [`CommentFormatter`](super::CommentFormatter).
+    /// They may be rewritten; clearing them vetoes the annotation and the
+    /// path is reported as excluded. For other decisions `comment` is empty
+    /// and changes to it are ignored.
+    fn observe(&self, event: &AnnotationEvent<'_>, comment: &mut Vec<String>);
+}
+
+/// Notify the configured observer, if any, returning whether it changed the
+/// comment
+pub(crate) fn notify(
+    config: &AnnotatorConfig,
+    event: AnnotationEvent<'_>,
+    comment: &mut Vec<String>,
+) -> bool {
+    let Some(observer) = &config.observer else {
+        return false;
+    };
+    if event.decision != AnnotationDecision::Apply {
+        observer.observe(&event, &mut Vec::new());
+        return false;
+    }
+    let original = comment.clone();
+    observer.observe(&event, comment);
+    *comment != original
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{
+    AnnotatorConfig, CommentSpacing, DuplicateKeys, ExistingCommentBehavior, LineEnding, NodeFilter, RedundantDefaults,
+    SectionStyle, YamlVersion,
+};
+
+/// A string that doesn't name a value of an option
+///
+/// Returned by the [`FromStr`] implementations of the option enums, which
+/// accept the values of the matching command line flags, such as `"leaves"`
+/// for [`NodeFilter::LeavesOnly`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptionError {
+    value: String,
+    expected: &'static [&'static str],
+}
+
+impl ParseOptionError {
+    /// The values that are accepted
+    pub fn expected(&self) -> &'static [&'static str] {
+        self.expected
+    }
+}
+
+impl fmt::Display for ParseOptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid value {:?}, expected one of: {}", self.value, self.expected.join(", "))
+    }
+}
+
+impl std::error::Error for ParseOptionError {}
+
+/// Which parts of a schema annotation go in comments
+///
+/// A shorthand for the pair of [`AnnotatorConfig::include_title`] and
+/// [`AnnotatorConfig::include_description`], parsed from `"title"`,
+/// `"description"` or `"both"` like the `--include` flag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Include {
+    /// Only titles
+    Title,
+    /// Only descriptions
+    Description,
+    /// Titles and descriptions
+    #[default]
+    Both,
+}
+
+impl Include {
+    /// Set the matching `include_*` fields of `config`
+    pub fn apply(self, config: &mut AnnotatorConfig) {
+        config.include_title = self != Include::Description;
+        config.include_description = self != Include::Title;
+    }
+}
+
+/// Look `value` up among `names`, which are in the same order as `values`
+fn parse<T: Copy>(value: &str, names: &'static [&'static str], values: &[T]) -> Result<T, ParseOptionError> {
+    match names.iter().position(|name| *name == value) {
+        Some(index) => Ok(values[index]),
+        None => Err(ParseOptionError {
+            value: value.to_string(),
+            expected: names,
+        }),
+    }
+}
+
+impl FromStr for Include {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Include::*;
+        parse(s, &["title", "description", "both"], &[Title, Description, Both])
+    }
+}
+
+impl FromStr for ExistingCommentBehavior {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use ExistingCommentBehavior::*;
+        parse(s, &["skip", "prepend", "append", "replace", "update"], &[Skip, Prepend, Append, Replace, Update])
+    }
+}
+
+impl FromStr for RedundantDefaults {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use RedundantDefaults::*;
+        parse(s, &["ignore", "report", "mark"], &[Ignore, Report, Mark])
+    }
+}
+
+impl FromStr for DuplicateKeys {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use DuplicateKeys::*;
+        parse(s, &["all", "first", "last", "error"], &[All, First, Last, Error])
+    }
+}
+
+impl FromStr for NodeFilter {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use NodeFilter::*;
+        parse(s, &["all", "leaves", "sections"], &[All, LeavesOnly, SectionsOnly])
+    }
+}
+
+impl FromStr for CommentSpacing {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use CommentSpacing::*;
+        parse(s, &["tight", "blank-line"], &[Tight, BlankLine])
+    }
+}
+
+impl FromStr for SectionStyle {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use SectionStyle::*;
+        parse(s, &["plain", "rule", "hash"], &[Plain, Rule, Hash])
+    }
+}
+
+impl FromStr for YamlVersion {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use YamlVersion::*;
+        parse(s, &["1.1", "1.2"], &[V1_1, V1_2])
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use LineEnding::*;
+        parse(s, &["lf", "crlf", "preserve"], &[Lf, Crlf, Preserve])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!("update".parse(), Ok(ExistingCommentBehavior::Update));
+        assert_eq!("leaves".parse(), Ok(NodeFilter::LeavesOnly));
+        assert_eq!("blank-line".parse(), Ok(CommentSpacing::BlankLine));
+        assert_eq!("1.1".parse(), Ok(YamlVersion::V1_1));
+
+        let mut config = AnnotatorConfig::default();
+        "title".parse::<Include>().unwrap().apply(&mut config);
+        assert!(config.include_title && !config.include_description);
+
+        let error = "some".parse::<NodeFilter>().unwrap_err();
+        assert_eq!(error.expected(), ["all", "leaves", "sections"]);
+        assert_eq!(error.to_string(), "invalid value \"some\", expected one of: all, leaves, sections");
+    }
+}
@@ -0,0 +1,307 @@
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+/// Convert Markdown (as commonly found in rustdoc-derived descriptions) to
+/// readable plain text
+///
+/// Inline markup (emphasis, code spans) is dropped, links are rendered as
+/// `text (url)`, list items become `- item` lines and block elements are
+/// separated by blank lines.
+pub fn markdown_to_plain(markdown: &str) -> String {
+    let mut out = String::new();
+    // Stack of open lists: Some(n) for ordered lists with the next number
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut link_urls: Vec<String> = Vec::new();
+    let mut link_text_start: Vec<usize> = Vec::new();
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Paragraph) | Event::Start(Tag::Heading { .. }) => {
+                start_block(&mut out, lists.is_empty());
+            }
+            Event::End(TagEnd::Paragraph) | Event::End(TagEnd::Heading(_)) => {}
+            Event::Start(Tag::CodeBlock(_)) => start_block(&mut out, lists.is_empty()),
+            Event::End(TagEnd::CodeBlock) => {
+                while out.ends_with('\n') {
+                    out.pop();
+                }
+            }
+            Event::Start(Tag::List(start)) => {
+                if lists.is_empty() {
+                    start_block(&mut out, true);
+                }
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        out.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => out.push_str("- "),
+                }
+            }
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                link_urls.push(dest_url.to_string());
+                link_text_start.push(out.len());
+            }
+            Event::End(TagEnd::Link) => {
+                if let (Some(url), Some(start)) = (link_urls.pop(), link_text_start.pop()) {
+                    if out[start..] != url && !url.is_empty() {
+                        out.push_str(&format!(" ({})", url));
+                    }
+                }
+            }
+            Event::Text(text) | Event::Code(text) => out.push_str(&text),
+            Event::Html(html) | Event::InlineHtml(html) => out.push_str(&html),
+            Event::SoftBreak => out.push(' '),
+            Event::HardBreak => out.push('\n'),
+            Event::Rule => {
+                start_block(&mut out, true);
+                out.push_str("---");
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+/// Wrap description text to `width` columns, preserving its structure
+///
+/// Explicit newlines are kept, runs of blank lines collapse into a single
+/// empty line between paragraphs, and bullet or numbered list items wrap
+/// with a hanging indent so continuation lines align with the item text.
+pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for line in text.trim().lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            if lines.last().is_some_and(|l: &String| !l.is_empty()) {
+                lines.push(String::new());
+            }
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+        let hanging = " ".repeat(indent + list_marker_len(line.trim_start()));
+        let options = textwrap::Options::new(width.max(1))
+            .initial_indent(&line[..indent])
+            .subsequent_indent(&hanging);
+        lines.extend(
+            textwrap::wrap(line.trim_start(), options)
+                .into_iter()
+                .map(|l| l.into_owned()),
+        );
+    }
+
+    lines
+}
+
+/// Wrap a one-line label such as a title to `width` columns
+///
+/// Whitespace runs, including newlines, collapse to single spaces and
+/// continuation lines get a two space hanging indent to set them apart from
+/// the lines that follow.
+pub fn wrap_line(text: &str, width: usize) -> Vec<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let options = textwrap::Options::new(width.max(1)).subsequent_indent("  ");
+    textwrap::wrap(&text, options)
+        .into_iter()
+        .map(|l| l.into_owned())
+        .collect()
+}
+
+/// Length of a leading list marker (`- `, `* `, `+ `, `1. `, `1) `), or 0
+fn list_marker_len(line: &str) -> usize {
+    if ["- ", "* ", "+ "].iter().any(|m| line.starts_with(m)) {
+        return 2;
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") ")) {
+        return digits + 2;
+    }
+    0
+}
+
+/// Strip inline HTML (as found in OpenAPI-origin descriptions) to plain text
+///
+/// Line and paragraph breaks (`<br>`, `<p>`) and list items (`<li>`) are
+/// converted to their plain text equivalents, all other tags are removed and
+/// common character entities are decoded. A `<` that doesn't start a tag is
+/// left as is.
+pub fn strip_html(html: &str) -> String {
+    let mut out = String::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let candidate = &rest[start..];
+        match parse_tag(candidate) {
+            Some((name, len)) => {
+                match name.as_str() {
+                    "br" => out.push('\n'),
+                    "p" | "/p" | "div" | "/div" | "ul" | "/ul" | "ol" | "/ol" => {
+                        let trimmed = out.trim_end_matches([' ', '\n']).len();
+                        out.truncate(trimmed);
+                        if !out.is_empty() {
+                            out.push_str("\n\n");
+                        }
+                    }
+                    "li" => {
+                        let trimmed = out.trim_end_matches(' ').len();
+                        out.truncate(trimmed);
+                        if !out.is_empty() && !out.ends_with('\n') {
+                            out.push('\n');
+                        }
+                        out.push_str("- ");
+                    }
+                    _ => {}
+                }
+                rest = &candidate[len..];
+            }
+            None => {
+                out.push('<');
+                rest = &candidate[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    decode_entities(out.trim())
+}
+
+/// Parse an HTML tag at the start of `text`, returning its lowercase name
+/// (prefixed with `/` for closing tags) and length in bytes
+fn parse_tag(text: &str) -> Option<(String, usize)> {
+    let end = text.find('>')?;
+    let inner = text[1..end].trim_end_matches('/').trim();
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(inner) => (true, inner),
+        None => (false, inner),
+    };
+    if !inner.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let name: String = inner
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let name = if closing { format!("/{}", name) } else { name };
+    Some((name, end + 1))
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Separate a new block from preceding content with a blank line
+fn start_block(out: &mut String, top_level: bool) {
+    if out.is_empty() {
+        return;
+    }
+    if top_level {
+        while out.ends_with('\n') {
+            out.pop();
+        }
+        out.push_str("\n\n");
+    } else if !out.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_markup() {
+        assert_eq!(
+            markdown_to_plain("Set to `true` to **enable** the *cache*."),
+            "Set to true to enable the cache."
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        assert_eq!(
+            markdown_to_plain("See [the docs](https://example.com/docs) or <https://example.com>."),
+            "See the docs (https://example.com/docs) or https://example.com."
+        );
+    }
+
+    #[test]
+    fn test_paragraphs_and_lists() {
+        let markdown = "First paragraph\ncontinues here.\n\nOptions:\n\n* `fast`\n* `slow`\n\n1. one\n2. two";
+        assert_eq!(
+            markdown_to_plain(markdown),
+            "First paragraph continues here.\n\nOptions:\n\n- fast\n- slow\n\n1. one\n2. two"
+        );
+    }
+
+    #[test]
+    fn test_wrap_text_paragraphs() {
+        assert_eq!(
+            wrap_text("First line\nsecond line\n\n\n\nNext paragraph", 40),
+            vec!["First line", "second line", "", "Next paragraph"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_text_list_hanging_indent() {
+        assert_eq!(
+            wrap_text("Modes:\n- fast mode skips validation\n10. numbered item wraps too", 20),
+            vec![
+                "Modes:",
+                "- fast mode skips",
+                "  validation",
+                "10. numbered item",
+                "    wraps too",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_line_hanging_indent() {
+        assert_eq!(
+            wrap_line("Maximum number of   concurrent\nconnections per worker", 20),
+            vec!["Maximum number", "  of concurrent", "  connections per", "  worker"]
+        );
+        assert_eq!(wrap_line("Port", 20), vec!["Port"]);
+    }
+
+    #[test]
+    fn test_strip_html_inline_tags() {
+        assert_eq!(
+            strip_html("Use <code>https</code> or <b>http</b>.<br/>Defaults to <i>https</i>."),
+            "Use https or http.\nDefaults to https."
+        );
+    }
+
+    #[test]
+    fn test_strip_html_blocks_and_entities() {
+        assert_eq!(
+            strip_html("<p>Modes:</p><ul><li>a &amp; b</li><li>x &lt; y</li></ul>"),
+            "Modes:\n\n- a & b\n- x < y"
+        );
+    }
+
+    #[test]
+    fn test_strip_html_keeps_non_tags() {
+        assert_eq!(strip_html("must be < 10 and > 2"), "must be < 10 and > 2");
+    }
+}
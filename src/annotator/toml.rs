@@ -1,10 +1,24 @@
-use toml_edit::{DocumentMut, Item, Table};
+use std::collections::{HashMap, HashSet};
 
-use super::{Annotator, AnnotatorConfig, ExistingCommentBehavior};
-use crate::error::{AnnotatorError, AnnotatorErrorKind, Error};
+use toml_edit::{DocumentMut, Item, Table, TableLike};
+
+use super::{
+    category_banner, check_missing, check_round_trip, check_unsupported, decide, select_annotation,
+    AnnotationReport, Annotator,
+    AnnotatorConfig, CommentContext, CommentSpacing, ExistingCommentBehavior,
+};
+use crate::discover::{directive_reference, schema_directive};
+use crate::error::{toml_parse_error, AnnotatorError};
+use crate::format::TargetFormat;
 use crate::schema::{Annotation, AnnotationMap};
+use crate::validate::toml_table_to_json;
 
 /// TOML document annotator using toml_edit
+///
+/// Annotators hold nothing but their configuration and do no setup per
+/// call, so one can be shared between threads (it's `Send + Sync`) or cloned
+/// cheaply, the formatter and observer being reference counted.
+#[derive(Debug, Clone)]
 pub struct TomlAnnotator {
     config: AnnotatorConfig,
 }
@@ -14,35 +28,94 @@ impl TomlAnnotator {
         Self { config }
     }
 
-    fn format_comment(&self, annotation: &Annotation) -> Option<String> {
-        let mut lines = Vec::new();
+    fn render(&self, annotation: &Annotation, is_section: bool, is_empty: bool) -> Vec<String> {
+        let context = CommentContext {
+            path: &annotation.path,
+            indent: 0,
+            is_section,
+            is_empty,
+            format: TargetFormat::Toml,
+            config: &self.config,
+        };
+        self.config.formatter.format(annotation, &context)
+    }
 
-        if self.config.include_title {
-            if let Some(title) = &annotation.title {
-                lines.push(format!("# {}", title));
-            }
-        }
+    /// Annotate one key, returning its new decor prefix if it changes
+    #[allow(clippy::too_many_arguments)]
+    fn annotate_key(
+        &self,
+        annotation: &Annotation,
+        is_section: bool,
+        is_empty: bool,
+        line: Option<usize>,
+        existing: &str,
+        category: &mut Option<String>,
+        report: &mut AnnotationReport,
+    ) -> Option<String> {
+        // Add newline after comments so it appears before the key
+        let merge = |comment: &[String]| match comment {
+            [] => None,
+            lines => self.merge_prefix(&annotation.path, existing, lines.join("\n") + "\n"),
+        };
 
-        if self.config.include_description {
-            if let Some(desc) = &annotation.description {
-                let width = self.config.max_line_width.unwrap_or(78);
-                for line in textwrap::wrap(desc, width.saturating_sub(2)) {
-                    lines.push(format!("# {}", line));
-                }
-            }
+        let mut comment = self.render(annotation, is_section, is_empty);
+        if let Some(banner) = category_banner(&self.config, annotation, category) {
+            comment.insert(0, banner);
         }
-
-        if self.config.include_default {
-            if let Some(default) = &annotation.default {
-                lines.push(format!("# Default: {}", default));
-            }
+        let prefix = merge(&comment);
+        if decide(&self.config, annotation, line, prefix.is_some(), &mut comment, report) {
+            return merge(&comment);
         }
+        prefix
+    }
+
+    /// Combine a generated comment with an existing decor prefix according
+    /// to the configured [`ExistingCommentBehavior`], returning `None` to
+    /// leave the prefix unchanged
+    ///
+    /// Blank lines at the start of the prefix, separating the key from what
+    /// comes before it, stay above the generated comment.
+    fn merge_prefix(&self, path: &str, existing: &str, comment: String) -> Option<String> {
+        let has_existing = existing.trim().starts_with('#');
+        let (blank, rest) = split_blank_lines(existing);
+        let spacing = match self.config.comment_spacing {
+            CommentSpacing::Tight => "",
+            CommentSpacing::BlankLine => "\n",
+        };
 
-        if lines.is_empty() {
-            None
-        } else {
-            // Add newline after comments so it appears before the key
-            Some(lines.join("\n") + "\n")
+        match self.config.existing_comments {
+            ExistingCommentBehavior::Skip if has_existing => None,
+            ExistingCommentBehavior::Prepend if has_existing => {
+                Some(format!("{}{}{}{}", blank, comment, spacing, rest))
+            }
+            ExistingCommentBehavior::Append if has_existing => {
+                Some(format!("{}{}{}", existing, spacing, comment))
+            }
+            ExistingCommentBehavior::Update => {
+                let marker = format!("# {}\n", self.config.generated_marker);
+                // Lines recorded by a lock identify generated blocks without markers
+                let marked = match self.config.lock {
+                    Some(_) => comment.clone(),
+                    None => format!("{}{}", marker, comment),
+                };
+                let lines: Vec<&str> = existing.split_inclusive('\n').collect();
+                let locked = self.config.lock.as_ref().and_then(|lock| lock.generated_lines(path, &lines));
+                // A generated block runs from the marker line to the key
+                let block_start = match locked {
+                    Some(count) => Some(lines[..lines.len() - count].concat().len()),
+                    None => existing
+                        .rmatch_indices(marker.as_str())
+                        .map(|(pos, _)| pos)
+                        .find(|&pos| pos == 0 || existing[..pos].ends_with('\n')),
+                };
+                match block_start {
+                    Some(pos) => Some(format!("{}{}", &existing[..pos], marked)),
+                    None if existing.ends_with(&comment) => None,
+                    None if has_existing => Some(format!("{}{}", existing, marked)),
+                    None => Some(format!("{}{}", blank, marked)),
+                }
+            }
+            _ => Some(format!("{}{}", blank, comment)), // Replace or no existing comment
         }
     }
 
@@ -51,15 +124,21 @@ impl TomlAnnotator {
         table: &mut Table,
         path: &[String],
         annotations: &AnnotationMap,
+        lines: &HashMap<String, usize>,
+        report: &mut AnnotationReport,
     ) {
         // Collect keys first to avoid borrow issues
         // Use deref to str to get the key string (Key derefs to str)
         let keys: Vec<String> = table.iter().map(|(k, _)| (*k).to_string()).collect();
+        // Values are written before sub-tables, so each runs its own categories
+        let mut value_category = None;
+        let mut table_category = None;
 
         for key_string in keys {
             let mut current_path = path.to_vec();
             current_path.push(key_string.clone());
             let path_string = current_path.join(".");
+            let line = lines.get(&path_string).copied();
 
             // Get mutable access to the key-value pair
             if let Some((mut key, item)) = table.get_key_value_mut(&key_string) {
@@ -67,56 +146,45 @@ impl TomlAnnotator {
                 match item {
                     Item::Table(nested) => {
                         // For tables, use the table's own decor (appears before the [header])
-                        if let Some(ann) = annotations.get(&path_string) {
-                            if let Some(comment) = self.format_comment(ann) {
-                                let decor = nested.decor_mut();
-                                let existing = decor.prefix().map(|s| s.as_str().unwrap_or("")).unwrap_or("");
-                                let has_existing = existing.trim().starts_with('#');
-
-                                let new_prefix = match self.config.existing_comments {
-                                    ExistingCommentBehavior::Skip if has_existing => None,
-                                    ExistingCommentBehavior::Prepend if has_existing => {
-                                        Some(format!("{}{}", comment, existing))
-                                    }
-                                    ExistingCommentBehavior::Append if has_existing => {
-                                        Some(format!("{}{}", existing, comment))
-                                    }
-                                    _ => Some(comment), // Replace or no existing comment
-                                };
-
-                                if let Some(prefix) = new_prefix {
-                                    decor.set_prefix(prefix);
-                                }
+                        if let Some(ann) = select_annotation(&self.config, annotations, &path_string, true, line, report) {
+                            let is_empty = nested.is_empty();
+                            let decor = nested.decor_mut();
+                            let existing = decor.prefix().map(|s| s.as_str().unwrap_or("")).unwrap_or("");
+                            if let Some(prefix) = self.annotate_key(
+                                ann,
+                                true,
+                                is_empty,
+                                line,
+                                existing,
+                                &mut table_category,
+                                report,
+                            ) {
+                                decor.set_prefix(prefix);
                             }
                         }
                         // Recurse into nested tables
-                        self.annotate_table(nested, &current_path, annotations);
-                    }
-                    Item::Value(toml_edit::Value::InlineTable(_)) => {
-                        // Can't easily modify inline tables, skip for now
+                        self.annotate_table(nested, &current_path, annotations, lines, report);
                     }
                     _ => {
-                        // For regular values, use the key's decor
-                        if let Some(ann) = annotations.get(&path_string) {
-                            if let Some(comment) = self.format_comment(ann) {
-                                let decor = key.leaf_decor_mut();
-                                let existing = decor.prefix().map(|s| s.as_str().unwrap_or("")).unwrap_or("");
-                                let has_existing = existing.trim().starts_with('#');
-
-                                let new_prefix = match self.config.existing_comments {
-                                    ExistingCommentBehavior::Skip if has_existing => None,
-                                    ExistingCommentBehavior::Prepend if has_existing => {
-                                        Some(format!("{}{}", comment, existing))
-                                    }
-                                    ExistingCommentBehavior::Append if has_existing => {
-                                        Some(format!("{}{}", existing, comment))
-                                    }
-                                    _ => Some(comment), // Replace or no existing comment
-                                };
-
-                                if let Some(prefix) = new_prefix {
-                                    decor.set_prefix(prefix);
-                                }
+                        // For regular values and inline tables, use the key's
+                        // decor; keys inside inline tables aren't annotated
+                        if let Some(ann) = select_annotation(&self.config, annotations, &path_string, false, line, report) {
+                            let is_empty = matches!(
+                                item,
+                                Item::Value(toml_edit::Value::InlineTable(table)) if table.is_empty()
+                            );
+                            let decor = key.leaf_decor_mut();
+                            let existing = decor.prefix().map(|s| s.as_str().unwrap_or("")).unwrap_or("");
+                            if let Some(prefix) = self.annotate_key(
+                                ann,
+                                false,
+                                is_empty,
+                                line,
+                                existing,
+                                &mut value_category,
+                                report,
+                            ) {
+                                decor.set_prefix(prefix);
                             }
                         }
                     }
@@ -124,6 +192,232 @@ impl TomlAnnotator {
             }
         }
     }
+
+    /// Annotate an already parsed document in place
+    ///
+    /// Comments are added to the document's decor, so the caller can keep
+    /// editing it or serialize it when done. The configured line ending,
+    /// normalization and round-trip verification only apply to the
+    /// string-based methods.
+    pub fn annotate_document(
+        &self,
+        doc: &mut DocumentMut,
+        annotations: &AnnotationMap,
+    ) -> Result<AnnotationReport, AnnotatorError> {
+        // An edited document has no positions, so observers get no line numbers
+        self.annotate_parsed(doc, annotations, &HashMap::new())
+    }
+
+    fn annotate_parsed(
+        &self,
+        doc: &mut DocumentMut,
+        annotations: &AnnotationMap,
+        lines: &HashMap<String, usize>,
+    ) -> Result<AnnotationReport, AnnotatorError> {
+        let mut report = AnnotationReport::default();
+        let mut document_paths = HashSet::new();
+        collect_paths(doc.as_table(), "", &mut document_paths);
+        check_missing(&self.config, annotations, &document_paths, &mut report)?;
+        self.annotate_table(doc.as_table_mut(), &Vec::new(), annotations, lines, &mut report);
+        check_unsupported(&self.config, annotations, &document_paths, Vec::new(), &mut report)?;
+        Ok(report)
+    }
+
+    /// Annotate a document, also reporting what happened to each path
+    pub fn annotate_with_report(
+        &self,
+        content: &str,
+        annotations: &AnnotationMap,
+    ) -> Result<(String, AnnotationReport), AnnotatorError> {
+        let parsed =
+            toml_edit::Document::parse(content).map_err(|e| toml_parse_error(content, e))?;
+
+        // Positions are lost once the document is made editable
+        let mut lines = HashMap::new();
+        if self.config.observer.is_some() {
+            key_lines(content, parsed.as_table(), "", &mut lines);
+        }
+        let mut doc = parsed.into_mut();
+
+        let original = self.config.verify_round_trip.then(|| toml_table_to_json(doc.as_table()));
+        let report = self.annotate_parsed(&mut doc, annotations, &lines)?;
+
+        let mut output = doc.to_string();
+        if let Some(reference) = &self.config.schema_directive {
+            if directive_reference(content).is_none() {
+                // A blank line keeps the directive out of the first key's comment
+                let directive = schema_directive(TargetFormat::Toml, reference).unwrap_or_default();
+                output = format!("{}\n\n{}", directive, output);
+            }
+        }
+        if self.config.normalize {
+            output = normalize(&output)?;
+        }
+        let output = self.config.line_ending.apply(content, output);
+        if let Some(original) = original {
+            let reparsed = output.parse::<DocumentMut>();
+            check_round_trip(&original, reparsed.map(|doc| toml_table_to_json(doc.as_table())))?;
+        }
+        Ok((output, report))
+    }
+}
+
+/// Collect the comment lines directly above each key, by path
+///
+/// Only the block of comments adjoining the key is taken, so comments
+/// separated from it by a blank line are left out.
+pub(crate) fn key_comments(content: &str) -> Result<Vec<(String, Vec<String>)>, AnnotatorError> {
+    let doc = content
+        .parse::<DocumentMut>()
+        .map_err(|e| toml_parse_error(content, e))?;
+    let mut comments = Vec::new();
+    table_comments(doc.as_table(), "", &mut comments);
+    Ok(comments)
+}
+
+fn table_comments(table: &Table, prefix: &str, comments: &mut Vec<(String, Vec<String>)>) {
+    for (key, item) in table.iter() {
+        let path = if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+        // Tables carry comments before their header, values before their key
+        let decor = match item {
+            Item::Table(nested) => Some(nested.decor()),
+            Item::ArrayOfTables(array) => array.get(0).map(|nested| nested.decor()),
+            _ => table.key(key).map(|k| k.leaf_decor()),
+        };
+        let prefix = decor.and_then(|d| d.prefix()).and_then(|p| p.as_str()).unwrap_or("");
+        let block: Vec<String> = prefix
+            .lines()
+            .rev()
+            .take_while(|line| line.trim_start().starts_with('#'))
+            .map(|line| line.trim().to_string())
+            .collect();
+        if !block.is_empty() {
+            comments.push((path.clone(), block.into_iter().rev().collect()));
+        }
+
+        match item {
+            Item::Table(nested) => table_comments(nested, &path, comments),
+            Item::ArrayOfTables(array) => {
+                for nested in array.iter() {
+                    table_comments(nested, &path, comments);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Tidy the formatting of a TOML document, as described for
+/// [`AnnotatorConfig::normalize`]
+fn normalize(content: &str) -> Result<String, AnnotatorError> {
+    let doc = toml_edit::Document::parse(content).map_err(|e| toml_parse_error(content, e))?;
+    // Values of keys never overlap, so sorted they can be searched
+    let mut values = Vec::new();
+    value_spans(doc.as_table(), &mut values);
+    values.sort_by_key(|span| span.start);
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for raw in content.split_inclusive('\n') {
+        let end = start + raw.len();
+        let line = raw.trim_end_matches(['\n', '\r']);
+        let next = values.partition_point(|span| span.start < start);
+        let inside = values[..next].last().filter(|span| span.end > start);
+        let value = values.get(next).filter(|span| span.start < start + line.len());
+        lines.push(match (inside, value) {
+            // Only what follows the end of a value may be trimmed
+            (Some(span), _) => (line.to_string(), span.end > start + line.len()),
+            (None, Some(span)) => {
+                let key = content[start..span.start].trim().trim_end_matches('=').trim_end();
+                let rest = &line[span.start - start..];
+                // The rest of a multi-line string's first line is part of it
+                (format!("{} = {}", key, rest), span.end > start + line.len())
+            }
+            (None, None) => (line.trim_start().to_string(), false),
+        });
+        start = end;
+    }
+    Ok(super::join_normalized(lines, "\n"))
+}
+
+/// Collect the byte ranges of every value in a freshly parsed document,
+/// including those in arrays of tables
+fn value_spans(table: &Table, spans: &mut Vec<std::ops::Range<usize>>) {
+    for (_, item) in table.iter() {
+        match item {
+            Item::Value(value) => spans.extend(value.span()),
+            Item::Table(nested) => value_spans(nested, spans),
+            Item::ArrayOfTables(array) => {
+                for nested in array.iter() {
+                    value_spans(nested, spans);
+                }
+            }
+            Item::None => {}
+        }
+    }
+}
+
+/// Split a decor prefix into its leading blank lines and the rest
+fn split_blank_lines(prefix: &str) -> (&str, &str) {
+    let end = prefix
+        .split_inclusive('\n')
+        .take_while(|line| line.ends_with('\n') && line.trim().is_empty())
+        .map(str::len)
+        .sum();
+    prefix.split_at(end)
+}
+
+/// The one-based line of every key of a document, by path
+pub(crate) fn key_line_map(content: &str) -> Result<HashMap<String, usize>, AnnotatorError> {
+    let doc = toml_edit::Document::parse(content).map_err(|e| toml_parse_error(content, e))?;
+    let mut lines = HashMap::new();
+    key_lines(content, doc.as_table(), "", &mut lines);
+    Ok(lines)
+}
+
+/// Map the path of every key of a freshly parsed document to its one-based line
+fn key_lines(content: &str, table: &Table, prefix: &str, lines: &mut HashMap<String, usize>) {
+    for (key, item) in table.iter() {
+        let path = if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+        let span = table.key(key).and_then(|k| k.span()).or_else(|| item.span());
+        if let Some(span) = span {
+            lines.insert(path.clone(), content[..span.start].matches('\n').count() + 1);
+        }
+        if let Item::Table(nested) = item {
+            key_lines(content, nested, &path, lines);
+        }
+    }
+}
+
+/// Collect the dot-separated path of every key, including keys of inline
+/// tables and of tables inside arrays
+fn collect_paths(table: &dyn TableLike, prefix: &str, paths: &mut HashSet<String>) {
+    for (key, item) in table.iter() {
+        let path = if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+        match item {
+            Item::Table(nested) => collect_paths(nested, &path, paths),
+            Item::ArrayOfTables(array) => {
+                for nested in array.iter() {
+                    collect_paths(nested, &path, paths);
+                }
+            }
+            Item::Value(value) => collect_value_paths(value, &path, paths),
+            Item::None => {}
+        }
+        paths.insert(path);
+    }
+}
+
+fn collect_value_paths(value: &toml_edit::Value, path: &str, paths: &mut HashSet<String>) {
+    match value {
+        toml_edit::Value::InlineTable(nested) => collect_paths(nested, path, paths),
+        toml_edit::Value::Array(array) => {
+            for item in array.iter() {
+                collect_value_paths(item, path, paths);
+            }
+        }
+        _ => {}
+    }
 }
 
 impl Annotator for TomlAnnotator {
@@ -132,19 +426,26 @@ impl Annotator for TomlAnnotator {
         content: &str,
         annotations: &AnnotationMap,
     ) -> Result<String, AnnotatorError> {
-        let mut doc: DocumentMut = content
-            .parse()
-            .map_err(|e| Error::new(AnnotatorErrorKind::Parse).with_source(e))?;
-
-        self.annotate_table(doc.as_table_mut(), &Vec::new(), annotations);
+        self.annotate_with_report(content, annotations)
+            .map(|(annotated, _)| annotated)
+    }
 
-        Ok(doc.to_string())
+    fn annotate_with_report(
+        &self,
+        content: &str,
+        annotations: &AnnotationMap,
+    ) -> Result<(String, AnnotationReport), AnnotatorError> {
+        TomlAnnotator::annotate_with_report(self, content, annotations)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::annotator::{
+        AnnotationDecision, AnnotationEvent, AnnotationLock, AnnotationObserver, LineEnding, NodeFilter,
+    };
+    use crate::error::AnnotatorErrorKind;
     use crate::schema::Annotation;
     use insta::assert_snapshot;
 
@@ -321,6 +622,85 @@ port = 5432
         assert_snapshot!(result);
     }
 
+    #[test]
+    fn test_blank_lines_preserved() {
+        let content = "name = \"app\"\n\n# Hand-written\n[server]\nport = 8080\n";
+        let annotations = make_annotations(&[("server", Some("Server"), None)]);
+
+        let annotate = |existing_comments, comment_spacing| {
+            let config = AnnotatorConfig {
+                existing_comments,
+                comment_spacing,
+                ..Default::default()
+            };
+            TomlAnnotator::new(config).annotate(content, &annotations).unwrap()
+        };
+
+        assert_eq!(
+            annotate(ExistingCommentBehavior::Prepend, CommentSpacing::Tight),
+            "name = \"app\"\n\n# Server\n# Hand-written\n[server]\nport = 8080\n"
+        );
+        assert_eq!(
+            annotate(ExistingCommentBehavior::Append, CommentSpacing::Tight),
+            "name = \"app\"\n\n# Hand-written\n# Server\n[server]\nport = 8080\n"
+        );
+        assert_eq!(
+            annotate(ExistingCommentBehavior::Replace, CommentSpacing::Tight),
+            "name = \"app\"\n\n# Server\n[server]\nport = 8080\n"
+        );
+        assert_eq!(
+            annotate(ExistingCommentBehavior::Prepend, CommentSpacing::BlankLine),
+            "name = \"app\"\n\n# Server\n\n# Hand-written\n[server]\nport = 8080\n"
+        );
+        assert_eq!(
+            annotate(ExistingCommentBehavior::Append, CommentSpacing::BlankLine),
+            "name = \"app\"\n\n# Hand-written\n\n# Server\n[server]\nport = 8080\n"
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        let content = "\n\nname   =  \"app\"  \n\n\n\n  [server]\n    # Listening port\n    port=8080\n    motd = \"\"\"\n  Hello   \n\n\n  there\"\"\"   \n\n";
+        let annotations = make_annotations(&[("server.port", Some("Port"), None)]);
+        let config = AnnotatorConfig {
+            normalize: true,
+            ..Default::default()
+        };
+        let result = TomlAnnotator::new(config)
+            .annotate(content, &annotations)
+            .unwrap();
+
+        assert_eq!(
+            result,
+            "name = \"app\"\n\n[server]\n# Port\n# Listening port\nport = 8080\nmotd = \"\"\"\n  Hello   \n\n\n  there\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_example_entries() {
+        let content = "env = {}\nlabels = { team = \"core\" }\n\n[headers]\n";
+        let mut annotations = AnnotationMap::new();
+        for path in ["env", "labels", "headers"] {
+            annotations.insert(
+                Annotation::new(path)
+                    .with_title("Map")
+                    .with_example_entry("name", serde_json::json!("value")),
+            );
+        }
+
+        let config = AnnotatorConfig {
+            example_entries: true,
+            ..Default::default()
+        };
+        let result = TomlAnnotator::new(config).annotate(content, &annotations).unwrap();
+
+        assert_eq!(
+            result,
+            "# Map\n# Example entry: name = \"value\"\nenv = {}\n# Map\nlabels = { team = \"core\" }\n\n\
+             # Map\n# Example entry: name = \"value\"\n[headers]\n"
+        );
+    }
+
     #[test]
     fn test_include_default_value() {
         let content = "port = 8080\n";
@@ -360,4 +740,379 @@ port = 5432
 
         assert_snapshot!(result);
     }
+
+    #[test]
+    fn test_custom_formatter() {
+        use crate::annotator::{CommentContext, CommentFormatter};
+
+        #[derive(Debug)]
+        struct PathFormatter;
+
+        impl CommentFormatter for PathFormatter {
+            fn format(&self, annotation: &Annotation, context: &CommentContext<'_>) -> Vec<String> {
+                vec![format!(
+                    "# {} ({})",
+                    annotation.title.as_deref().unwrap_or_default(),
+                    context.path
+                )]
+            }
+        }
+
+        let content = "[server]\nport = 8080\n";
+        let annotations = make_annotations(&[("server.port", Some("Port"), None)]);
+
+        let config = AnnotatorConfig::default().with_formatter(PathFormatter);
+        let annotator = TomlAnnotator::new(config);
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_eq!(result, "[server]\n# Port (server.port)\nport = 8080\n");
+    }
+
+    #[test]
+    fn test_path_filters() {
+        let content = r#"[server]
+port = 8080
+host = "localhost"
+
+[database]
+url = "postgres://localhost"
+"#;
+        let annotations = make_annotations(&[
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), None),
+            ("server.host", Some("Host"), None),
+            ("database", Some("Database"), None),
+            ("database.url", Some("URL"), None),
+        ]);
+
+        let config = AnnotatorConfig {
+            include_paths: vec!["server.*".to_string(), "database.**".to_string()],
+            exclude_paths: vec!["server.host".to_string()],
+            ..Default::default()
+        };
+        let annotator = TomlAnnotator::new(config);
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let content = r#"[database]
+[database.connection]
+host = "localhost"
+"#;
+        let annotations = make_annotations(&[
+            ("database", Some("Database"), None),
+            ("database.connection", Some("Connection Settings"), None),
+            ("database.connection.host", Some("Host"), None),
+        ]);
+
+        let config = AnnotatorConfig {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let annotator = TomlAnnotator::new(config);
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_opt_out_keyword() {
+        let content = r#"name = "app"
+
+[internal]
+token = "secret"
+"#;
+        let schema = serde_json::json!({
+            "properties": {
+                "name": {"title": "Name"},
+                "internal": {
+                    "title": "Internal",
+                    "x-annotate": false,
+                    "properties": {"token": {"title": "Token"}}
+                }
+            }
+        });
+        let annotations = crate::extract_annotations(&schema);
+
+        let (result, report) = TomlAnnotator::new(AnnotatorConfig::default())
+            .annotate_with_report(content, &annotations)
+            .unwrap();
+        assert_eq!(result, "# Name\nname = \"app\"\n\n[internal]\ntoken = \"secret\"\n");
+        assert_eq!(report.excluded, vec!["internal", "internal.token"]);
+
+        let everything = TomlAnnotator::new(AnnotatorConfig {
+            opt_out_keyword: None,
+            ..Default::default()
+        });
+        assert!(everything.annotate(content, &annotations).unwrap().contains("# Token\n"));
+    }
+
+    #[test]
+    fn test_node_filter() {
+        let content = r#"name = "app"
+
+[server]
+port = 8080
+"#;
+        let annotations = make_annotations(&[
+            ("name", Some("Name"), None),
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), None),
+        ]);
+
+        let leaves = TomlAnnotator::new(AnnotatorConfig {
+            node_filter: NodeFilter::LeavesOnly,
+            ..Default::default()
+        });
+        let sections = TomlAnnotator::new(AnnotatorConfig {
+            node_filter: NodeFilter::SectionsOnly,
+            ..Default::default()
+        });
+
+        assert_snapshot!("node_filter_leaves", leaves.annotate(content, &annotations).unwrap());
+        assert_snapshot!("node_filter_sections", sections.annotate(content, &annotations).unwrap());
+    }
+
+    #[test]
+    fn test_schema_directive() {
+        let annotations = make_annotations(&[("port", Some("Port"), None)]);
+        let annotator = TomlAnnotator::new(AnnotatorConfig {
+            schema_directive: Some("https://example.com/schema.json".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            annotator.annotate("port = 8080\n", &annotations).unwrap(),
+            "#:schema https://example.com/schema.json\n\n# Port\nport = 8080\n"
+        );
+        // A document declaring its schema already keeps its directive
+        let declared = "#:schema ./schema.json\n\nport = 8080\n";
+        let result = annotator.annotate(declared, &annotations).unwrap();
+        assert_eq!(result.matches("#:schema").count(), 1);
+        assert!(result.contains("#:schema ./schema.json\n"));
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let content = "[server]\r\nport = 8080\r\n";
+        let annotations = make_annotations(&[
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), None),
+        ]);
+
+        let preserve = TomlAnnotator::new(AnnotatorConfig::default());
+        assert_eq!(
+            preserve.annotate(content, &annotations).unwrap(),
+            "# Server\r\n[server]\r\n# Port\r\nport = 8080\r\n"
+        );
+
+        let lf = TomlAnnotator::new(AnnotatorConfig {
+            line_ending: LineEnding::Lf,
+            ..Default::default()
+        });
+        assert_eq!(
+            lf.annotate(content, &annotations).unwrap(),
+            "# Server\n[server]\n# Port\nport = 8080\n"
+        );
+    }
+
+    #[test]
+    fn test_update_existing_comments() {
+        let content = "# Hand-written note\nport = 8080\n# Existing comment\nhost = \"localhost\"\n";
+        let config = AnnotatorConfig {
+            existing_comments: ExistingCommentBehavior::Update,
+            ..Default::default()
+        };
+        let annotator = TomlAnnotator::new(config);
+
+        let old = make_annotations(&[("port", Some("Port"), Some("Old description"))]);
+        let first = annotator.annotate(content, &old).unwrap();
+
+        let new = make_annotations(&[
+            ("port", Some("Port"), Some("New description")),
+            ("host", Some("Host"), None),
+        ]);
+        let second = annotator.annotate(&first, &new).unwrap();
+
+        // Re-running with the same annotations is a no-op
+        assert_eq!(annotator.annotate(&second, &new).unwrap(), second);
+        assert_snapshot!(second);
+    }
+
+    #[test]
+    fn test_update_with_lock() {
+        let annotate = |content: &str, lock: AnnotationLock, annotations: &AnnotationMap| {
+            let annotator = TomlAnnotator::new(AnnotatorConfig {
+                existing_comments: ExistingCommentBehavior::Update,
+                lock: Some(std::sync::Arc::new(lock)),
+                ..Default::default()
+            });
+            annotator.annotate_with_report(content, annotations).unwrap()
+        };
+        let old = make_annotations(&[("port", Some("Port"), Some("Old description"))]);
+        let new = make_annotations(&[("port", Some("Port"), Some("New description"))]);
+
+        let (first, report) = annotate("# Hand-written note\nport = 8080\n", AnnotationLock::new(), &old);
+        assert_eq!(first, "# Hand-written note\n# Port\n# Old description\nport = 8080\n");
+        let (second, report) = annotate(&first, report.lock, &new);
+        assert_eq!(second, "# Hand-written note\n# Port\n# New description\nport = 8080\n");
+
+        // An edited comment is no longer the generated one, so it's kept
+        let edited = second.replace("New description", "Edited");
+        let (third, _) = annotate(&edited, report.lock, &new);
+        assert_eq!(
+            third,
+            "# Hand-written note\n# Port\n# Edited\n# Port\n# New description\nport = 8080\n"
+        );
+    }
+
+    #[test]
+    fn test_strict_missing_paths() {
+        let content = "[server]\nprot = 8080\nlimits = { burst = 5 }\n";
+        let annotations = make_annotations(&[
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), None),
+            ("server.limits.burst", Some("Burst"), None),
+            ("database", Some("Database"), None),
+            ("database.url", Some("URL"), None),
+        ]);
+
+        let (_, report) = TomlAnnotator::new(AnnotatorConfig::default())
+            .annotate_with_report(content, &annotations)
+            .unwrap();
+        assert_eq!(report.missing, vec!["database", "server.port"]);
+
+        let strict = TomlAnnotator::new(AnnotatorConfig {
+            strict: true,
+            ..Default::default()
+        });
+        let error = strict.annotate(content, &annotations).unwrap_err();
+        assert!(matches!(error.kind, AnnotatorErrorKind::UnmatchedAnnotations));
+        assert!(error.to_string().contains("missing paths: database, server.port"));
+    }
+
+    #[test]
+    fn test_unsupported_paths() {
+        let content = "limits = { burst = 5 }\n\n[[servers]]\nport = 80\n";
+        let annotations = make_annotations(&[
+            ("limits", Some("Limits"), None),
+            ("limits.burst", Some("Burst"), None),
+            ("servers", Some("Servers"), None),
+            ("servers.port", Some("Port"), None),
+        ]);
+
+        let (_, report) = TomlAnnotator::new(AnnotatorConfig::default())
+            .annotate_with_report(content, &annotations)
+            .unwrap();
+        assert_eq!(report.unsupported, vec!["limits.burst", "servers.port"]);
+
+        let strict = TomlAnnotator::new(AnnotatorConfig {
+            fail_on_unsupported: true,
+            exclude_paths: vec!["limits.**".to_string()],
+            ..Default::default()
+        });
+        let error = strict.annotate(content, &annotations).unwrap_err();
+        assert!(matches!(error.kind, AnnotatorErrorKind::Unsupported));
+        assert!(error.to_string().contains("unsupported paths: servers.port"));
+    }
+
+    #[test]
+    fn test_verify_round_trip() {
+        use crate::annotator::{CommentContext, CommentFormatter};
+
+        // A formatter emitting a non-comment line corrupts the document
+        #[derive(Debug)]
+        struct BrokenFormatter;
+
+        impl CommentFormatter for BrokenFormatter {
+            fn format(&self, _: &Annotation, _: &CommentContext<'_>) -> Vec<String> {
+                vec!["injected = true".to_string()]
+            }
+        }
+
+        let content = "[server]\nport = 8080\n";
+        let annotations = make_annotations(&[("server.port", Some("Port"), None)]);
+
+        let unchecked = TomlAnnotator::new(AnnotatorConfig::default().with_formatter(BrokenFormatter));
+        assert!(unchecked.annotate(content, &annotations).is_ok());
+
+        let verified = TomlAnnotator::new(AnnotatorConfig {
+            verify_round_trip: true,
+            ..AnnotatorConfig::default().with_formatter(BrokenFormatter)
+        });
+        let error = verified.annotate(content, &annotations).unwrap_err();
+        assert!(matches!(error.kind, AnnotatorErrorKind::RoundTrip));
+
+        // Well-behaved annotation passes verification
+        let config = AnnotatorConfig {
+            verify_round_trip: true,
+            ..Default::default()
+        };
+        assert!(TomlAnnotator::new(config).annotate(content, &annotations).is_ok());
+    }
+
+    #[test]
+    fn test_annotate_document() {
+        let mut doc: DocumentMut = "[server]\nport = 8080\n".parse().unwrap();
+        let annotations = make_annotations(&[("server.port", Some("Port"), None)]);
+
+        let annotator = TomlAnnotator::new(AnnotatorConfig::default());
+        let report = annotator.annotate_document(&mut doc, &annotations).unwrap();
+        assert_eq!(report.applied, vec!["server.port"]);
+
+        // The document stays editable after annotation
+        doc["server"]["host"] = toml_edit::value("localhost");
+        assert_eq!(doc.to_string(), "[server]\n# Port\nport = 8080\nhost = \"localhost\"\n");
+    }
+
+    /// Records every event, vetoes `server.host` and rewrites `server.port`
+    #[derive(Debug, Default)]
+    struct Recorder(std::sync::Mutex<Vec<(String, Option<usize>, AnnotationDecision)>>);
+
+    impl AnnotationObserver for Recorder {
+        fn observe(&self, event: &AnnotationEvent<'_>, comment: &mut Vec<String>) {
+            self.0.lock().unwrap().push((event.path.to_string(), event.line, event.decision));
+            match event.path {
+                "server.host" => comment.clear(),
+                "server.port" => *comment = vec!["# Port (overridden)".to_string()],
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let content = "[server]\nport = 8080\nhost = \"localhost\"\ntls = false\n";
+        let annotations = make_annotations(&[
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), None),
+            ("server.host", Some("Host"), None),
+        ]);
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let config = AnnotatorConfig {
+            observer: Some(recorder.clone()),
+            ..Default::default()
+        };
+
+        let annotator = TomlAnnotator::new(config);
+        let (result, report) = annotator.annotate_with_report(content, &annotations).unwrap();
+        assert_eq!(report.applied, vec!["server", "server.port"]);
+        assert_eq!(report.excluded, vec!["server.host"]);
+        assert_eq!(report.unmatched, vec!["server.tls"]);
+
+        let events = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            vec![
+                ("server".to_string(), Some(1), AnnotationDecision::Apply),
+                ("server.port".to_string(), Some(2), AnnotationDecision::Apply),
+                ("server.host".to_string(), Some(3), AnnotationDecision::Apply),
+                ("server.tls".to_string(), Some(4), AnnotationDecision::Unmatched),
+            ]
+        );
+        assert_snapshot!(result);
+    }
 }
@@ -1,11 +1,29 @@
-use super::{Annotator, AnnotatorConfig, ExistingCommentBehavior};
-use crate::error::{AnnotatorError, AnnotatorErrorKind, Error};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use serde::de::{Deserialize, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+
+use super::{
+    category_banner, check_missing, check_round_trip, check_unsupported, decide, select_annotation,
+    AnnotationReport, Annotator,
+    AnnotatorConfig, CommentContext, CommentSpacing, DuplicateKeys, ExistingCommentBehavior, LineEnding,
+    YamlVersion,
+};
+use crate::discover::{directive_reference, schema_directive};
+use crate::error::{yaml_parse_error, AnnotatorError, AnnotatorErrorKind, Error, SourceLocation};
+use crate::format::TargetFormat;
 use crate::schema::{Annotation, AnnotationMap};
 
 /// YAML document annotator using string-based line injection
 ///
 /// Since yaml-edit doesn't support comment injection, we use a string-based
 /// approach that tracks indentation to map lines to paths.
+///
+/// Like [`TomlAnnotator`](super::TomlAnnotator), it can be shared between
+/// threads or cloned cheaply.
+#[derive(Debug, Clone)]
 pub struct YamlAnnotator {
     config: AnnotatorConfig,
 }
@@ -15,111 +33,884 @@ impl YamlAnnotator {
         Self { config }
     }
 
-    fn format_comment(&self, annotation: &Annotation, indent: usize) -> Option<String> {
-        let mut lines = Vec::new();
-        let indent_str = " ".repeat(indent);
+    fn render(&self, annotation: &Annotation, indent: usize, is_section: bool, is_empty: bool) -> Vec<String> {
+        let context = CommentContext {
+            path: &annotation.path,
+            indent,
+            is_section,
+            is_empty,
+            format: TargetFormat::Yaml,
+            config: &self.config,
+        };
+        self.config.formatter.format(annotation, &context)
+    }
 
-        if self.config.include_title {
-            if let Some(title) = &annotation.title {
-                lines.push(format!("{}# {}", indent_str, title));
+    /// Annotate a document, also reporting what happened to each path
+    pub fn annotate_with_report(
+        &self,
+        content: &str,
+        annotations: &AnnotationMap,
+    ) -> Result<(String, AnnotationReport), AnnotatorError> {
+        let keys = self.scan_keys(content)?;
+        // Validate YAML syntax by attempting to parse
+        let document = self.parse(content).map_err(|e| yaml_parse_error(content, e))?;
+        self.annotate_parsed(content, &document, keys, annotations)
+    }
+
+    /// Annotate an already parsed document in place
+    ///
+    /// The document's [`value`](YamlDocument::value) is used to tell which
+    /// paths it has instead of parsing it again. The YAML backend works on
+    /// source text, so the source is replaced by the annotated one; only
+    /// comments are added, so the value is unchanged.
+    pub fn annotate_document(
+        &self,
+        doc: &mut YamlDocument,
+        annotations: &AnnotationMap,
+    ) -> Result<AnnotationReport, AnnotatorError> {
+        let keys = self.scan_keys(&doc.source)?;
+        let (source, report) = self.annotate_parsed(&doc.source, &doc.value, keys, annotations)?;
+        doc.source = source;
+        Ok(report)
+    }
+
+    /// Scan the keys of a document, failing on repeated keys if they're an
+    /// error
+    fn scan_keys(&self, content: &str) -> Result<KeyScan, AnnotatorError> {
+        let keys = scan_keys(content, self.config.yaml_version)?;
+        if self.config.duplicate_keys == DuplicateKeys::Error {
+            if let Some((path, lines)) = keys.duplicates.first() {
+                return Err(duplicate_key_error(content, path, lines));
             }
         }
+        Ok(keys)
+    }
+
+    /// Annotate the lines of `content`, whose parsed value is `document`
+    fn annotate_parsed(
+        &self,
+        content: &str,
+        document: &serde_yaml::Value,
+        keys: KeyScan,
+        annotations: &AnnotationMap,
+    ) -> Result<(String, AnnotationReport), AnnotatorError> {
+        let KeyScan { duplicates, quoted } = keys;
+        let mut report = AnnotationReport::default();
+        // Lines of the occurrences of repeated keys the policy leaves alone
+        let mut skip_lines = HashSet::new();
+        for (path, lines) in duplicates {
+            tracing::debug!(path, ?lines, "duplicate key");
+            let skipped = match self.config.duplicate_keys {
+                DuplicateKeys::First => &lines[1..],
+                DuplicateKeys::Last => &lines[..lines.len() - 1],
+                _ => &[],
+            };
+            skip_lines.extend(skipped.iter().copied());
+            report.duplicate_keys.push(path);
+        }
+        let mut document_paths = HashSet::new();
+        let mut empty_paths = HashSet::new();
+        let mut complex_keys = Vec::new();
+        let version = self.config.yaml_version;
+        let keys = KeyNames { version, quoted: &quoted };
+        collect_paths(document, keys, "", &mut document_paths, &mut empty_paths, &mut complex_keys);
+        check_missing(&self.config, annotations, &document_paths, &mut report)?;
+
+        let mut output = Vec::with_capacity(content.len());
+        self.annotate_lines(content.as_bytes(), annotations, &skip_lines, &empty_paths, &mut output, &mut report)?;
+        check_unsupported(&self.config, annotations, &document_paths, complex_keys, &mut report)?;
+        // Both sides are UTF-8 strings, so the output is too
+        let mut output = String::from_utf8(output).expect("annotated YAML is valid UTF-8");
+        if self.config.normalize {
+            output = normalize(&output, self.config.yaml_version)?;
+        }
+
+        if self.config.verify_round_trip {
+            check_round_trip(document, self.parse(&output))?;
+        }
+        Ok((output, report))
+    }
+
+    /// Parse a whole document, taking the last value of repeated keys unless
+    /// they're an error
+    fn parse(&self, content: &str) -> Result<serde_yaml::Value, serde_yaml::Error> {
+        match self.config.duplicate_keys {
+            DuplicateKeys::Error => serde_yaml::from_str(content),
+            _ => serde_yaml::from_str::<LenientValue>(content).map(|value| value.0),
+        }
+    }
+
+    /// Annotate a document read line by line, writing the result as it goes
+    ///
+    /// Only the comments directly above the current line are held in memory,
+    /// so arbitrarily large documents can be annotated. The document isn't
+    /// parsed as a whole, which means invalid YAML isn't rejected,
+    /// [`AnnotationReport::missing`] and [`AnnotationReport::unsupported`]
+    /// stay empty, and [`strict`](AnnotatorConfig::strict),
+    /// [`fail_on_unsupported`](AnnotatorConfig::fail_on_unsupported) and
+    /// [`verify_round_trip`](AnnotatorConfig::verify_round_trip) are not
+    /// supported; use [`annotate_with_report`](Self::annotate_with_report)
+    /// for those. Every occurrence of a repeated key is annotated, whatever
+    /// the [`duplicate_keys`](AnnotatorConfig::duplicate_keys) policy.
+    pub fn annotate_stream(
+        &self,
+        input: impl BufRead,
+        annotations: &AnnotationMap,
+        output: impl Write,
+    ) -> Result<AnnotationReport, AnnotatorError> {
+        let mut report = AnnotationReport::default();
+        self.annotate_lines(input, annotations, &HashSet::new(), &HashSet::new(), output, &mut report)?;
+        Ok(report)
+    }
+
+    /// Copy lines from `input` to `output`, inserting comments above keys
+    ///
+    /// Comment and blank lines are held back until the next content line, so
+    /// existing comments above a key can still be inspected and rewritten.
+    /// Keys on `skip_lines` are left unannotated, and keys at `empty_paths`
+    /// hold an empty map or no value.
+    fn annotate_lines(
+        &self,
+        mut input: impl BufRead,
+        annotations: &AnnotationMap,
+        skip_lines: &HashSet<usize>,
+        empty_paths: &HashSet<String>,
+        mut output: impl Write,
+        report: &mut AnnotationReport,
+    ) -> Result<(), AnnotatorError> {
+        let io_error = |e| Error::new(AnnotatorErrorKind::Io).with_source(e);
+        let mut tracker = PathTracker::new(self.config.yaml_version);
+        // Category of the last annotated key of each mapping, by mapping id
+        let mut categories: HashMap<usize, Option<String>> = HashMap::new();
+        let mut pending: Vec<String> = Vec::new();
+        let mut line_ending = None;
+        let mut lines_written = false;
+        // The leading comments are held back until the first content line,
+        // which is when a missing schema directive can be added above them
+        let mut directive = self.config.schema_directive.as_deref();
+        let mut ends_with_newline = false;
+        let mut line_number = 0;
+        let mut buf = String::new();
+
+        // Lines are joined with newlines, adding a final one only if the input had it
+        let mut write_line = |output: &mut dyn Write, line: &str, newline: &str| {
+            if lines_written {
+                output.write_all(newline.as_bytes())?;
+            }
+            lines_written = true;
+            output.write_all(line.as_bytes())
+        };
 
-        if self.config.include_description {
-            if let Some(desc) = &annotation.description {
-                let width = self.config.max_line_width.unwrap_or(78).saturating_sub(indent + 2);
-                for line in textwrap::wrap(desc, width) {
-                    lines.push(format!("{}# {}", indent_str, line));
+        loop {
+            buf.clear();
+            if input.read_line(&mut buf).map_err(io_error)? == 0 {
+                break;
+            }
+            line_number += 1;
+            // The first line decides the line ending when preserving it
+            let newline = *line_ending.get_or_insert_with(|| {
+                match self.config.line_ending.resolve(&buf) {
+                    LineEnding::Crlf => "\r\n",
+                    _ => "\n",
                 }
+            });
+            ends_with_newline = buf.ends_with('\n');
+            let line = match buf.strip_suffix('\n') {
+                Some(line) => line.strip_suffix('\r').unwrap_or(line),
+                None => &buf,
+            };
+
+            let entry = match tracker.scan(line) {
+                Ok(ScannedLine::Trivia) => {
+                    // Hold back empty lines and comments until the next key
+                    pending.push(line.to_string());
+                    continue;
+                }
+                Ok(ScannedLine::Key(entry)) => Some(entry),
+                Ok(ScannedLine::Other) => None,
+                Err(e) => return Err(e.at(line_number, line)),
+            };
+            if let Some(reference) = directive.take() {
+                insert_directive(&mut pending, reference);
+            }
+            if let Some(entry) = entry.filter(|_| !skip_lines.contains(&line_number)) {
+                let category = categories.entry(entry.mapping).or_default();
+                let is_empty = empty_paths.contains(entry.path);
+                self.annotate_key(&entry, is_empty, line_number, &mut pending, category, annotations, report);
+            }
+
+            for held in pending.drain(..) {
+                write_line(&mut output, &held, newline).map_err(io_error)?;
             }
+            write_line(&mut output, line, newline).map_err(io_error)?;
         }
 
-        if self.config.include_default {
-            if let Some(default) = &annotation.default {
-                lines.push(format!("{}# Default: {}", indent_str, default));
+        let newline = line_ending.unwrap_or("\n");
+        if let Some(reference) = directive.take() {
+            insert_directive(&mut pending, reference);
+        }
+        for held in pending.drain(..) {
+            write_line(&mut output, &held, newline).map_err(io_error)?;
+        }
+        if ends_with_newline {
+            output.write_all(newline.as_bytes()).map_err(io_error)?;
+        }
+        output.flush().map_err(io_error)
+    }
+
+    /// Add the comment for a key to the lines held above it
+    #[allow(clippy::too_many_arguments)]
+    fn annotate_key(
+        &self,
+        entry: &LineEntry,
+        is_empty: bool,
+        line: usize,
+        above: &mut Vec<String>,
+        category: &mut Option<String>,
+        annotations: &AnnotationMap,
+        report: &mut AnnotationReport,
+    ) {
+        let Some(ann) = select_annotation(
+            &self.config,
+            annotations,
+            entry.path,
+            entry.is_section,
+            Some(line),
+            report,
+        ) else {
+            return;
+        };
+        let indent = " ".repeat(entry.indent);
+        let indented = |comment: &[String]| -> Vec<String> {
+            comment.iter().map(|line| format!("{}{}", indent, line)).collect()
+        };
+        let mut comment = self.render(ann, entry.indent, entry.is_section, is_empty);
+        if let Some(banner) = category_banner(&self.config, ann, category) {
+            comment.insert(0, banner);
+        }
+
+        // A comment immediately above at the key's indentation belongs to the key
+        let has_existing_comment = above
+            .last()
+            .is_some_and(|prev| is_comment_at(prev, entry.indent));
+
+        // Where the new comment goes, or None to leave the key alone
+        let insert_at = match (self.config.existing_comments, has_existing_comment) {
+            _ if comment.is_empty() => None,
+            (ExistingCommentBehavior::Skip, true) => None,
+            (ExistingCommentBehavior::Update, _) => {
+                self.update_position(entry, above, &indented(&comment))
+            }
+            (ExistingCommentBehavior::Replace, true) => {
+                // Replace the existing comment block with the new one, keeping
+                // blank lines and comments separated from it by one
+                Some(comment_block_start(above, entry.indent))
+            }
+            // Prepend (default), Append or no existing comment: the new
+            // comment goes right above the key
+            _ => Some(above.len()),
+        };
+
+        decide(&self.config, ann, Some(line), insert_at.is_some(), &mut comment, report);
+        let Some(insert_at) = insert_at.filter(|_| !comment.is_empty()) else {
+            return;
+        };
+        above.truncate(insert_at);
+        match self.config.existing_comments {
+            ExistingCommentBehavior::Update if self.config.lock.is_none() => {
+                above.push(format!("{}# {}", indent, self.config.generated_marker));
             }
+            ExistingCommentBehavior::Prepend | ExistingCommentBehavior::Append
+                if has_existing_comment && self.config.comment_spacing == CommentSpacing::BlankLine =>
+            {
+                above.push(String::new());
+            }
+            _ => {}
         }
+        above.extend(indented(&comment));
+    }
 
-        if lines.is_empty() {
-            None
-        } else {
-            Some(lines.join("\n"))
+    /// Find where an [`ExistingCommentBehavior::Update`] writes its block
+    ///
+    /// A previously generated block starts at the marker line and runs up to
+    /// the key; it is replaced while any hand-written comments above it are
+    /// kept. With a lock, the block is the lines it recorded for the key
+    /// instead. Without either, a block identical to the new comment is left
+    /// alone (returning None) and otherwise the marked comment is added right
+    /// above the key.
+    fn update_position(
+        &self,
+        entry: &LineEntry,
+        above: &[String],
+        comment: &[String],
+    ) -> Option<usize> {
+        let marker = format!("# {}", self.config.generated_marker);
+        let block_start = comment_block_start(above, entry.indent);
+        let existing = &above[block_start..];
+
+        let locked = self.config.lock.as_ref().and_then(|lock| lock.generated_lines(entry.path, existing));
+        if let Some(count) = locked {
+            return Some(above.len() - count);
+        }
+        match existing.iter().rposition(|line| line.trim() == marker) {
+            Some(pos) => Some(block_start + pos),
+            None if existing.ends_with(comment) => None,
+            None => Some(above.len()),
         }
     }
+}
 
-    /// Build a map of line numbers to (path, indent, has_existing_comment) for YAML content
-    fn build_line_path_map(&self, content: &str) -> Vec<(usize, String, usize, bool)> {
-        let mut result = Vec::new();
-        let mut path_stack: Vec<(String, usize)> = Vec::new();
-        let lines: Vec<&str> = content.lines().collect();
+/// A YAML document parsed for annotating in place
+///
+/// Keeps the source text alongside the parsed value, since comments are
+/// spliced into the text rather than a document tree. Repeated mapping keys
+/// take the last value, as with [`DuplicateKeys::Last`].
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::YamlDocument;
+///
+/// let doc: YamlDocument = "port: 8080\n".parse().unwrap();
+/// assert_eq!(doc.value()["port"], 8080);
+/// assert_eq!(doc.to_string(), "port: 8080\n");
+/// ```
+#[derive(Debug, Clone)]
+pub struct YamlDocument {
+    source: String,
+    value: serde_yaml::Value,
+}
 
-        for (line_num, line) in lines.iter().enumerate() {
-            // Skip empty lines and comments
-            if line.trim().is_empty() || line.trim().starts_with('#') {
-                continue;
+impl YamlDocument {
+    /// The parsed value of the document
+    pub fn value(&self) -> &serde_yaml::Value {
+        &self.value
+    }
+
+    /// The source text of the document, including any comments
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl std::str::FromStr for YamlDocument {
+    type Err = AnnotatorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = serde_yaml::from_str::<LenientValue>(s).map_err(|e| yaml_parse_error(s, e))?;
+        Ok(Self {
+            source: s.to_string(),
+            value: value.0,
+        })
+    }
+}
+
+impl fmt::Display for YamlDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// A mapping key found while scanning the YAML lines
+struct LineEntry<'a> {
+    /// Dot-separated path of the key
+    path: &'a str,
+    /// Indentation of the key in columns
+    indent: usize,
+    /// Whether the key opens a nested block rather than holding an inline value
+    is_section: bool,
+    /// Identifies the mapping holding the key
+    mapping: usize,
+}
+
+/// What a line of a YAML document holds, as far as paths are concerned
+enum ScannedLine<'a> {
+    /// A blank or comment line
+    Trivia,
+    /// A mapping key
+    Key(LineEntry<'a>),
+    /// Anything else, such as sequence items and the text of multi-line values
+    Other,
+}
+
+/// A mapping opened by a key or a sequence item
+struct OpenMapping {
+    /// Indentation of the line opening it
+    indent: usize,
+    /// Length of the path before it was entered
+    parent_len: usize,
+    /// Whether it's an item of a block sequence rather than a key's value
+    item: bool,
+    /// Indentation of its keys, once one is seen
+    key_indent: Option<usize>,
+    id: usize,
+}
+
+/// Follows the indentation of a YAML document line by line to find the path
+/// of each key
+///
+/// Indentation is counted in spaces as YAML requires, so keys indented with
+/// tabs or out of line with their siblings are rejected rather than given a
+/// wrong path. A block sequence may sit at its parent key's indentation, and
+/// the text of block scalars, multi-line quoted strings and flow collections
+/// is skipped. Keys are named as [`key_name`] reads them.
+#[derive(Default)]
+struct PathTracker {
+    /// How plain keys are read
+    version: YamlVersion,
+    /// Dot-separated path of the innermost open mapping
+    path: String,
+    /// Open mappings, innermost last
+    open: Vec<OpenMapping>,
+    /// Indentation of the root mapping's keys, once one is seen
+    root_indent: Option<usize>,
+    root_id: usize,
+    /// The last id given to a mapping
+    last_id: usize,
+    /// Length of the path before the last key, when it holds a value
+    leaf_parent_len: Option<usize>,
+    /// Indentation of the line starting the block scalar being skipped
+    block_scalar: Option<usize>,
+    /// State of a quoted string or flow collection spanning lines
+    flow: FlowState,
+    /// Paths of quoted keys that would be named differently if they were
+    /// plain, which only happens with YAML 1.1
+    quoted: HashSet<String>,
+}
+
+/// A line whose indentation doesn't follow YAML's rules
+struct IndentationError {
+    /// One-based column of the offending character
+    column: usize,
+    message: String,
+}
+
+impl IndentationError {
+    fn at(self, line_number: usize, line: &str) -> AnnotatorError {
+        Error::new(AnnotatorErrorKind::Parse)
+            .add_context(self.message)
+            .with_location(SourceLocation {
+                line: line_number,
+                column: self.column,
+                excerpt: line.to_string(),
+            })
+    }
+}
+
+impl PathTracker {
+    fn new(version: YamlVersion) -> Self {
+        Self { version, ..Self::default() }
+    }
+
+    /// Classify the next line of the document
+    fn scan(&mut self, line: &str) -> Result<ScannedLine<'_>, IndentationError> {
+        if let Some(parent_len) = self.leaf_parent_len.take() {
+            self.path.truncate(parent_len);
+        }
+        let trimmed = line.trim();
+        let indent = line.len() - line.trim_start_matches(' ').len();
+
+        if let Some(scalar_indent) = self.block_scalar {
+            if trimmed.is_empty() || indent > scalar_indent {
+                return Ok(ScannedLine::Other);
             }
+            self.block_scalar = None;
+        }
+        if self.flow.is_open() {
+            self.flow.feed(line);
+            return Ok(ScannedLine::Other);
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return Ok(ScannedLine::Trivia);
+        }
+        // A new document starts over at the root
+        if line == "..." || line == "---" || line.starts_with("--- ") {
+            let last_id = self.last_id + 1;
+            *self = Self {
+                version: self.version,
+                quoted: std::mem::take(&mut self.quoted),
+                root_id: last_id,
+                last_id,
+                ..Self::default()
+            };
+            return Ok(ScannedLine::Other);
+        }
 
-            // Calculate indentation
-            let indent = line.len() - line.trim_start().len();
+        let content = line.trim_start();
+        let is_item = content == "-" || content.starts_with("- ");
+        if line[..line.len() - content.len()].contains('\t')
+            && (is_item || split_yaml_key(content).is_some())
+        {
+            return Err(IndentationError {
+                column: indent + 1,
+                message: "tab in indentation, YAML only allows spaces".to_string(),
+            });
+        }
 
-            // Pop path components that are at same or deeper indentation
-            while let Some((_, prev_indent)) = path_stack.last() {
-                if indent <= *prev_indent {
-                    path_stack.pop();
-                } else {
-                    break;
-                }
+        // Leave mappings at the same or deeper indentation, except that a
+        // block sequence may sit at its parent key's indentation
+        while let Some(open) = self.open.last() {
+            if indent > open.indent || (indent == open.indent && is_item && !open.item) {
+                break;
+            }
+            self.path.truncate(open.parent_len);
+            self.open.pop();
+        }
+
+        if is_item {
+            // The item is a mapping of its own, with keys lined up after the dash
+            let rest = &content[1..];
+            let item = rest.trim_start();
+            let key_indent = indent + 1 + (rest.len() - item.len());
+            let at_root = self.open.is_empty();
+            let id = self.next_id();
+            let key = split_yaml_key(item);
+            self.open.push(OpenMapping {
+                indent,
+                parent_len: self.path.len(),
+                item: true,
+                key_indent: key.map(|_| key_indent),
+                id,
+            });
+            let Some((key, value)) = key else {
+                self.start_value(item, indent);
+                return Ok(ScannedLine::Other);
+            };
+            let parent_len = self.path.len();
+            self.push_key(key);
+            let is_section = opens_mapping(value);
+            if is_section {
+                let id = self.next_id();
+                self.open.push(OpenMapping {
+                    indent: key_indent,
+                    parent_len,
+                    item: false,
+                    key_indent: None,
+                    id,
+                });
+            } else {
+                self.leaf_parent_len = Some(parent_len);
+                self.start_value(value, key_indent);
+            }
+            // The key on the dash line can only be commented above the dash,
+            // which in a nested sequence would sit among the parent's items,
+            // so it's only given a path in a document that is a sequence
+            if !at_root {
+                return Ok(ScannedLine::Other);
             }
+            return Ok(ScannedLine::Key(LineEntry {
+                path: &self.path,
+                indent,
+                is_section,
+                mapping: id,
+            }));
+        }
 
-            // Extract key from line (handle "key:" and "key: value" formats)
-            if let Some(key) = extract_yaml_key(line) {
-                // Build current path
-                let path = if path_stack.is_empty() {
-                    key.clone()
-                } else {
-                    let parent_path: Vec<_> = path_stack.iter().map(|(k, _)| k.as_str()).collect();
-                    format!("{}.{}", parent_path.join("."), key)
-                };
+        let Some((key, value)) = split_yaml_key(content) else {
+            return Ok(ScannedLine::Other);
+        };
+        let (expected, mapping) = match self.open.last_mut() {
+            Some(open) => (&mut open.key_indent, open.id),
+            None => (&mut self.root_indent, self.root_id),
+        };
+        match *expected {
+            Some(expected) if expected != indent => {
+                return Err(IndentationError {
+                    column: indent + 1,
+                    message: format!(
+                        "inconsistent indentation, expected {} spaces like the keys before it",
+                        expected
+                    ),
+                });
+            }
+            _ => *expected = Some(indent),
+        }
 
-                // Check if there's an existing comment immediately before this line
-                let has_existing_comment = self.has_preceding_comment(&lines, line_num, indent);
+        let parent_len = self.path.len();
+        self.push_key(key);
+        let is_section = opens_mapping(value);
+        if is_section {
+            let id = self.next_id();
+            self.open.push(OpenMapping { indent, parent_len, item: false, key_indent: None, id });
+        } else {
+            self.leaf_parent_len = Some(parent_len);
+            self.start_value(value, indent);
+        }
+        Ok(ScannedLine::Key(LineEntry {
+            path: &self.path,
+            indent,
+            is_section,
+            mapping,
+        }))
+    }
 
-                result.push((line_num, path.clone(), indent, has_existing_comment));
+    fn push_key(&mut self, key: &str) {
+        if !self.path.is_empty() {
+            self.path.push('.');
+        }
+        let name = key_name(key, self.version);
+        self.path.push_str(&name);
+        if key.starts_with(['"', '\'']) && plain_key_name(&name, self.version) != name {
+            self.quoted.insert(self.path.clone());
+        }
+    }
+
+    fn next_id(&mut self) -> usize {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    /// Note a value that continues on the following lines
+    fn start_value(&mut self, value: &str, indent: usize) {
+        let value = value.trim_start();
+        let indicators = value[1.min(value.len())..].split(" #").next().unwrap_or_default();
+        if value.starts_with(['|', '>'])
+            && indicators.trim_end().chars().all(|c| c.is_ascii_digit() || c == '+' || c == '-')
+        {
+            self.block_scalar = Some(indent);
+        } else {
+            self.flow.feed(value);
+        }
+    }
+}
+
+/// Tracks quoted strings and flow collections (`[...]`, `{...}`) left open at
+/// the end of a line
+#[derive(Default)]
+struct FlowState {
+    quote: Option<char>,
+    depth: usize,
+    /// The last non-space character of the value, across lines
+    last: Option<char>,
+}
+
+impl FlowState {
+    fn is_open(&self) -> bool {
+        self.quote.is_some() || self.depth > 0
+    }
+
+    /// Scan a line of a value
+    fn feed(&mut self, text: &str) {
+        if !self.is_open() {
+            self.last = None;
+        }
+        let mut chars = text.chars();
+        let mut prev: Option<char> = None;
+        while let Some(c) = chars.next() {
+            let after_space = prev.is_none_or(char::is_whitespace);
+            // Quotes and brackets only have a meaning at the start of the
+            // value or of an entry of a flow collection; elsewhere they're
+            // part of a plain scalar
+            let token_start = match self.last {
+                None => true,
+                Some(last) => self.depth > 0 && "[{,:".contains(last),
+            };
+            match (self.quote, c) {
+                (Some('"'), '\\') => {
+                    chars.next();
+                }
+                (Some(quote), c) if c == quote => self.quote = None,
+                (Some(_), _) => {}
+                (None, '#') if after_space => return,
+                (None, '"' | '\'') if token_start => self.quote = Some(c),
+                (None, '[' | '{') if token_start => self.depth += 1,
+                (None, ']' | '}') if self.depth > 0 => self.depth -= 1,
+                _ => {}
+            }
+            if !c.is_whitespace() {
+                self.last = Some(c);
+            }
+            prev = Some(c);
+        }
+    }
+}
 
-                // Check if this line starts a nested object (ends with ":" or has nested content)
-                if line.trim().ends_with(':') || is_mapping_start(line) {
-                    path_stack.push((key, indent));
+/// Collect the comment lines directly above each key, by path
+///
+/// Only comments at the key's own indentation and adjoining it are taken,
+/// so comments separated from it by a blank line are left out.
+pub(crate) fn key_comments(content: &str) -> Result<Vec<(String, Vec<String>)>, AnnotatorError> {
+    let mut comments = Vec::new();
+    let mut tracker = PathTracker::default();
+    let mut above: Vec<&str> = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        match tracker.scan(line).map_err(|e| e.at(index + 1, line))? {
+            ScannedLine::Trivia => {
+                above.push(line);
+                continue;
+            }
+            ScannedLine::Key(entry) => {
+                let block: Vec<String> = above
+                    .iter()
+                    .rev()
+                    .take_while(|line| is_comment_at(line, entry.indent))
+                    .map(|line| line.trim().to_string())
+                    .collect();
+                if !block.is_empty() {
+                    comments.push((entry.path.to_string(), block.into_iter().rev().collect()));
                 }
             }
+            ScannedLine::Other => {}
         }
+        above.clear();
+    }
+    Ok(comments)
+}
 
-        result
+/// The one-based line of every key of a document, by path
+///
+/// Keys appearing more than once are mapped to their first line.
+pub(crate) fn key_line_map(content: &str) -> Result<HashMap<String, usize>, AnnotatorError> {
+    let mut tracker = PathTracker::default();
+    let mut lines = HashMap::new();
+    for (index, line) in content.lines().enumerate() {
+        if let ScannedLine::Key(entry) = tracker.scan(line).map_err(|e| e.at(index + 1, line))? {
+            lines.entry(entry.path.to_string()).or_insert(index + 1);
+        }
     }
+    Ok(lines)
+}
 
-    /// Check if there's a comment line immediately preceding the given line
-    /// that belongs to this key (at the same or appropriate indentation)
-    fn has_preceding_comment(&self, lines: &[&str], line_num: usize, key_indent: usize) -> bool {
-        if line_num == 0 {
-            return false;
+/// What scanning the keys of a document line by line finds
+struct KeyScan {
+    /// Keys that appear more than once in the same mapping, with the line
+    /// numbers of each occurrence, in order of their first occurrence
+    duplicates: Vec<(String, Vec<usize>)>,
+    /// Paths of quoted keys a plain key of the same text would be named
+    /// differently from
+    quoted: HashSet<String>,
+}
+
+/// Scan the keys of a document for repeated and quoted keys
+fn scan_keys(content: &str, version: YamlVersion) -> Result<KeyScan, AnnotatorError> {
+    let mut tracker = PathTracker::new(version);
+    // Occurrences of each key, by the mapping holding it
+    let mut keys: Vec<((usize, String), Vec<usize>)> = Vec::new();
+    let mut index_of: HashMap<(usize, String), usize> = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let ScannedLine::Key(entry) = tracker.scan(line).map_err(|e| e.at(index + 1, line))? else {
+            continue;
+        };
+        let key = (entry.mapping, entry.path.to_string());
+        match index_of.get(&key) {
+            Some(&i) => keys[i].1.push(index + 1),
+            None => {
+                index_of.insert(key.clone(), keys.len());
+                keys.push((key, vec![index + 1]));
+            }
         }
+    }
+    let duplicates = keys
+        .into_iter()
+        .filter(|(_, lines)| lines.len() > 1)
+        .map(|((_, path), lines)| (path, lines))
+        .collect();
+    Ok(KeyScan {
+        duplicates,
+        quoted: tracker.quoted,
+    })
+}
+
+/// A parse error for the second occurrence of a repeated key
+fn duplicate_key_error(content: &str, path: &str, lines: &[usize]) -> AnnotatorError {
+    let line = content.lines().nth(lines[1] - 1).unwrap_or_default();
+    Error::new(AnnotatorErrorKind::Parse)
+        .add_context(format!("duplicate key `{}`, first defined at line {}", path, lines[0]))
+        .with_location(SourceLocation {
+            line: lines[1],
+            column: line.len() - line.trim_start().len() + 1,
+            excerpt: line.to_string(),
+        })
+}
+
+/// A YAML value parsed without rejecting repeated mapping keys, the last of
+/// which wins
+struct LenientValue(serde_yaml::Value);
+
+impl<'de> Deserialize<'de> for LenientValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(LenientVisitor).map(LenientValue)
+    }
+}
+
+struct LenientVisitor;
+
+impl<'de> Visitor<'de> for LenientVisitor {
+    type Value = serde_yaml::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any YAML value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Self::Value, E> {
+        Ok(serde_yaml::Value::Bool(b))
+    }
+
+    fn visit_i64<E>(self, i: i64) -> Result<Self::Value, E> {
+        Ok(serde_yaml::Value::Number(i.into()))
+    }
 
-        // Look at the line immediately before
-        let prev_line = lines[line_num - 1];
-        let prev_trimmed = prev_line.trim();
+    fn visit_u64<E>(self, u: u64) -> Result<Self::Value, E> {
+        Ok(serde_yaml::Value::Number(u.into()))
+    }
+
+    fn visit_f64<E>(self, f: f64) -> Result<Self::Value, E> {
+        Ok(serde_yaml::Value::Number(f.into()))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Self::Value, E> {
+        Ok(serde_yaml::Value::String(s.to_string()))
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<Self::Value, E> {
+        Ok(serde_yaml::Value::String(s))
+    }
 
-        // If it's a comment, check if it's at the same indentation level
-        if prev_trimmed.starts_with('#') {
-            let prev_indent = prev_line.len() - prev_line.trim_start().len();
-            // Comment belongs to this key if it's at the same indentation
-            return prev_indent == key_indent;
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(serde_yaml::Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(serde_yaml::Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        LenientValue::deserialize(deserializer).map(|value| value.0)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(LenientValue(item)) = seq.next_element()? {
+            items.push(item);
         }
+        Ok(serde_yaml::Value::Sequence(items))
+    }
 
-        false
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut mapping = serde_yaml::Mapping::new();
+        while let Some((LenientValue(key), LenientValue(value))) = map.next_entry()? {
+            mapping.insert(key, value);
+        }
+        Ok(serde_yaml::Value::Mapping(mapping))
+    }
+
+    // Tagged values (`!tag value`) are presented as enums
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let (tag, variant): (String, _) = data.variant()?;
+        let LenientValue(value) = variant.newtype_variant()?;
+        Ok(serde_yaml::Value::Tagged(Box::new(serde_yaml::value::TaggedValue {
+            tag: serde_yaml::value::Tag::new(tag),
+            value,
+        })))
     }
 }
 
-/// Extract the key from a YAML line like "key: value" or "key:"
-fn extract_yaml_key(line: &str) -> Option<String> {
+/// Split a YAML line like "key: value" or "key:" into its key and value
+///
+/// The colon must be followed by whitespace or end the line, so a value like
+/// `http://example.com` isn't taken for a key.
+fn split_yaml_key(line: &str) -> Option<(&str, &str)> {
     let trimmed = line.trim();
 
     // Skip list items for now (lines starting with -)
@@ -127,8 +918,9 @@ fn extract_yaml_key(line: &str) -> Option<String> {
         return None;
     }
 
-    // Find the colon
-    let colon_pos = trimmed.find(':')?;
+    let colon_pos = trimmed.match_indices(':').map(|(pos, _)| pos).find(|&pos| {
+        trimmed[pos + 1..].is_empty() || trimmed[pos + 1..].starts_with([' ', '\t'])
+    })?;
     let key = trimmed[..colon_pos].trim();
 
     // Skip if key is empty or quoted (complex keys)
@@ -136,143 +928,247 @@ fn extract_yaml_key(line: &str) -> Option<String> {
         return None;
     }
 
-    Some(key.to_string())
+    Some((key, &trimmed[colon_pos + 1..]))
 }
 
-/// Check if a line is a mapping start (key with no inline value)
-fn is_mapping_start(line: &str) -> bool {
-    let trimmed = line.trim();
-    if let Some(colon_pos) = trimmed.find(':') {
-        let after_colon = trimmed[colon_pos + 1..].trim();
-        after_colon.is_empty() || after_colon.starts_with('#')
-    } else {
-        false
+/// Tidy the formatting of a YAML document, as described for
+/// [`AnnotatorConfig::normalize`]
+fn normalize(content: &str, version: YamlVersion) -> Result<String, AnnotatorError> {
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut tracker = PathTracker::new(version);
+    let mut lines: Vec<(String, bool)> = Vec::new();
+    // Comments adjoining the next line, as indices into `lines`
+    let mut comments: Vec<usize> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        // The text of block scalars and multi-line flow values is kept as is
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let verbatim = tracker.flow.is_open()
+            || tracker
+                .block_scalar
+                .is_some_and(|scalar_indent| line.trim().is_empty() || indent > scalar_indent);
+        let scanned = tracker.scan(line).map_err(|e| e.at(index + 1, line))?;
+        let content = line.trim_start();
+        let indent = &line[..line.len() - content.len()];
+        match scanned {
+            ScannedLine::Trivia if content.is_empty() => {
+                comments.clear();
+                lines.push((String::new(), false));
+                continue;
+            }
+            ScannedLine::Trivia => {
+                comments.push(lines.len());
+                lines.push((line.to_string(), false));
+                continue;
+            }
+            ScannedLine::Key(_) if !content.starts_with('-') => {
+                let (key, value) = split_yaml_key(content).unwrap_or((content, ""));
+                let line = match value.trim() {
+                    "" => format!("{}{}:", indent, key),
+                    value => format!("{}{}: {}", indent, key, value),
+                };
+                lines.push((line, false));
+            }
+            ScannedLine::Key(_) => lines.push((line.to_string(), false)),
+            ScannedLine::Other => lines.push((line.to_string(), verbatim)),
+        }
+        for held in comments.drain(..) {
+            lines[held].0 = format!("{}{}", indent, lines[held].0.trim_start());
+        }
     }
+    Ok(super::join_normalized(lines, newline))
 }
 
-/// Represents an operation to perform on the YAML lines
-enum YamlOperation {
-    /// Insert comment lines before the target line
-    Insert { line_num: usize, comment: String },
-    /// Replace the existing comment line with a new one
-    Replace { line_num: usize, comment: String },
-    /// Insert comment lines after an existing comment (before the key)
-    Append { line_num: usize, comment: String },
+/// Name a key as written on a line the way a loader reading the given YAML
+/// version does: quoted keys unquoted, and plain booleans and integers by
+/// their value
+fn key_name(key: &str, version: YamlVersion) -> Cow<'_, str> {
+    if key.starts_with(['"', '\'']) {
+        return serde_yaml::from_str::<String>(key).map_or(Cow::Borrowed(key), Cow::Owned);
+    }
+    plain_key_name(key, version)
 }
 
-impl Annotator for YamlAnnotator {
-    fn annotate(
-        &self,
-        content: &str,
-        annotations: &AnnotationMap,
-    ) -> Result<String, AnnotatorError> {
-        // Validate YAML syntax by attempting to parse
-        let _: serde_yaml::Value = serde_yaml::from_str(content)
-            .map_err(|e| Error::new(AnnotatorErrorKind::Parse).with_source(e))?;
-
-        let line_paths = self.build_line_path_map(content);
-
-        // Collect operations
-        let mut operations: Vec<YamlOperation> = Vec::new();
-
-        for (line_num, path, indent, has_existing_comment) in &line_paths {
-            if let Some(ann) = annotations.get(path) {
-                if let Some(comment) = self.format_comment(ann, *indent) {
-                    let op = match (self.config.existing_comments, *has_existing_comment) {
-                        (ExistingCommentBehavior::Skip, true) => None,
-                        (ExistingCommentBehavior::Replace, true) => {
-                            Some(YamlOperation::Replace {
-                                line_num: *line_num,
-                                comment,
-                            })
-                        }
-                        (ExistingCommentBehavior::Append, true) => {
-                            Some(YamlOperation::Append {
-                                line_num: *line_num,
-                                comment,
-                            })
-                        }
-                        _ => {
-                            // Prepend (default) or no existing comment
-                            Some(YamlOperation::Insert {
-                                line_num: *line_num,
-                                comment,
-                            })
-                        }
-                    };
-
-                    if let Some(operation) = op {
-                        operations.push(operation);
-                    }
-                }
-            }
-        }
+/// Name a plain key by its value if it's a boolean or an integer in the given
+/// YAML version
+fn plain_key_name(key: &str, version: YamlVersion) -> Cow<'_, str> {
+    let boolean = match key {
+        "true" | "True" | "TRUE" => Some(true),
+        "false" | "False" | "FALSE" => Some(false),
+        _ if version == YamlVersion::V1_2 => None,
+        "y" | "Y" | "yes" | "Yes" | "YES" | "on" | "On" | "ON" => Some(true),
+        "n" | "N" | "no" | "No" | "NO" | "off" | "Off" | "OFF" => Some(false),
+        _ => None,
+    };
+    if let Some(boolean) = boolean {
+        return Cow::Owned(boolean.to_string());
+    }
+    match yaml_integer(key, version) {
+        Some(integer) => Cow::Owned(integer.to_string()),
+        None => Cow::Borrowed(key),
+    }
+}
 
-        // Sort by line number descending to process from bottom up
-        operations.sort_by(|a, b| {
-            let line_a = match a {
-                YamlOperation::Insert { line_num, .. }
-                | YamlOperation::Replace { line_num, .. }
-                | YamlOperation::Append { line_num, .. } => *line_num,
-            };
-            let line_b = match b {
-                YamlOperation::Insert { line_num, .. }
-                | YamlOperation::Replace { line_num, .. }
-                | YamlOperation::Append { line_num, .. } => *line_num,
-            };
-            line_b.cmp(&line_a)
-        });
+/// Parse a plain scalar as an integer of the given YAML version
+fn yaml_integer(scalar: &str, version: YamlVersion) -> Option<i128> {
+    let (negative, digits) = match scalar.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, scalar.strip_prefix('+').unwrap_or(scalar)),
+    };
+    let digits: Cow<'_, str> = match version {
+        YamlVersion::V1_1 if !digits.starts_with('_') => Cow::Owned(digits.replace('_', "")),
+        _ => Cow::Borrowed(digits),
+    };
+    let (radix, digits) = match (version, digits.as_ref()) {
+        (_, digits) if digits.starts_with("0x") => (16, &digits[2..]),
+        (YamlVersion::V1_2, digits) if digits.starts_with("0o") => (8, &digits[2..]),
+        (YamlVersion::V1_1, digits) if digits.starts_with("0b") => (2, &digits[2..]),
+        (YamlVersion::V1_1, digits) if digits.len() > 1 && digits.starts_with('0') => (8, &digits[1..]),
+        (_, digits) => (10, digits),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    let value = i128::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -value } else { value })
+}
 
-        // Apply operations
-        let mut lines: Vec<String> = content.lines().map(String::from).collect();
+/// How [`collect_paths`] names the string keys of a parsed document
+#[derive(Clone, Copy)]
+struct KeyNames<'a> {
+    version: YamlVersion,
+    /// Paths of keys quoted in the source, see [`KeyScan::quoted`]
+    quoted: &'a HashSet<String>,
+}
 
-        for op in operations {
-            match op {
-                YamlOperation::Insert { line_num, comment } => {
-                    let comment_lines: Vec<String> = comment.lines().map(String::from).collect();
-                    for (i, comment_line) in comment_lines.into_iter().enumerate() {
-                        lines.insert(line_num + i, comment_line);
-                    }
-                }
-                YamlOperation::Replace { line_num, comment } => {
-                    // Find and count existing comment lines before this key
-                    let mut start_line = line_num - 1;
-                    while start_line > 0 && lines[start_line - 1].trim().starts_with('#') {
-                        start_line -= 1;
-                    }
-                    // Remove old comments
-                    for _ in start_line..line_num {
-                        lines.remove(start_line);
+/// Collect the dot-separated path of every mapping key, including keys of
+/// mappings inside sequences
+///
+/// Complex keys (mappings, sequences or null used as a key) have no path, so
+/// they're collected separately as their parent's path followed by the key
+/// as JSON. Keys holding an empty mapping or no value are also collected in
+/// `empty`.
+///
+/// The document is parsed as YAML 1.2, so with [`YamlVersion::V1_1`] string
+/// keys are named as plain keys of that version would be, unless they were
+/// quoted in the source.
+fn collect_paths(
+    value: &serde_yaml::Value,
+    keys: KeyNames<'_>,
+    prefix: &str,
+    paths: &mut HashSet<String>,
+    empty: &mut HashSet<String>,
+    complex_keys: &mut Vec<String>,
+) {
+    let join = |key: &str| if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                let key = match key {
+                    serde_yaml::Value::String(s)
+                        if keys.version == YamlVersion::V1_1 && !keys.quoted.contains(&join(s)) =>
+                    {
+                        plain_key_name(s, keys.version).into_owned()
                     }
-                    // Insert new comments at the start position
-                    let comment_lines: Vec<String> = comment.lines().map(String::from).collect();
-                    for (i, comment_line) in comment_lines.into_iter().enumerate() {
-                        lines.insert(start_line + i, comment_line);
-                    }
-                }
-                YamlOperation::Append { line_num, comment } => {
-                    // Insert after existing comments (right before the key)
-                    let comment_lines: Vec<String> = comment.lines().map(String::from).collect();
-                    for (i, comment_line) in comment_lines.into_iter().enumerate() {
-                        lines.insert(line_num + i, comment_line);
+                    serde_yaml::Value::String(s) => s.clone(),
+                    serde_yaml::Value::Number(n) => n.to_string(),
+                    serde_yaml::Value::Bool(b) => b.to_string(),
+                    complex => {
+                        let key = serde_json::to_string(complex).unwrap_or_else(|_| "?".to_string());
+                        complex_keys.push(join(&key));
+                        continue;
                     }
+                };
+                let path = join(&key);
+                collect_paths(value, keys, &path, paths, empty, complex_keys);
+                if is_empty(value) {
+                    empty.insert(path.clone());
                 }
+                paths.insert(path);
             }
         }
-
-        // Preserve trailing newline if original had one
-        let mut result = lines.join("\n");
-        if content.ends_with('\n') {
-            result.push('\n');
+        serde_yaml::Value::Sequence(items) => {
+            for item in items {
+                collect_paths(item, keys, prefix, paths, empty, complex_keys);
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => {
+            collect_paths(&tagged.value, keys, prefix, paths, empty, complex_keys);
         }
+        _ => {}
+    }
+}
 
-        Ok(result)
+/// Check whether a value is an empty mapping or missing, as for a bare `key:`
+fn is_empty(value: &serde_yaml::Value) -> bool {
+    match value {
+        serde_yaml::Value::Null => true,
+        serde_yaml::Value::Mapping(mapping) => mapping.is_empty(),
+        serde_yaml::Value::Tagged(tagged) => is_empty(&tagged.value),
+        _ => false,
+    }
+}
+
+/// Add a schema modeline above the leading comments of a document, unless
+/// they already declare a schema
+fn insert_directive(leading: &mut Vec<String>, reference: &str) {
+    if directive_reference(&leading.join("\n")).is_none() {
+        let directive = schema_directive(TargetFormat::Yaml, reference).unwrap_or_default();
+        // A blank line keeps the modeline out of the first key's comment
+        leading.splice(0..0, [directive, String::new()]);
+    }
+}
+
+/// Find where the comment block directly above a key starts: the run of
+/// comments at the key's indentation ending at the last held line
+///
+/// A blank line or a comment at another indentation, such as a trailing
+/// comment of a nested mapping, ends the block.
+fn comment_block_start(above: &[String], indent: usize) -> usize {
+    above
+        .iter()
+        .rposition(|line| !is_comment_at(line, indent))
+        .map_or(0, |pos| pos + 1)
+}
+
+/// Check if a line is a comment at exactly the given indentation
+fn is_comment_at(line: &str, indent: usize) -> bool {
+    line.trim_start().starts_with('#') && line.len() - line.trim_start().len() == indent
+}
+
+/// Check whether the value after a key leaves it to open a nested block:
+/// nothing but a comment, anchor or tag
+fn opens_mapping(value: &str) -> bool {
+    let value = value.split(" #").next().unwrap_or_default().trim();
+    value.is_empty()
+        || value.starts_with('#')
+        || (value.starts_with(['&', '!']) && !value.contains(char::is_whitespace))
+}
+
+impl Annotator for YamlAnnotator {
+    fn annotate(
+        &self,
+        content: &str,
+        annotations: &AnnotationMap,
+    ) -> Result<String, AnnotatorError> {
+        self.annotate_with_report(content, annotations)
+            .map(|(annotated, _)| annotated)
+    }
+
+    fn annotate_with_report(
+        &self,
+        content: &str,
+        annotations: &AnnotationMap,
+    ) -> Result<(String, AnnotationReport), AnnotatorError> {
+        YamlAnnotator::annotate_with_report(self, content, annotations)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::annotator::{
+        AnnotationDecision, AnnotationEvent, AnnotationLock, AnnotationObserver, LineEnding, NodeFilter,
+    };
     use crate::schema::Annotation;
     use insta::assert_snapshot;
 
@@ -299,196 +1195,860 @@ mod tests {
         let annotator = YamlAnnotator::new(AnnotatorConfig::default());
         let result = annotator.annotate(content, &annotations).unwrap();
 
-        assert_snapshot!(result);
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_nested_mapping() {
+        let content = r#"server:
+  port: 8080
+  host: localhost
+"#;
+        let annotations = make_annotations(&[
+            ("server", Some("Server Config"), None),
+            ("server.port", Some("Port"), Some("The port to listen on")),
+            ("server.host", Some("Host"), None),
+        ]);
+
+        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_annotate_document() {
+        let content = "server:\n  port: 8080\n  port: 9090\n";
+        let annotations = make_annotations(&[("server.port", Some("Port"), None)]);
+        let mut doc: YamlDocument = content.parse().unwrap();
+        assert_eq!(doc.value()["server"]["port"], 9090);
+
+        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        let report = annotator.annotate_document(&mut doc, &annotations).unwrap();
+        assert_eq!(report.applied, ["server.port", "server.port"]);
+        assert_eq!(doc.as_str(), "server:\n  # Port\n  port: 8080\n  # Port\n  port: 9090\n");
+        assert_eq!(doc.value()["server"]["port"], 9090);
+
+        // The paths come from the parsed value, which isn't parsed again
+        let mut doc: YamlDocument = "port: 1\n".parse().unwrap();
+        doc.value = serde_yaml::from_str("host: a\n").unwrap();
+        let annotations = make_annotations(&[("host", Some("Host"), None)]);
+        let strict = YamlAnnotator::new(AnnotatorConfig { strict: true, ..Default::default() });
+        assert!(strict.annotate_document(&mut doc, &annotations).is_ok());
+        let annotations = make_annotations(&[("port", Some("Port"), None)]);
+        assert!(strict.annotate_document(&mut doc, &annotations).is_err());
+
+        let err = "server: [".parse::<YamlDocument>().unwrap_err();
+        assert!(matches!(err.kind, AnnotatorErrorKind::Parse));
+    }
+
+    #[test]
+    fn test_title_only() {
+        let content = "name: test\n";
+        let annotations = make_annotations(&[("name", Some("Name"), Some("Full description"))]);
+
+        let annotator = YamlAnnotator::new(AnnotatorConfig::titles_only());
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_description_only() {
+        let content = "name: test\n";
+        let annotations = make_annotations(&[("name", Some("Name"), Some("Full description"))]);
+
+        let annotator = YamlAnnotator::new(AnnotatorConfig::descriptions_only());
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_preserve_existing_comments() {
+        let content = "# Existing comment\nport: 8080\n";
+        let annotations = make_annotations(&[("port", Some("Port"), None)]);
+
+        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_no_matching_annotations() {
+        let content = "name: test\nage: 30\n";
+        let annotations = make_annotations(&[("other", Some("Other"), None)]);
+
+        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_deeply_nested() {
+        let content = r#"database:
+  connection:
+    host: localhost
+    port: 5432
+"#;
+        let annotations = make_annotations(&[
+            ("database", Some("Database"), None),
+            ("database.connection", Some("Connection Settings"), None),
+            ("database.connection.host", Some("Host"), Some("Database server hostname")),
+            ("database.connection.port", Some("Port"), None),
+        ]);
+
+        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_inline_values() {
+        let content = r#"server:
+  host: localhost
+  port: 8080
+  enabled: true
+"#;
+        let annotations = make_annotations(&[
+            ("server.host", Some("Hostname"), None),
+            ("server.port", Some("Port Number"), None),
+            ("server.enabled", Some("Enabled"), Some("Whether the server is enabled")),
+        ]);
+
+        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_skip_existing_comments() {
+        let content = "# Existing comment\nport: 8080\nhost: localhost\n";
+        let annotations = make_annotations(&[
+            ("port", Some("Port"), None),
+            ("host", Some("Host"), None),
+        ]);
+
+        let config = AnnotatorConfig {
+            existing_comments: ExistingCommentBehavior::Skip,
+            ..Default::default()
+        };
+        let annotator = YamlAnnotator::new(config);
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        // port should keep its existing comment, host should get the annotation
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_append_to_existing_comments() {
+        let content = "# Existing comment\nport: 8080\n";
+        let annotations = make_annotations(&[("port", Some("Port"), None)]);
+
+        let config = AnnotatorConfig {
+            existing_comments: ExistingCommentBehavior::Append,
+            ..Default::default()
+        };
+        let annotator = YamlAnnotator::new(config);
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_replace_existing_comments() {
+        let content = "# Existing comment\nport: 8080\n";
+        let annotations = make_annotations(&[("port", Some("Port"), None)]);
+
+        let config = AnnotatorConfig {
+            existing_comments: ExistingCommentBehavior::Replace,
+            ..Default::default()
+        };
+        let annotator = YamlAnnotator::new(config);
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_replace_keeps_separated_comments() {
+        let content = r#"server:
+  host: localhost
+  # Trailing note on server
+# Detached note
+
+# Existing comment
+# spanning two lines
+port: 8080
+"#;
+        let annotations = make_annotations(&[("port", Some("Port"), None)]);
+
+        let config = AnnotatorConfig {
+            existing_comments: ExistingCommentBehavior::Replace,
+            ..Default::default()
+        };
+        let annotator = YamlAnnotator::new(config);
+        let result = annotator.annotate(content, &annotations).unwrap();
+
+        assert_snapshot!(result);
+    }
+
+    #[test]
+    fn test_comment_spacing() {
+        let content = "name: app\n\n# Existing comment\nport: 8080\n";
+        let annotations = make_annotations(&[("port", Some("Port"), None)]);
+
+        let annotate = |existing_comments| {
+            let config = AnnotatorConfig {
+                existing_comments,
+                comment_spacing: CommentSpacing::BlankLine,
+                ..Default::default()
+            };
+            YamlAnnotator::new(config).annotate(content, &annotations).unwrap()
+        };
+
+        assert_eq!(
+            annotate(ExistingCommentBehavior::Prepend),
+            "name: app\n\n# Existing comment\n\n# Port\nport: 8080\n"
+        );
+        assert_eq!(
+            annotate(ExistingCommentBehavior::Replace),
+            "name: app\n\n# Port\nport: 8080\n"
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        let content = "\nname:    app   \n\n\n   # Hand-written\nserver:\n  port:   8080\n  motd: |\n    Hello   \n\n\n    there\n  hosts:\n    - a   \n\n";
+        let annotations = make_annotations(&[("server.port", Some("Port"), None)]);
+        let config = AnnotatorConfig {
+            normalize: true,
+            ..Default::default()
+        };
+        let result = YamlAnnotator::new(config)
+            .annotate(content, &annotations)
+            .unwrap();
+
+        assert_eq!(
+            result,
+            "name: app\n\n# Hand-written\nserver:\n  # Port\n  port: 8080\n  motd: |\n    Hello   \n\n\n    there\n  hosts:\n    - a\n"
+        );
+    }
+
+    #[test]
+    fn test_example_entries() {
+        let content = "env: {}\nheaders:\nlabels:\n  team: core\n";
+        let mut annotations = AnnotationMap::new();
+        for path in ["env", "headers", "labels"] {
+            annotations.insert(
+                Annotation::new(path)
+                    .with_title("Map")
+                    .with_example_entry("name", serde_json::json!("value")),
+            );
+        }
+
+        let config = AnnotatorConfig {
+            example_entries: true,
+            ..Default::default()
+        };
+        let result = YamlAnnotator::new(config).annotate(content, &annotations).unwrap();
+
+        assert_eq!(
+            result,
+            "# Map\n# Example entry: name: value\nenv: {}\n# Map\n# Example entry: name: value\nheaders:\n\
+             # Map\nlabels:\n  team: core\n"
+        );
+    }
+
+    #[test]
+    fn test_key_names() {
+        let name = |key, version| key_name(key, version).into_owned();
+        for (key, v1_2, v1_1) in [
+            ("on", "on", "true"),
+            ("No", "No", "false"),
+            ("True", "true", "true"),
+            ("\"on\"", "on", "on"),
+            ("'it''s'", "it's", "it's"),
+            ("404", "404", "404"),
+            ("0x1F", "31", "31"),
+            ("0o17", "15", "0o17"),
+            ("017", "17", "15"),
+            ("0b101", "0b101", "5"),
+            ("1_000", "1_000", "1000"),
+            ("-12", "-12", "-12"),
+            ("1.5", "1.5", "1.5"),
+            ("port", "port", "port"),
+        ] {
+            assert_eq!(name(key, YamlVersion::V1_2), v1_2, "{} in YAML 1.2", key);
+            assert_eq!(name(key, YamlVersion::V1_1), v1_1, "{} in YAML 1.1", key);
+        }
     }
 
     #[test]
-    fn test_nested_mapping() {
-        let content = r#"server:
-  port: 8080
-  host: localhost
-"#;
+    fn test_numeric_and_boolean_keys() {
+        let content = "on:\n  push: {}\ncodes:\n  0x194: a\n  \"500\": b\n";
         let annotations = make_annotations(&[
-            ("server", Some("Server Config"), None),
-            ("server.port", Some("Port"), Some("The port to listen on")),
-            ("server.host", Some("Host"), None),
+            ("on", Some("Triggers"), None),
+            ("true", Some("Enabled"), None),
+            ("codes.404", Some("Not found"), None),
+            ("codes.500", Some("Server error"), None),
         ]);
 
-        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
-        let result = annotator.annotate(content, &annotations).unwrap();
+        let result = YamlAnnotator::new(AnnotatorConfig::default()).annotate(content, &annotations).unwrap();
+        assert_eq!(
+            result,
+            "# Triggers\non:\n  push: {}\ncodes:\n  # Not found\n  0x194: a\n  # Server error\n  \"500\": b\n"
+        );
 
-        assert_snapshot!(result);
+        let config = AnnotatorConfig {
+            yaml_version: YamlVersion::V1_1,
+            ..Default::default()
+        };
+        let (result, report) = YamlAnnotator::new(config).annotate_with_report(content, &annotations).unwrap();
+        assert!(result.starts_with("# Enabled\non:\n"));
+        assert!(report.missing.contains(&"on".to_string()));
+        assert!(!report.missing.contains(&"true".to_string()));
+
+        // Quoted keys stay strings
+        let content = "\"yes\": 1\nlist:\n  - 'on': 2\n";
+        let annotations = make_annotations(&[("yes", Some("Yes"), None), ("list.on", Some("On"), None)]);
+        let config = AnnotatorConfig {
+            yaml_version: YamlVersion::V1_1,
+            strict: true,
+            ..Default::default()
+        };
+        let (result, report) = YamlAnnotator::new(config).annotate_with_report(content, &annotations).unwrap();
+        assert_eq!(result, "# Yes\n\"yes\": 1\nlist:\n  - 'on': 2\n");
+        assert_eq!(report.applied, ["yes"]);
+        assert!(report.missing.is_empty());
     }
 
     #[test]
-    fn test_title_only() {
-        let content = "name: test\n";
-        let annotations = make_annotations(&[("name", Some("Name"), Some("Full description"))]);
-
-        let annotator = YamlAnnotator::new(AnnotatorConfig::titles_only());
-        let result = annotator.annotate(content, &annotations).unwrap();
-
-        assert_snapshot!(result);
-    }
+    fn test_include_default_value() {
+        let content = "port: 8080\n";
 
-    #[test]
-    fn test_description_only() {
-        let content = "name: test\n";
-        let annotations = make_annotations(&[("name", Some("Name"), Some("Full description"))]);
+        let mut map = AnnotationMap::new();
+        map.insert(
+            Annotation::new("port")
+                .with_title("Port")
+                .with_description("The port number")
+                .with_default("3000"),
+        );
 
-        let annotator = YamlAnnotator::new(AnnotatorConfig::descriptions_only());
-        let result = annotator.annotate(content, &annotations).unwrap();
+        let config = AnnotatorConfig {
+            include_default: true,
+            ..Default::default()
+        };
+        let annotator = YamlAnnotator::new(config);
+        let result = annotator.annotate(content, &map).unwrap();
 
         assert_snapshot!(result);
     }
 
     #[test]
-    fn test_preserve_existing_comments() {
-        let content = "# Existing comment\nport: 8080\n";
-        let annotations = make_annotations(&[("port", Some("Port"), None)]);
+    fn test_default_value_disabled_by_default() {
+        let content = "port: 8080\n";
+
+        let mut map = AnnotationMap::new();
+        map.insert(
+            Annotation::new("port")
+                .with_title("Port")
+                .with_default("3000"),
+        );
 
+        // Default config has include_default = false
         let annotator = YamlAnnotator::new(AnnotatorConfig::default());
-        let result = annotator.annotate(content, &annotations).unwrap();
+        let result = annotator.annotate(content, &map).unwrap();
 
         assert_snapshot!(result);
     }
 
     #[test]
-    fn test_no_matching_annotations() {
-        let content = "name: test\nage: 30\n";
-        let annotations = make_annotations(&[("other", Some("Other"), None)]);
+    fn test_path_filters() {
+        let content = r#"server:
+  port: 8080
+  host: localhost
+database:
+  url: postgres://localhost
+"#;
+        let annotations = make_annotations(&[
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), None),
+            ("server.host", Some("Host"), None),
+            ("database", Some("Database"), None),
+            ("database.url", Some("URL"), None),
+        ]);
 
-        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        let config = AnnotatorConfig {
+            include_paths: vec!["server.*".to_string(), "database.**".to_string()],
+            exclude_paths: vec!["server.host".to_string()],
+            ..Default::default()
+        };
+        let annotator = YamlAnnotator::new(config);
         let result = annotator.annotate(content, &annotations).unwrap();
 
         assert_snapshot!(result);
     }
 
     #[test]
-    fn test_deeply_nested() {
+    fn test_max_depth() {
         let content = r#"database:
   connection:
     host: localhost
-    port: 5432
 "#;
         let annotations = make_annotations(&[
             ("database", Some("Database"), None),
             ("database.connection", Some("Connection Settings"), None),
-            ("database.connection.host", Some("Host"), Some("Database server hostname")),
-            ("database.connection.port", Some("Port"), None),
+            ("database.connection.host", Some("Host"), None),
         ]);
 
-        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        let config = AnnotatorConfig {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let annotator = YamlAnnotator::new(config);
         let result = annotator.annotate(content, &annotations).unwrap();
 
         assert_snapshot!(result);
     }
 
     #[test]
-    fn test_inline_values() {
-        let content = r#"server:
-  host: localhost
+    fn test_node_filter() {
+        let content = r#"name: app
+server:
   port: 8080
-  enabled: true
 "#;
         let annotations = make_annotations(&[
-            ("server.host", Some("Hostname"), None),
-            ("server.port", Some("Port Number"), None),
-            ("server.enabled", Some("Enabled"), Some("Whether the server is enabled")),
+            ("name", Some("Name"), None),
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), None),
         ]);
 
-        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
-        let result = annotator.annotate(content, &annotations).unwrap();
+        let leaves = YamlAnnotator::new(AnnotatorConfig {
+            node_filter: NodeFilter::LeavesOnly,
+            ..Default::default()
+        });
+        let sections = YamlAnnotator::new(AnnotatorConfig {
+            node_filter: NodeFilter::SectionsOnly,
+            ..Default::default()
+        });
 
-        assert_snapshot!(result);
+        assert_snapshot!("node_filter_leaves", leaves.annotate(content, &annotations).unwrap());
+        assert_snapshot!("node_filter_sections", sections.annotate(content, &annotations).unwrap());
     }
 
     #[test]
-    fn test_skip_existing_comments() {
-        let content = "# Existing comment\nport: 8080\nhost: localhost\n";
+    fn test_deduplicate() {
+        let content = r#"port: 8080
+users:
+  name: admin
+"#;
         let annotations = make_annotations(&[
-            ("port", Some("Port"), None),
-            ("host", Some("Host"), None),
+            ("port", Some("Port"), Some("Port to listen on")),
+            ("users", Some("User"), Some("A user account")),
+            ("users.name", Some("User"), Some("A user account")),
         ]);
 
         let config = AnnotatorConfig {
-            existing_comments: ExistingCommentBehavior::Skip,
+            deduplicate: true,
             ..Default::default()
         };
         let annotator = YamlAnnotator::new(config);
         let result = annotator.annotate(content, &annotations).unwrap();
 
-        // port should keep its existing comment, host should get the annotation
         assert_snapshot!(result);
     }
 
     #[test]
-    fn test_append_to_existing_comments() {
-        let content = "# Existing comment\nport: 8080\n";
+    fn test_schema_directive() {
         let annotations = make_annotations(&[("port", Some("Port"), None)]);
+        let annotator = YamlAnnotator::new(AnnotatorConfig {
+            schema_directive: Some("./schema.json".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            annotator.annotate("# Service config\nport: 8080\n", &annotations).unwrap(),
+            "# yaml-language-server: $schema=./schema.json\n\n# Service config\n# Port\nport: 8080\n"
+        );
+        let declared = "# yaml-language-server: $schema=other.json\nport: 8080\n";
+        assert_eq!(
+            annotator.annotate(declared, &annotations).unwrap(),
+            "# yaml-language-server: $schema=other.json\n# Port\nport: 8080\n"
+        );
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let content = "server:\n  port: 8080\n";
+        let annotations = make_annotations(&[
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), None),
+        ]);
+
+        let crlf = YamlAnnotator::new(AnnotatorConfig {
+            line_ending: LineEnding::Crlf,
+            ..Default::default()
+        });
+        assert_eq!(
+            crlf.annotate(content, &annotations).unwrap(),
+            "# Server\r\nserver:\r\n  # Port\r\n  port: 8080\r\n"
+        );
+
+        let preserve = YamlAnnotator::new(AnnotatorConfig::default());
+        assert_eq!(
+            preserve.annotate("port: 8080\r\n", &annotations).unwrap(),
+            "port: 8080\r\n"
+        );
+    }
 
+    #[test]
+    fn test_update_existing_comments() {
+        let content = "server:\n  # Hand-written note\n  port: 8080\n";
         let config = AnnotatorConfig {
-            existing_comments: ExistingCommentBehavior::Append,
+            existing_comments: ExistingCommentBehavior::Update,
             ..Default::default()
         };
         let annotator = YamlAnnotator::new(config);
-        let result = annotator.annotate(content, &annotations).unwrap();
 
-        assert_snapshot!(result);
+        let old = make_annotations(&[("server.port", Some("Port"), Some("Old description"))]);
+        let first = annotator.annotate(content, &old).unwrap();
+
+        let new = make_annotations(&[
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), Some("New description")),
+        ]);
+        let second = annotator.annotate(&first, &new).unwrap();
+
+        // Re-running with the same annotations is a no-op
+        assert_eq!(annotator.annotate(&second, &new).unwrap(), second);
+        assert_snapshot!(second);
     }
 
     #[test]
-    fn test_replace_existing_comments() {
-        let content = "# Existing comment\nport: 8080\n";
+    fn test_update_with_lock() {
+        let annotate = |content: &str, lock: AnnotationLock, annotations: &AnnotationMap| {
+            let annotator = YamlAnnotator::new(AnnotatorConfig {
+                existing_comments: ExistingCommentBehavior::Update,
+                lock: Some(std::sync::Arc::new(lock)),
+                ..Default::default()
+            });
+            annotator.annotate_with_report(content, annotations).unwrap()
+        };
+        let old = make_annotations(&[("server.port", Some("Port"), Some("Old description"))]);
+        let new = make_annotations(&[("server.port", Some("Port"), Some("New description"))]);
+
+        let (first, report) = annotate("server:\n  # Hand-written note\n  port: 8080\n", AnnotationLock::new(), &old);
+        assert_eq!(first, "server:\n  # Hand-written note\n  # Port\n  # Old description\n  port: 8080\n");
+        let (second, report) = annotate(&first, report.lock, &new);
+        assert_eq!(second, "server:\n  # Hand-written note\n  # Port\n  # New description\n  port: 8080\n");
+        let (third, _) = annotate(&second, report.lock, &new);
+        assert_eq!(third, second);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let content = "port: 80\nhost: a\nport: 8080\n";
         let annotations = make_annotations(&[("port", Some("Port"), None)]);
+        let annotate = |duplicate_keys| {
+            let annotator = YamlAnnotator::new(AnnotatorConfig {
+                duplicate_keys,
+                verify_round_trip: true,
+                ..Default::default()
+            });
+            annotator.annotate_with_report(content, &annotations)
+        };
 
-        let config = AnnotatorConfig {
-            existing_comments: ExistingCommentBehavior::Replace,
-            ..Default::default()
+        let (output, report) = annotate(DuplicateKeys::All).unwrap();
+        assert_eq!(output, "# Port\nport: 80\nhost: a\n# Port\nport: 8080\n");
+        assert_eq!(report.duplicate_keys, vec!["port"]);
+        let (output, _) = annotate(DuplicateKeys::First).unwrap();
+        assert_eq!(output, "# Port\nport: 80\nhost: a\nport: 8080\n");
+        let (output, _) = annotate(DuplicateKeys::Last).unwrap();
+        assert_eq!(output, "port: 80\nhost: a\n# Port\nport: 8080\n");
+
+        let error = annotate(DuplicateKeys::Error).unwrap_err();
+        assert!(error.to_string().contains("duplicate key `port`, first defined at line 1"));
+        assert_eq!(error.location().map(|location| location.line), Some(3));
+    }
+
+    #[test]
+    fn test_duplicate_keys_scope() {
+        // Keys repeat across sequence items and documents without being duplicates
+        let content = "servers:\n  - name: a\n    port: 80\n  - name: b\n    port: 81\n---\nservers: []\n";
+        assert!(scan_keys(content, YamlVersion::V1_2).unwrap().duplicates.is_empty());
+
+        let content = "db:\n  port: 1\n  tls: !secret x\n  port: 2\ndb:\n  port: 3\n";
+        assert_eq!(
+            scan_keys(content, YamlVersion::V1_2).unwrap().duplicates,
+            vec![("db".to_string(), vec![1, 5]), ("db.port".to_string(), vec![2, 4])]
+        );
+        let lenient: LenientValue = serde_yaml::from_str(content).unwrap();
+        let last: serde_yaml::Value = serde_yaml::from_str("db:\n  port: 3\n").unwrap();
+        assert_eq!(lenient.0, last);
+        let tagged: LenientValue = serde_yaml::from_str("tls: !secret x\n").unwrap();
+        assert_eq!(tagged.0, serde_yaml::from_str::<serde_yaml::Value>("tls: !secret x\n").unwrap());
+    }
+
+    #[test]
+    fn test_indentation_errors() {
+        let annotations = make_annotations(&[("server.port", Some("Port"), None)]);
+        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        let stream = |content: &str| {
+            annotator.annotate_stream(content.as_bytes(), &annotations, Vec::new())
         };
-        let annotator = YamlAnnotator::new(config);
+
+        // Tabs aren't indentation, even where the parser isn't run
+        for content in ["server:\n\tport: 1\n", "server:\n  port: 1\n  \thost: a\n"] {
+            let error = stream(content).unwrap_err();
+            assert!(error.to_string().contains("tab in indentation"), "{}", error);
+            assert!(annotator.annotate(content, &annotations).is_err());
+        }
+        // Tabs elsewhere are fine
+        assert!(stream("server:\n  port:\t1 # \tnote\n").is_ok());
+
+        let error = stream("server:\n    port: 1\n  host: a\n").unwrap_err();
+        assert!(error.to_string().contains("expected 4 spaces"), "{}", error);
+        assert_eq!(error.location().map(|location| (location.line, location.column)), Some((3, 3)));
+    }
+
+    #[test]
+    fn test_multiline_values() {
+        let content = "\
+server:
+  motd: |
+    port: not a key
+
+  port: 1
+servers:
+- name: a
+  port: 2
+- tls:
+    port: 3
+limits: [1,
+  port: 2]
+note: \"a
+  port: b\"
+port: see
+  http://example.com
+";
+        let annotations = make_annotations(&[
+            ("port", Some("Port"), None),
+            ("server.port", Some("Server port"), None),
+            ("servers.port", Some("Servers port"), None),
+            ("servers.tls.port", Some("TLS port"), None),
+        ]);
+        let annotator = YamlAnnotator::new(AnnotatorConfig {
+            verify_round_trip: true,
+            ..Default::default()
+        });
         let result = annotator.annotate(content, &annotations).unwrap();
 
         assert_snapshot!(result);
     }
 
     #[test]
-    fn test_include_default_value() {
-        let content = "port: 8080\n";
+    fn test_quotes_and_brackets_in_plain_scalars() {
+        let annotations = make_annotations(&[
+            ("msg", Some("Message"), None),
+            ("port", Some("Port"), None),
+            ("server", Some("Server"), None),
+            ("server.host", Some("Host"), None),
+        ]);
+        let annotator = YamlAnnotator::new(AnnotatorConfig::default());
+        for msg in ["it is 'fine", "hello [world", "say \"hi", "a {b"] {
+            let content = format!("msg: {}\nport: 1\nserver:\n  host: a\n", msg);
+            let (_, report) = annotator.annotate_with_report(&content, &annotations).unwrap();
+            assert_eq!(report.applied, ["msg", "port", "server", "server.host"], "{}", msg);
+        }
 
-        let mut map = AnnotationMap::new();
-        map.insert(
-            Annotation::new("port")
-                .with_title("Port")
-                .with_description("The port number")
-                .with_default("3000"),
-        );
+        // Entries of flow collections may still be quoted
+        let content = "limits: [1, 'a]',\n  \"b}\"]\nport: 1\n";
+        let (_, report) = annotator.annotate_with_report(content, &annotations).unwrap();
+        assert_eq!(report.applied, ["port"]);
+    }
 
-        let config = AnnotatorConfig {
-            include_default: true,
+    #[test]
+    fn test_strict_missing_paths() {
+        let content = "servers:\n  - name: a\n    port: 80\n";
+        let annotations = make_annotations(&[
+            ("servers", Some("Servers"), None),
+            ("servers.name", Some("Name"), None),
+            ("servers.host", Some("Host"), None),
+            ("debug", Some("Debug"), None),
+        ]);
+
+        let strict = YamlAnnotator::new(AnnotatorConfig {
+            strict: true,
             ..Default::default()
-        };
-        let annotator = YamlAnnotator::new(config);
-        let result = annotator.annotate(content, &map).unwrap();
+        });
+        let error = strict.annotate(content, &annotations).unwrap_err();
+        assert!(error.to_string().contains("missing paths: debug, servers.host"));
+
+        // Filtered out paths aren't required
+        let filtered = YamlAnnotator::new(AnnotatorConfig {
+            strict: true,
+            include_paths: vec!["servers.name".to_string()],
+            ..Default::default()
+        });
+        assert!(filtered.annotate(content, &annotations).is_ok());
+    }
 
+    #[test]
+    fn test_root_sequence() {
+        let content = "- name: build\n  schedule: daily\n  env:\n    level: info\n-\n  name: test\n";
+        let schema = serde_json::json!({
+            "type": "array",
+            "items": {
+                "properties": {
+                    "name": {"description": "Job name"},
+                    "schedule": {"description": "Cron schedule"},
+                    "env": {"description": "Environment", "properties": {"level": {"description": "Log level"}}}
+                }
+            }
+        });
+
+        let (result, report) = YamlAnnotator::new(AnnotatorConfig::default())
+            .annotate_with_report(content, &crate::extract_annotations(&schema))
+            .unwrap();
+        assert!(report.unsupported.is_empty());
         assert_snapshot!(result);
     }
 
     #[test]
-    fn test_default_value_disabled_by_default() {
-        let content = "port: 8080\n";
+    fn test_unsupported_paths() {
+        let content = "servers:\n  - name: a\n    port: 80\nlimits: {burst: 5}\n? [a, b]\n: c\n";
+        let annotations = make_annotations(&[
+            ("servers", Some("Servers"), None),
+            ("servers.name", Some("Name"), None),
+            ("servers.port", Some("Port"), None),
+            ("limits.burst", Some("Burst"), None),
+        ]);
 
-        let mut map = AnnotationMap::new();
-        map.insert(
-            Annotation::new("port")
-                .with_title("Port")
-                .with_default("3000"),
-        );
+        let (_, report) = YamlAnnotator::new(AnnotatorConfig::default())
+            .annotate_with_report(content, &annotations)
+            .unwrap();
+        assert_eq!(report.unsupported, vec![r#"["a","b"]"#, "limits.burst", "servers.name"]);
 
-        // Default config has include_default = false
+        let strict = YamlAnnotator::new(AnnotatorConfig {
+            fail_on_unsupported: true,
+            ..Default::default()
+        });
+        let error = strict.annotate(content, &annotations).unwrap_err();
+        assert!(matches!(error.kind, AnnotatorErrorKind::Unsupported));
+    }
+
+    #[test]
+    fn test_verify_round_trip() {
+        use crate::annotator::{CommentContext, CommentFormatter};
+
+        // A formatter emitting a non-comment line corrupts the document
+        #[derive(Debug)]
+        struct BrokenFormatter;
+
+        impl CommentFormatter for BrokenFormatter {
+            fn format(&self, _: &Annotation, _: &CommentContext<'_>) -> Vec<String> {
+                vec!["injected: true".to_string()]
+            }
+        }
+
+        let content = "server:\n  port: 8080\n";
+        let annotations = make_annotations(&[("server.port", Some("Port"), None)]);
+
+        let unchecked = YamlAnnotator::new(AnnotatorConfig::default().with_formatter(BrokenFormatter));
+        assert!(unchecked.annotate(content, &annotations).is_ok());
+
+        let verified = YamlAnnotator::new(AnnotatorConfig {
+            verify_round_trip: true,
+            ..AnnotatorConfig::default().with_formatter(BrokenFormatter)
+        });
+        let error = verified.annotate(content, &annotations).unwrap_err();
+        assert!(matches!(error.kind, AnnotatorErrorKind::RoundTrip));
+
+        // Well-behaved annotation passes verification
+        let config = AnnotatorConfig {
+            verify_round_trip: true,
+            ..Default::default()
+        };
+        assert!(YamlAnnotator::new(config).annotate(content, &annotations).is_ok());
+    }
+
+    #[test]
+    fn test_annotate_stream() {
+        let annotations = make_annotations(&[
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), Some("Listen port")),
+        ]);
         let annotator = YamlAnnotator::new(AnnotatorConfig::default());
-        let result = annotator.annotate(content, &map).unwrap();
 
+        for content in [
+            "server:\n  port: 8080\n",
+            "# Header\n\nserver:\n  # Existing\n  port: 8080\n# Trailing\n",
+            "server:\r\n  port: 8080",
+            "",
+        ] {
+            let mut output = Vec::new();
+            let report = annotator
+                .annotate_stream(content.as_bytes(), &annotations, &mut output)
+                .unwrap();
+            let (expected, mut expected_report) =
+                annotator.annotate_with_report(content, &annotations).unwrap();
+            // Missing paths need the whole document, which streaming doesn't have
+            expected_report.missing.clear();
+            assert_eq!(String::from_utf8(output).unwrap(), expected);
+            assert_eq!(report, expected_report);
+        }
+    }
+
+    /// Records every event, vetoes `server.host` and rewrites `server.port`
+    #[derive(Debug, Default)]
+    struct Recorder(std::sync::Mutex<Vec<(String, Option<usize>, AnnotationDecision)>>);
+
+    impl AnnotationObserver for Recorder {
+        fn observe(&self, event: &AnnotationEvent<'_>, comment: &mut Vec<String>) {
+            self.0.lock().unwrap().push((event.path.to_string(), event.line, event.decision));
+            match event.path {
+                "server.host" => comment.clear(),
+                "server.port" => *comment = vec!["# Port (overridden)".to_string()],
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let content = "server:\n  port: 8080\n  host: localhost\n  tls: false\n";
+        let annotations = make_annotations(&[
+            ("server", Some("Server"), None),
+            ("server.port", Some("Port"), None),
+            ("server.host", Some("Host"), None),
+        ]);
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let config = AnnotatorConfig {
+            observer: Some(recorder.clone()),
+            ..Default::default()
+        };
+
+        let annotator = YamlAnnotator::new(config);
+        let (result, report) = annotator.annotate_with_report(content, &annotations).unwrap();
+        assert_eq!(report.applied, vec!["server", "server.port"]);
+        assert_eq!(report.excluded, vec!["server.host"]);
+        assert_eq!(report.unmatched, vec!["server.tls"]);
+
+        let events = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            vec![
+                ("server".to_string(), Some(1), AnnotationDecision::Apply),
+                ("server.port".to_string(), Some(2), AnnotationDecision::Apply),
+                ("server.host".to_string(), Some(3), AnnotationDecision::Apply),
+                ("server.tls".to_string(), Some(4), AnnotationDecision::Unmatched),
+            ]
+        );
         assert_snapshot!(result);
     }
 }
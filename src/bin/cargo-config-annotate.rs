@@ -0,0 +1,235 @@
+//! `cargo config-annotate`: annotate a crate's example configs with comments
+//! from its schema
+//!
+//! Settings are read from the package manifest, all of them optional:
+//!
+//! ```toml
+//! [package.metadata.config-annotate]
+//! # The schema, relative to the manifest
+//! schema = "config.schema.json"
+//! # Or a command printing it, run in the manifest's directory
+//! schema-command = ["cargo", "run", "--example", "dump-schema"]
+//! # Globs of the configs to annotate, relative to the manifest
+//! configs = ["examples/*.toml"]
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use clap::Parser;
+use jsonschema_annotator::{
+    annotate, load_schema, AnnotatorConfig, ExistingCommentBehavior, TargetFormat,
+};
+use serde_json::Value;
+
+/// Schemas tried, in order, when the manifest doesn't name one
+const DEFAULT_SCHEMAS: &[&str] = &["config.schema.json", "schema.json", "examples/schema.json"];
+
+/// Configs annotated when the manifest doesn't list any
+const DEFAULT_CONFIGS: &[&str] = &["examples/**/*.toml", "examples/**/*.yaml", "examples/**/*.yml"];
+
+// Cargo runs `cargo-config-annotate config-annotate <args>`
+#[derive(Parser)]
+#[command(name = "cargo", bin_name = "cargo")]
+enum Cargo {
+    /// Annotate the crate's example configs with comments from its schema
+    #[command(version)]
+    ConfigAnnotate(Args),
+}
+
+#[derive(clap::Args)]
+struct Args {
+    /// Path to Cargo.toml, found from the working directory upward by default
+    #[arg(long)]
+    manifest_path: Option<PathBuf>,
+
+    /// Schema to use instead of the one configured in the manifest
+    #[arg(long)]
+    schema: Option<PathBuf>,
+
+    /// Fail if a config isn't up to date instead of writing it
+    #[arg(long)]
+    check: bool,
+
+    /// Maximum line width for comments
+    #[arg(long, default_value = "80")]
+    max_width: usize,
+}
+
+/// The `[package.metadata.config-annotate]` table of the manifest
+#[derive(Default)]
+struct Settings {
+    schema: Option<PathBuf>,
+    schema_command: Option<Vec<String>>,
+    configs: Vec<String>,
+}
+
+fn main() {
+    let Cargo::ConfigAnnotate(args) = Cargo::parse();
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = match &args.manifest_path {
+        Some(path) => path.clone(),
+        None => find_manifest()?,
+    };
+    let root = manifest.parent().unwrap_or(Path::new(""));
+    let settings = read_settings(&manifest)?;
+    let schema = load_crate_schema(&args, root, &settings)?;
+
+    let patterns = if settings.configs.is_empty() {
+        DEFAULT_CONFIGS.iter().map(|p| p.to_string()).collect()
+    } else {
+        settings.configs
+    };
+    let mut configs = Vec::new();
+    for pattern in &patterns {
+        let pattern = root.join(pattern);
+        for entry in glob::glob(&pattern.to_string_lossy())? {
+            configs.push(entry?);
+        }
+    }
+    if configs.is_empty() {
+        return Err(format!(
+            "No configs match {}, set `configs` in [package.metadata.config-annotate]",
+            patterns.join(", ")
+        )
+        .into());
+    }
+
+    // Update mode refreshes the comments of earlier runs, keeping hand-written ones
+    let config = AnnotatorConfig {
+        existing_comments: ExistingCommentBehavior::Update,
+        max_line_width: Some(args.max_width),
+        ..Default::default()
+    };
+    let mut outdated = 0;
+    for path in &configs {
+        let name = path.strip_prefix(root).unwrap_or(path).display();
+        let content = fs::read_to_string(path)?;
+        let format = TargetFormat::from_path(path)
+            .ok_or_else(|| format!("Unknown file format: {}", name))?;
+        let annotated = annotate(&schema, &content, format, config.clone())
+            .map_err(|e| format!("{}: {}", name, e))?;
+        if annotated == content {
+            continue;
+        }
+        if args.check {
+            eprintln!("{} is out of date", name);
+            outdated += 1;
+        } else {
+            fs::write(path, annotated)?;
+            eprintln!("Annotated {}", name);
+        }
+    }
+
+    match outdated {
+        0 => Ok(()),
+        1 => Err("1 config is out of date, run `cargo config-annotate`".into()),
+        n => Err(format!("{} configs are out of date, run `cargo config-annotate`", n).into()),
+    }
+}
+
+/// Find the nearest Cargo.toml, like cargo does
+fn find_manifest() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let cwd = std::env::current_dir()?;
+    cwd.ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("No Cargo.toml found in {} or any parent", cwd.display()).into())
+}
+
+fn read_settings(manifest: &Path) -> Result<Settings, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(manifest)?;
+    let doc: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|e| format!("{}: {}", manifest.display(), e))?;
+    let Some(table) = doc
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get("config-annotate"))
+    else {
+        return Ok(Settings::default());
+    };
+
+    let invalid = |key: &str, expected: &str| {
+        format!("{}: `{}` must be {}", manifest.display(), key, expected)
+    };
+    let strings = |item: &toml_edit::Item| -> Option<Vec<String>> {
+        match item.as_str() {
+            Some(s) => Some(vec![s.to_string()]),
+            None => item
+                .as_array()?
+                .iter()
+                .map(|v| v.as_str().map(str::to_string))
+                .collect(),
+        }
+    };
+
+    let mut settings = Settings::default();
+    if let Some(schema) = table.get("schema") {
+        let schema = schema.as_str().ok_or_else(|| invalid("schema", "a string"))?;
+        settings.schema = Some(PathBuf::from(schema));
+    }
+    if let Some(command) = table.get("schema-command") {
+        let command = strings(command)
+            .filter(|command| !command.is_empty())
+            .ok_or_else(|| invalid("schema-command", "an array of strings"))?;
+        settings.schema_command = Some(command);
+    }
+    if let Some(configs) = table.get("configs") {
+        settings.configs =
+            strings(configs).ok_or_else(|| invalid("configs", "a string or an array of strings"))?;
+    }
+    Ok(settings)
+}
+
+/// Load the schema from `--schema`, the manifest's settings or a default location
+fn load_crate_schema(
+    args: &Args,
+    root: &Path,
+    settings: &Settings,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    if let Some(path) = &args.schema {
+        return Ok(load_schema(path)?);
+    }
+    if let Some(command) = &settings.schema_command {
+        return run_schema_command(command, root);
+    }
+    if let Some(path) = &settings.schema {
+        return Ok(load_schema(root.join(path))?);
+    }
+    let path = DEFAULT_SCHEMAS
+        .iter()
+        .map(|path| root.join(path))
+        .find(|path| path.is_file())
+        .ok_or(
+            "No schema found, set `schema` or `schema-command` in [package.metadata.config-annotate]",
+        )?;
+    Ok(load_schema(path)?)
+}
+
+/// Run a command printing the schema as JSON, such as a `schemars` dump
+fn run_schema_command(command: &[String], root: &Path) -> Result<Value, Box<dyn std::error::Error>> {
+    // Use the cargo that invoked us, which may not be the one on PATH
+    let program = match (command[0].as_str(), std::env::var_os("CARGO")) {
+        ("cargo", Some(cargo)) => PathBuf::from(cargo),
+        _ => PathBuf::from(&command[0]),
+    };
+    let output = Command::new(program)
+        .args(&command[1..])
+        .current_dir(root)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .map_err(|e| format!("Failed to run `{}`: {}", command.join(" "), e))?;
+    if !output.status.success() {
+        return Err(format!("`{}` failed with {}", command.join(" "), output.status).into());
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("`{}` didn't print a JSON schema: {}", command.join(" "), e).into())
+}
@@ -0,0 +1,152 @@
+use std::fmt::{Debug, Display};
+
+use miette::{
+    Diagnostic, LabeledSpan, MietteError, MietteSpanContents, SourceCode, SourceSpan, SpanContents,
+};
+
+use crate::error::{AnnotatorErrorKind, Error, SchemaErrorKind, SourceLocation};
+
+/// Diagnostic details shared by every error of a kind
+pub(crate) trait DiagnosticKind {
+    /// Stable code identifying the kind of error
+    fn code(&self) -> &'static str;
+    /// How the error can usually be fixed, if there's a general answer
+    fn help(&self) -> Option<&'static str>;
+}
+
+impl DiagnosticKind for AnnotatorErrorKind {
+    fn code(&self) -> &'static str {
+        match self {
+            AnnotatorErrorKind::Parse => "jsonschema_annotator::parse",
+            AnnotatorErrorKind::Io => "jsonschema_annotator::io",
+            AnnotatorErrorKind::InvalidSchema => "jsonschema_annotator::invalid_schema",
+            AnnotatorErrorKind::UnmatchedAnnotations => "jsonschema_annotator::unmatched_annotations",
+            AnnotatorErrorKind::RoundTrip => "jsonschema_annotator::round_trip",
+            AnnotatorErrorKind::UnsupportedFormat => "jsonschema_annotator::unsupported_format",
+            AnnotatorErrorKind::Load => "jsonschema_annotator::load",
+            AnnotatorErrorKind::Unsupported => "jsonschema_annotator::unsupported",
+            AnnotatorErrorKind::Serialize => "jsonschema_annotator::serialize",
+        }
+    }
+
+    fn help(&self) -> Option<&'static str> {
+        match self {
+            AnnotatorErrorKind::Parse => Some("fix the syntax at the marked position"),
+            AnnotatorErrorKind::UnmatchedAnnotations => {
+                Some("add the missing keys to the document, or stop failing on unmatched paths")
+            }
+            AnnotatorErrorKind::RoundTrip => {
+                Some("this is a bug in jsonschema-annotator, please report it with the document")
+            }
+            AnnotatorErrorKind::UnsupportedFormat => {
+                Some("formats other than TOML and YAML need to be registered with `register_format`")
+            }
+            AnnotatorErrorKind::Unsupported => {
+                Some("move the keys out of inline tables, flow mappings or sequences to annotate them")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl DiagnosticKind for SchemaErrorKind {
+    fn code(&self) -> &'static str {
+        match self {
+            SchemaErrorKind::Io => "jsonschema_annotator::schema::io",
+            SchemaErrorKind::ValueParse => "jsonschema_annotator::schema::parse",
+            SchemaErrorKind::InvalidSchema => "jsonschema_annotator::schema::invalid",
+            SchemaErrorKind::RefResolution => "jsonschema_annotator::schema::ref_resolution",
+        }
+    }
+
+    fn help(&self) -> Option<&'static str> {
+        match self {
+            SchemaErrorKind::ValueParse => Some("schemas must be valid JSON, or YAML in a .yaml/.yml file"),
+            SchemaErrorKind::RefResolution => {
+                Some("only local references (starting with `#`) within the schema are resolved")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<K> Diagnostic for Error<K>
+where
+    K: DiagnosticKind + Display + Debug,
+{
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.kind.code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.kind.help().map(|help| Box::new(help) as Box<dyn Display>)
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.location.as_ref().map(|location| location as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let location = self.location.as_ref()?;
+        Some(Box::new(std::iter::once(LabeledSpan::new_with_span(None, location.span()))))
+    }
+}
+
+impl SourceLocation {
+    /// Span of the character at the location, within the excerpt
+    fn span(&self) -> SourceSpan {
+        let offset = self
+            .excerpt
+            .char_indices()
+            .nth(self.column.saturating_sub(1))
+            .map_or(self.excerpt.len(), |(offset, _)| offset);
+        let length = self.excerpt[offset..].chars().next().map_or(0, char::len_utf8);
+        (offset, length).into()
+    }
+}
+
+/// Only the excerpt is kept, so snippets show the offending line alone, but
+/// with its line number in the document
+impl SourceCode for SourceLocation {
+    fn read_span<'a>(
+        &'a self,
+        span: &SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        let contents = self.excerpt.read_span(span, context_lines_before, context_lines_after)?;
+        Ok(Box::new(MietteSpanContents::new(
+            contents.data(),
+            *contents.span(),
+            self.line - 1 + contents.line(),
+            contents.column(),
+            contents.line_count(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::TargetFormat;
+
+    #[test]
+    fn test_parse_error_diagnostic() {
+        let error = crate::annotate(
+            &serde_json::json!({}),
+            "[server]\nport = = 1\n",
+            TargetFormat::Toml,
+            Default::default(),
+        )
+        .unwrap_err();
+
+        assert_eq!(error.code().unwrap().to_string(), "jsonschema_annotator::parse");
+        let labels: Vec<_> = error.labels().unwrap().collect();
+        assert_eq!(labels[0].offset(), 7);
+
+        let source = error.source_code().unwrap();
+        let contents = source.read_span(labels[0].inner(), 1, 1).unwrap();
+        assert_eq!(contents.line(), 1);
+        assert_eq!(contents.data(), b"port = = 1");
+    }
+}
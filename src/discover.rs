@@ -0,0 +1,115 @@
+use crate::format::TargetFormat;
+
+/// Find the schema a target document declares for itself
+///
+/// Recognizes, in order of precedence:
+/// - a `# yaml-language-server: $schema=<ref>` modeline (YAML)
+/// - a Taplo `#:schema <ref>` directive (TOML)
+/// - a top-level `$schema` key
+///
+/// The returned reference is exactly as written in the document, so relative
+/// paths still need resolving against the document's location.
+pub fn find_schema_reference(target: &str, format: TargetFormat) -> Option<String> {
+    if let Some(reference) = directive_reference(target) {
+        return Some(reference);
+    }
+
+    match format {
+        #[cfg(feature = "toml")]
+        TargetFormat::Toml => {
+            let doc: toml_edit::DocumentMut = target.parse().ok()?;
+            non_empty(doc.get("$schema")?.as_str()?)
+        }
+        #[cfg(feature = "yaml")]
+        TargetFormat::Yaml => {
+            let doc: serde_yaml::Value = serde_yaml::from_str(target).ok()?;
+            non_empty(doc.get("$schema")?.as_str()?)
+        }
+        // Formats without their feature can't be parsed either
+        _ => None,
+    }
+}
+
+/// The schema declared by a modeline or `#:schema` directive in the leading
+/// comment block of a document
+pub(crate) fn directive_reference(target: &str) -> Option<String> {
+    for line in target.lines() {
+        let line = line.trim();
+        if !line.starts_with('#') {
+            if line.is_empty() {
+                continue;
+            }
+            // Directives are only recognized in the leading comment block
+            break;
+        }
+        if let Some(rest) = line.strip_prefix("#:schema") {
+            return non_empty(rest);
+        }
+        if let Some(rest) = line[1..].trim_start().strip_prefix("yaml-language-server:") {
+            if let Some(reference) = rest.trim_start().strip_prefix("$schema=") {
+                return non_empty(reference);
+            }
+        }
+    }
+    None
+}
+
+/// The comment declaring a document's schema the way editors of the format
+/// recognize it: Taplo's `#:schema` directive for TOML and the
+/// `yaml-language-server` modeline for YAML
+pub(crate) fn schema_directive(format: TargetFormat, reference: &str) -> Option<String> {
+    match format {
+        TargetFormat::Toml => Some(format!("#:schema {}", reference)),
+        TargetFormat::Yaml => Some(format!("# yaml-language-server: $schema={}", reference)),
+        TargetFormat::Custom(_) => None,
+    }
+}
+
+fn non_empty(reference: &str) -> Option<String> {
+    let reference = reference.trim();
+    (!reference.is_empty()).then(|| reference.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml_language_server_modeline() {
+        let yaml = "# yaml-language-server: $schema=./schema.json\nport: 8080\n";
+        assert_eq!(
+            find_schema_reference(yaml, TargetFormat::Yaml),
+            Some("./schema.json".to_string())
+        );
+    }
+
+    #[test]
+    fn test_taplo_directive() {
+        let toml = "# Service config\n#:schema https://example.com/schema.json\n\nport = 8080\n";
+        assert_eq!(
+            find_schema_reference(toml, TargetFormat::Toml),
+            Some("https://example.com/schema.json".to_string())
+        );
+    }
+
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    #[test]
+    fn test_schema_key() {
+        assert_eq!(
+            find_schema_reference("\"$schema\" = \"schema.json\"\nport = 8080\n", TargetFormat::Toml),
+            Some("schema.json".to_string())
+        );
+        assert_eq!(
+            find_schema_reference("$schema: schema.yaml\nport: 8080\n", TargetFormat::Yaml),
+            Some("schema.yaml".to_string())
+        );
+    }
+
+    #[test]
+    fn test_no_reference() {
+        assert_eq!(find_schema_reference("port = 8080\n", TargetFormat::Toml), None);
+        // Directives after the leading comment block don't count
+        let yaml = "port: 8080\n# yaml-language-server: $schema=schema.json\n";
+        assert_eq!(find_schema_reference(yaml, TargetFormat::Yaml), None);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::annotator::{
+    AnnotationDecision, AnnotationEvent, AnnotationObserver, AnnotatorConfig,
+    ExistingCommentBehavior,
+};
+use crate::enrich::key_comments;
+use crate::error::AnnotatorError;
+use crate::format::TargetFormat;
+use crate::schema::{extract_annotations, AnnotationMap, SchemaSource};
+
+/// How a key's comments differ from what the schema would generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    /// The key has no comment, but the schema documents it
+    Missing,
+    /// The key's comments don't contain the current documentation, e.g.
+    /// because the schema changed or the comment was edited by hand
+    Stale,
+    /// The comment generated for the key was edited by hand since, as told
+    /// by the config's [`lock`](AnnotatorConfig::lock)
+    Edited,
+}
+
+/// A key whose comments have drifted from the schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommentDrift {
+    /// Dot-separated path of the key
+    pub path: String,
+    /// One-based line of the key, if known
+    pub line: Option<usize>,
+    pub kind: DriftKind,
+    /// Comment lines the schema would generate
+    pub expected: Vec<String>,
+    /// Comment lines currently above the key
+    pub existing: Vec<String>,
+}
+
+/// Compare a document's comments with what annotating it would generate
+///
+/// The document isn't modified. A key's comments are up to date when the
+/// lines `config` would render for it appear, in order, somewhere in the
+/// block of comments directly above it, so hand-written notes before or
+/// after them (as left by the prepend, append and update modes) don't count
+/// as drift. Paths selected out by `config` aren't checked. With a
+/// [`lock`](AnnotatorConfig::lock), a comment that doesn't hash to what the
+/// lock recorded for its key was edited by hand, which is reported as
+/// [`DriftKind::Edited`] instead of stale.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "toml")] {
+/// use jsonschema_annotator::{detect_drift, AnnotatorConfig, DriftKind, TargetFormat};
+///
+/// let schema = serde_json::json!({"properties": {"port": {"title": "Listen port"}}});
+/// let drift = detect_drift(&schema, "# Port\nport = 8080\n", TargetFormat::Toml, AnnotatorConfig::default())
+///     .unwrap();
+/// assert_eq!(drift[0].kind, DriftKind::Stale);
+/// assert_eq!(drift[0].expected, vec!["# Listen port"]);
+/// # }
+/// ```
+pub fn detect_drift(
+    schema: &impl SchemaSource,
+    target: &str,
+    target_format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<Vec<CommentDrift>, AnnotatorError> {
+    let existing: HashMap<String, Vec<String>> = key_comments(target, target_format)?
+        .into_iter()
+        .rev() // The first block of a repeated path wins
+        .collect();
+
+    let lock = config.lock.clone();
+    let expected = render_comments(&extract_annotations(schema), target, target_format, config)?;
+    let mut drift: Vec<CommentDrift> = expected
+        .into_iter()
+        .filter_map(|Rendered { path, line, lines: expected }| {
+            let existing = existing.get(&path).cloned().unwrap_or_default();
+            let kind = if existing.is_empty() {
+                DriftKind::Missing
+            } else if contains_run(&existing, &expected) {
+                return None;
+            } else if lock.as_ref().is_some_and(|lock| {
+                lock.get(&path).is_some() && lock.generated_lines(&path, &existing).is_none()
+            }) {
+                DriftKind::Edited
+            } else {
+                DriftKind::Stale
+            };
+            Some(CommentDrift {
+                path,
+                line,
+                kind,
+                expected,
+                existing,
+            })
+        })
+        .collect();
+    drift.sort_by_key(|d| d.line);
+    Ok(drift)
+}
+
+/// The comment `config` renders for each documented key of a document,
+/// whatever comments the key already has
+pub(crate) fn render_comments(
+    annotations: &AnnotationMap,
+    target: &str,
+    target_format: TargetFormat,
+    mut config: AnnotatorConfig,
+) -> Result<Vec<Rendered>, AnnotatorError> {
+    // Replacing makes the annotator render a comment for every documented key
+    let recorder = Arc::new(Recorder::default());
+    config.existing_comments = ExistingCommentBehavior::Replace;
+    config.observer = Some(recorder.clone());
+    crate::annotate_extracted(annotations, target, target_format, config)?;
+    let rendered = std::mem::take(&mut *recorder.comments.lock().unwrap());
+    Ok(rendered)
+}
+
+/// Records the comment rendered for each annotated path
+#[derive(Debug, Default)]
+struct Recorder {
+    comments: Mutex<Vec<Rendered>>,
+}
+
+/// The comment rendered for a path and where the path is
+#[derive(Debug)]
+pub(crate) struct Rendered {
+    pub(crate) path: String,
+    pub(crate) line: Option<usize>,
+    /// Comment lines, without indentation
+    pub(crate) lines: Vec<String>,
+}
+
+impl AnnotationObserver for Recorder {
+    fn observe(&self, event: &AnnotationEvent<'_>, comment: &mut Vec<String>) {
+        if event.decision == AnnotationDecision::Apply {
+            let lines = comment.iter().map(|line| line.trim().to_string()).collect();
+            self.comments.lock().unwrap().push(Rendered {
+                path: event.path.to_string(),
+                line: event.line,
+                lines,
+            });
+        }
+    }
+}
+
+/// Check whether `run` appears as consecutive lines of `lines`
+fn contains_run(lines: &[String], run: &[String]) -> bool {
+    run.is_empty() || lines.windows(run.len()).any(|window| window == run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "toml")]
+    use crate::annotator::AnnotationLock;
+
+    fn schema() -> serde_json::Value {
+        serde_json::json!({
+            "properties": {
+                "server": {
+                    "title": "Server",
+                    "properties": {
+                        "port": { "title": "Port", "description": "The port to listen on" },
+                        "host": { "title": "Host" },
+                        "tls": { "title": "TLS" }
+                    }
+                }
+            }
+        })
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_detect_drift_toml() {
+        let toml = r#"# Server
+[server]
+# Port
+# The old description
+port = 8080
+# Note: keep in sync with the proxy
+# Host
+host = "localhost"
+tls = false
+"#;
+
+        let drift = detect_drift(&schema(), toml, TargetFormat::Toml, AnnotatorConfig::default())
+            .unwrap();
+        let summary: Vec<_> = drift.iter().map(|d| (d.path.as_str(), d.line, d.kind)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("server.port", Some(5), DriftKind::Stale),
+                ("server.tls", Some(9), DriftKind::Missing),
+            ]
+        );
+        assert_eq!(drift[0].expected, vec!["# Port", "# The port to listen on"]);
+        assert_eq!(drift[0].existing, vec!["# Port", "# The old description"]);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_detect_drift_with_lock() {
+        let mut lock = AnnotationLock::new();
+        lock.record("server", &["# Server"]);
+        lock.record("server.port", &["# Port", "# The old description"]);
+        let config = AnnotatorConfig {
+            lock: Some(Arc::new(lock)),
+            ..Default::default()
+        };
+
+        let toml = "# Server (edited)\n[server]\n# Port\n# The old description\nport = 8080\n";
+        let drift = detect_drift(&schema(), toml, TargetFormat::Toml, config).unwrap();
+        let kinds: Vec<_> = drift.iter().map(|d| (d.path.as_str(), d.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![("server", DriftKind::Edited), ("server.port", DriftKind::Stale)]
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_detect_drift_yaml() {
+        let yaml = "# Server\nserver:\n  # Port\n  # The port to listen on\n  port: 8080\n  host: a\n";
+        let config = AnnotatorConfig {
+            exclude_paths: vec!["server.host".to_string()],
+            ..Default::default()
+        };
+
+        let drift = detect_drift(&schema(), yaml, TargetFormat::Yaml, config).unwrap();
+        assert!(drift.is_empty());
+
+        // Keys without comments are missing theirs
+        let drift = detect_drift(&schema(), "server:\n  port: 1\n", TargetFormat::Yaml, Default::default())
+            .unwrap();
+        let paths: Vec<_> = drift.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["server", "server.port"]);
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+#[cfg(feature = "toml")]
+use crate::annotator::toml_key_comments;
+#[cfg(feature = "yaml")]
+use crate::annotator::yaml_key_comments;
+use crate::error::{AnnotatorError, AnnotatorErrorKind, Error};
+use crate::format::TargetFormat;
+use crate::schema::percent_decode;
+
+/// Collect the comments of a hand-documented config, by path
+///
+/// This is the reverse of annotation: the comment lines directly above each
+/// key are joined into one text, with wrapped lines joined by spaces and
+/// empty comment lines starting a new paragraph. Comments separated from a
+/// key by a blank line aren't taken as its documentation.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "toml")] {
+/// use jsonschema_annotator::{extract_comments, TargetFormat};
+///
+/// let comments = extract_comments("# The port to\n# listen on\nport = 8080\n", TargetFormat::Toml)
+///     .unwrap();
+/// assert_eq!(comments["port"], "The port to listen on");
+/// # }
+/// ```
+pub fn extract_comments(
+    document: &str,
+    format: TargetFormat,
+) -> Result<BTreeMap<String, String>, AnnotatorError> {
+    let mut comments = BTreeMap::new();
+    for (path, lines) in key_comments(document, format)? {
+        let text = comment_text(&lines);
+        // Tables repeated in arrays share a path, the first one documents it
+        if !text.is_empty() {
+            comments.entry(path).or_insert(text);
+        }
+    }
+    Ok(comments)
+}
+
+/// The comment lines directly above each key of a document, by path
+pub(crate) fn key_comments(
+    document: &str,
+    format: TargetFormat,
+) -> Result<Vec<(String, Vec<String>)>, AnnotatorError> {
+    match format {
+        #[cfg(feature = "toml")]
+        TargetFormat::Toml => toml_key_comments(document),
+        #[cfg(feature = "yaml")]
+        TargetFormat::Yaml => yaml_key_comments(document),
+        TargetFormat::Custom(name) => Err(Error::new(AnnotatorErrorKind::UnsupportedFormat)
+            .add_context(format!("reading comments from {} documents isn't supported", name))),
+        #[cfg(not(all(feature = "toml", feature = "yaml")))]
+        format => Err(crate::format::feature_disabled(format)),
+    }
+}
+
+/// Write comments into the `description` of the schema property at their path
+///
+/// Paths are matched the way annotations are extracted, looking through
+/// `properties`, `items`, `additionalProperties`, `allOf`/`anyOf`/`oneOf`
+/// and local `$ref`s. A property that's a reference gets the description
+/// written to the definition it refers to, which is shared by every other
+/// use of it. Returns the paths that have no property in the schema.
+///
+/// # Example
+/// ```rust
+/// use std::collections::BTreeMap;
+/// use jsonschema_annotator::enrich_schema;
+///
+/// let mut schema = serde_json::json!({"properties": {"port": {"type": "integer"}}});
+/// let comments = BTreeMap::from([("port".to_string(), "Port to listen on".to_string())]);
+///
+/// assert!(enrich_schema(&mut schema, &comments).is_empty());
+/// assert_eq!(schema["properties"]["port"]["description"], "Port to listen on");
+/// ```
+pub fn enrich_schema(schema: &mut Value, comments: &BTreeMap<String, String>) -> Vec<String> {
+    let mut unmatched = Vec::new();
+    for (path, comment) in comments {
+        let property = locate(schema, path)
+            .and_then(|pointer| schema.pointer_mut(&pointer))
+            .and_then(Value::as_object_mut);
+        match property {
+            Some(property) => {
+                property.insert("description".to_string(), Value::String(comment.clone()));
+            }
+            None => {
+                tracing::debug!(path, "no schema property for comment");
+                unmatched.push(path.clone());
+            }
+        }
+    }
+    unmatched
+}
+
+/// Turn comment lines into text, unwrapping lines within a paragraph
+fn comment_text(lines: &[String]) -> String {
+    let mut paragraphs = vec![Vec::new()];
+    for line in lines {
+        let line = line.trim_start_matches('#').trim();
+        if line.is_empty() {
+            paragraphs.push(Vec::new());
+        } else if let Some(paragraph) = paragraphs.last_mut() {
+            paragraph.push(line);
+        }
+    }
+    paragraphs
+        .iter()
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Find the JSON Pointer to the schema describing a dot-separated path
+pub(crate) fn locate(root: &Value, path: &str) -> Option<String> {
+    let mut pointer = String::new();
+    for key in path.split('.') {
+        pointer = find_property(root, pointer, key, &mut Vec::new())?;
+    }
+    resolve(root, pointer)
+}
+
+/// Find the property `key` of the schema at `pointer`, looking through
+/// references, array items and composition
+fn find_property(root: &Value, pointer: String, key: &str, seen: &mut Vec<String>) -> Option<String> {
+    if seen.contains(&pointer) {
+        return None;
+    }
+    let schema = root.pointer(&pointer)?.as_object()?;
+    seen.push(pointer.clone());
+
+    if schema.get("properties").and_then(|p| p.get(key)).is_some() {
+        return Some(format!("{}/properties/{}", pointer, escape(key)));
+    }
+
+    let mut nested: Vec<String> = ref_target(schema.get("$ref")).into_iter().collect();
+    for keyword in ["items", "additionalProperties"] {
+        if schema.get(keyword).is_some_and(Value::is_object) {
+            nested.push(format!("{}/{}", pointer, keyword));
+        }
+    }
+    for keyword in ["allOf", "anyOf", "oneOf"] {
+        let count = schema.get(keyword).and_then(Value::as_array).map_or(0, Vec::len);
+        nested.extend((0..count).map(|i| format!("{}/{}/{}", pointer, keyword, i)));
+    }
+    nested.into_iter().find_map(|nested| find_property(root, nested, key, seen))
+}
+
+/// Follow the references of the schema at `pointer` to the schema they end at
+pub(crate) fn resolve(root: &Value, mut pointer: String) -> Option<String> {
+    let mut seen = Vec::new();
+    while let Some(target) = ref_target(root.pointer(&pointer)?.get("$ref")) {
+        if seen.contains(&target) {
+            break;
+        }
+        seen.push(std::mem::replace(&mut pointer, target));
+    }
+    root.pointer(&pointer).is_some().then_some(pointer)
+}
+
+/// The JSON Pointer a local `$ref` refers to
+fn ref_target(reference: Option<&Value>) -> Option<String> {
+    let fragment = reference?.as_str()?.strip_prefix('#')?;
+    percent_decode(fragment).map(|pointer| pointer.into_owned())
+}
+
+/// Escape a key for use as a JSON Pointer segment
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_extract_comments_toml() {
+        let document = r#"# Unrelated header
+
+# The name
+name = "app"
+
+# Server settings
+#
+# Applies to every listener
+[server]
+# Port to
+# listen on
+port = 8080
+host = "localhost"
+
+# Backend
+[[backends]]
+url = "a"
+
+# Ignored, the first table documents the array
+[[backends]]
+url = "b"
+"#;
+
+        let comments = extract_comments(document, TargetFormat::Toml).unwrap();
+        assert_eq!(
+            comments,
+            BTreeMap::from([
+                ("backends".to_string(), "Backend".to_string()),
+                ("name".to_string(), "The name".to_string()),
+                ("server".to_string(), "Server settings\n\nApplies to every listener".to_string()),
+                ("server.port".to_string(), "Port to listen on".to_string()),
+            ])
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_extract_comments_yaml() {
+        let document = r#"# Unrelated header
+
+# The name
+name: app
+# Server settings
+server:
+  # Port to
+  # listen on
+  port: 8080
+# Not indented like host
+  host: localhost
+"#;
+
+        let comments = extract_comments(document, TargetFormat::Yaml).unwrap();
+        assert_eq!(
+            comments,
+            BTreeMap::from([
+                ("name".to_string(), "The name".to_string()),
+                ("server".to_string(), "Server settings".to_string()),
+                ("server.port".to_string(), "Port to listen on".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_enrich_schema() {
+        let mut schema = json!({
+            "$defs": {
+                "Server": {
+                    "properties": {
+                        "port": { "type": "integer", "description": "Old" }
+                    }
+                }
+            },
+            "properties": {
+                "server": { "$ref": "#/$defs/Server" },
+                "hosts": {
+                    "type": "array",
+                    "items": { "properties": { "a/b": { "type": "string" } } }
+                },
+                "mode": {
+                    "oneOf": [
+                        { "properties": { "fast": { "type": "boolean" } } }
+                    ]
+                }
+            }
+        });
+        let comments = BTreeMap::from([
+            ("server".to_string(), "Server".to_string()),
+            ("server.port".to_string(), "Port".to_string()),
+            ("hosts.a/b".to_string(), "Escaped".to_string()),
+            ("mode.fast".to_string(), "Fast".to_string()),
+            ("missing".to_string(), "Missing".to_string()),
+        ]);
+
+        let unmatched = enrich_schema(&mut schema, &comments);
+        assert_eq!(unmatched, vec!["missing"]);
+        assert_eq!(schema["$defs"]["Server"]["description"], "Server");
+        assert_eq!(schema["$defs"]["Server"]["properties"]["port"]["description"], "Port");
+        assert_eq!(schema["properties"]["hosts"]["items"]["properties"]["a/b"]["description"], "Escaped");
+        assert_eq!(schema["properties"]["mode"]["oneOf"][0]["properties"]["fast"]["description"], "Fast");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_enrich_round_trip() {
+        let mut schema = json!({
+            "properties": {
+                "server": { "properties": { "port": { "type": "integer" } } }
+            }
+        });
+        let document = "server:\n  # Port to listen on\n  port: 8080\n";
+
+        let comments = extract_comments(document, TargetFormat::Yaml).unwrap();
+        assert!(enrich_schema(&mut schema, &comments).is_empty());
+
+        let annotated =
+            crate::annotate(&schema, "server:\n  port: 1\n", TargetFormat::Yaml, Default::default());
+        assert_eq!(annotated.unwrap(), "server:\n  # Port to listen on\n  port: 1\n");
+    }
+}
@@ -6,6 +6,35 @@ pub struct Error<K> {
     pub kind: K,
     pub(crate) context: Vec<Cow<'static, str>>,
     pub(crate) source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    pub(crate) location: Option<SourceLocation>,
+}
+
+/// Where in a document an error was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// One-based line number
+    pub line: usize,
+    /// One-based column, in characters
+    pub column: usize,
+    /// The text of the line, without its line ending
+    pub excerpt: String,
+}
+
+impl SourceLocation {
+    /// Locate a byte offset into `content`
+    pub fn from_offset(content: &str, offset: usize) -> Self {
+        let mut offset = offset.min(content.len());
+        while !content.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = content[offset..].find('\n').map_or(content.len(), |i| offset + i);
+        Self {
+            line: content[..start].matches('\n').count() + 1,
+            column: content[start..offset].chars().count() + 1,
+            excerpt: content[start..end].trim_end_matches('\r').to_string(),
+        }
+    }
 }
 
 impl<K> Error<K> {
@@ -14,9 +43,15 @@ impl<K> Error<K> {
             kind,
             context: Vec::new(),
             source: None,
+            location: None,
         }
     }
 
+    /// Where in the document the error was found, if known
+    pub fn location(&self) -> Option<&SourceLocation> {
+        self.location.as_ref()
+    }
+
     pub fn map_kind<NK, F>(self, mapper: F) -> Error<NK>
     where
         F: Fn(K) -> NK,
@@ -25,6 +60,7 @@ impl<K> Error<K> {
             kind: mapper(self.kind),
             context: self.context,
             source: self.source,
+            location: self.location,
         }
     }
 
@@ -38,6 +74,11 @@ impl<K> Error<K> {
         self
     }
 
+    pub fn with_location(mut self, location: SourceLocation) -> Self {
+        self.location = Some(location);
+        self
+    }
+
     pub fn with_boxed_source(
         mut self,
         source: Box<dyn std::error::Error + Send + Sync + 'static>,
@@ -86,6 +127,9 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.kind.fmt(f)?;
+        if let Some(location) = &self.location {
+            write!(f, " at line {}, column {}", location.line, location.column)?;
+        }
 
         if !self.context.is_empty() {
             write!(f, " context: [")?;
@@ -136,6 +180,13 @@ impl std::fmt::Display for SchemaErrorKind {
 pub enum AnnotatorErrorKind {
     Parse,
     Io,
+    InvalidSchema,
+    UnmatchedAnnotations,
+    RoundTrip,
+    UnsupportedFormat,
+    Load,
+    Unsupported,
+    Serialize,
 }
 
 impl std::fmt::Display for AnnotatorErrorKind {
@@ -143,9 +194,82 @@ impl std::fmt::Display for AnnotatorErrorKind {
         match self {
             AnnotatorErrorKind::Parse => write!(f, "failed to parse target document"),
             AnnotatorErrorKind::Io => write!(f, "I/O error"),
+            AnnotatorErrorKind::InvalidSchema => write!(f, "invalid schema"),
+            AnnotatorErrorKind::UnmatchedAnnotations => {
+                write!(f, "schema annotates paths missing from the document")
+            }
+            AnnotatorErrorKind::RoundTrip => write!(f, "annotation changed the document's data"),
+            AnnotatorErrorKind::UnsupportedFormat => write!(f, "unsupported target format"),
+            AnnotatorErrorKind::Load => write!(f, "failed to load configuration"),
+            AnnotatorErrorKind::Unsupported => {
+                write!(f, "document has keys in constructs that can't be annotated")
+            }
+            AnnotatorErrorKind::Serialize => write!(f, "failed to serialize document"),
         }
     }
 }
 
 pub type SchemaError = Error<SchemaErrorKind>;
 pub type AnnotatorError = Error<AnnotatorErrorKind>;
+
+/// A parse error for a TOML target, located in its content
+#[cfg(feature = "toml")]
+pub(crate) fn toml_parse_error(content: &str, error: toml_edit::TomlError) -> AnnotatorError {
+    let mut parse_error = Error::new(AnnotatorErrorKind::Parse).add_context(error.message().to_string());
+    if let Some(span) = error.span() {
+        parse_error = parse_error.with_location(SourceLocation::from_offset(content, span.start));
+    }
+    parse_error.with_source(error)
+}
+
+/// A parse error for a YAML target, located in its content
+#[cfg(feature = "yaml")]
+pub(crate) fn yaml_parse_error(content: &str, error: serde_yaml::Error) -> AnnotatorError {
+    // The message ends with the location, which is kept separately
+    let message = error.to_string();
+    let message = message.split_once(" at line ").map_or(message.as_str(), |(message, _)| message);
+    let mut parse_error = Error::new(AnnotatorErrorKind::Parse).add_context(message.to_string());
+    if let Some(location) = error.location() {
+        parse_error = parse_error.with_location(SourceLocation::from_offset(content, location.index()));
+    }
+    parse_error.with_source(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_parse_error_location() {
+        let content = "[server]\nport = = 1\n";
+        let error = toml_parse_error(content, content.parse::<toml_edit::DocumentMut>().unwrap_err());
+        assert_eq!(
+            error.location(),
+            Some(&SourceLocation {
+                line: 2,
+                column: 8,
+                excerpt: "port = = 1".to_string(),
+            })
+        );
+        insta::assert_snapshot!(error.to_string());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_parse_error_location() {
+        let content = "server:\n  port: 1: 2\n";
+        let error = serde_yaml::from_str::<serde_yaml::Value>(content).unwrap_err();
+        let error = yaml_parse_error(content, error);
+        let location = error.location().unwrap();
+        assert_eq!((location.line, location.excerpt.as_str()), (2, "  port: 1: 2"));
+        insta::assert_snapshot!(error.to_string());
+    }
+
+    #[test]
+    fn test_source_location_from_offset() {
+        let location = SourceLocation::from_offset("a\r\nbé = c\r\n", 6);
+        assert_eq!((location.line, location.column), (2, 3));
+        assert_eq!(location.excerpt, "bé = c");
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use crate::annotator::wrap_text;
+use crate::enrich::locate;
+use crate::schema::{extract_annotations, schema_types, Annotation, SchemaSource};
+use crate::validate::constraints;
+
+/// Width descriptions are wrapped to, not counting their indent
+const WIDTH: usize = 76;
+
+/// Document a single config path, as a terminal page
+///
+/// The page lists everything the schema says about the path: its title and
+/// description, type, default, allowed values and other constraints,
+/// examples, `x-category` and `x-since`, the keys below it and the JSON
+/// Pointer of the schema defining it, with references followed. Returns
+/// `None` if the schema has no property at the path.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::explain;
+///
+/// let schema = serde_json::json!({
+///     "properties": {"port": {"title": "Port", "type": "integer", "default": 8080}},
+/// });
+/// let page = explain(&schema, "port").unwrap();
+/// assert!(page.starts_with("port — Port\n"));
+/// assert!(page.contains("Type:        integer\n"));
+/// ```
+pub fn explain(schema: &impl SchemaSource, path: &str) -> Option<String> {
+    let root = schema.as_json();
+    let pointer = locate(root, path)?;
+    let definition = root.pointer(&pointer)?;
+    let annotations = extract_annotations(schema);
+    let annotation = annotations.get(path);
+
+    let mut out = String::from(path);
+    if let Some(title) = annotation.and_then(|annotation| annotation.title.as_ref()) {
+        let _ = write!(out, " — {}", title);
+    }
+    out.push('\n');
+    if let Some(description) = annotation.and_then(|annotation| annotation.description.as_ref()) {
+        out.push('\n');
+        for line in wrap_text(description, WIDTH) {
+            if line.is_empty() {
+                out.push('\n');
+            } else {
+                let _ = writeln!(out, "    {}", line);
+            }
+        }
+    }
+
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    if let Some(types) = types(definition) {
+        fields.push(("Type", types));
+    }
+    if let Some(default) = annotation.and_then(|annotation| annotation.default.as_ref()) {
+        fields.push(("Default", default.clone()));
+    }
+    if let Some(value) = definition.get("const") {
+        fields.push(("Values", value.to_string()));
+    } else if let Some(values) = definition.get("enum").and_then(Value::as_array) {
+        let values: Vec<String> = values.iter().map(Value::to_string).collect();
+        fields.push(("Values", values.join(", ")));
+    }
+    let constraints = constraints(definition);
+    if !constraints.is_empty() {
+        fields.push(("Constraints", constraints.join(", ")));
+    }
+    if let Some(annotation) = annotation {
+        if let Some(items) = &annotation.items {
+            fields.push(("Items", items.clone()));
+        }
+        if let Some(hint) = annotation.content_hint() {
+            fields.push(("Content", hint));
+        }
+        if !annotation.examples.is_empty() {
+            let examples: Vec<String> = annotation.examples.iter().map(Value::to_string).collect();
+            fields.push(("Examples", examples.join(", ")));
+        }
+        if let Some(category) = &annotation.category {
+            fields.push(("Category", category.clone()));
+        }
+        match annotation.extensions.get("x-since") {
+            Some(Value::String(version)) => fields.push(("Since", version.clone())),
+            Some(Value::Number(version)) => fields.push(("Since", version.to_string())),
+            _ => {}
+        }
+        if annotation.secret {
+            fields.push(("Secret", "yes".to_string()));
+        }
+    }
+    if let Some(properties) = definition.get("properties").and_then(Value::as_object) {
+        let keys: Vec<&str> = properties.keys().map(String::as_str).collect();
+        fields.push(("Keys", keys.join(", ")));
+    }
+    fields.push(("Defined at", format!("#{}", pointer)));
+
+    out.push('\n');
+    for (label, value) in fields {
+        let _ = writeln!(out, "    {:<12} {}", format!("{}:", label), value);
+    }
+    Some(out)
+}
+
+/// List every path the schema documents, as an indented tree
+///
+/// Each line holds a path, indented by its depth, followed by its type and a
+/// summary: the title, or else the first sentence of the description. Paths
+/// are sorted, each before the paths below it.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::describe;
+///
+/// let schema = serde_json::json!({
+///     "properties": {"server": {"properties": {"port": {"type": "integer", "title": "Port"}}}},
+/// });
+/// assert_eq!(describe(&schema), "server\n  server.port  integer  Port\n");
+/// ```
+pub fn describe(schema: &impl SchemaSource) -> String {
+    let root = schema.as_json();
+    let annotations = extract_annotations(schema);
+    // Parents of documented paths are included even when they aren't documented
+    let mut paths = BTreeSet::new();
+    let documented = annotations.iter().map(|(path, _)| path).chain(annotations.properties());
+    for path in documented.filter(|path| !path.is_empty()) {
+        let segments: Vec<&str> = path.split('.').collect();
+        for end in 1..=segments.len() {
+            paths.insert(segments[..end].to_vec());
+        }
+    }
+
+    let rows: Vec<(String, String, String)> = paths
+        .iter()
+        .map(|segments| {
+            let path = segments.join(".");
+            let definition = locate(root, &path).and_then(|pointer| root.pointer(&pointer));
+            let summary = annotations.get(&path).and_then(summary).unwrap_or_default();
+            let indented = format!("{}{}", "  ".repeat(segments.len() - 1), path);
+            (indented, definition.and_then(types).unwrap_or_default(), summary)
+        })
+        .collect();
+    let path_width = rows.iter().map(|(path, _, _)| path.chars().count()).max().unwrap_or(0);
+    let type_width = rows.iter().map(|(_, types, _)| types.len()).max().unwrap_or(0);
+
+    let mut out = String::new();
+    for (path, types, summary) in rows {
+        let line = format!("{:<path_width$}  {:<type_width$}  {}", path, types, summary);
+        let _ = writeln!(out, "{}", line.trim_end());
+    }
+    out
+}
+
+/// The types a schema allows, e.g. "string or null"
+fn types(schema: &Value) -> Option<String> {
+    let types = schema_types(schema);
+    (!types.is_empty()).then(|| types.join(" or "))
+}
+
+/// A one-line summary of a path: its title, or else the first sentence of its
+/// description
+fn summary(annotation: &Annotation) -> Option<String> {
+    if let Some(title) = &annotation.title {
+        return Some(title.clone());
+    }
+    let line = annotation.description.as_deref()?.trim().lines().next()?;
+    Some(match line.find(". ") {
+        Some(end) => line[..=end].to_string(),
+        None => line.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn test_explain() {
+        let schema = serde_json::json!({
+            "$defs": {
+                "Level": { "type": "string", "enum": ["info", "debug"], "default": "info" },
+                "Server": {
+                    "properties": {
+                        "port": {
+                            "title": "Port",
+                            "description": "Port to listen on.\n\nPorts below 1024 need elevated privileges on most systems, so the default is above them.",
+                            "type": "integer",
+                            "default": 8080,
+                            "minimum": 1,
+                            "maximum": 65535,
+                            "examples": [8080, 9090],
+                            "x-category": "Networking",
+                            "x-since": "1.2"
+                        },
+                        "log_level": { "$ref": "#/$defs/Level" }
+                    }
+                }
+            },
+            "properties": {
+                "server": { "title": "Server", "$ref": "#/$defs/Server" }
+            }
+        });
+        assert_snapshot!("explain_leaf", explain(&schema, "server.port").unwrap());
+        assert_snapshot!("explain_section", explain(&schema, "server").unwrap());
+        assert_eq!(
+            explain(&schema, "server.log_level").unwrap(),
+            "server.log_level\n\n    Type:        string\n    Default:     \"info\"\n    \
+             Values:      \"info\", \"debug\"\n    Defined at:  #/$defs/Level\n"
+        );
+        assert_eq!(explain(&schema, "server.missing"), None);
+    }
+
+    #[test]
+    fn test_describe() {
+        let schema = serde_json::json!({
+            "title": "Service",
+            "properties": {
+                "name": { "description": "Name of the service. Shown in logs.", "type": "string" },
+                "server": {
+                    "title": "Server",
+                    "type": "object",
+                    "properties": {
+                        "port": { "title": "Port", "type": "integer" },
+                        "tls": {
+                            "properties": {
+                                "cert": { "type": ["string", "null"], "description": "Certificate file" },
+                                "verify": { "type": "boolean" }
+                            }
+                        }
+                    }
+                },
+                "hosts": {
+                    "type": "array",
+                    "items": { "properties": { "address": { "type": "string", "format": "ipv4" } } }
+                }
+            }
+        });
+        assert_snapshot!(describe(&schema));
+    }
+}
@@ -0,0 +1,189 @@
+//! C ABI for embedding the annotator in non-Rust applications
+//!
+//! Build a shared or static library with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or
+//! `staticlib`), and declare the functions as in
+//! `include/jsonschema_annotator.h`.
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::annotator::AnnotatorConfig;
+use crate::error::AnnotatorErrorKind;
+use crate::format::TargetFormat;
+
+/// The document was annotated
+pub const JSONSCHEMA_ANNOTATOR_OK: c_int = 0;
+/// An argument was null, not UTF-8 or named an unknown format
+pub const JSONSCHEMA_ANNOTATOR_INVALID_ARGUMENT: c_int = 1;
+/// The schema isn't valid JSON or not a usable schema
+pub const JSONSCHEMA_ANNOTATOR_INVALID_SCHEMA: c_int = 2;
+/// The target document couldn't be parsed
+pub const JSONSCHEMA_ANNOTATOR_PARSE_ERROR: c_int = 3;
+/// Annotation failed for another reason, including a panic
+pub const JSONSCHEMA_ANNOTATOR_ERROR: c_int = 4;
+
+/// Annotate a document with the comments of a JSON Schema
+///
+/// `schema_json` is the schema as JSON text, `target` the document and
+/// `format` its format (`"toml"`, `"yaml"` or a registered custom format).
+/// On success, `*out` is set to the annotated document and
+/// `JSONSCHEMA_ANNOTATOR_OK` is returned. Otherwise an error code is returned
+/// and `*out` is set to an error message, or null if even that failed. Strings
+/// written to `*out` must be released with
+/// [`jsonschema_annotator_free_string`].
+///
+/// # Safety
+///
+/// `schema_json`, `target` and `format` must be null or point to
+/// NUL-terminated strings, and `out` must be null or valid for writing a
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn jsonschema_annotator_annotate(
+    schema_json: *const c_char,
+    target: *const c_char,
+    format: *const c_char,
+    out: *mut *mut c_char,
+) -> c_int {
+    if out.is_null() {
+        return JSONSCHEMA_ANNOTATOR_INVALID_ARGUMENT;
+    }
+    // Unwinding into the caller is undefined behavior, so panics are reported
+    // as errors
+    let result = panic::catch_unwind(AssertUnwindSafe(|| annotate(schema_json, target, format)));
+    let (code, message) = match result {
+        Ok(Ok(annotated)) => (JSONSCHEMA_ANNOTATOR_OK, annotated),
+        Ok(Err((code, message))) => (code, message),
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown cause");
+            (JSONSCHEMA_ANNOTATOR_ERROR, format!("annotating panicked: {}", reason))
+        }
+    };
+    // Interior NULs can't be represented, leaving `*out` null
+    *out = CString::new(message).map_or(ptr::null_mut(), CString::into_raw);
+    code
+}
+
+/// Release a string returned by this library
+///
+/// # Safety
+///
+/// `string` must be null or a pointer written to `out` by
+/// [`jsonschema_annotator_annotate`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn jsonschema_annotator_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+unsafe fn annotate(
+    schema_json: *const c_char,
+    target: *const c_char,
+    format: *const c_char,
+) -> Result<String, (c_int, String)> {
+    let schema_json = str_arg(schema_json, "schema_json")?;
+    let target = str_arg(target, "target")?;
+    let format = str_arg(format, "format")?;
+
+    let format = TargetFormat::from_extension(format).ok_or_else(|| {
+        (JSONSCHEMA_ANNOTATOR_INVALID_ARGUMENT, format!("unknown format: {}", format))
+    })?;
+    let schema: serde_json::Value = serde_json::from_str(schema_json)
+        .map_err(|e| (JSONSCHEMA_ANNOTATOR_INVALID_SCHEMA, format!("invalid schema: {}", e)))?;
+
+    crate::annotate(&schema, target, format, AnnotatorConfig::default()).map_err(|e| {
+        let code = match e.kind {
+            AnnotatorErrorKind::InvalidSchema => JSONSCHEMA_ANNOTATOR_INVALID_SCHEMA,
+            AnnotatorErrorKind::Parse => JSONSCHEMA_ANNOTATOR_PARSE_ERROR,
+            _ => JSONSCHEMA_ANNOTATOR_ERROR,
+        };
+        (code, e.to_string())
+    })
+}
+
+unsafe fn str_arg<'a>(arg: *const c_char, name: &str) -> Result<&'a str, (c_int, String)> {
+    if arg.is_null() {
+        return Err((JSONSCHEMA_ANNOTATOR_INVALID_ARGUMENT, format!("{} is null", name)));
+    }
+    CStr::from_ptr(arg)
+        .to_str()
+        .map_err(|_| (JSONSCHEMA_ANNOTATOR_INVALID_ARGUMENT, format!("{} isn't UTF-8", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(schema: &str, target: &str, format: &str) -> (c_int, String) {
+        let schema = CString::new(schema).unwrap();
+        let target = CString::new(target).unwrap();
+        let format = CString::new(format).unwrap();
+        let mut out = ptr::null_mut();
+        unsafe {
+            let code = jsonschema_annotator_annotate(
+                schema.as_ptr(),
+                target.as_ptr(),
+                format.as_ptr(),
+                &mut out,
+            );
+            let result = CStr::from_ptr(out).to_str().unwrap().to_string();
+            jsonschema_annotator_free_string(out);
+            (code, result)
+        }
+    }
+
+    #[test]
+    fn test_ffi_annotate() {
+        let schema = r#"{"properties": {"port": {"title": "Port"}}}"#;
+        assert_eq!(
+            call(schema, "port = 8080\n", "toml"),
+            (JSONSCHEMA_ANNOTATOR_OK, "# Port\nport = 8080\n".to_string())
+        );
+
+        let (code, message) = call(schema, "port = [", "toml");
+        assert_eq!(code, JSONSCHEMA_ANNOTATOR_PARSE_ERROR);
+        assert!(!message.is_empty());
+
+        assert_eq!(call("{", "", "toml").0, JSONSCHEMA_ANNOTATOR_INVALID_SCHEMA);
+        assert_eq!(call(schema, "", "ini").0, JSONSCHEMA_ANNOTATOR_INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn test_ffi_panic() {
+        struct Panicking;
+
+        impl crate::Annotator for Panicking {
+            fn annotate(&self, _: &str, _: &crate::AnnotationMap) -> Result<String, crate::AnnotatorError> {
+                panic!("broken annotator")
+            }
+        }
+
+        crate::register_format("ffi-panic", &["ffi-panic"], |_| Box::new(Panicking));
+        assert_eq!(
+            call("{}", "", "ffi-panic"),
+            (JSONSCHEMA_ANNOTATOR_ERROR, "annotating panicked: broken annotator".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ffi_null_arguments() {
+        let mut out = ptr::null_mut();
+        unsafe {
+            let code = jsonschema_annotator_annotate(ptr::null(), ptr::null(), ptr::null(), &mut out);
+            assert_eq!(code, JSONSCHEMA_ANNOTATOR_INVALID_ARGUMENT);
+            assert_eq!(CStr::from_ptr(out).to_str().unwrap(), "schema_json is null");
+            jsonschema_annotator_free_string(out);
+
+            assert_eq!(
+                jsonschema_annotator_annotate(ptr::null(), ptr::null(), ptr::null(), ptr::null_mut()),
+                JSONSCHEMA_ANNOTATOR_INVALID_ARGUMENT
+            );
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::annotator::AnnotatorConfig;
+use crate::error::{
+    AnnotatorError, AnnotatorErrorKind, Error, SchemaError, SchemaErrorKind, SourceLocation,
+};
+use crate::format::TargetFormat;
+
+/// Load a schema from a JSON or YAML file
+///
+/// Files with a `.yaml` or `.yml` extension are parsed as YAML, anything else
+/// as JSON. YAML schemas require the `yaml` feature.
+pub fn load_schema(path: impl AsRef<Path>) -> Result<Value, SchemaError> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path).map_err(|e| {
+        Error::new(SchemaErrorKind::Io)
+            .add_context(format!("reading {}", path.display()))
+            .with_source(e)
+    })?;
+
+    parse_schema(&content, &path.display().to_string())
+}
+
+/// Parse a schema read from `source`, as YAML if its extension says so and as
+/// JSON otherwise
+///
+/// `source` is the path or URL the content came from; a query string or
+/// fragment doesn't count as part of its extension. YAML schemas require the
+/// `yaml` feature.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::parse_schema;
+///
+/// let schema = parse_schema(r#"{"title": "Config"}"#, "https://example.com/schema.json?v=2").unwrap();
+/// assert_eq!(schema["title"], "Config");
+/// ```
+pub fn parse_schema(content: &str, source: &str) -> Result<Value, SchemaError> {
+    // Query strings and fragments of URLs aren't part of the extension
+    let path = Path::new(source.split(['?', '#']).next().unwrap_or(source));
+    let is_yaml = path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml");
+    let parse_error = |offset: Option<usize>| {
+        let error = Error::new(SchemaErrorKind::ValueParse).add_context(format!("parsing {}", source));
+        match offset {
+            Some(offset) => error.with_location(SourceLocation::from_offset(content, offset)),
+            None => error,
+        }
+    };
+    if is_yaml {
+        #[cfg(feature = "yaml")]
+        return serde_yaml::from_str(content)
+            .map_err(|e| parse_error(e.location().map(|l| l.index())).with_source(e));
+        #[cfg(not(feature = "yaml"))]
+        return Err(parse_error(None).add_context("YAML schemas require the `yaml` feature"));
+    } else {
+        serde_json::from_str(content).map_err(|e| {
+            parse_error(json_offset(content, e.line(), e.column())).with_source(e)
+        })
+    }
+}
+
+/// Byte offset of the one-based line and column serde_json reports errors at
+fn json_offset(content: &str, line: usize, column: usize) -> Option<usize> {
+    let start = match line {
+        0 => return None,
+        1 => 0,
+        _ => content.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    Some(start + column.saturating_sub(1))
+}
+
+/// Report a schema that couldn't be loaded as an annotation error
+pub(crate) fn schema_error(error: SchemaError) -> AnnotatorError {
+    error.map_kind(|kind| match kind {
+        SchemaErrorKind::Io => AnnotatorErrorKind::Io,
+        _ => AnnotatorErrorKind::InvalidSchema,
+    })
+}
+
+/// Annotate a file with the schema in another file
+///
+/// The schema is loaded with [`load_schema`], and the target's format is
+/// detected from its extension, falling back to its content. Returns the
+/// annotated document; see [`annotate_file_in_place`] to write it back.
+///
+/// # Example
+/// ```rust,no_run
+/// use jsonschema_annotator::{annotate_file, AnnotatorConfig};
+///
+/// let annotated = annotate_file("schema.json", "config.toml", AnnotatorConfig::default()).unwrap();
+/// println!("{}", annotated);
+/// ```
+pub fn annotate_file(
+    schema_path: impl AsRef<Path>,
+    target_path: impl AsRef<Path>,
+    config: AnnotatorConfig,
+) -> Result<String, AnnotatorError> {
+    let schema = load_schema(schema_path).map_err(schema_error)?;
+
+    let target_path = target_path.as_ref();
+    let target = fs::read_to_string(target_path).map_err(|e| {
+        Error::new(AnnotatorErrorKind::Io)
+            .add_context(format!("reading {}", target_path.display()))
+            .with_source(e)
+    })?;
+    let format = TargetFormat::from_path(target_path)
+        .or_else(|| TargetFormat::from_content(&target))
+        .ok_or_else(|| {
+            Error::new(AnnotatorErrorKind::Parse)
+                .add_context(format!("unrecognized format of {}", target_path.display()))
+        })?;
+
+    crate::annotate(&schema, &target, format, config)
+}
+
+/// Annotate a file with the schema in another file, writing the result back
+///
+/// Like [`annotate_file`], but replaces the target's contents. The file is
+/// left untouched if annotation fails.
+pub fn annotate_file_in_place(
+    schema_path: impl AsRef<Path>,
+    target_path: impl AsRef<Path>,
+    config: AnnotatorConfig,
+) -> Result<(), AnnotatorError> {
+    let target_path = target_path.as_ref();
+    let annotated = annotate_file(schema_path, target_path, config)?;
+    fs::write(target_path, annotated).map_err(|e| {
+        Error::new(AnnotatorErrorKind::Io)
+            .add_context(format!("writing {}", target_path.display()))
+            .with_source(e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").join(name)
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_annotate_file() {
+        let config = AnnotatorConfig::default();
+        let annotated = annotate_file(example("schema.json"), example("config.toml"), config).unwrap();
+        assert!(annotated.starts_with("# Server Configuration\n"));
+        assert!(annotated.contains("# Port\n"));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_parse_schema_error_location() {
+        let error = parse_schema("{\n  \"title\": \"x\",\n}\n", "schema.json").unwrap_err();
+        let location = error.location().unwrap();
+        assert_eq!((location.line, location.column, location.excerpt.as_str()), (3, 1, "}"));
+
+        let error = parse_schema("a: [b\n", "schema.yaml").unwrap_err();
+        assert!(error.location().is_some());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_annotate_file_in_place() {
+        let target = std::env::temp_dir()
+            .join(format!("jsonschema-annotator-{}-config.yaml", std::process::id()));
+        fs::write(&target, "server:\n  port: 8080\n").unwrap();
+
+        let result =
+            annotate_file_in_place(example("schema.json"), &target, AnnotatorConfig::titles_only());
+        let written = fs::read_to_string(&target).unwrap();
+        fs::remove_file(&target).unwrap();
+
+        result.unwrap();
+        assert_eq!(written, "# Server Configuration\nserver:\n  # Port\n  port: 8080\n");
+    }
+
+    #[test]
+    fn test_missing_schema() {
+        let config = AnnotatorConfig::default();
+        let error = annotate_file(example("missing.json"), example("config.toml"), config).unwrap_err();
+        assert!(matches!(error.kind, AnnotatorErrorKind::Io));
+    }
+}
@@ -1,10 +1,23 @@
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::annotator::{Annotator, AnnotatorConfig};
+#[cfg(not(all(feature = "toml", feature = "yaml")))]
+use crate::error::{AnnotatorError, AnnotatorErrorKind, Error};
 
 /// Format of the target file to annotate
+///
+/// The built-in formats are always listed, but working with one requires its
+/// cargo feature (`toml` or `yaml`, both enabled by default). Without it,
+/// operations on the format fail with
+/// [`UnsupportedFormat`](crate::AnnotatorErrorKind::UnsupportedFormat).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TargetFormat {
     Toml,
     Yaml,
+    /// A format registered with [`register_format`], by name
+    Custom(&'static str),
 }
 
 impl TargetFormat {
@@ -15,21 +28,155 @@ impl TargetFormat {
     }
 
     /// Detect format from extension string
+    ///
+    /// Extensions of formats added with [`register_format`] are recognized
+    /// too, with the built-in formats taking precedence.
     pub fn from_extension(ext: &str) -> Option<Self> {
-        match ext.to_lowercase().as_str() {
+        let ext = ext.to_lowercase();
+        match ext.as_str() {
             "toml" => Some(Self::Toml),
             "yaml" | "yml" => Some(Self::Yaml),
-            _ => None,
+            _ => {
+                let registry = registry().read().unwrap_or_else(|e| e.into_inner());
+                registry
+                    .iter()
+                    .find(|(_, format)| format.extensions.contains(&ext))
+                    .map(|(name, _)| Self::Custom(name))
+            }
         }
     }
 
+    /// Detect format heuristically from the document body
+    ///
+    /// The first significant line decides: a `[table]` header or `key = value`
+    /// means TOML, while `key: value`, a `- item` or a `---` document marker
+    /// means YAML. Documents without a telling line fall back to whichever
+    /// parser accepts them. Returns `None` if neither does, and falls back to
+    /// no parser whose feature is disabled.
+    pub fn from_content(content: &str) -> Option<Self> {
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "---" || line.starts_with("- ") || line == "-" {
+                return Some(Self::Yaml);
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                return Some(Self::Toml);
+            }
+            match first_separator(line) {
+                Some('=') => return Some(Self::Toml),
+                Some(':') => return Some(Self::Yaml),
+                _ => break,
+            }
+        }
+
+        #[cfg(feature = "toml")]
+        if content.parse::<toml_edit::DocumentMut>().is_ok() {
+            return Some(Self::Toml);
+        }
+        #[cfg(feature = "yaml")]
+        if serde_yaml::from_str::<serde_yaml::Value>(content).is_ok() {
+            return Some(Self::Yaml);
+        }
+        None
+    }
+
     /// Get the canonical file extension for this format
+    ///
+    /// Custom formats use their name.
     pub fn extension(&self) -> &'static str {
         match self {
             Self::Toml => "toml",
             Self::Yaml => "yaml",
+            Self::Custom(name) => name,
+        }
+    }
+}
+
+/// Error for a built-in format whose cargo feature isn't enabled
+#[cfg(not(all(feature = "toml", feature = "yaml")))]
+pub(crate) fn feature_disabled(format: TargetFormat) -> AnnotatorError {
+    Error::new(AnnotatorErrorKind::UnsupportedFormat).add_context(format!(
+        "{} support requires the `{}` feature",
+        format.extension().to_uppercase(),
+        format.extension()
+    ))
+}
+
+/// Creates the annotator for a custom format from the annotation config
+pub type AnnotatorFactory = dyn Fn(AnnotatorConfig) -> Box<dyn Annotator> + Send + Sync;
+
+struct CustomFormat {
+    /// Lowercase file extensions, without the dot
+    extensions: Vec<String>,
+    factory: Arc<AnnotatorFactory>,
+}
+
+fn registry() -> &'static RwLock<HashMap<&'static str, CustomFormat>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<&'static str, CustomFormat>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Register an annotator for [`TargetFormat::Custom`] documents
+///
+/// Files with one of `extensions` are detected as `TargetFormat::Custom(name)`,
+/// and [`annotate`](crate::annotate) and friends hand such documents to the
+/// annotator `factory` creates. Registering a name again replaces it.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::{
+///     register_format, AnnotationMap, Annotator, AnnotatorConfig, AnnotatorError, TargetFormat,
+/// };
+///
+/// struct IniAnnotator;
+///
+/// impl Annotator for IniAnnotator {
+///     fn annotate(&self, content: &str, _: &AnnotationMap) -> Result<String, AnnotatorError> {
+///         Ok(format!("; annotated\n{}", content))
+///     }
+/// }
+///
+/// register_format("ini", &["ini"], |_config: AnnotatorConfig| Box::new(IniAnnotator));
+/// assert_eq!(TargetFormat::from_extension("ini"), Some(TargetFormat::Custom("ini")));
+/// ```
+pub fn register_format(
+    name: &'static str,
+    extensions: &[&str],
+    factory: impl Fn(AnnotatorConfig) -> Box<dyn Annotator> + Send + Sync + 'static,
+) {
+    let format = CustomFormat {
+        extensions: extensions.iter().map(|ext| ext.to_lowercase()).collect(),
+        factory: Arc::new(factory),
+    };
+    registry().write().unwrap_or_else(|e| e.into_inner()).insert(name, format);
+}
+
+/// Create the annotator registered for a custom format
+pub(crate) fn custom_annotator(name: &str, config: AnnotatorConfig) -> Option<Box<dyn Annotator>> {
+    let factory = registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .map(|format| format.factory.clone())?;
+    // The lock is released before calling out, so factories may use the registry
+    Some(factory(config))
+}
+
+/// Find the first `=` or `:` outside of a quoted key
+fn first_separator(line: &str) -> Option<char> {
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '=' | ':') => return Some(c),
+            _ => {}
         }
     }
+    None
 }
 
 #[cfg(test)]
@@ -53,4 +200,49 @@ mod tests {
         assert_eq!(TargetFormat::from_path(Path::new("config.json")), None);
         assert_eq!(TargetFormat::from_path(Path::new("noext")), None);
     }
+
+    #[test]
+    fn test_from_content() {
+        assert_eq!(TargetFormat::from_content("# comment\n[server]\nport = 1\n"), Some(TargetFormat::Toml));
+        assert_eq!(TargetFormat::from_content("url = \"http://x\"\n"), Some(TargetFormat::Toml));
+        assert_eq!(TargetFormat::from_content("\"a:b\" = 1\n"), Some(TargetFormat::Toml));
+        assert_eq!(TargetFormat::from_content("server:\n  port: 1\n"), Some(TargetFormat::Yaml));
+        assert_eq!(TargetFormat::from_content("url: http://x?a=b\n"), Some(TargetFormat::Yaml));
+        assert_eq!(TargetFormat::from_content("---\n"), Some(TargetFormat::Yaml));
+        assert_eq!(TargetFormat::from_content("- a\n- b\n"), Some(TargetFormat::Yaml));
+        assert_eq!(TargetFormat::from_content("[a\n"), None);
+    }
+
+    #[test]
+    fn test_custom_format() {
+        use crate::{AnnotationMap, AnnotatorError, AnnotatorErrorKind};
+
+        struct PathListAnnotator;
+
+        impl Annotator for PathListAnnotator {
+            fn annotate(
+                &self,
+                content: &str,
+                annotations: &AnnotationMap,
+            ) -> Result<String, AnnotatorError> {
+                let mut paths: Vec<_> = annotations.iter().map(|(path, _)| path.as_str()).collect();
+                paths.sort();
+                Ok(format!("; {}\n{}", paths.join(", "), content))
+            }
+        }
+
+        register_format("test-list", &["LST"], |_| Box::new(PathListAnnotator));
+        let format = TargetFormat::from_path(Path::new("config.lst")).unwrap();
+        assert_eq!(format, TargetFormat::Custom("test-list"));
+
+        let schema = serde_json::json!({
+            "properties": { "a": { "title": "A" }, "b": { "title": "B" } }
+        });
+        let result = crate::annotate(&schema, "a b\n", format, AnnotatorConfig::default()).unwrap();
+        assert_eq!(result, "; a, b\na b\n");
+
+        let missing = TargetFormat::Custom("test-missing");
+        let unknown = crate::annotate(&schema, "", missing, AnnotatorConfig::default());
+        assert!(matches!(unknown.unwrap_err().kind, AnnotatorErrorKind::UnsupportedFormat));
+    }
 }
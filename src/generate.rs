@@ -0,0 +1,875 @@
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+
+use crate::annotator::AnnotatorConfig;
+use crate::error::{AnnotatorError, AnnotatorErrorKind, Error};
+use crate::format::TargetFormat;
+use crate::schema::{is_secret, Refs, SchemaSource};
+use crate::validate::{key_line_map, parse_target};
+
+/// Generate an annotated example document from a schema alone
+///
+/// Every property in the schema becomes a field, valued with its `default`
+/// or `const`. Fields without one get a placeholder marked with a
+/// `# TODO: set this` line: the first `enum` value or example, or else a
+/// value of their type (`""`, `0`, `false`, `[]`, `{}`), valid for string
+/// formats like `date-time` or `email`. The `default` of an
+/// object provides the values of its properties without a default of their
+/// own, and is merged key by key with the defaults of nested objects, as most
+/// applications resolve them. Properties with an `x-category` are grouped
+/// after the uncategorized ones, by category in order of first appearance,
+/// each group opened by a banner comment. Secrets get the placeholder
+/// regardless with [`redact_secrets`](AnnotatorConfig::redact_secrets). The
+/// result is then annotated with `config` like any other document, but
+/// always with category banners whatever `config` says. With
+/// [`required_only`](AnnotatorConfig::required_only) the properties the
+/// schema doesn't require are commented out, comments and all. A schema of an
+/// array gets a YAML list with one such item; TOML documents can't be lists.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "toml")] {
+/// use jsonschema_annotator::{generate_document, AnnotatorConfig, TargetFormat};
+///
+/// let schema: serde_json::Value = serde_json::from_str(
+///     r#"{"properties": {"port": {"title": "Port", "type": "integer", "default": 8080}}}"#,
+/// ).unwrap();
+///
+/// let generated = generate_document(&schema, TargetFormat::Toml, AnnotatorConfig::default()).unwrap();
+/// assert_eq!(generated, "# Port\nport = 8080\n");
+/// # }
+/// ```
+pub fn generate_document(
+    schema: &impl SchemaSource,
+    format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<String, AnnotatorError> {
+    let root = schema.as_json();
+    let mut refs = Refs::new(root);
+    let mut examples = Examples {
+        redact_secrets: config.redact_secrets,
+        placeholders: Vec::new(),
+    };
+    let skeleton = match example_value(root, &mut refs, &mut examples, None, Some("")) {
+        Value::Object(map) => Value::Object(map),
+        // A document that is a list gets one item showing their shape
+        Value::Array(items) if items.is_empty() => match root.get("items") {
+            Some(items) => Value::Array(vec![example_value(items, &mut refs, &mut examples, None, None)]),
+            None => Value::Array(items),
+        },
+        Value::Array(items) => Value::Array(items),
+        _ => Value::Object(Map::new()),
+    };
+
+    let document = render(&skeleton, format)?;
+    let required_only = config.required_only;
+    let config = AnnotatorConfig {
+        category_banners: true,
+        ..config
+    };
+    let annotated = crate::annotate(schema, &document, format, config)?;
+    let annotated = mark_placeholders(&annotated, format, &examples.placeholders);
+    if !required_only {
+        return Ok(annotated);
+    }
+    let mut optional = Vec::new();
+    optional_paths(root, &mut Refs::new(root), &skeleton, "", &mut optional);
+    Ok(comment_out(&annotated, format, &optional))
+}
+
+/// Collect the paths of the properties of `value` the schema doesn't require
+///
+/// Required objects are searched for optional properties in turn, following
+/// the generated value so recursive schemas terminate.
+fn optional_paths<'a>(
+    schema: &'a Value,
+    refs: &mut Refs<'a>,
+    value: &Value,
+    prefix: &str,
+    paths: &mut Vec<String>,
+) {
+    let Some(map) = value.as_object() else {
+        return;
+    };
+    let mut properties = Vec::new();
+    let mut required = HashSet::new();
+    object_members(schema, refs, &mut properties, &mut required);
+    for (key, prop) in properties {
+        let Some(value) = map.get(key) else {
+            continue;
+        };
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        if required.contains(key.as_str()) {
+            optional_paths(prop, refs, value, &path, paths);
+        } else {
+            paths.push(path);
+        }
+    }
+}
+
+/// The properties of an object schema and the names it requires, including
+/// those of its `allOf` branches
+fn object_members<'a>(
+    schema: &'a Value,
+    refs: &mut Refs<'a>,
+    properties: &mut Vec<(&'a String, &'a Value)>,
+    required: &mut HashSet<&'a str>,
+) {
+    refs.visit(schema, |refs, schema| {
+        let Some(obj) = schema.as_object() else {
+            return;
+        };
+        for (key, prop) in obj.get("properties").and_then(Value::as_object).into_iter().flatten() {
+            if !properties.iter().any(|(seen, _)| *seen == key) {
+                properties.push((key, prop));
+            }
+        }
+        let names = obj.get("required").and_then(Value::as_array).into_iter().flatten();
+        required.extend(names.filter_map(Value::as_str));
+        for branch in obj.get("allOf").and_then(Value::as_array).into_iter().flatten() {
+            object_members(branch, refs, properties, required);
+        }
+    })
+}
+
+/// Add a `# TODO: set this` line above the keys of the given paths, below
+/// their annotations
+fn mark_placeholders(document: &str, format: TargetFormat, paths: &[String]) -> String {
+    let lines: HashSet<usize> = key_line_map(document, format)
+        .into_iter()
+        .filter(|(path, _)| paths.contains(path))
+        .map(|(_, line)| line)
+        .collect();
+
+    let mut output = String::with_capacity(document.len() + lines.len() * 20);
+    for (index, line) in document.split_inclusive('\n').enumerate() {
+        if lines.contains(&(index + 1)) {
+            let content = line.trim_start();
+            let ending = if line.ends_with("\r\n") { "\r\n" } else { "\n" };
+            output.push_str(&line[..line.len() - content.len()]);
+            output.push_str("# TODO: set this");
+            output.push_str(ending);
+        }
+        output.push_str(line);
+    }
+    output
+}
+
+/// Comment out the entries of the given paths, from each key's line up to
+/// the next key outside it
+///
+/// Comment lines are left alone, so the comments of an entry read the same
+/// above its commented-out value and those of the next key stay intact.
+fn comment_out(document: &str, format: TargetFormat, paths: &[String]) -> String {
+    let mut keys: Vec<(usize, String)> =
+        key_line_map(document, format).into_iter().map(|(path, line)| (line, path)).collect();
+    keys.sort();
+
+    let mut commented = HashSet::new();
+    for (line, path) in keys.iter().filter(|(_, path)| paths.contains(path)) {
+        let within = |other: &str| {
+            other.strip_prefix(path.as_str()).is_some_and(|rest| rest.starts_with(['.', '[']))
+        };
+        let end = keys
+            .iter()
+            .find(|(other_line, other)| other_line > line && !within(other))
+            .map_or(document.lines().count() + 1, |(other_line, _)| *other_line);
+        commented.extend(*line..end);
+    }
+
+    let mut output = String::with_capacity(document.len() + commented.len() * 2);
+    for (index, line) in document.split_inclusive('\n').enumerate() {
+        let content = line.trim_start();
+        if commented.contains(&(index + 1)) && !content.trim().is_empty() && !content.starts_with('#') {
+            output.push_str(&line[..line.len() - content.len()]);
+            output.push_str("# ");
+            output.push_str(content);
+        } else {
+            output.push_str(line);
+        }
+    }
+    output
+}
+
+/// Convert a document to another format, annotating the result
+///
+/// Only the values carry over: comments and formatting of the source are
+/// replaced by the schema's annotations. YAML nulls are left out of TOML
+/// output, which has no null, and TOML datetimes become YAML strings.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(all(feature = "toml", feature = "yaml"))] {
+/// use jsonschema_annotator::{convert, AnnotatorConfig, TargetFormat};
+///
+/// let schema = serde_json::json!({"properties": {"port": {"title": "Port"}}});
+/// let converted = convert(&schema, "port: 8080\n", TargetFormat::Yaml, TargetFormat::Toml, AnnotatorConfig::default())
+///     .unwrap();
+/// assert_eq!(converted, "# Port\nport = 8080\n");
+/// # }
+/// ```
+pub fn convert(
+    schema: &impl SchemaSource,
+    source: &str,
+    source_format: TargetFormat,
+    target_format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<String, AnnotatorError> {
+    let value = parse_target(source, source_format)?;
+    let document = render(&value, target_format)?;
+    crate::annotate(schema, &document, target_format, config)
+}
+
+/// Serialize a value to TOML, annotated with the schema of its type
+///
+/// The schema is derived with [`schemars`], so doc comments on the type's
+/// fields end up as comments in the output. `None` fields are left out, as
+/// TOML has no null.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::{to_annotated_toml_string, AnnotatorConfig};
+///
+/// #[derive(serde::Serialize, schemars::JsonSchema)]
+/// struct Config {
+///     /// Port to listen on
+///     port: u16,
+/// }
+///
+/// let annotated = to_annotated_toml_string(&Config { port: 8080 }, AnnotatorConfig::default())
+///     .unwrap();
+/// assert_eq!(annotated, "# Port to listen on\nport = 8080\n");
+/// ```
+#[cfg(all(feature = "schemars", feature = "toml"))]
+pub fn to_annotated_toml_string<T>(value: &T, config: AnnotatorConfig) -> Result<String, AnnotatorError>
+where
+    T: serde::Serialize + schemars::JsonSchema,
+{
+    to_annotated_string(value, TargetFormat::Toml, config)
+}
+
+/// Serialize a value to YAML, annotated with the schema of its type
+///
+/// Like [`to_annotated_toml_string`], but emitting YAML.
+#[cfg(all(feature = "schemars", feature = "yaml"))]
+pub fn to_annotated_yaml_string<T>(value: &T, config: AnnotatorConfig) -> Result<String, AnnotatorError>
+where
+    T: serde::Serialize + schemars::JsonSchema,
+{
+    to_annotated_string(value, TargetFormat::Yaml, config)
+}
+
+/// Generate an annotated document holding the default value of a type
+///
+/// Shorthand for serializing `T::default()` with
+/// [`to_annotated_toml_string`] or [`to_annotated_yaml_string`], for
+/// applications writing out a documented default config.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "yaml")] {
+/// use jsonschema_annotator::{generate_default_config, AnnotatorConfig, TargetFormat};
+///
+/// #[derive(Default, serde::Serialize, schemars::JsonSchema)]
+/// struct Config {
+///     /// Enable verbose logging
+///     verbose: bool,
+/// }
+///
+/// let generated =
+///     generate_default_config::<Config>(TargetFormat::Yaml, AnnotatorConfig::default()).unwrap();
+/// assert_eq!(generated, "# Enable verbose logging\nverbose: false\n");
+/// # }
+/// ```
+#[cfg(feature = "schemars")]
+pub fn generate_default_config<T>(
+    format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<String, AnnotatorError>
+where
+    T: Default + serde::Serialize + schemars::JsonSchema,
+{
+    to_annotated_string(&T::default(), format, config)
+}
+
+#[cfg(feature = "schemars")]
+pub(crate) fn to_annotated_string<T>(
+    value: &T,
+    format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<String, AnnotatorError>
+where
+    T: serde::Serialize + schemars::JsonSchema,
+{
+    let schema = schemars::schema_for!(T);
+    let value = serde_json::to_value(value).map_err(|e| {
+        Error::new(AnnotatorErrorKind::Serialize)
+            .add_context("Failed to serialize value")
+            .with_source(e)
+    })?;
+    let document = render(&value, format)?;
+    crate::annotate(&schema, &document, format, config)
+}
+
+/// Render a value as a document of the given format
+fn render(value: &Value, format: TargetFormat) -> Result<String, AnnotatorError> {
+    Ok(match (format, value) {
+        #[cfg(feature = "toml")]
+        (TargetFormat::Toml, Value::Object(map)) => {
+            toml_edit::DocumentMut::from(toml_table(map)).to_string()
+        }
+        #[cfg(feature = "toml")]
+        (TargetFormat::Toml, _) => {
+            return Err(Error::new(AnnotatorErrorKind::Serialize)
+                .add_context("TOML documents must be tables at the root"))
+        }
+        #[cfg(feature = "yaml")]
+        (TargetFormat::Yaml, Value::Object(map)) if map.is_empty() => String::new(),
+        #[cfg(feature = "yaml")]
+        (TargetFormat::Yaml, _) => serde_yaml::to_string(value).map_err(|e| {
+            Error::new(AnnotatorErrorKind::Serialize)
+                .add_context("Failed to serialize YAML")
+                .with_source(e)
+        })?,
+        (TargetFormat::Custom(name), _) => {
+            return Err(Error::new(AnnotatorErrorKind::UnsupportedFormat)
+                .add_context(format!("generating {} documents isn't supported", name)))
+        }
+        #[cfg(not(all(feature = "toml", feature = "yaml")))]
+        (format, _) => return Err(crate::format::feature_disabled(format)),
+    })
+}
+
+/// Pick an example value for a schema
+///
+/// `inherited` is the value the `default` of an enclosing object gives this
+/// one. The schema's own `default` takes precedence, merged over it when both
+/// are objects, and the result provides the values of properties without a
+/// default of their own.
+///
+/// `path` is the path of the value, or None inside arrays, whose items
+/// aren't marked. Values that aren't a default or `const` are placeholders
+/// to fill in, and their paths are recorded in `examples`.
+fn example_value<'a>(
+    schema: &'a Value,
+    refs: &mut Refs<'a>,
+    examples: &mut Examples,
+    inherited: Option<&Value>,
+    path: Option<&str>,
+) -> Value {
+    refs.visit(schema, |refs, schema| example_resolved(schema, refs, examples, inherited, path))
+}
+
+/// An example value for a schema, picked as for generated documents, with
+/// secrets given a placeholder
+pub(crate) fn sample_value<'a>(schema: &'a Value, refs: &mut Refs<'a>) -> Value {
+    let mut examples = Examples { redact_secrets: true, placeholders: Vec::new() };
+    example_value(schema, refs, &mut examples, None, None)
+}
+
+/// State of the walk picking example values
+struct Examples {
+    redact_secrets: bool,
+    /// Paths given a placeholder instead of a default
+    placeholders: Vec<String>,
+}
+
+impl Examples {
+    fn placeholder(&mut self, path: Option<&str>, value: Value) -> Value {
+        if let Some(path) = path.filter(|path| !path.is_empty()) {
+            self.placeholders.push(path.to_string());
+        }
+        value
+    }
+}
+
+fn example_resolved<'a>(
+    schema: &'a Value,
+    refs: &mut Refs<'a>,
+    examples: &mut Examples,
+    inherited: Option<&Value>,
+    path: Option<&str>,
+) -> Value {
+    let Some(obj) = schema.as_object() else {
+        return match inherited {
+            Some(inherited) => inherited.clone(),
+            None => examples.placeholder(path, Value::Null),
+        };
+    };
+    if examples.redact_secrets && is_secret(obj) {
+        return examples.placeholder(path, placeholder(obj));
+    }
+
+    let default = match (inherited, obj.get("default")) {
+        (Some(inherited), Some(own)) => Some(merge_defaults(inherited.clone(), own)),
+        (inherited, own) => own.or(inherited).cloned(),
+    };
+    let explicit = default.as_ref().or_else(|| obj.get("const"));
+    // The first allowed or example value stands in for a value to choose
+    let suggested = obj
+        .get("enum")
+        .and_then(|v| v.as_array()?.first())
+        .or_else(|| obj.get("examples").and_then(|v| v.as_array()?.first()));
+
+    let mut properties = Map::new();
+    for keyword in ["allOf", "anyOf", "oneOf"] {
+        let Some(branches) = obj.get(keyword).and_then(|v| v.as_array()) else {
+            continue;
+        };
+        // All branches of allOf apply, otherwise the first alternative is representative
+        let branches = if keyword == "allOf" { &branches[..] } else { &branches[..branches.len().min(1)] };
+        for branch in branches {
+            if let Value::Object(map) = example_value(branch, refs, examples, default.as_ref(), path) {
+                properties.extend(map);
+            } else if explicit.is_none() && suggested.is_none() && obj.get("properties").is_none() {
+                return example_value(branch, refs, examples, None, path);
+            }
+        }
+    }
+    if let Some(props) = obj.get("properties").and_then(|v| v.as_object()) {
+        let mut categorized: Vec<(&str, &String, Value)> = Vec::new();
+        for (key, prop) in props {
+            let inherited = default.as_ref().and_then(|default| default.get(key));
+            let prop_path = path.map(|path| if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) });
+            let value = example_value(prop, refs, examples, inherited, prop_path.as_deref());
+            let category = refs.visit(prop, |_, prop| prop.get("x-category").and_then(Value::as_str));
+            match category {
+                Some(category) => categorized.push((category, key, value)),
+                None => {
+                    properties.insert(key.clone(), value);
+                }
+            }
+        }
+        // Group categories in order of first appearance
+        let mut order: Vec<&str> = Vec::new();
+        for (category, _, _) in &categorized {
+            if !order.contains(category) {
+                order.push(category);
+            }
+        }
+        categorized.sort_by_key(|(category, _, _)| order.iter().position(|c| c == category));
+        for (_, key, value) in categorized {
+            properties.insert(key.clone(), value);
+        }
+    }
+
+    match explicit {
+        // The properties took their values from an object default already,
+        // which may also hold keys the schema doesn't list
+        Some(Value::Object(default)) if !properties.is_empty() => {
+            for (key, value) in default {
+                if !properties.contains_key(key) {
+                    properties.insert(key.clone(), value.clone());
+                }
+            }
+            Value::Object(properties)
+        }
+        Some(value) => value.clone(),
+        None if !properties.is_empty() => Value::Object(properties),
+        None => examples.placeholder(path, suggested.cloned().unwrap_or_else(|| placeholder(obj))),
+    }
+}
+
+/// Merge a default over the one an enclosing object's default gives it, the
+/// keys of objects merging recursively
+fn merge_defaults(inherited: Value, own: &Value) -> Value {
+    match (inherited, own) {
+        (Value::Object(mut merged), Value::Object(own)) => {
+            for (key, value) in own {
+                match merged.get_mut(key) {
+                    Some(inherited) => *inherited = merge_defaults(inherited.take(), value),
+                    None => {
+                        merged.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            Value::Object(merged)
+        }
+        (_, own) => own.clone(),
+    }
+}
+
+/// Placeholder value for a schema's `type`, valid for its string `format`
+fn placeholder(obj: &Map<String, Value>) -> Value {
+    if let Some(format) = obj.get("format").and_then(Value::as_str) {
+        let example = match format {
+            "date-time" => "1970-01-01T00:00:00Z",
+            "date" => "1970-01-01",
+            "time" => "00:00:00",
+            "email" | "idn-email" => "user@example.com",
+            "hostname" | "idn-hostname" => "localhost",
+            "ipv4" => "0.0.0.0",
+            "ipv6" => "::",
+            "uri" | "iri" | "url" => "https://example.com",
+            "uuid" => "00000000-0000-0000-0000-000000000000",
+            _ => "",
+        };
+        let string_type = match obj.get("type") {
+            None => true,
+            Some(Value::String(t)) => t == "string",
+            Some(Value::Array(types)) => types.iter().any(|t| t == "string"),
+            _ => false,
+        };
+        if string_type && !example.is_empty() {
+            return Value::String(example.to_string());
+        }
+    }
+    let schema_type = match obj.get("type") {
+        Some(Value::String(t)) => t.as_str(),
+        // Nullable types like ["string", "null"] use the non-null placeholder
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(|t| t.as_str())
+            .find(|t| *t != "null")
+            .unwrap_or("null"),
+        _ => "null",
+    };
+    match schema_type {
+        "string" => Value::String(String::new()),
+        "integer" => Value::from(0),
+        "number" => Value::from(0.0),
+        "boolean" => Value::Bool(false),
+        "array" => Value::Array(Vec::new()),
+        "object" => Value::Object(Map::new()),
+        _ => Value::Null,
+    }
+}
+
+/// Convert an example object to a TOML table, with nested objects as sub-tables
+#[cfg(feature = "toml")]
+fn toml_table(map: &Map<String, Value>) -> toml_edit::Table {
+    let mut table = toml_edit::Table::new();
+    for (key, value) in map {
+        match value {
+            Value::Object(inner) => {
+                table.insert(key, toml_edit::Item::Table(toml_table(inner)));
+            }
+            Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+                let mut array = toml_edit::ArrayOfTables::new();
+                for item in items.iter().filter_map(Value::as_object) {
+                    array.push(toml_table(item));
+                }
+                table.insert(key, toml_edit::Item::ArrayOfTables(array));
+            }
+            // TOML has no null, so fields without a value are left out
+            _ => {
+                if let Some(value) = toml_value(value) {
+                    table.insert(key, toml_edit::value(value));
+                }
+            }
+        }
+    }
+    table
+}
+
+#[cfg(feature = "toml")]
+pub(crate) fn toml_value(value: &Value) -> Option<toml_edit::Value> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64()?.into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => items.iter().filter_map(toml_value).collect::<toml_edit::Array>().into(),
+        Value::Object(map) => {
+            let mut inline = toml_edit::InlineTable::new();
+            for (key, value) in map {
+                if let Some(value) = toml_value(value) {
+                    inline.insert(key, value);
+                }
+            }
+            inline.into()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+
+    fn schema() -> Value {
+        serde_json::json!({
+            "$defs": {
+                "Level": { "type": "string", "enum": ["info", "debug"] }
+            },
+            "properties": {
+                "name": { "title": "Name", "type": "string" },
+                "log_level": { "description": "Verbosity", "$ref": "#/$defs/Level" },
+                "server": {
+                    "title": "Server",
+                    "description": "HTTP server settings",
+                    "properties": {
+                        "port": { "title": "Port", "type": "integer", "default": 8080 },
+                        "tls": { "type": ["boolean", "null"] },
+                        "hosts": { "type": "array", "items": { "type": "string" } }
+                    }
+                },
+                "timeout": { "description": "Optional timeout", "type": "null" }
+            }
+        })
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_generate_toml() {
+        let result = generate_document(&schema(), TargetFormat::Toml, AnnotatorConfig::default()).unwrap();
+        assert_snapshot!(result);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_generate_redacted_secrets() {
+        let schema = serde_json::json!({
+            "properties": {
+                "user": { "type": "string", "default": "admin" },
+                "password": { "title": "Password", "type": "string", "format": "password", "default": "hunter2" },
+                "api_key": { "type": "string", "writeOnly": true, "examples": ["sk-123"] },
+                "pin": { "type": "integer", "x-secret": true, "default": 1234 }
+            }
+        });
+        let config = AnnotatorConfig {
+            include_default: true,
+            mark_secrets: true,
+            redact_secrets: true,
+            ..Default::default()
+        };
+        let result = generate_document(&schema, TargetFormat::Yaml, config).unwrap();
+        assert_snapshot!(result);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_generate_format_placeholders() {
+        let schema = serde_json::json!({
+            "properties": {
+                "since": { "type": "string", "format": "date-time" },
+                "contact": { "format": "email" },
+                "homepage": { "type": ["string", "null"], "format": "uri" },
+                "code": { "type": "string", "format": "custom" },
+                "options": { "type": "object" }
+            }
+        });
+        let result = generate_document(&schema, TargetFormat::Yaml, AnnotatorConfig::default()).unwrap();
+        assert_eq!(
+            result,
+            "# TODO: set this\nsince: 1970-01-01T00:00:00Z\n# TODO: set this\ncontact: user@example.com\n\
+             # TODO: set this\nhomepage: https://example.com\n# TODO: set this\ncode: ''\n\
+             # TODO: set this\noptions: {}\n"
+        );
+    }
+
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    #[test]
+    fn test_generate_required_only() {
+        let schema = serde_json::json!({
+            "required": ["name", "server"],
+            "properties": {
+                "name": { "title": "Name", "type": "string" },
+                "debug": { "title": "Debug", "type": "boolean" },
+                "server": {
+                    "title": "Server",
+                    "required": ["port"],
+                    "properties": {
+                        "port": { "title": "Port", "type": "integer", "default": 8080 },
+                        "hosts": { "title": "Hosts", "type": "array", "items": { "type": "string" }, "default": ["a", "b"] }
+                    }
+                },
+                "tls": {
+                    "title": "TLS",
+                    "properties": { "cert": { "title": "Certificate", "type": "string" } }
+                }
+            }
+        });
+        let config = || AnnotatorConfig {
+            required_only: true,
+            ..Default::default()
+        };
+        let yaml = generate_document(&schema, TargetFormat::Yaml, config()).unwrap();
+        let toml = generate_document(&schema, TargetFormat::Toml, config()).unwrap();
+        assert_snapshot!("generate_required_only_yaml", yaml);
+        assert_snapshot!("generate_required_only_toml", toml);
+    }
+
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    #[test]
+    fn test_generate_categories() {
+        let schema = serde_json::json!({
+            "properties": {
+                "host": { "description": "Address to bind", "type": "string", "x-category": "Networking" },
+                "log_level": { "description": "Verbosity", "default": "info", "x-category": "Logging" },
+                "name": { "description": "Service name", "type": "string" },
+                "port": { "description": "Port to listen on", "default": 8080, "x-category": "Networking" },
+                "tls": {
+                    "description": "TLS settings",
+                    "x-category": "Networking",
+                    "properties": { "cert": { "type": "string", "x-category": "Files" } }
+                }
+            }
+        });
+        let yaml = generate_document(&schema, TargetFormat::Yaml, AnnotatorConfig::default()).unwrap();
+        let toml = generate_document(&schema, TargetFormat::Toml, AnnotatorConfig::default()).unwrap();
+        assert_snapshot!("generate_categories_yaml", yaml);
+        assert_snapshot!("generate_categories_toml", toml);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_generate_merges_nested_defaults() {
+        let schema = serde_json::json!({
+            "properties": {
+                "server": {
+                    "default": { "port": 9000, "tls": { "enabled": true }, "extra": "kept" },
+                    "properties": {
+                        "host": { "type": "string", "default": "localhost" },
+                        "port": { "type": "integer" },
+                        "workers": { "type": "integer", "default": 4 },
+                        "tls": {
+                            "default": { "cert": "tls.pem" },
+                            "properties": {
+                                "enabled": { "type": "boolean" },
+                                "cert": { "type": "string" },
+                                "key": { "type": "string" }
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        let result = generate_document(&schema, TargetFormat::Yaml, AnnotatorConfig::default()).unwrap();
+        assert_eq!(
+            result,
+            "server:\n  host: localhost\n  port: 9000\n  workers: 4\n  tls:\n    enabled: true\n    cert: tls.pem\n    # TODO: set this\n    key: ''\n  extra: kept\n"
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_generate_root_array() {
+        let schema = serde_json::json!({
+            "type": "array",
+            "items": {
+                "properties": {
+                    "name": { "description": "Job name", "type": "string" },
+                    "retries": { "description": "Attempts before giving up", "default": 3 }
+                }
+            }
+        });
+        let result = generate_document(&schema, TargetFormat::Yaml, AnnotatorConfig::default()).unwrap();
+        assert_eq!(result, "# Job name\n- name: ''\n  # Attempts before giving up\n  retries: 3\n");
+        assert!(generate_document(&schema, TargetFormat::Toml, AnnotatorConfig::default()).is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_generate_yaml() {
+        let result = generate_document(&schema(), TargetFormat::Yaml, AnnotatorConfig::default()).unwrap();
+        assert_snapshot!(result);
+    }
+
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    #[test]
+    fn test_convert_toml_to_yaml() {
+        let toml = r#"# Dropped, the schema documents the result
+name = "app"
+log_level = "debug"
+
+[server]
+port = 9090
+hosts = ["a", "b"]
+"#;
+        let result =
+            convert(&schema(), toml, TargetFormat::Toml, TargetFormat::Yaml, AnnotatorConfig::default())
+                .unwrap();
+        assert_snapshot!(result);
+    }
+
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    #[test]
+    fn test_convert_yaml_to_toml() {
+        let yaml = "server:\n  port: 9090\n  tls: null\nname: app\ntimeout: null\n";
+        let result =
+            convert(&schema(), yaml, TargetFormat::Yaml, TargetFormat::Toml, AnnotatorConfig::default())
+                .unwrap();
+        assert_snapshot!(result);
+
+        let error = convert(&schema(), "- 1\n", TargetFormat::Yaml, TargetFormat::Toml, Default::default())
+            .unwrap_err();
+        assert!(matches!(error.kind, AnnotatorErrorKind::Serialize));
+    }
+
+    #[cfg(feature = "schemars")]
+    mod typed {
+        use super::*;
+
+        /// Application settings
+        #[derive(serde::Serialize, schemars::JsonSchema)]
+        struct Settings {
+            /// Name of the service
+            name: String,
+            /// Optional timeout in seconds
+            timeout: Option<u64>,
+            server: Server,
+        }
+
+        /// HTTP server settings
+        #[derive(serde::Serialize, schemars::JsonSchema)]
+        struct Server {
+            /// Port to listen on
+            port: u16,
+            hosts: Vec<String>,
+        }
+
+        fn settings() -> Settings {
+            Settings {
+                name: "app".to_string(),
+                timeout: None,
+                server: Server { port: 8080, hosts: vec!["localhost".to_string()] },
+            }
+        }
+
+        #[cfg(feature = "toml")]
+        #[test]
+        fn test_to_annotated_toml_string() {
+            let result = to_annotated_toml_string(&settings(), AnnotatorConfig::default()).unwrap();
+            assert_snapshot!(result);
+        }
+
+        #[cfg(feature = "yaml")]
+        #[test]
+        fn test_to_annotated_yaml_string() {
+            let result = to_annotated_yaml_string(&settings(), AnnotatorConfig::default()).unwrap();
+            assert_snapshot!(result);
+        }
+
+        #[cfg(feature = "toml")]
+        #[test]
+        fn test_generate_default_config() {
+            #[derive(Default, serde::Serialize, schemars::JsonSchema)]
+            struct Limits {
+                /// Maximum number of connections
+                max_connections: u32,
+                /// Whether requests are logged
+                log_requests: bool,
+            }
+
+            let result =
+                generate_default_config::<Limits>(TargetFormat::Toml, AnnotatorConfig::default());
+            assert_eq!(
+                result.unwrap(),
+                "# Maximum number of connections\nmax_connections = 0\n\
+                 # Whether requests are logged\nlog_requests = false\n"
+            );
+        }
+
+        #[cfg(feature = "toml")]
+        #[test]
+        fn test_to_annotated_toml_string_scalar() {
+            let error = to_annotated_toml_string(&8080, AnnotatorConfig::default()).unwrap_err();
+            assert!(matches!(error.kind, AnnotatorErrorKind::Serialize));
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use serde_json::{json, Map, Value};
+
+use crate::enrich::{enrich_schema, extract_comments};
+use crate::error::AnnotatorError;
+use crate::format::TargetFormat;
+use crate::schema::schema_types;
+use crate::validate::parse_target;
+
+/// Draft a JSON Schema from a config, to start a schema-first workflow from
+///
+/// The schema's structure and types come from the values: tables and
+/// mappings become objects listing their keys as `properties`, arrays get an
+/// `items` schema covering all of their elements, and integers found next
+/// to floats widen to numbers. Nulls and empty arrays say nothing about
+/// their type and are left untyped. The comments directly above each key
+/// become its `description`, as [`extract_comments`] reads them. Nothing is
+/// marked required, and objects stay open to other keys.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "toml")] {
+/// use jsonschema_annotator::{infer_schema, TargetFormat};
+///
+/// let schema = infer_schema("# Port to listen on\nport = 8080\n", TargetFormat::Toml).unwrap();
+/// assert_eq!(schema["properties"]["port"]["type"], "integer");
+/// assert_eq!(schema["properties"]["port"]["description"], "Port to listen on");
+/// # }
+/// ```
+pub fn infer_schema(document: &str, format: TargetFormat) -> Result<Value, AnnotatorError> {
+    let value = parse_target(document, format)?;
+    let mut schema = json!({ "$schema": "https://json-schema.org/draft/2020-12/schema" });
+    if let (Value::Object(schema), Value::Object(inferred)) = (&mut schema, infer(&value)) {
+        schema.extend(inferred);
+    }
+    // Every commented key has a property, so no comment is left over
+    enrich_schema(&mut schema, &extract_comments(document, format)?);
+    Ok(schema)
+}
+
+/// The schema of a value
+fn infer(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({}),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(n) if n.is_f64() => json!({ "type": "number" }),
+        Value::Number(_) => json!({ "type": "integer" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Array(items) => {
+            let mut schema = json!({ "type": "array" });
+            if let Some(items) = items.iter().map(infer).reduce(merge) {
+                schema["items"] = items;
+            }
+            schema
+        }
+        Value::Object(map) => {
+            let properties: Map<String, Value> =
+                map.iter().map(|(key, value)| (key.clone(), infer(value))).collect();
+            json!({ "type": "object", "properties": properties })
+        }
+    }
+}
+
+/// A schema covering the values of both schemas
+fn merge(a: Value, b: Value) -> Value {
+    let (a_types, b_types) = (types(&a), types(&b));
+    if a_types.is_empty() {
+        // An untyped schema (from a null) adds nothing
+        return b;
+    }
+    if b_types.is_empty() {
+        return a;
+    }
+    if a_types == b_types {
+        return match a_types[0].as_str() {
+            "object" => merge_objects(a, b),
+            "array" => {
+                let items = match (a.get("items"), b.get("items")) {
+                    (Some(a_items), Some(b_items)) => Some(merge(a_items.clone(), b_items.clone())),
+                    (items, other) => items.or(other).cloned(),
+                };
+                match items {
+                    Some(items) => json!({ "type": "array", "items": items }),
+                    None => json!({ "type": "array" }),
+                }
+            }
+            _ => a,
+        };
+    }
+
+    let mut union = a_types;
+    for name in b_types {
+        if !union.contains(&name) {
+            union.push(name);
+        }
+    }
+    if union.iter().any(|name| name == "number") {
+        union.retain(|name| name != "integer");
+    }
+    match union.as_slice() {
+        [single] => json!({ "type": single }),
+        _ => json!({ "type": union }),
+    }
+}
+
+/// The types a schema allows, none for an untyped one
+fn types(schema: &Value) -> Vec<String> {
+    schema_types(schema).into_iter().map(str::to_string).collect()
+}
+
+/// Merge object schemas, keeping the properties of both in order of appearance
+fn merge_objects(mut a: Value, mut b: Value) -> Value {
+    let mut properties = match a.get_mut("properties").map(Value::take) {
+        Some(Value::Object(properties)) => properties,
+        _ => Map::new(),
+    };
+    if let Some(Value::Object(other)) = b.get_mut("properties").map(Value::take) {
+        for (key, schema) in other {
+            let merged = match properties.remove(&key) {
+                Some(existing) => merge(existing, schema),
+                None => schema,
+            };
+            properties.insert(key, merged);
+        }
+    }
+    json!({ "type": "object", "properties": properties })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "toml")]
+    use insta::assert_snapshot;
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_infer_schema_toml() {
+        let toml = r#"# Name of the service
+name = "api"
+# Seconds between health checks,
+# at least one
+interval = 5
+
+# Upstream servers
+[[servers]]
+# Address to connect to
+host = "10.0.0.1"
+weight = 1
+
+[[servers]]
+host = "10.0.0.2"
+weight = 0.5
+backup = true
+"#;
+        let schema = infer_schema(toml, TargetFormat::Toml).unwrap();
+        assert_snapshot!(serde_json::to_string_pretty(&schema).unwrap());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_infer_schema_yaml() {
+        let yaml = "# Log level\nlevel: info\ntags: []\nproxy: ~\nports: [80, \"8080\"]\n";
+        let schema = infer_schema(yaml, TargetFormat::Yaml).unwrap();
+        let properties = &schema["properties"];
+        assert_eq!(properties["level"]["description"], "Log level");
+        assert_eq!(properties["tags"], json!({ "type": "array" }));
+        assert_eq!(properties["proxy"], json!({}));
+        assert_eq!(properties["ports"]["items"], json!({ "type": ["integer", "string"] }));
+    }
+}
@@ -1,32 +1,101 @@
 #![doc = include_str!("../README.md")]
+// Without a built-in format only custom formats are left, which don't use the
+// shared annotation machinery
+#![cfg_attr(not(any(feature = "toml", feature = "yaml")), allow(dead_code, unused))]
 
 mod annotator;
+#[cfg(feature = "miette")]
+mod diagnostic;
+mod discover;
+mod drift;
+mod enrich;
 mod error;
+mod explain;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod file;
 mod format;
+mod generate;
+mod infer;
+mod lint;
+#[cfg(any(feature = "figment", feature = "config-rs"))]
+mod loaders;
+#[cfg(feature = "node")]
+mod node;
+#[cfg(feature = "python")]
+mod python;
+mod reference;
+#[cfg(feature = "async")]
+mod remote;
+mod sarif;
 mod schema;
+mod schema_diff;
+mod strip;
+mod validate;
 
-pub use annotator::{Annotator, AnnotatorConfig, ExistingCommentBehavior, TomlAnnotator, YamlAnnotator};
-pub use error::{AnnotatorError, AnnotatorErrorKind, Error, ResultExt, SchemaError, SchemaErrorKind};
-pub use format::TargetFormat;
-pub use schema::{extract_annotations, Annotation, AnnotationMap};
+pub use annotator::{
+    AnnotateOutcome, AnnotationDecision, AnnotationEvent, AnnotationLock, AnnotationObserver, AnnotationReport, Annotator,
+    AnnotatorConfig, CommentContext, CommentFormatter, CommentSpacing, DefaultCommentFormatter, EnvVarStyle,
+    DuplicateKeys, ExistingCommentBehavior, Include, LineEnding, LockEntry, NodeFilter, ParseOptionError,
+    RedundantDefaults, SectionStyle, YamlVersion,
+};
+#[cfg(feature = "toml")]
+pub use annotator::TomlAnnotator;
+#[cfg(feature = "yaml")]
+pub use annotator::{YamlAnnotator, YamlDocument};
+pub use discover::find_schema_reference;
+pub use drift::{detect_drift, CommentDrift, DriftKind};
+pub use enrich::{enrich_schema, extract_comments};
+pub use error::{
+    AnnotatorError, AnnotatorErrorKind, Error, ResultExt, SchemaError, SchemaErrorKind, SourceLocation,
+};
+pub use explain::{describe, explain};
+pub use file::{annotate_file, annotate_file_in_place, load_schema, parse_schema};
+pub use format::{register_format, AnnotatorFactory, TargetFormat};
+pub use generate::{convert, generate_document};
+pub use infer::infer_schema;
+pub use reference::html_reference;
+#[cfg(feature = "schemars")]
+pub use generate::generate_default_config;
+#[cfg(all(feature = "schemars", feature = "toml"))]
+pub use generate::to_annotated_toml_string;
+#[cfg(all(feature = "schemars", feature = "yaml"))]
+pub use generate::to_annotated_yaml_string;
+#[cfg(feature = "config-rs")]
+pub use loaders::annotate_config_rs;
+#[cfg(feature = "figment")]
+pub use loaders::annotate_figment;
+#[cfg(feature = "async")]
+pub use remote::{annotate_async, AsyncSchemaResolver};
+pub use sarif::{sarif_report, SarifFile};
+pub use schema::{
+    extract_annotations, path_to_pointer, pointer_to_path, Annotation, AnnotationMap, SchemaCache,
+    SchemaSource,
+};
+pub use schema_diff::{diff_annotations, diff_schemas, SchemaChange};
+pub use strip::{strip_annotations, strip_extracted};
+pub use validate::{annotate_with_validation, validate, ValidationIssue};
 
-use schemars::Schema;
+use std::io::{Read, Write};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 /// Annotate a target document with schema descriptions
 ///
 /// # Arguments
-/// * `schema` - JSON Schema as a `schemars::Schema`
+/// * `schema` - JSON Schema as a `serde_json::Value` or `schemars::Schema`
 /// * `target` - Target document as a string (TOML or YAML)
 /// * `target_format` - Format of the target document
 /// * `config` - Annotation configuration options
 ///
 /// # Example
 /// ```rust
+/// # #[cfg(feature = "toml")] {
 /// use jsonschema_annotator::{annotate, TargetFormat, AnnotatorConfig};
-/// use schemars::Schema;
 ///
 /// let schema_json = r#"{"properties": {"port": {"title": "Port"}}}"#;
-/// let schema: Schema = serde_json::from_str(schema_json).unwrap();
+/// let schema: serde_json::Value = serde_json::from_str(schema_json).unwrap();
 ///
 /// let annotated = annotate(
 ///     &schema,
@@ -36,25 +105,275 @@ use schemars::Schema;
 /// ).unwrap();
 ///
 /// assert!(annotated.contains("# Port"));
+/// # }
 /// ```
 pub fn annotate(
-    schema: &Schema,
+    schema: &impl SchemaSource,
     target: &str,
     target_format: TargetFormat,
     config: AnnotatorConfig,
 ) -> Result<String, AnnotatorError> {
+    annotate_with_report(schema, target, target_format, config).map(|(annotated, _)| annotated)
+}
+
+/// Annotate a target document, also reporting what happened to each path
+///
+/// Like [`annotate`], but additionally returns an [`AnnotationReport`] listing
+/// the paths that were annotated, left alone because of existing comments,
+/// excluded by the configuration, or had no annotation in the schema.
+pub fn annotate_with_report(
+    schema: &impl SchemaSource,
+    target: &str,
+    target_format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<(String, AnnotationReport), AnnotatorError> {
+    let _span = tracing::debug_span!("annotate", format = ?target_format).entered();
     let annotations = extract_annotations(schema);
+    annotate_extracted(&annotations, target, target_format, config)
+}
 
+/// Annotate a target document, telling whether anything changed
+///
+/// Like [`annotate_with_report`], but the [`AnnotateOutcome`] borrows
+/// `target` when it was already up to date, so callers can skip writing it
+/// back.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "toml")] {
+/// use jsonschema_annotator::{annotate_outcome, AnnotatorConfig, TargetFormat};
+///
+/// let schema = serde_json::json!({"properties": {"port": {"title": "Port"}}});
+/// let outcome = annotate_outcome(&schema, "port = 8080\n", TargetFormat::Toml, AnnotatorConfig::default())
+///     .unwrap();
+/// assert!(outcome.changed);
+///
+/// // Nothing to annotate in this one
+/// let outcome = annotate_outcome(&schema, "host = \"::\"\n", TargetFormat::Toml, AnnotatorConfig::default())
+///     .unwrap();
+/// assert!(!outcome.changed);
+/// # }
+/// ```
+pub fn annotate_outcome<'a>(
+    schema: &impl SchemaSource,
+    target: &'a str,
+    target_format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<AnnotateOutcome<'a>, AnnotatorError> {
+    let (annotated, report) = annotate_with_report(schema, target, target_format, config)?;
+    Ok(AnnotateOutcome::new(target, annotated, report))
+}
+
+/// Annotate a target document with previously extracted annotations
+///
+/// Like [`annotate_with_report`], but takes the result of
+/// [`extract_annotations`] so a schema shared by many documents only has to
+/// be processed once.
+pub fn annotate_extracted(
+    annotations: &AnnotationMap,
+    target: &str,
+    target_format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<(String, AnnotationReport), AnnotatorError> {
+    if config.warn_unknown_keys {
+        return lint::annotate_marking_unknown(annotations, target, target_format, config);
+    }
+    if config.redundant_defaults != RedundantDefaults::Ignore {
+        return lint::annotate_finding_redundant_defaults(annotations, target, target_format, config);
+    }
     match target_format {
+        #[cfg(feature = "toml")]
         TargetFormat::Toml => {
             let annotator = TomlAnnotator::new(config);
-            annotator.annotate(target, &annotations)
+            annotator.annotate_with_report(target, annotations)
         }
+        #[cfg(feature = "yaml")]
         TargetFormat::Yaml => {
             let annotator = YamlAnnotator::new(config);
-            annotator.annotate(target, &annotations)
+            annotator.annotate_with_report(target, annotations)
         }
+        TargetFormat::Custom(name) => {
+            let annotator = format::custom_annotator(name, config).ok_or_else(|| {
+                Error::new(AnnotatorErrorKind::UnsupportedFormat)
+                    .add_context(format!("no annotator registered for format {}", name))
+            })?;
+            annotator.annotate_with_report(target, annotations)
+        }
+        #[cfg(not(all(feature = "toml", feature = "yaml")))]
+        format => Err(format::feature_disabled(format)),
+    }
+}
+
+/// Annotate many documents with the same schema in parallel
+///
+/// The schema's annotations are extracted once and shared by a pool of
+/// threads, one per available CPU. Results are returned in the order of
+/// `documents`, each document failing or succeeding on its own.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "yaml")] {
+/// use jsonschema_annotator::{annotate_many, AnnotatorConfig, TargetFormat};
+///
+/// let schema = serde_json::json!({"properties": {"port": {"title": "Port"}}});
+/// let documents = vec![
+///     ("port = 8080".to_string(), TargetFormat::Toml),
+///     ("port: 8080".to_string(), TargetFormat::Yaml),
+/// ];
+///
+/// let results = annotate_many(&schema, documents, AnnotatorConfig::default());
+/// assert_eq!(results[1].as_ref().unwrap().0, "# Port\nport: 8080");
+/// # }
+/// ```
+pub fn annotate_many(
+    schema: &impl SchemaSource,
+    documents: impl IntoIterator<Item = (String, TargetFormat)>,
+    config: AnnotatorConfig,
+) -> Vec<Result<(String, AnnotationReport), AnnotatorError>> {
+    let annotations = extract_annotations(schema);
+    let documents: Vec<_> = documents.into_iter().collect();
+    let annotate = |(target, target_format): &(String, TargetFormat)| {
+        let _span = tracing::debug_span!("annotate", format = ?target_format).entered();
+        annotate_extracted(&annotations, target, *target_format, config.clone())
+    };
+
+    let jobs = std::thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(documents.len());
+    if jobs <= 1 {
+        return documents.iter().map(annotate).collect();
+    }
+
+    // Workers take the next unprocessed document until none are left
+    let next = AtomicUsize::new(0);
+    let slots = Mutex::new((0..documents.len()).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(document) = documents.get(i) else { break };
+                let result = annotate(document);
+                slots.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    slots.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+/// Annotate a document from a reader, writing the result to a writer
+///
+/// YAML documents are annotated line by line, so neither the input nor the
+/// output is held in memory as a whole (see
+/// [`YamlAnnotator::annotate_stream`]). Other formats, and YAML with
+/// [`strict`](AnnotatorConfig::strict),
+/// [`fail_on_unsupported`](AnnotatorConfig::fail_on_unsupported),
+/// [`verify_round_trip`](AnnotatorConfig::verify_round_trip),
+/// [`warn_unknown_keys`](AnnotatorConfig::warn_unknown_keys),
+/// [`redundant_defaults`](AnnotatorConfig::redundant_defaults) or
+/// [`duplicate_keys`](AnnotatorConfig::duplicate_keys) set, need the whole
+/// document and are read fully first.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "yaml")] {
+/// use jsonschema_annotator::{annotate_to_writer, AnnotatorConfig, TargetFormat};
+///
+/// let schema = serde_json::json!({"properties": {"port": {"title": "Port"}}});
+/// let mut output = Vec::new();
+///
+/// annotate_to_writer(
+///     &schema,
+///     "port: 8080\n".as_bytes(),
+///     TargetFormat::Yaml,
+///     AnnotatorConfig::default(),
+///     &mut output,
+/// ).unwrap();
+///
+/// assert_eq!(output, b"# Port\nport: 8080\n");
+/// # }
+/// ```
+pub fn annotate_to_writer(
+    schema: &impl SchemaSource,
+    mut reader: impl Read,
+    target_format: TargetFormat,
+    config: AnnotatorConfig,
+    mut writer: impl Write,
+) -> Result<AnnotationReport, AnnotatorError> {
+    let io_error = |e| Error::new(AnnotatorErrorKind::Io).with_source(e);
+    let annotations = extract_annotations(schema);
+
+    #[cfg(feature = "yaml")]
+    if target_format == TargetFormat::Yaml
+        && !config.strict
+        && !config.fail_on_unsupported
+        && !config.verify_round_trip
+        && !config.warn_unknown_keys
+        && config.redundant_defaults == RedundantDefaults::Ignore
+        && config.duplicate_keys == DuplicateKeys::All
+    {
+        let annotator = YamlAnnotator::new(config);
+        return annotator.annotate_stream(std::io::BufReader::new(reader), &annotations, writer);
     }
+
+    let mut target = String::new();
+    reader.read_to_string(&mut target).map_err(io_error)?;
+    let (annotated, report) = annotate_extracted(&annotations, &target, target_format, config)?;
+    writer.write_all(annotated.as_bytes()).map_err(io_error)?;
+    writer.flush().map_err(io_error)?;
+    Ok(report)
+}
+
+/// Annotate an already parsed TOML document in place
+///
+/// For applications that hold a [`toml_edit::DocumentMut`], this avoids
+/// serializing it to a string and parsing it back. See
+/// [`TomlAnnotator::annotate_document`].
+///
+/// [`annotate_yaml_document`] is the YAML equivalent.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::{annotate_toml_document, extract_annotations, AnnotatorConfig};
+///
+/// let schema = serde_json::json!({"properties": {"port": {"title": "Port"}}});
+/// let mut doc: toml_edit::DocumentMut = "port = 8080".parse().unwrap();
+///
+/// annotate_toml_document(&mut doc, &extract_annotations(&schema), &AnnotatorConfig::default())
+///     .unwrap();
+/// assert_eq!(doc.to_string(), "# Port\nport = 8080\n");
+/// ```
+#[cfg(feature = "toml")]
+pub fn annotate_toml_document(
+    doc: &mut toml_edit::DocumentMut,
+    annotations: &AnnotationMap,
+    config: &AnnotatorConfig,
+) -> Result<AnnotationReport, AnnotatorError> {
+    TomlAnnotator::new(config.clone()).annotate_document(doc, annotations)
+}
+
+/// Annotate an already parsed YAML document in place
+///
+/// The YAML counterpart of [`annotate_toml_document`], for applications that
+/// hold a [`YamlDocument`]. See [`YamlAnnotator::annotate_document`].
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::{annotate_yaml_document, extract_annotations, AnnotatorConfig, YamlDocument};
+///
+/// let schema = serde_json::json!({"properties": {"port": {"title": "Port"}}});
+/// let mut doc: YamlDocument = "port: 8080\n".parse().unwrap();
+///
+/// annotate_yaml_document(&mut doc, &extract_annotations(&schema), &AnnotatorConfig::default())
+///     .unwrap();
+/// assert_eq!(doc.to_string(), "# Port\nport: 8080\n");
+/// ```
+#[cfg(feature = "yaml")]
+pub fn annotate_yaml_document(
+    doc: &mut YamlDocument,
+    annotations: &AnnotationMap,
+    config: &AnnotatorConfig,
+) -> Result<AnnotationReport, AnnotatorError> {
+    YamlAnnotator::new(config.clone()).annotate_document(doc, annotations)
 }
 
 #[cfg(test)]
@@ -62,6 +381,7 @@ mod tests {
     use super::*;
     use insta::assert_snapshot;
 
+    #[cfg(feature = "toml")]
     #[test]
     fn test_annotate_toml() {
         let schema_json = r#"{
@@ -79,7 +399,7 @@ mod tests {
             }
         }"#;
 
-        let schema: Schema = serde_json::from_str(schema_json).unwrap();
+        let schema: serde_json::Value = serde_json::from_str(schema_json).unwrap();
         let config = r#"[server]
 port = 8080
 "#;
@@ -88,6 +408,7 @@ port = 8080
         assert_snapshot!(result);
     }
 
+    #[cfg(feature = "yaml")]
     #[test]
     fn test_annotate_yaml() {
         let schema_json = r#"{
@@ -105,7 +426,7 @@ port = 8080
             }
         }"#;
 
-        let schema: Schema = serde_json::from_str(schema_json).unwrap();
+        let schema: serde_json::Value = serde_json::from_str(schema_json).unwrap();
         let config = r#"server:
   port: 8080
 "#;
@@ -114,6 +435,58 @@ port = 8080
         assert_snapshot!(result);
     }
 
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_annotate_with_report() {
+        let schema_json = r#"{
+            "properties": {
+                "server": {
+                    "title": "Server",
+                    "properties": {
+                        "port": { "title": "Port" },
+                        "host": { "title": "Host" }
+                    }
+                }
+            }
+        }"#;
+
+        let schema: serde_json::Value = serde_json::from_str(schema_json).unwrap();
+        let config = "server:\n  # Existing\n  port: 8080\n  host: localhost\n  debug: true\n";
+        let annotator_config = AnnotatorConfig {
+            existing_comments: ExistingCommentBehavior::Skip,
+            ..Default::default()
+        };
+
+        let (_, report) =
+            annotate_with_report(&schema, config, TargetFormat::Yaml, annotator_config).unwrap();
+        assert_eq!(report.applied, vec!["server", "server.host"]);
+        assert_eq!(report.skipped_existing, vec!["server.port"]);
+        assert_eq!(report.unmatched, vec!["server.debug"]);
+        assert!(report.excluded.is_empty());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_annotate_outcome() {
+        let schema = serde_json::json!({ "properties": { "port": { "title": "Port" } } });
+        let config = AnnotatorConfig::default;
+
+        let outcome = annotate_outcome(&schema, "port: 8080\n", TargetFormat::Yaml, config()).unwrap();
+        assert!(outcome.changed);
+        assert!(matches!(outcome.text, std::borrow::Cow::Owned(_)));
+        assert_eq!(outcome.text, "# Port\nport: 8080\n");
+        assert_eq!(outcome.report.applied, vec!["port"]);
+
+        let config = || AnnotatorConfig {
+            existing_comments: ExistingCommentBehavior::Skip,
+            ..Default::default()
+        };
+        let outcome = annotate_outcome(&schema, "# Port\nport: 8080\n", TargetFormat::Yaml, config()).unwrap();
+        assert!(!outcome.changed);
+        assert!(matches!(outcome.text, std::borrow::Cow::Borrowed(_)));
+    }
+
+    #[cfg(feature = "toml")]
     #[test]
     fn test_annotate_with_refs() {
         let schema_json = r##"{
@@ -129,10 +502,72 @@ port = 8080
             }
         }"##;
 
-        let schema: Schema = serde_json::from_str(schema_json).unwrap();
+        let schema: serde_json::Value = serde_json::from_str(schema_json).unwrap();
         let config = "http_port = 80\nhttps_port = 443\n";
 
         let result = annotate(&schema, config, TargetFormat::Toml, AnnotatorConfig::default()).unwrap();
         assert_snapshot!(result);
     }
+
+    #[cfg(all(feature = "schemars", feature = "toml"))]
+    #[test]
+    fn test_annotate_schemars_schema() {
+        let schema = schemars::json_schema!({ "properties": { "port": { "title": "Port" } } });
+        let result = annotate(&schema, "port = 8080\n", TargetFormat::Toml, AnnotatorConfig::default());
+        assert_eq!(result.unwrap(), "# Port\nport = 8080\n");
+    }
+
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    #[test]
+    fn test_annotate_many() {
+        let schema = serde_json::json!({ "properties": { "port": { "title": "Port" } } });
+        let documents = (0..20).map(|i| match i % 3 {
+            0 => (format!("port = {}\n", i), TargetFormat::Toml),
+            1 => (format!("port: {}\n", i), TargetFormat::Yaml),
+            _ => ("port = [".to_string(), TargetFormat::Toml),
+        });
+
+        let results = annotate_many(&schema, documents, AnnotatorConfig::default());
+        assert_eq!(results.len(), 20);
+        for (i, result) in results.iter().enumerate() {
+            match i % 3 {
+                0 => assert_eq!(result.as_ref().unwrap().0, format!("# Port\nport = {}\n", i)),
+                1 => assert_eq!(result.as_ref().unwrap().0, format!("# Port\nport: {}\n", i)),
+                _ => assert!(matches!(result.as_ref().unwrap_err().kind, AnnotatorErrorKind::Parse)),
+            }
+        }
+    }
+
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    #[test]
+    fn test_annotators_are_shareable() {
+        fn assert_shareable<T: Send + Sync + Clone + 'static>() {}
+        assert_shareable::<AnnotatorConfig>();
+        assert_shareable::<AnnotationMap>();
+        assert_shareable::<TomlAnnotator>();
+        assert_shareable::<YamlAnnotator>();
+    }
+
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    #[test]
+    fn test_annotator_shared_between_threads() {
+        let schema = serde_json::json!({ "properties": { "port": { "title": "Port" } } });
+        let annotations = extract_annotations(&schema);
+        let toml = TomlAnnotator::new(AnnotatorConfig::default());
+        let yaml = YamlAnnotator::new(AnnotatorConfig::default());
+
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let (toml, yaml, annotations) = (&toml, yaml.clone(), &annotations);
+                scope.spawn(move || {
+                    let document = format!("port = {}\n", i);
+                    let annotated = toml.annotate(&document, annotations).unwrap();
+                    assert_eq!(annotated, format!("# Port\nport = {}\n", i));
+                    let document = format!("port: {}\n", i);
+                    let annotated = yaml.annotate(&document, annotations).unwrap();
+                    assert_eq!(annotated, format!("# Port\nport: {}\n", i));
+                });
+            }
+        });
+    }
 }
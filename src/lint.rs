@@ -0,0 +1,307 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::annotator::{
+    AnnotationReport, AnnotatorConfig, CommentContext, CommentFormatter, RedundantDefaults,
+};
+use crate::error::AnnotatorError;
+use crate::format::TargetFormat;
+use crate::schema::{format_default_value, Annotation, AnnotationMap};
+use crate::validate::parse_target;
+
+/// Annotate a document, marking the keys the schema doesn't define
+///
+/// Implements [`AnnotatorConfig::warn_unknown_keys`]: unknown keys get an
+/// empty annotation so the annotator visits them, and a wrapping formatter
+/// adds the warning to whatever the configured formatter renders.
+pub(crate) fn annotate_marking_unknown(
+    annotations: &AnnotationMap,
+    target: &str,
+    target_format: TargetFormat,
+    mut config: AnnotatorConfig,
+) -> Result<(String, AnnotationReport), AnnotatorError> {
+    let mut paths = HashSet::new();
+    collect_paths(&parse_target(target, target_format)?, "", &mut paths);
+    let unknown: HashSet<String> = paths.into_iter().filter(|p| annotations.is_unknown(p)).collect();
+    tracing::debug!(count = unknown.len(), "found unknown keys");
+
+    let mut annotations = annotations.clone();
+    for path in &unknown {
+        if annotations.get(path).is_none() {
+            annotations.insert_unchecked(Annotation::new(path.clone()));
+        }
+    }
+    config.warn_unknown_keys = false;
+    config.formatter = Arc::new(UnknownKeyFormatter {
+        inner: config.formatter.clone(),
+        unknown: unknown.clone(),
+    });
+
+    let (annotated, mut report) =
+        crate::annotate_extracted(&annotations, target, target_format, config)?;
+    report.applied.retain(|path| !unknown.contains(path));
+    report.unknown = unknown.into_iter().collect();
+    report.unknown.sort();
+    Ok((annotated, report))
+}
+
+/// Appends an unknown key warning to the comments of another formatter
+#[derive(Debug)]
+struct UnknownKeyFormatter {
+    inner: Arc<dyn CommentFormatter>,
+    unknown: HashSet<String>,
+}
+
+impl CommentFormatter for UnknownKeyFormatter {
+    fn format(&self, annotation: &Annotation, context: &CommentContext<'_>) -> Vec<String> {
+        let mut lines = self.inner.format(annotation, context);
+        if self.unknown.contains(context.path) {
+            lines.push("# WARNING: unknown key (not in schema)".to_string());
+        }
+        lines
+    }
+}
+
+/// Annotate a document, finding the values that are the same as their default
+///
+/// Implements [`AnnotatorConfig::redundant_defaults`]: values are compared
+/// with the default of their annotation as comments render it. In
+/// [`Mark`](RedundantDefaults::Mark) mode, a wrapping formatter adds a note to
+/// whatever the configured formatter renders.
+pub(crate) fn annotate_finding_redundant_defaults(
+    annotations: &AnnotationMap,
+    target: &str,
+    target_format: TargetFormat,
+    mut config: AnnotatorConfig,
+) -> Result<(String, AnnotationReport), AnnotatorError> {
+    let mut redundant = Vec::new();
+    collect_redundant_defaults(&parse_target(target, target_format)?, "", annotations, &mut redundant);
+    tracing::debug!(count = redundant.len(), "found values same as their default");
+
+    if config.redundant_defaults == RedundantDefaults::Mark {
+        config.formatter = Arc::new(RedundantDefaultFormatter {
+            inner: config.formatter.clone(),
+            redundant: redundant.iter().cloned().collect(),
+        });
+    }
+    config.redundant_defaults = RedundantDefaults::Ignore;
+
+    let (annotated, mut report) =
+        crate::annotate_extracted(annotations, target, target_format, config)?;
+    redundant.sort();
+    report.redundant_defaults = redundant;
+    Ok((annotated, report))
+}
+
+/// Appends a note to the comments of values that are the same as their default
+#[derive(Debug)]
+struct RedundantDefaultFormatter {
+    inner: Arc<dyn CommentFormatter>,
+    redundant: HashSet<String>,
+}
+
+impl CommentFormatter for RedundantDefaultFormatter {
+    fn format(&self, annotation: &Annotation, context: &CommentContext<'_>) -> Vec<String> {
+        let mut lines = self.inner.format(annotation, context);
+        if self.redundant.contains(context.path) {
+            lines.push("# (same as default)".to_string());
+        }
+        lines
+    }
+}
+
+/// Collect the paths of values equal to the default of their annotation
+///
+/// Objects inside arrays aren't compared, since their keys share a path with
+/// those of every other item.
+fn collect_redundant_defaults(
+    value: &Value,
+    prefix: &str,
+    annotations: &AnnotationMap,
+    redundant: &mut Vec<String>,
+) {
+    let Value::Object(map) = value else { return };
+    for (key, value) in map {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        let default = annotations.get(&path).and_then(|a| a.default.as_deref());
+        if default == Some(format_default_value(value).as_str()) {
+            redundant.push(path);
+        } else {
+            collect_redundant_defaults(value, &path, annotations, redundant);
+        }
+    }
+}
+
+/// Collect the dot-separated path of every key, with keys of objects inside
+/// arrays sharing the array's path
+fn collect_paths(value: &Value, prefix: &str, paths: &mut HashSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                collect_paths(value, &path, paths);
+                paths.insert(path);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_paths(item, prefix, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::extract_annotations;
+    use insta::assert_snapshot;
+
+    fn schema() -> Value {
+        serde_json::json!({
+            "properties": {
+                "server": {
+                    "title": "Server",
+                    "properties": {
+                        "port": { "title": "Port" },
+                        "host": { "type": "string" }
+                    }
+                },
+                "labels": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" }
+                },
+                "extra": { "type": "object" }
+            }
+        })
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_unknown_keys_toml() {
+        let toml = r#"[server]
+port = 8080
+prot = 8081
+host = "localhost"
+
+[labels]
+team = "core"
+
+[extra]
+anything = 1
+
+[typo]
+nested = true
+"#;
+        let config = AnnotatorConfig {
+            warn_unknown_keys: true,
+            ..Default::default()
+        };
+
+        let (annotated, report) =
+            crate::annotate_with_report(&schema(), toml, TargetFormat::Toml, config).unwrap();
+        assert_eq!(report.unknown, vec!["server.prot", "typo"]);
+        assert_eq!(report.applied, vec!["server", "server.port"]);
+        assert_snapshot!(annotated);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_unknown_keys_yaml() {
+        let yaml = "server:\n  # Existing\n  prot: 8081\nlabels:\n  team: core\n";
+        let config = AnnotatorConfig {
+            warn_unknown_keys: true,
+            ..Default::default()
+        };
+
+        let (annotated, report) =
+            crate::annotate_with_report(&schema(), yaml, TargetFormat::Yaml, config).unwrap();
+        assert_eq!(report.unknown, vec!["server.prot"]);
+        assert_snapshot!(annotated);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_redundant_defaults() {
+        let schema = serde_json::json!({
+            "properties": {
+                "server": {
+                    "title": "Server",
+                    "properties": {
+                        "port": { "title": "Port", "default": 8080 },
+                        "host": { "title": "Host", "default": "localhost" },
+                        "tags": { "default": ["web"] }
+                    }
+                },
+                "limits": { "default": { "max": 10 } }
+            }
+        });
+        let toml = r#"[server]
+port = 8080
+host = "0.0.0.0"
+tags = ["web"]
+
+[limits]
+max = 10
+"#;
+
+        let config = AnnotatorConfig {
+            redundant_defaults: RedundantDefaults::Report,
+            ..Default::default()
+        };
+        let (annotated, report) =
+            crate::annotate_with_report(&schema, toml, TargetFormat::Toml, config).unwrap();
+        assert_eq!(report.redundant_defaults, vec!["limits", "server.port", "server.tags"]);
+        assert!(!annotated.contains("same as default"));
+
+        let config = AnnotatorConfig {
+            redundant_defaults: RedundantDefaults::Mark,
+            ..Default::default()
+        };
+        let (annotated, _) =
+            crate::annotate_with_report(&schema, toml, TargetFormat::Toml, config).unwrap();
+        assert_snapshot!(annotated);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_redundant_defaults_yaml() {
+        let schema = serde_json::json!({
+            "properties": {
+                "verbose": { "title": "Verbose", "default": false },
+                "level": { "title": "Level", "default": "info" }
+            }
+        });
+        let config = AnnotatorConfig {
+            redundant_defaults: RedundantDefaults::Mark,
+            ..Default::default()
+        };
+
+        let (annotated, report) = crate::annotate_with_report(
+            &schema,
+            "verbose: false\nlevel: debug\n",
+            TargetFormat::Yaml,
+            config,
+        )
+        .unwrap();
+        assert_eq!(report.redundant_defaults, vec!["verbose"]);
+        assert_snapshot!(annotated);
+    }
+
+    #[test]
+    fn test_is_unknown() {
+        let annotations = extract_annotations(&schema());
+        assert!(!annotations.is_unknown("server.port"));
+        assert!(annotations.is_unknown("server.prot"));
+        assert!(!annotations.is_unknown("labels.team"));
+        assert!(!annotations.is_unknown("extra.anything"));
+        // Only the outermost unknown key is reported
+        assert!(annotations.is_unknown("typo"));
+        assert!(!annotations.is_unknown("typo.nested"));
+
+        assert!(!AnnotationMap::new().is_unknown("anything"));
+    }
+}
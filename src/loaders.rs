@@ -0,0 +1,127 @@
+//! Adapters annotating the configuration loaded by figment or config-rs
+//!
+//! The configuration is deserialized into the application's type exactly as
+//! the application would load it, then serialized back and annotated with the
+//! type's schema. This shows the effective, merged configuration, e.g. for a
+//! `--print-config` command.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::annotator::AnnotatorConfig;
+use crate::error::{AnnotatorError, AnnotatorErrorKind, Error};
+use crate::format::TargetFormat;
+use crate::generate::to_annotated_string;
+
+/// Annotate the configuration a [`figment::Figment`] extracts as `T`
+///
+/// # Example
+/// ```rust
+/// use figment::{providers::Serialized, Figment};
+/// use jsonschema_annotator::{annotate_figment, AnnotatorConfig, TargetFormat};
+///
+/// #[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+/// struct Config {
+///     /// Port to listen on
+///     port: u16,
+/// }
+///
+/// let figment = Figment::from(Serialized::defaults(Config { port: 8080 }));
+/// let annotated =
+///     annotate_figment::<Config>(&figment, TargetFormat::Toml, AnnotatorConfig::default())
+///         .unwrap();
+/// assert_eq!(annotated, "# Port to listen on\nport = 8080\n");
+/// ```
+#[cfg(feature = "figment")]
+pub fn annotate_figment<T>(
+    figment: &figment::Figment,
+    format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<String, AnnotatorError>
+where
+    T: DeserializeOwned + Serialize + schemars::JsonSchema,
+{
+    let value: T = figment.extract().map_err(load_error)?;
+    to_annotated_string(&value, format, config)
+}
+
+/// Annotate the configuration a config-rs [`config::Config`] deserializes to
+/// as `T`
+#[cfg(feature = "config-rs")]
+pub fn annotate_config_rs<T>(
+    source: &config::Config,
+    format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<String, AnnotatorError>
+where
+    T: DeserializeOwned + Serialize + schemars::JsonSchema,
+{
+    let value: T = source.clone().try_deserialize().map_err(load_error)?;
+    to_annotated_string(&value, format, config)
+}
+
+fn load_error(e: impl std::error::Error + Send + Sync + 'static) -> AnnotatorError {
+    Error::new(AnnotatorErrorKind::Load).with_source(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Service settings
+    #[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+    struct Settings {
+        /// Name of the service
+        name: String,
+        server: Server,
+    }
+
+    #[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+    struct Server {
+        /// Port to listen on
+        port: u16,
+    }
+
+    #[cfg(feature = "figment")]
+    #[test]
+    fn test_annotate_figment() {
+        use figment::providers::Serialized;
+
+        let figment = figment::Figment::new()
+            .merge(Serialized::default("name", "app"))
+            .merge(Serialized::default("server.port", 8080))
+            .merge(Serialized::default("server.port", 9090));
+
+        let annotated =
+            annotate_figment::<Settings>(&figment, TargetFormat::Yaml, AnnotatorConfig::default());
+        assert_eq!(
+            annotated.unwrap(),
+            "# Name of the service\nname: app\nserver:\n  # Port to listen on\n  port: 9090\n"
+        );
+
+        let missing = figment::Figment::new().merge(Serialized::default("name", "app"));
+        let error = annotate_figment::<Settings>(&missing, TargetFormat::Yaml, Default::default());
+        assert!(matches!(error.unwrap_err().kind, AnnotatorErrorKind::Load));
+    }
+
+    #[cfg(feature = "config-rs")]
+    #[test]
+    fn test_annotate_config_rs() {
+        let source = config::Config::builder()
+            .set_default("name", "app")
+            .unwrap()
+            .set_default("server.port", 8080)
+            .unwrap()
+            .set_override("server.port", 9090)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let annotated =
+            annotate_config_rs::<Settings>(&source, TargetFormat::Toml, AnnotatorConfig::default());
+        assert_eq!(
+            annotated.unwrap(),
+            "# Name of the service\nname = \"app\"\n\n[server]\n# Port to listen on\nport = 9090\n"
+        );
+    }
+}
@@ -1,47 +1,415 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 
-use clap::{Parser, ValueEnum};
-use jsonschema_annotator::{annotate, AnnotatorConfig, ExistingCommentBehavior, TargetFormat};
-use schemars::Schema;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use jsonschema_annotator::{
+    annotate_extracted, annotate_with_validation, convert, detect_drift, diff_schemas, enrich_schema,
+    describe, explain, extract_annotations, extract_comments, find_schema_reference, generate_document, html_reference,
+    infer_schema, pointer_to_path, sarif_report, strip_extracted,
+    validate, Annotation, AnnotationLock, AnnotationMap, AnnotationReport, AnnotatorConfig, AnnotatorError,
+    AnnotatorErrorKind, CommentDrift, CommentSpacing, DriftKind, DuplicateKeys, EnvVarStyle, ExistingCommentBehavior,
+    LineEnding, NodeFilter, RedundantDefaults, SarifFile, SchemaError, SectionStyle, TargetFormat,
+    ValidationIssue, YamlVersion,
+};
+use serde::Serialize;
+use serde_json::Value;
 
 #[derive(Parser)]
 #[command(name = "jsonschema-annotator")]
 #[command(about = "Annotate YAML and TOML files with comments from JSON Schema")]
 #[command(version)]
+#[command(subcommand_negates_reqs = true)]
+#[command(arg_required_else_help = true)]
 struct Cli {
-    /// Path to JSON Schema file (JSON or YAML)
-    #[arg(short, long)]
-    schema: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// Path to config file to annotate (YAML or TOML), or - for stdin
-    #[arg(short, long)]
-    input: String,
+    /// Path or http(s) URL of the JSON Schema (JSON or YAML), read from the input if omitted
+    #[arg(short, long, global = true)]
+    schema: Option<String>,
 
-    /// Output path (default: stdout)
-    #[arg(short, long)]
+    /// Don't fetch schema URLs, only use previously cached copies
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Fetch schema URLs again even if the cached copy is less than a day old
+    #[arg(long, global = true, conflicts_with = "offline")]
+    refresh_schema: bool,
+
+    #[command(flatten)]
+    annotate: AnnotateArgs,
+
+    /// Format of the inputs, overriding detection from the file extension
+    #[arg(short, long, value_enum, global = true)]
+    format: Option<FormatMode>,
+
+    /// Output path (default: stdout), or a directory when given multiple inputs
+    #[arg(short, long, global = true)]
     output: Option<PathBuf>,
 
     /// What to include in comments
-    #[arg(long, value_enum, default_value = "both")]
+    #[arg(long, value_enum, default_value = "both", global = true)]
     include: IncludeMode,
 
-    /// Maximum line width for description wrapping
-    #[arg(long, default_value = "80")]
-    max_width: usize,
+    /// Maximum line width for comment wrapping: a number, "auto" for the terminal width
+    /// (or $COLUMNS) when writing to a terminal, or "none" (or 0) to not wrap
+    #[arg(long, value_name = "WIDTH", value_parser = parse_max_width, default_value = "80", global = true)]
+    max_width: MaxWidth,
 
     /// Include default values in comments
-    #[arg(long)]
+    #[arg(long, global = true)]
     include_default: bool,
 
+    /// Add the schema's examples as commented-out assignments below the comment
+    #[arg(long, global = true)]
+    include_examples: bool,
+
+    /// Add an example entry, from additionalProperties, to the comment of empty maps
+    #[arg(long, global = true)]
+    example_entries: bool,
+
+    /// Add a "# SECRET — do not commit real values" note to writeOnly, format: password and
+    /// x-secret values
+    #[arg(long, global = true)]
+    mark_secrets: bool,
+
+    /// Leave the defaults and examples of secrets out of comments, and generate placeholders
+    /// instead of their defaults
+    #[arg(long, global = true)]
+    redact_secrets: bool,
+
+    /// Comment out the properties the schema doesn't require in generated documents
+    #[arg(long, global = true)]
+    required_only: bool,
+
+    /// Schema keyword giving the version that introduced each option, shown as "# Since: ..."
+    #[arg(long, value_name = "KEYWORD", default_value = "x-since", global = true)]
+    since_keyword: String,
+
+    /// Name the environment variable overriding each value, with this prefix (e.g. APP)
+    #[arg(long, value_name = "PREFIX", global = true)]
+    env_prefix: Option<String>,
+
+    /// Separator between the prefix and path segments of environment variable names
+    #[arg(long, value_name = "SEP", default_value = "__", requires = "env_prefix", global = true)]
+    env_separator: String,
+
+    /// Convert Markdown in descriptions to plain text
+    #[arg(long, global = true)]
+    markdown: bool,
+
+    /// Strip HTML tags from descriptions
+    #[arg(long, global = true)]
+    strip_html: bool,
+
+    /// How section titles are rendered
+    #[arg(long, value_enum, default_value = "plain", global = true)]
+    section_style: SectionStyleMode,
+
+    /// Line ending for the output
+    #[arg(long, value_enum, default_value = "preserve", global = true)]
+    line_ending: LineEndingMode,
+
+    /// How plain YAML keys like on, yes or 0x10 are read when matching them with the schema
+    #[arg(long, value_enum, default_value = "1.2", global = true)]
+    yaml_version: YamlVersionMode,
+
+    /// Omit titles that restate the key name and annotations identical to the parent's
+    #[arg(long, global = true)]
+    dedupe: bool,
+
+    /// Declare this schema reference at the top of each output, as Taplo's "#:schema" directive
+    /// in TOML and a yaml-language-server modeline in YAML, unless one is already there
+    #[arg(long, value_name = "REF", global = true)]
+    schema_directive: Option<String>,
+
+    /// Only annotate paths matching this glob (e.g. "server.*", "database.**"), repeatable
+    #[arg(long = "only", value_name = "GLOB", global = true)]
+    only: Vec<String>,
+
+    /// Skip paths matching this glob, repeatable
+    #[arg(long, value_name = "GLOB", global = true)]
+    exclude: Vec<String>,
+
+    /// Only annotate the top N levels of the document
+    #[arg(long, value_name = "N", global = true)]
+    max_depth: Option<usize>,
+
+    /// Which kinds of nodes to annotate
+    #[arg(long, value_enum, default_value = "all", global = true)]
+    nodes: NodesMode,
+
+    /// Mark keys the schema doesn't define with a "# WARNING: unknown key" comment
+    #[arg(long, global = true)]
+    warn_unknown_keys: bool,
+
+    /// Report values that are the same as their schema default, or also mark them with a
+    /// "# (same as default)" comment
+    #[arg(long, value_enum, value_name = "MODE", global = true)]
+    redundant_defaults: Option<RedundantDefaultsMode>,
+
+    /// Which occurrence of a key repeated in the same YAML mapping to annotate; repeated keys
+    /// are warned about unless this is "error", which fails instead
+    #[arg(long, value_enum, value_name = "POLICY", default_value = "all", global = true)]
+    duplicate_keys: DuplicateKeysMode,
+
+    /// Fail if the schema annotates paths missing from the input, listing them
+    #[arg(long, global = true)]
+    fail_on_unmatched: bool,
+
+    /// Fail if keys the schema annotates are in inline tables, flow mappings or sequences,
+    /// where they can't be annotated, listing them
+    #[arg(long, global = true)]
+    fail_on_unsupported: bool,
+
+    /// Re-parse the output and fail if annotation changed any values
+    #[arg(long, global = true)]
+    verify: bool,
+
+    /// Also tidy the formatting of the output: indentation, spacing around = and :, blank lines
+    /// and the final newline
+    #[arg(long, global = true)]
+    normalize: bool,
+
+    /// Log progress to stderr, repeat for more detail (-v, -vv, -vvv)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Format of messages and logs on stderr
+    #[arg(long, value_enum, default_value = "text", global = true)]
+    log_format: LogFormat,
+
+    /// Overwrite output file if it exists
+    #[arg(long, global = true)]
+    force: bool,
+}
+
+/// Options of annotating configs, taken by the `annotate` and `check` subcommands
+/// and, for compatibility, without a subcommand
+#[derive(Args, Clone)]
+struct AnnotateArgs {
+    /// YAML file binding path globs to schemas, for inputs without --schema or a schema
+    /// declaration of their own (default: schemas.yaml in the --recursive directory, if any)
+    #[arg(long, value_name = "FILE")]
+    schema_map: Option<PathBuf>,
+
+    /// YAML or JSON file mapping paths (dot-separated or JSON Pointers) to wording replacing
+    /// the schema's: a description, or a mapping with a `title` and `description`
+    #[arg(long, value_name = "FILE")]
+    annotations: Option<PathBuf>,
+
+    /// Config files to annotate (YAML or TOML) or glob patterns, or - for stdin
+    #[arg(short, long, num_args = 1.., required_unless_present_any = ["recursive", "files"])]
+    input: Vec<String>,
+
+    /// Config files to annotate, like --input (e.g. the files pre-commit passes to hooks)
+    #[arg(value_name = "FILE")]
+    files: Vec<String>,
+
+    /// Annotate every .toml, .yaml and .yml file below this directory
+    #[arg(short, long, value_name = "DIR")]
+    recursive: Option<PathBuf>,
+
+    /// Skip files and directories matching this glob in recursive mode, repeatable
+    #[arg(long, value_name = "GLOB", requires = "recursive")]
+    ignore: Vec<String>,
+
+    /// Write the annotated result back to the input file
+    #[arg(long, conflicts_with = "output")]
+    in_place: bool,
+
+    /// Print a unified diff of the changes instead of the annotated document
+    #[arg(long, conflicts_with_all = ["output", "in_place"])]
+    diff: bool,
+
+    /// Report keys whose comments differ from what the schema would generate, without writing output
+    #[arg(long, conflicts_with_all = ["output", "in_place", "diff"])]
+    drift: bool,
+
+    /// Fail if annotating would change any input, without writing output
+    #[arg(long, conflicts_with_all = ["output", "in_place", "diff", "drift"])]
+    check: bool,
+
+    /// Remove the comments annotating would add instead, as the `strip` subcommand does
+    #[arg(skip)]
+    strip: bool,
+
+    /// When to color diff output
+    #[arg(long, value_enum, default_value = "auto")]
+    color: ColorMode,
+
     /// How to handle fields that already have comments
     #[arg(long, value_enum, default_value = "prepend")]
     existing_comments: ExistingCommentsMode,
 
-    /// Overwrite output file if it exists
+    /// Whether comments added next to existing ones attach tightly or leave a blank line
+    #[arg(long, value_enum, default_value = "tight")]
+    comment_spacing: CommentSpacingMode,
+
+    /// Lock file recording the comments generated for each input, telling them from hand
+    /// edits without marker lines in "update" mode and --drift; updated when annotating in
+    /// place in "update" mode
+    #[arg(long, value_name = "FILE")]
+    lock: Option<PathBuf>,
+
+    /// Validate inputs against the schema, failing on any violation
     #[arg(long)]
-    force: bool,
+    validate: bool,
+
+    /// Also embed each violation as an "# INVALID:" comment above its key, saying what the
+    /// schema expects
+    #[arg(long, requires = "validate")]
+    validation_comments: bool,
+
+    /// Number of files to process in parallel (default: number of CPUs)
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+
+    /// Write a report of what happened to each path of each input to this file
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    /// Format of the --report (default: sarif for a .sarif file, json otherwise)
+    #[arg(long, value_enum, value_name = "FORMAT", requires = "report")]
+    report_format: Option<ReportFormat>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Annotate configs with comments from the schema, as when no subcommand is given
+    Annotate(AnnotateArgs),
+    /// Fail if annotating would change any input, without writing output; the same as
+    /// annotating with --check
+    Check(AnnotateArgs),
+    /// Remove the comments annotating with the same options would add, keeping hand-written
+    /// and edited comments
+    Strip(AnnotateArgs),
+    /// Report how many keys of each config the schema documents, listing the undocumented ones
+    Coverage {
+        /// Config files (YAML or TOML) to measure
+        #[arg(required = true)]
+        configs: Vec<PathBuf>,
+        /// Print the coverage as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Generate an annotated example document from the schema alone
+    Generate,
+    /// Print the annotations extracted from the schema as JSON
+    DumpAnnotations,
+    /// Render the schema's annotations as a self-contained HTML reference page
+    #[command(visible_alias = "docs")]
+    Reference,
+    /// Print everything the schema documents about one config path
+    Explain {
+        /// Dot-separated path of the key (e.g. server.port)
+        path: String,
+    },
+    /// List every path the schema documents as an indented tree, with its type and summary
+    Describe,
+    /// Copy the comments of a documented config into the schema's descriptions
+    Enrich {
+        /// Config file (YAML or TOML) whose comments document the schema
+        config: PathBuf,
+    },
+    /// List the properties added, removed or renamed between two versions of a schema and
+    /// the changes to their defaults and descriptions, as migration notes
+    SchemaDiff {
+        /// Path or http(s) URL of the old schema
+        old: String,
+        /// Path or http(s) URL of the new schema
+        new: String,
+        /// Print the changes as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Draft a JSON Schema from a config, with types from its values and descriptions from
+    /// its comments
+    InferSchema {
+        /// Config file (YAML or TOML) to infer the schema from
+        config: PathBuf,
+    },
+    /// Convert a config between YAML and TOML, annotating the result
+    Convert {
+        /// Config file to convert, whose format is detected like other inputs
+        input: PathBuf,
+        /// Format to convert to (default: from the --output extension)
+        #[arg(long, value_enum)]
+        to: Option<FormatMode>,
+    },
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatMode {
+    Toml,
+    Yaml,
+}
+
+impl From<FormatMode> for TargetFormat {
+    fn from(mode: FormatMode) -> Self {
+        match mode {
+            FormatMode::Toml => TargetFormat::Toml,
+            FormatMode::Yaml => TargetFormat::Yaml,
+        }
+    }
+}
+
+/// Line width given to `--max-width`
+#[derive(Clone, Copy, Debug)]
+enum MaxWidth {
+    Columns(usize),
+    Auto,
+    Unlimited,
+}
+
+impl MaxWidth {
+    /// The width to wrap at, falling back to 80 columns for `auto` when the
+    /// output isn't a terminal
+    ///
+    /// `$COLUMNS` is only consulted when writing to a terminal, as files and
+    /// pipes shouldn't depend on the shell they were produced from.
+    fn resolve(self, to_stdout: bool) -> Option<usize> {
+        match self {
+            MaxWidth::Columns(width) => Some(width),
+            MaxWidth::Unlimited => None,
+            MaxWidth::Auto => {
+                let width = (to_stdout && io::stdout().is_terminal())
+                    .then(|| {
+                        let columns = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok());
+                        columns.or_else(terminal_width)
+                    })
+                    .flatten();
+                Some(width.filter(|&width| width > 0).unwrap_or(80))
+            }
+        }
+    }
+}
+
+fn parse_max_width(value: &str) -> Result<MaxWidth, String> {
+    match value {
+        "auto" => Ok(MaxWidth::Auto),
+        "none" | "0" => Ok(MaxWidth::Unlimited),
+        _ => value
+            .parse()
+            .map(MaxWidth::Columns)
+            .map_err(|_| format!("expected a number, \"auto\" or \"none\", got {:?}", value)),
+    }
+}
+
+/// Width of the terminal on stdout, if known
+fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(width), _)| width as usize)
 }
 
 #[derive(Clone, Copy, ValueEnum)]
@@ -51,6 +419,99 @@ enum IncludeMode {
     Both,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum NodesMode {
+    /// Annotate both sections and leaf values
+    All,
+    /// Only annotate leaf values
+    Leaves,
+    /// Only annotate sections (tables and mappings)
+    Sections,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SectionStyleMode {
+    /// Same as leaf comments
+    Plain,
+    /// A horizontal rule banner: # ───── Server ─────
+    Rule,
+    /// A hash banner: ##### Server #####
+    Hash,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorMode {
+    /// Color when writing to a terminal and NO_COLOR isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LineEndingMode {
+    /// Unix line endings
+    Lf,
+    /// Windows line endings
+    Crlf,
+    /// Keep the line ending used by the input
+    Preserve,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum YamlVersionMode {
+    /// yes, no, on and off are booleans, and integers may be binary, octal with a leading zero
+    /// or use _ separators
+    #[value(name = "1.1")]
+    V1_1,
+    /// Only true and false are booleans, and integers are decimal, 0o octal or 0x hexadecimal
+    #[value(name = "1.2")]
+    V1_2,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RedundantDefaultsMode {
+    /// List them on stderr and in the --report
+    Report,
+    /// Also add a comment to each
+    Mark,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DuplicateKeysMode {
+    /// Annotate every occurrence
+    All,
+    /// Annotate only the first occurrence
+    First,
+    /// Annotate only the last occurrence, the one that takes effect
+    Last,
+    /// Fail with a parse error
+    Error,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human readable messages
+    Text,
+    /// One JSON object per event, for CI systems and bots
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ReportFormat {
+    /// What happened to each path of each input
+    Json,
+    /// SARIF 2.1.0 results for code scanning, from validation, drift, --check and coverage
+    Sarif,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CommentSpacingMode {
+    /// Attach generated comments directly to existing ones
+    Tight,
+    /// Leave a blank line between generated and existing comments
+    BlankLine,
+}
+
 #[derive(Clone, Copy, ValueEnum)]
 enum ExistingCommentsMode {
     /// Skip fields that already have comments
@@ -61,76 +522,1844 @@ enum ExistingCommentsMode {
     Append,
     /// Replace existing comment with annotation
     Replace,
+    /// Refresh comments generated by a previous update run, keeping hand-written ones
+    Update,
 }
 
 fn main() {
     if let Err(e) = run() {
-        eprintln!("error: {}", e);
-        std::process::exit(1);
+        emit(&Event::Error {
+            file: None,
+            message: error_message(e.as_ref()),
+        });
+        std::process::exit(failure_of(e.as_ref()) as i32);
     }
 }
 
+/// Set once the command line is parsed, messages before that are text
+static LOG_FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+/// Something reported on stderr, a line of text or a JSON object per event
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    /// Processing of an input began
+    FileStarted { file: &'a str },
+    /// A path of an input received a comment
+    AnnotationApplied { file: &'a str, path: &'a str },
+    /// A schema violation found with `--validate`
+    ValidationError {
+        file: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+        path: &'a str,
+        message: &'a str,
+    },
+    /// A value that's the same as its default, found with `--redundant-defaults`
+    RedundantDefault { file: &'a str, path: &'a str },
+    /// A key whose comment doesn't match the schema, found with `--drift`
+    Drift {
+        file: &'a str,
+        line: Option<usize>,
+        path: &'a str,
+        kind: DriftKind,
+    },
+    /// A place where annotating would change an input, found with `--check`
+    OutOfDate { file: &'a str, line: usize },
+    /// A result was written to a file
+    Written { what: &'a str, output: &'a Path },
+    /// An in-place edit found nothing to change
+    UpToDate { output: &'a Path },
+    Warning {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<&'a str>,
+        message: String,
+    },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<&'a str>,
+        message: String,
+    },
+    /// Totals once every input is processed
+    Summary {
+        files: usize,
+        failed: usize,
+        applied: usize,
+    },
+}
+
+/// Write an event to stderr in the `--log-format`
+///
+/// In GitHub Actions, problems found in inputs are also written as workflow
+/// commands, which show them inline in pull requests.
+fn emit(event: &Event) {
+    if in_github_actions() {
+        emit_workflow_command(event);
+    }
+    if LOG_FORMAT.get() == Some(&LogFormat::Json) {
+        if let Ok(line) = serde_json::to_string(event) {
+            eprintln!("{}", line);
+        }
+        return;
+    }
+    match event {
+        Event::FileStarted { .. } | Event::AnnotationApplied { .. } | Event::Summary { .. } => {}
+        Event::ValidationError { file, line, path, message } => {
+            let path = if path.is_empty() { "<root>" } else { path };
+            eprintln!("{}: {}: {}", location(file, *line), path, message);
+        }
+        Event::RedundantDefault { file, path } => {
+            eprintln!("{}: {}: same as the default", file, path);
+        }
+        Event::Drift { file, line, path, kind } => {
+            let kind = match kind {
+                DriftKind::Missing => "missing",
+                DriftKind::Stale => "stale",
+                DriftKind::Edited => "hand-edited",
+            };
+            eprintln!("{}: {}: {} documentation", location(file, *line), path, kind);
+        }
+        Event::OutOfDate { file, line } => eprintln!("{}: out of date", location(file, Some(*line))),
+        Event::Written { what, output } => eprintln!("Wrote {} to {}", what, output.display()),
+        Event::UpToDate { output } => eprintln!("{} is already up to date", output.display()),
+        Event::Warning { file: Some(file), message } => eprintln!("warning: {}: {}", file, message),
+        Event::Warning { file: None, message } => eprintln!("warning: {}", message),
+        Event::Error { file: Some(file), message } => eprintln!("error: {}: {}", file, message),
+        Event::Error { file: None, message } => eprintln!("error: {}", message),
+    }
+}
+
+/// `file:line`, or just the file when the line isn't known
+fn location(file: &str, line: Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{}:{}", file, line),
+        None => file.to_string(),
+    }
+}
+
+fn in_github_actions() -> bool {
+    std::env::var_os("GITHUB_ACTIONS").is_some_and(|value| value == "true")
+}
+
+/// Write a problem found in an input as a GitHub Actions `::error` command
+fn emit_workflow_command(event: &Event) {
+    let (file, line, title, message) = match event {
+        Event::ValidationError { file, line, path, message } => {
+            let message = if path.is_empty() { message.to_string() } else { format!("{}: {}", path, message) };
+            (file, *line, "Validation error", message)
+        }
+        Event::Drift { file, line, path, kind } => {
+            let message = match kind {
+                DriftKind::Missing => format!("{}: documentation from the schema is missing", path),
+                DriftKind::Stale => format!("{}: documentation differs from the schema", path),
+                DriftKind::Edited => format!("{}: generated documentation was edited by hand", path),
+            };
+            (file, *line, "Drifted documentation", message)
+        }
+        Event::OutOfDate { file, line } => {
+            let message = "Comments are out of date with the schema, run jsonschema-annotator to update them";
+            (file, Some(*line), "Out of date", message.to_string())
+        }
+        _ => return,
+    };
+    if *file == "<stdin>" {
+        return;
+    }
+    // Workflow commands are parsed line by line, with these characters escaped
+    let escape = |s: &str, property: bool| {
+        let s = s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A");
+        if property { s.replace(':', "%3A").replace(',', "%2C") } else { s }
+    };
+    let mut properties = format!("file={}", escape(file, true));
+    if let Some(line) = line {
+        properties += &format!(",line={}", line);
+    }
+    properties += &format!(",title={}", escape(title, true));
+    eprintln!("::error {}::{}", properties, escape(&message, false));
+}
+
+/// Category of failure, reported as the process exit code so scripts can
+/// tell them apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// Anything not covered below, such as I/O errors
+    Other = 1,
+    /// Invalid arguments or configuration, the same code clap uses
+    Usage = 2,
+    /// The schema couldn't be loaded, fetched or compiled
+    Schema = 3,
+    /// An input document couldn't be parsed
+    Parse = 4,
+    /// Comments are out of date with the schema, found by `--drift` or a
+    /// `--fail-on-*` flag
+    Check = 5,
+    /// An input document doesn't validate against the schema
+    Validation = 6,
+}
+
+/// An error raised by the CLI itself, tagged with its failure category
+#[derive(Debug)]
+struct CliError {
+    failure: Failure,
+    message: String,
+}
+
+impl CliError {
+    fn new(failure: Failure, message: impl Into<String>) -> Self {
+        Self {
+            failure,
+            message: message.into(),
+        }
+    }
+
+    fn usage(message: impl Into<String>) -> Self {
+        Self::new(Failure::Usage, message)
+    }
+
+    fn schema(message: impl Into<String>) -> Self {
+        Self::new(Failure::Schema, message)
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Classify an error for the exit code
+fn failure_of(error: &(dyn std::error::Error + 'static)) -> Failure {
+    if let Some(error) = error.downcast_ref::<CliError>() {
+        return error.failure;
+    }
+    if let Some(error) = error.downcast_ref::<AnnotatorError>() {
+        return match error.kind {
+            AnnotatorErrorKind::Parse => Failure::Parse,
+            AnnotatorErrorKind::InvalidSchema => Failure::Schema,
+            AnnotatorErrorKind::UnmatchedAnnotations | AnnotatorErrorKind::Unsupported => {
+                Failure::Check
+            }
+            AnnotatorErrorKind::UnsupportedFormat => Failure::Usage,
+            _ => Failure::Other,
+        };
+    }
+    if error.is::<SchemaError>() {
+        return Failure::Schema;
+    }
+    if error.is::<clap::Error>() {
+        return Failure::Usage;
+    }
+    Failure::Other
+}
+
 fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+    let command = apply_config_file(Cli::command())?;
+    let mut cli = Cli::from_arg_matches(&command.get_matches())?;
+    LOG_FORMAT.get_or_init(|| cli.log_format);
+    init_logging(cli.verbose, cli.log_format);
+
+    match cli.command.take() {
+        // Running without a subcommand annotates with the top-level options
+        Some(Command::Annotate(args)) => cli.annotate = args,
+        Some(Command::Check(args)) => cli.annotate = AnnotateArgs { check: true, ..args },
+        Some(Command::Strip(args)) => cli.annotate = AnnotateArgs { strip: true, ..args },
+        Some(Command::Coverage { configs, json }) => return coverage(&cli, &configs, json),
+        Some(Command::Generate) => return generate(&cli),
+        Some(Command::DumpAnnotations) => {
+            let source = cli.schema.as_deref().ok_or_else(|| CliError::usage("dump-annotations requires --schema"))?;
+            let annotations = extract_annotations(&load_schema(source, cli.schema_cache())?);
+            serde_json::to_writer_pretty(io::stdout(), &annotations)?;
+            println!();
+            return Ok(());
+        }
+        Some(Command::Reference) => {
+            let source = cli.schema.as_deref().ok_or_else(|| CliError::usage("reference requires --schema"))?;
+            let annotations = extract_annotations(&load_schema(source, cli.schema_cache())?);
+            return write_result(&cli, &html_reference(&annotations), "reference");
+        }
+        Some(Command::Explain { path }) => {
+            let source = cli.schema.as_deref().ok_or_else(|| CliError::usage("explain requires --schema"))?;
+            let page = explain(&load_schema(source, cli.schema_cache())?, &path)
+                .ok_or_else(|| CliError::usage(format!("The schema has no property at {}", path)))?;
+            return write_result(&cli, &page, "explanation");
+        }
+        Some(Command::Describe) => {
+            let source = cli.schema.as_deref().ok_or_else(|| CliError::usage("describe requires --schema"))?;
+            return write_result(&cli, &describe(&load_schema(source, cli.schema_cache())?), "description");
+        }
+        Some(Command::Enrich { config }) => return enrich(&cli, &config),
+        Some(Command::InferSchema { config }) => return infer(&cli, &config),
+        Some(Command::SchemaDiff { old, new, json }) => {
+            let (old, new) = (load_schema(&old, cli.schema_cache())?, load_schema(&new, cli.schema_cache())?);
+            let changes = diff_schemas(&old, &new);
+            let output = if json {
+                serde_json::to_string_pretty(&changes)? + "\n"
+            } else {
+                changes.iter().map(|change| format!("- {}\n", change)).collect()
+            };
+            return write_result(&cli, &output, "schema changes");
+        }
+        Some(Command::Convert { input, to }) => return convert_input(&cli, &input, to),
+        Some(Command::Completions { shell }) => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let schemas = SchemaResolver::new(&cli)?;
+    let config = build_config(&cli);
+    let mut inputs = expand_inputs(&cli.annotate.input)?;
+    inputs.extend(expand_inputs(&cli.annotate.files)?);
+    if let Some(root) = &cli.annotate.recursive {
+        inputs.extend(walk_directory(root, &cli.annotate.ignore)?);
+    }
+    if let Some(map) = &schemas.map {
+        inputs.retain(|input| fs::canonicalize(&input.path).ok().as_ref() != Some(&map.file));
+    }
+    if inputs.is_empty() {
+        return Err(CliError::usage("No input files found").into());
+    }
 
-    // Read and parse schema
-    let schema_content = fs::read_to_string(&cli.schema)?;
-    let schema: Schema = if cli.schema.extension().map(|e| e == "yaml" || e == "yml").unwrap_or(false) {
-        serde_yaml::from_str(&schema_content)?
+    if inputs.iter().any(|i| i.path == "-") {
+        if cli.annotate.in_place {
+            return Err(CliError::usage("--in-place requires an input file, not stdin").into());
+        }
+        if inputs.len() > 1 {
+            return Err(CliError::usage("stdin (-) can't be combined with other inputs").into());
+        }
+    }
+    if let (Some(dir), true) = (&cli.output, inputs.len() > 1 && !cli.annotate.in_place) {
+        check_output_names(&inputs, dir)?;
+    }
+
+    // Keep going after a failure so every file gets a chance, then summarize
+    let multiple = inputs.len() > 1;
+    let jobs = cli
+        .annotate
+        .jobs
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(inputs.len());
+    let schemas = Mutex::new(schemas);
+    let mut locks = cli.annotate.lock.as_deref().map(LockFile::load).transpose()?;
+    let process = |input: &InputFile| {
+        emit(&Event::FileStarted { file: &input.path });
+        let mut report = FileReport {
+            file: input.path.clone(),
+            ..Default::default()
+        };
+        let mut config = config.clone();
+        if let Some(locks) = &locks {
+            config.lock = Some(Arc::new(locks.get(&input.path)));
+        }
+        let mut out = Vec::new();
+        let result =
+            process_input(&cli, &schemas, &config, input, multiple, &mut report, &mut out);
+        if let Err(e) = result {
+            report.error = Some(error_message(e.as_ref()));
+            report.failure = Some(failure_of(e.as_ref()));
+        }
+        (report, out)
+    };
+
+    let results: Vec<(FileReport, Vec<u8>)> = if jobs > 1 {
+        // Workers take the next unprocessed input until none are left
+        let next = AtomicUsize::new(0);
+        let slots = Mutex::new((0..inputs.len()).map(|_| None).collect::<Vec<_>>());
+        std::thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(i) else { break };
+                    let result = process(input);
+                    slots.lock().unwrap()[i] = Some(result);
+                });
+            }
+        });
+        slots.into_inner().unwrap().into_iter().flatten().collect()
     } else {
-        serde_json::from_str(&schema_content)?
+        inputs.iter().map(process).collect()
     };
 
-    // Read input content
-    let (input_content, target_format) = if cli.input == "-" {
-        let mut content = String::new();
-        io::stdin().read_to_string(&mut content)?;
-        // Default to YAML for stdin, user can override by specifying output extension
-        let format = cli.output
-            .as_ref()
-            .and_then(|p| TargetFormat::from_path(p))
-            .unwrap_or(TargetFormat::Yaml);
-        (content, format)
+    // Output is written in input order regardless of which worker finished first
+    let mut stdout = io::stdout();
+    let mut reports = Vec::new();
+    let mut errors = Vec::new();
+    for (report, out) in results {
+        stdout.write_all(&out)?;
+        if let (Some(e), Some(failure)) = (&report.error, report.failure) {
+            if multiple {
+                emit(&Event::Error {
+                    file: Some(&report.file),
+                    message: e.clone(),
+                });
+            }
+            errors.push(CliError::new(failure, e.clone()));
+        }
+        reports.push(report);
+    }
+
+    // The lock describes the inputs as they are on disk
+    if let Some(locks) = &mut locks {
+        if config.existing_comments == ExistingCommentBehavior::Update {
+            for report in reports.iter().filter(|r| r.in_place && r.error.is_none()) {
+                locks.set(&report.file, report.annotations.lock.clone());
+            }
+            locks.save()?;
+        }
+    }
+
+    if let Some(path) = &cli.annotate.report {
+        let format = cli.annotate.report_format.unwrap_or_else(|| {
+            match path.extension().is_some_and(|ext| ext == "sarif") {
+                true => ReportFormat::Sarif,
+                false => ReportFormat::Json,
+            }
+        });
+        let report = match format {
+            ReportFormat::Json => serde_json::to_value(&reports)?,
+            ReportFormat::Sarif => {
+                let files: Vec<SarifFile> = reports
+                    .iter()
+                    .filter(|report| report.file != "-")
+                    .map(|report| SarifFile {
+                        file: &report.file,
+                        annotations: Some(&report.annotations),
+                        validation_errors: &report.validation_errors,
+                        drift: &report.drift,
+                        out_of_date: &report.out_of_date,
+                    })
+                    .collect();
+                sarif_report(&files)
+            }
+        };
+        fs::write(path, serde_json::to_string_pretty(&report)? + "\n")?;
+    }
+    emit(&Event::Summary {
+        files: inputs.len(),
+        failed: errors.len(),
+        applied: reports.iter().map(|r| r.annotations.applied.len()).sum(),
+    });
+
+    match errors.len() {
+        0 => {}
+        1 if !multiple => return Err(errors.remove(0).into()),
+        failed => {
+            // Only report a specific category when every failure shares it
+            let failure = errors[0].failure;
+            let failure = if errors.iter().all(|e| e.failure == failure) {
+                failure
+            } else {
+                Failure::Other
+            };
+            let message = format!("{} of {} files failed", failed, inputs.len());
+            return Err(CliError::new(failure, message).into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Outcome of processing one input, as written by `--report`
+#[derive(Default, Serialize)]
+struct FileReport {
+    file: String,
+    #[serde(flatten)]
+    annotations: AnnotationReport,
+    validation_errors: Vec<ValidationIssue>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    drift: Vec<CommentDrift>,
+    /// First lines of the places `--check` found out of date
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    out_of_date: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip)]
+    failure: Option<Failure>,
+    /// Whether the annotated input was written back to itself
+    #[serde(skip)]
+    in_place: bool,
+}
+
+/// The lock file of `--lock`: an [`AnnotationLock`] per input, by its path
+/// relative to the lock file
+struct LockFile {
+    path: PathBuf,
+    locks: BTreeMap<String, AnnotationLock>,
+    changed: bool,
+}
+
+impl LockFile {
+    /// Read the lock file, which is created on saving if it doesn't exist
+    fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let locks = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| CliError::usage(format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            locks,
+            changed: false,
+        })
+    }
+
+    fn get(&self, input: &str) -> AnnotationLock {
+        self.locks.get(&self.key(input)).cloned().unwrap_or_default()
+    }
+
+    fn set(&mut self, input: &str, lock: AnnotationLock) {
+        let key = self.key(input);
+        if self.locks.get(&key) != Some(&lock) {
+            self.locks.insert(key, lock);
+            self.changed = true;
+        }
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.changed {
+            fs::write(&self.path, serde_json::to_string_pretty(&self.locks)? + "\n")?;
+            emit(&Event::Written {
+                what: "lock file",
+                output: &self.path,
+            });
+        }
+        Ok(())
+    }
+
+    /// The input's path relative to the lock file, with `/` separators, so
+    /// the lock works from any directory and on any platform
+    fn key(&self, input: &str) -> String {
+        let base = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let relative = fs::canonicalize(base).ok().and_then(|base| {
+            let input = fs::canonicalize(input).ok()?;
+            Some(input.strip_prefix(base).ok()?.to_path_buf())
+        });
+        match relative {
+            Some(relative) => relative.to_string_lossy().replace('\\', "/"),
+            None => input.replace('\\', "/"),
+        }
+    }
+}
+
+/// Log to stderr at a level chosen by the number of `-v` flags, as JSON with
+/// `--log-format json`
+fn init_logging(verbose: u8, format: LogFormat) {
+    let level = match verbose {
+        0 => tracing::Level::WARN,
+        1 => tracing::Level::INFO,
+        2 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false)
+        .with_writer(io::stderr);
+    match format {
+        LogFormat::Text => subscriber.with_ansi(io::stderr().is_terminal()).init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
+
+/// Write the schema with descriptions taken from a config's comments
+fn enrich(cli: &Cli, config: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let source = cli.schema.as_deref().ok_or_else(|| CliError::usage("enrich requires --schema"))?;
+    let mut schema = load_schema(source, cli.schema_cache())?;
+    let content = fs::read_to_string(config)?;
+    let format = cli
+        .format
+        .map(TargetFormat::from)
+        .or_else(|| TargetFormat::from_path(config))
+        .or_else(|| TargetFormat::from_content(&content))
+        .ok_or_else(|| {
+            CliError::usage(format!("Unknown file format: {} (use --format)", config.display()))
+        })?;
+
+    let comments = extract_comments(&content, format)?;
+    for path in enrich_schema(&mut schema, &comments) {
+        emit(&Event::Warning {
+            file: Some(&config.to_string_lossy()),
+            message: format!("no schema property for {}", path),
+        });
+    }
+    let enriched = serde_json::to_string_pretty(&schema)? + "\n";
+    write_result(cli, &enriched, "enriched schema")
+}
+
+/// How much of one config the schema documents, as printed by `coverage`
+#[derive(Debug, PartialEq, Serialize)]
+struct Coverage {
+    file: String,
+    /// Keys of the config the schema has annotations for
+    documented: usize,
+    keys: usize,
+    undocumented: Vec<String>,
+}
+
+impl Coverage {
+    fn new(file: String, report: AnnotationReport) -> Self {
+        let documented = report.applied.len()
+            + report.skipped_existing.len()
+            + report.excluded.len()
+            + report.unsupported.len();
+        Self {
+            file,
+            documented,
+            keys: documented + report.unmatched.len(),
+            undocumented: report.unmatched,
+        }
+    }
+}
+
+impl std::fmt::Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} of {} keys documented", self.file, self.documented, self.keys)?;
+        if let Some(percent) = (self.documented * 100).checked_div(self.keys) {
+            write!(f, " ({}%)", percent)?;
+        }
+        if !self.undocumented.is_empty() {
+            write!(f, "\n  undocumented: {}", self.undocumented.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn coverage(cli: &Cli, configs: &[PathBuf], json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut schemas = SchemaResolver::new(cli)?;
+    let config = build_config(cli);
+    let mut coverages = Vec::new();
+    for path in configs {
+        let content = fs::read_to_string(path)?;
+        let format = cli
+            .format
+            .map(TargetFormat::from)
+            .or_else(|| TargetFormat::from_path(path))
+            .or_else(|| TargetFormat::from_content(&content))
+            .ok_or_else(|| {
+                CliError::usage(format!("Unknown file format: {} (use --format)", path.display()))
+            })?;
+        let file = path.to_string_lossy().into_owned();
+        let loaded = schemas.resolve(&file, &content, format)?;
+        let (_, report) = annotate_extracted(&loaded.annotations, &content, format, config.clone())?;
+        coverages.push(Coverage::new(file, report));
+    }
+
+    let output = if json {
+        serde_json::to_string_pretty(&coverages)? + "\n"
     } else {
-        let path = PathBuf::from(&cli.input);
-        let content = fs::read_to_string(&path)?;
-        let format = TargetFormat::from_path(&path)
-            .ok_or_else(|| format!("Unknown file format: {}", path.display()))?;
-        (content, format)
+        coverages.iter().map(|coverage| format!("{}\n", coverage)).collect()
+    };
+    write_result(cli, &output, "coverage report")
+}
+
+fn infer(cli: &Cli, config: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(config)?;
+    let format = cli
+        .format
+        .map(TargetFormat::from)
+        .or_else(|| TargetFormat::from_path(config))
+        .or_else(|| TargetFormat::from_content(&content))
+        .ok_or_else(|| {
+            CliError::usage(format!("Unknown file format: {} (use --format)", config.display()))
+        })?;
+
+    let schema = infer_schema(&content, format)?;
+    write_result(cli, &(serde_json::to_string_pretty(&schema)? + "\n"), "inferred schema")
+}
+
+/// Write a config converted to another format
+fn convert_input(
+    cli: &Cli,
+    input: &Path,
+    to: Option<FormatMode>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = cli.schema.as_deref().ok_or_else(|| CliError::usage("convert requires --schema"))?;
+    let schema = load_schema(source, cli.schema_cache())?;
+    let content = fs::read_to_string(input)?;
+    let source_format = cli
+        .format
+        .map(TargetFormat::from)
+        .or_else(|| TargetFormat::from_path(input))
+        .or_else(|| TargetFormat::from_content(&content))
+        .ok_or_else(|| {
+            CliError::usage(format!("Unknown file format: {} (use --format)", input.display()))
+        })?;
+    let target_format = to
+        .map(TargetFormat::from)
+        .or_else(|| cli.output.as_deref().and_then(TargetFormat::from_path))
+        .ok_or_else(|| {
+            CliError::usage("convert requires --to or an --output with a known extension")
+        })?;
+
+    let converted = convert(&schema, &content, source_format, target_format, build_config(cli))?;
+    write_result(cli, &converted, "converted config")
+}
+
+/// Write an example document generated from the schema
+fn generate(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let source = cli.schema.as_deref().ok_or_else(|| CliError::usage("generate requires --schema"))?;
+    let schema = load_schema(source, cli.schema_cache())?;
+    let format = cli
+        .format
+        .map(TargetFormat::from)
+        .or_else(|| cli.output.as_deref().and_then(TargetFormat::from_path))
+        .ok_or_else(|| {
+            CliError::usage("generate requires --format or an --output with a known extension")
+        })?;
+
+    let generated = generate_document(&schema, format, build_config(cli))?;
+    write_result(cli, &generated, "generated config")
+}
+
+/// Write the output of a subcommand to `--output`, or stdout
+fn write_result(cli: &Cli, content: &str, what: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match &cli.output {
+        Some(output_path) => {
+            if output_path.exists() && !cli.force {
+                return Err(CliError::usage(format!(
+                    "Output file exists: {}. Use --force to overwrite.",
+                    output_path.display()
+                ))
+                .into());
+            }
+            fs::write(output_path, content)?;
+            emit(&Event::Written {
+                what,
+                output: output_path,
+            });
+        }
+        None => io::stdout().write_all(content.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Name of the schema map used by default in recursive mode
+const SCHEMA_MAP_NAME: &str = "schemas.yaml";
+
+/// Name of the configuration file discovered from the working directory upward
+const CONFIG_FILE_NAME: &str = ".jsonschema-annotator.toml";
+
+/// Options that can be set in the configuration file, named like their flags
+const CONFIG_KEYS: &[&str] = &[
+    "schema",
+    "offline",
+    "schema-map",
+    "annotations",
+    "format",
+    "ignore",
+    "include",
+    "max-width",
+    "include-default",
+    "include-examples",
+    "example-entries",
+    "mark-secrets",
+    "redact-secrets",
+    "required-only",
+    "since-keyword",
+    "env-prefix",
+    "env-separator",
+    "markdown",
+    "strip-html",
+    "section-style",
+    "line-ending",
+    "yaml-version",
+    "dedupe",
+    "existing-comments",
+    "comment-spacing",
+    "lock",
+    "schema-directive",
+    "only",
+    "exclude",
+    "max-depth",
+    "nodes",
+    "warn-unknown-keys",
+    "redundant-defaults",
+    "duplicate-keys",
+    "fail-on-unmatched",
+    "fail-on-unsupported",
+    "verify",
+    "normalize",
+    "log-format",
+];
+
+/// Use the options in the nearest configuration file as the flag defaults
+///
+/// Flags given on the command line still take precedence. A relative schema
+/// path is resolved against the directory containing the configuration file.
+fn apply_config_file(command: clap::Command) -> Result<clap::Command, Box<dyn std::error::Error>> {
+    let cwd = std::env::current_dir()?;
+    let Some(path) = cwd
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+    else {
+        return Ok(command);
+    };
+
+    let content = fs::read_to_string(&path)?;
+    apply_config(command, &path, &content)
+}
+
+/// Use the options of the configuration file at `path` as the flag defaults
+fn apply_config(
+    mut command: clap::Command,
+    path: &Path,
+    content: &str,
+) -> Result<clap::Command, Box<dyn std::error::Error>> {
+    let doc: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|e| CliError::usage(format!("{}: {}", path.display(), e)))?;
+
+    for (key, item) in doc.iter() {
+        if !CONFIG_KEYS.contains(&key) {
+            let message = format!("{}: unknown option `{}`", path.display(), key);
+            return Err(CliError::usage(message).into());
+        }
+        let invalid = || CliError::usage(format!("{}: invalid value for `{}`", path.display(), key));
+
+        let id = key.replace('-', "_");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str())
+            .ok_or_else(invalid)?;
+        let mut values = match item.as_value().ok_or_else(invalid)? {
+            toml_edit::Value::String(s) => vec![s.value().clone()],
+            toml_edit::Value::Integer(i) => vec![i.value().to_string()],
+            toml_edit::Value::Boolean(b) => vec![b.value().to_string()],
+            // Only options taking several values can be given a list
+            toml_edit::Value::Array(array) if matches!(arg.get_action(), clap::ArgAction::Append) => array
+                .iter()
+                .map(|v| v.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(invalid)?,
+            _ => return Err(invalid().into()),
+        };
+        let relative = values.first_mut().filter(|value| !is_url(value));
+        if let (true, Some(value)) = (matches!(key, "schema" | "schema-map" | "annotations" | "lock"), relative) {
+            let base = path.parent().unwrap_or(Path::new(""));
+            *value = base.join(&*value).to_string_lossy().into_owned();
+        }
+
+        // Validate now, invalid defaults would only surface as a clap panic later
+        let validator = clap::Command::new("config").no_binary_name(true).arg(
+            clap::Arg::new("value")
+                .long("value")
+                .value_parser(arg.get_value_parser().clone())
+                .action(clap::ArgAction::Append),
+        );
+        for value in &values {
+            validator
+                .clone()
+                .try_get_matches_from(["--value", value.as_str()])
+                .map_err(|_| {
+                    format!("{}: invalid value `{}` for `{}`", path.display(), value, key)
+                })?;
+        }
+        // Options of annotating are also the `annotate` and `check` subcommands'
+        for name in ["annotate", "check"] {
+            let defines = |sub: &clap::Command| sub.get_arguments().any(|arg| arg.get_id() == id.as_str());
+            if command.find_subcommand(name).is_some_and(defines) {
+                let values = values.clone();
+                command = command.mut_subcommand(name, |sub| sub.mut_arg(&id, |arg| arg.default_values(values)));
+            }
+        }
+        command = command.mut_arg(id, |arg| arg.default_values(values));
+    }
+
+    Ok(command)
+}
+
+/// Read and parse a JSON or YAML schema from a file or http(s) URL
+fn load_schema(source: &str, cache: SchemaCache) -> Result<Value, Box<dyn std::error::Error>> {
+    tracing::info!(source, "loading schema");
+    if !is_url(source) {
+        return Ok(jsonschema_annotator::load_schema(source)?);
+    }
+    let schema_content = fetch_schema(source, cache)?;
+    Ok(jsonschema_annotator::parse_schema(&schema_content, source)?)
+}
+
+/// Provides the schema for each input: the one given with `--schema`, the one
+/// the input declares itself or the one `--schema-map` binds it to, in that
+/// order
+struct SchemaResolver {
+    explicit: Option<Arc<LoadedSchema>>,
+    cache: SchemaCache,
+    map: Option<SchemaMap>,
+    /// Discovered schemas by resolved location, loaded once per run
+    discovered: HashMap<String, Arc<LoadedSchema>>,
+    /// Wording from `--annotations`, replacing that of every schema
+    overrides: Option<AnnotationMap>,
+}
+
+/// A schema along with its extracted annotations, shared by all inputs using it
+struct LoadedSchema {
+    schema: Value,
+    annotations: AnnotationMap,
+}
+
+impl LoadedSchema {
+    fn new(schema: Value, overrides: Option<&AnnotationMap>) -> Arc<Self> {
+        let mut annotations = extract_annotations(&schema);
+        if let Some(overrides) = overrides {
+            annotations = AnnotationMap::merge_with_priority(vec![annotations, overrides.clone()]);
+        }
+        Arc::new(Self { schema, annotations })
+    }
+}
+
+impl SchemaResolver {
+    fn new(cli: &Cli) -> Result<Self, Box<dyn std::error::Error>> {
+        let overrides = cli.annotate.annotations.as_deref().map(load_overrides).transpose()?;
+        let cache = cli.schema_cache();
+        Ok(Self {
+            explicit: cli
+                .schema
+                .as_deref()
+                .map(|s| load_schema(s, cache).map(|schema| LoadedSchema::new(schema, overrides.as_ref())))
+                .transpose()?,
+            cache,
+            map: schema_map(cli)?,
+            discovered: HashMap::new(),
+            overrides,
+        })
+    }
+
+    fn resolve(
+        &mut self,
+        input: &str,
+        content: &str,
+        format: TargetFormat,
+    ) -> Result<Arc<LoadedSchema>, Box<dyn std::error::Error>> {
+        if let Some(schema) = &self.explicit {
+            return Ok(schema.clone());
+        }
+
+        let reference = find_schema_reference(content, format);
+        if let Some(reference) = &reference {
+            tracing::debug!(reference, "discovered schema reference");
+        }
+        let source = match reference {
+            Some(reference) if is_url(&reference) => reference,
+            Some(reference) => {
+                // Relative references are relative to the document, not the working directory
+                let path = reference.strip_prefix("file://").unwrap_or(&reference);
+                let base = match Path::new(input).parent() {
+                    Some(parent) if input != "-" => parent,
+                    _ => Path::new(""),
+                };
+                base.join(path).to_string_lossy().into_owned()
+            }
+            None => self.map.as_ref().and_then(|map| map.lookup(input)).ok_or_else(|| {
+                CliError::usage(
+                    "No --schema given, the input doesn't declare a schema and no --schema-map entry matches it",
+                )
+            })?,
+        };
+
+        if !self.discovered.contains_key(&source) {
+            let schema = LoadedSchema::new(load_schema(&source, self.cache)?, self.overrides.as_ref());
+            self.discovered.insert(source.clone(), schema);
+        }
+        Ok(self.discovered[&source].clone())
+    }
+}
+
+/// Schemas bound to path globs by a `--schema-map` file, such as
+///
+/// ```yaml
+/// "deploy/**/values.yaml": ./values.schema.json
+/// "config/*.toml": https://example.com/app.schema.json
+/// ```
+///
+/// Globs and relative schema paths are relative to the map file, and the
+/// first glob matching an input wins.
+struct SchemaMap {
+    /// The map file itself, which isn't an input
+    file: PathBuf,
+    /// Directory of the map file
+    base: PathBuf,
+    entries: Vec<(glob::Pattern, String)>,
+}
+
+impl SchemaMap {
+    fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let invalid = |message: String| CliError::usage(format!("{}: {}", path.display(), message));
+        let content = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let mapping: serde_yaml::Mapping =
+            serde_yaml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+        let file = fs::canonicalize(path)?;
+        let base = file.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        let mut entries = Vec::new();
+        for (glob, schema) in mapping {
+            let (Some(glob), Some(schema)) = (glob.as_str(), schema.as_str()) else {
+                return Err(invalid("entries must map a glob to a schema path or URL".to_string()).into());
+            };
+            let pattern = glob::Pattern::new(glob)
+                .map_err(|e| invalid(format!("invalid glob `{}`: {}", glob, e)))?;
+            let schema = if is_url(schema) {
+                schema.to_string()
+            } else {
+                base.join(schema).to_string_lossy().into_owned()
+            };
+            entries.push((pattern, schema));
+        }
+        Ok(Self { file, base, entries })
+    }
+
+    /// The schema bound to an input, if any glob matches it
+    fn lookup(&self, input: &str) -> Option<String> {
+        let path = fs::canonicalize(input).ok()?;
+        let relative = path.strip_prefix(&self.base).ok()?;
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let (pattern, schema) = self
+            .entries
+            .iter()
+            .find(|(pattern, _)| pattern.matches_path_with(relative, options))?;
+        tracing::debug!(pattern = pattern.as_str(), schema, "schema map entry matched");
+        Some(schema.clone())
+    }
+}
+
+/// Load an `--annotations` file of wording replacing the schema's, such as
+///
+/// ```yaml
+/// server.port: Port the HTTP server listens on
+/// /log/level:
+///   title: Log level
+///   description: One of error, warn, info or debug
+/// ```
+///
+/// A plain string replaces the description, leaving the schema's title.
+fn load_overrides(path: &Path) -> Result<AnnotationMap, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| CliError::usage(format!("{}: {}", path.display(), e)))?;
+    parse_overrides(path, &content)
+}
+
+/// Parse the content of an `--annotations` file read from `path`
+fn parse_overrides(path: &Path, content: &str) -> Result<AnnotationMap, Box<dyn std::error::Error>> {
+    let invalid = |message: String| CliError::usage(format!("{}: {}", path.display(), message));
+    let mapping: serde_yaml::Mapping =
+        serde_yaml::from_str(content).map_err(|e| invalid(e.to_string()))?;
+
+    let mut overrides = AnnotationMap::new();
+    for (key, value) in mapping {
+        let Some(key) = key.as_str() else {
+            return Err(invalid("keys must be dot-separated paths or JSON Pointers".to_string()).into());
+        };
+        let annotation = if key.starts_with('/') {
+            pointer_to_path(key).map(Annotation::new)
+        } else {
+            Some(Annotation::new(key))
+        }
+        .ok_or_else(|| invalid(format!("invalid JSON Pointer `{}`", key)))?;
+        let unexpected = || invalid(format!("`{}` must map to a description or to a `title` and `description`", key));
+
+        let annotation = match value {
+            serde_yaml::Value::String(description) => annotation.with_description(description),
+            serde_yaml::Value::Mapping(fields) => {
+                let mut annotation = annotation;
+                for (field, text) in fields {
+                    annotation = match (field.as_str(), text) {
+                        (Some("title"), serde_yaml::Value::String(title)) => annotation.with_title(title),
+                        (Some("description"), serde_yaml::Value::String(description)) => {
+                            annotation.with_description(description)
+                        }
+                        _ => return Err(unexpected().into()),
+                    };
+                }
+                annotation
+            }
+            _ => return Err(unexpected().into()),
+        };
+        overrides.insert(annotation);
+    }
+    Ok(overrides)
+}
+
+/// Load the `--schema-map`, or the `schemas.yaml` at the root of a recursive run
+fn schema_map(cli: &Cli) -> Result<Option<SchemaMap>, Box<dyn std::error::Error>> {
+    let path = match (&cli.annotate.schema_map, &cli.annotate.recursive) {
+        (Some(path), _) => path.clone(),
+        (None, Some(root)) if root.join(SCHEMA_MAP_NAME).is_file() => root.join(SCHEMA_MAP_NAME),
+        _ => return Ok(None),
     };
+    SchemaMap::load(&path).map(Some)
+}
 
-    // Build config
-    let existing_comments = match cli.existing_comments {
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// How long a downloaded schema is used before it's fetched again
+const SCHEMA_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How schema URLs use the cached copies of downloaded schemas
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SchemaCache {
+    /// Use a copy younger than [`SCHEMA_CACHE_TTL`], fetching otherwise
+    Fresh,
+    /// Always fetch, falling back to the cached copy (`--refresh-schema`)
+    Refresh,
+    /// Never fetch, only use cached copies (`--offline`)
+    Offline,
+}
+
+impl Cli {
+    fn schema_cache(&self) -> SchemaCache {
+        // The flags conflict, so both are only set when `offline` comes from
+        // the configuration file, which the command line overrides
+        match (self.refresh_schema, self.offline) {
+            (true, _) => SchemaCache::Refresh,
+            (false, true) => SchemaCache::Offline,
+            (false, false) => SchemaCache::Fresh,
+        }
+    }
+}
+
+/// Download a schema, keeping a copy in the user cache directory
+///
+/// Unless `cache` is [`SchemaCache::Refresh`], a copy younger than
+/// [`SCHEMA_CACHE_TTL`] is used without fetching. Older copies are used when
+/// the download fails or `cache` is [`SchemaCache::Offline`], so a schema
+/// fetched once keeps working without network access.
+fn fetch_schema(url: &str, cache: SchemaCache) -> Result<String, Box<dyn std::error::Error>> {
+    let cache_path = schema_cache_path(url);
+    let cached = || cache_path.as_deref().and_then(|p| fs::read_to_string(p).ok());
+
+    if cache == SchemaCache::Offline {
+        return cached().ok_or_else(|| {
+            CliError::schema(format!("Schema {} is not cached (--offline)", url)).into()
+        });
+    }
+    let fresh = cache_path.as_deref().is_some_and(|path| is_fresh(path, SCHEMA_CACHE_TTL));
+    if cache == SchemaCache::Fresh && fresh {
+        if let Some(content) = cached() {
+            tracing::debug!(url, "using cached schema");
+            return Ok(content);
+        }
+    }
+
+    tracing::debug!(url, "fetching schema");
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(10))
+        .timeout_read(Duration::from_secs(30))
+        .build();
+    match agent.get(url).call() {
+        Ok(response) => {
+            let content = response.into_string()?;
+            if let Some(path) = &cache_path {
+                // Failing to cache shouldn't fail the run
+                let written = path
+                    .parent()
+                    .map(fs::create_dir_all)
+                    .transpose()
+                    .and_then(|_| fs::write(path, &content));
+                if let Err(e) = written {
+                    tracing::debug!(path = %path.display(), "failed to cache schema: {}", e);
+                }
+            }
+            Ok(content)
+        }
+        Err(e) => match cached() {
+            Some(content) => {
+                emit(&Event::Warning {
+                    file: None,
+                    message: format!("{}, using cached schema", e),
+                });
+                Ok(content)
+            }
+            None => Err(CliError::schema(format!("Failed to fetch schema: {}", e)).into()),
+        },
+    }
+}
+
+/// Whether the file at `path` was modified less than `ttl` ago
+fn is_fresh(path: &Path, ttl: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < ttl)
+}
+
+/// Location of the cached copy of a schema URL
+fn schema_cache_path(url: &str) -> Option<PathBuf> {
+    // FNV-1a, stable across builds unlike std's DefaultHasher
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    let dir = dirs::cache_dir()?.join("jsonschema-annotator").join("schemas");
+    Some(dir.join(format!("{:016x}", hash)))
+}
+
+/// Build the annotator configuration from the command line flags
+fn build_config(cli: &Cli) -> AnnotatorConfig {
+    let existing_comments = match cli.annotate.existing_comments {
         ExistingCommentsMode::Skip => ExistingCommentBehavior::Skip,
         ExistingCommentsMode::Prepend => ExistingCommentBehavior::Prepend,
         ExistingCommentsMode::Append => ExistingCommentBehavior::Append,
         ExistingCommentsMode::Replace => ExistingCommentBehavior::Replace,
+        ExistingCommentsMode::Update => ExistingCommentBehavior::Update,
+    };
+    let comment_spacing = match cli.annotate.comment_spacing {
+        CommentSpacingMode::Tight => CommentSpacing::Tight,
+        CommentSpacingMode::BlankLine => CommentSpacing::BlankLine,
+    };
+
+    let node_filter = match cli.nodes {
+        NodesMode::All => NodeFilter::All,
+        NodesMode::Leaves => NodeFilter::LeavesOnly,
+        NodesMode::Sections => NodeFilter::SectionsOnly,
+    };
+
+    let section_style = match cli.section_style {
+        SectionStyleMode::Plain => SectionStyle::Plain,
+        SectionStyleMode::Rule => SectionStyle::Rule,
+        SectionStyleMode::Hash => SectionStyle::Hash,
+    };
+
+    let line_ending = match cli.line_ending {
+        LineEndingMode::Lf => LineEnding::Lf,
+        LineEndingMode::Crlf => LineEnding::Crlf,
+        LineEndingMode::Preserve => LineEnding::Preserve,
+    };
+    let yaml_version = match cli.yaml_version {
+        YamlVersionMode::V1_1 => YamlVersion::V1_1,
+        YamlVersionMode::V1_2 => YamlVersion::V1_2,
+    };
+
+    let redundant_defaults = match cli.redundant_defaults {
+        None => RedundantDefaults::Ignore,
+        Some(RedundantDefaultsMode::Report) => RedundantDefaults::Report,
+        Some(RedundantDefaultsMode::Mark) => RedundantDefaults::Mark,
     };
 
-    let config = AnnotatorConfig {
+    let duplicate_keys = match cli.duplicate_keys {
+        DuplicateKeysMode::All => DuplicateKeys::All,
+        DuplicateKeysMode::First => DuplicateKeys::First,
+        DuplicateKeysMode::Last => DuplicateKeys::Last,
+        DuplicateKeysMode::Error => DuplicateKeys::Error,
+    };
+
+    AnnotatorConfig {
         include_title: matches!(cli.include, IncludeMode::Title | IncludeMode::Both),
         include_description: matches!(cli.include, IncludeMode::Description | IncludeMode::Both),
         include_default: cli.include_default,
-        max_line_width: Some(cli.max_width),
+        include_examples: cli.include_examples,
+        example_entries: cli.example_entries,
+        mark_secrets: cli.mark_secrets,
+        redact_secrets: cli.redact_secrets,
+        required_only: cli.required_only,
+        since_keyword: Some(cli.since_keyword.clone()),
+        env_vars: cli
+            .env_prefix
+            .as_ref()
+            .map(|prefix| EnvVarStyle::new(prefix, &cli.env_separator)),
+        max_line_width: cli.max_width.resolve(cli.output.is_none() && !cli.annotate.in_place),
+        convert_markdown: cli.markdown,
+        strip_html: cli.strip_html,
         existing_comments,
+        comment_spacing,
+        schema_directive: cli.schema_directive.clone(),
+        include_paths: cli.only.clone(),
+        exclude_paths: cli.exclude.clone(),
+        max_depth: cli.max_depth,
+        node_filter,
+        section_style,
+        line_ending,
+        yaml_version,
+        deduplicate: cli.dedupe,
+        warn_unknown_keys: cli.warn_unknown_keys,
+        redundant_defaults,
+        duplicate_keys,
+        strict: cli.fail_on_unmatched,
+        fail_on_unsupported: cli.fail_on_unsupported,
+        verify_round_trip: cli.verify,
+        normalize: cli.normalize,
+        ..Default::default()
+    }
+}
+
+/// A file to annotate
+struct InputFile {
+    /// Path as given on the command line, or - for stdin
+    path: String,
+    /// Location of the result inside the output directory in batch mode
+    output_name: PathBuf,
+}
+
+impl InputFile {
+    fn new(path: String) -> Self {
+        let output_name = Path::new(&path)
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        Self { path, output_name }
+    }
+}
+
+/// Fail if two inputs would be written to the same file of the output
+/// directory, as inputs given by path are named by their file name only
+fn check_output_names(inputs: &[InputFile], dir: &Path) -> Result<(), CliError> {
+    let mut outputs: HashMap<&Path, &str> = HashMap::new();
+    for input in inputs {
+        match outputs.insert(&input.output_name, &input.path) {
+            Some(other) if other != input.path => {
+                return Err(CliError::usage(format!(
+                    "{} and {} would both be written to {}",
+                    other,
+                    input.path,
+                    dir.join(&input.output_name).display()
+                )));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Expand shell-style glob patterns in the input list
+///
+/// Patterns are expanded here so they also work when the shell didn't expand
+/// them (e.g. when quoted, or on Windows). Plain paths are passed through.
+fn expand_inputs(inputs: &[String]) -> Result<Vec<InputFile>, Box<dyn std::error::Error>> {
+    let mut expanded = Vec::new();
+    for input in inputs {
+        if !input.contains(['*', '?', '[']) {
+            expanded.push(InputFile::new(input.clone()));
+            continue;
+        }
+        let mut matched = false;
+        for entry in glob::glob(input)? {
+            expanded.push(InputFile::new(entry?.to_string_lossy().into_owned()));
+            matched = true;
+        }
+        if !matched {
+            return Err(CliError::usage(format!("No files match pattern: {}", input)).into());
+        }
+    }
+    Ok(expanded)
+}
+
+/// Find all supported config files below `root`
+///
+/// Hidden files and directories are skipped, as is anything whose path
+/// relative to `root` (or file name) matches one of the `ignore` globs. The
+/// relative path is kept so batch output mirrors the directory layout.
+fn walk_directory(
+    root: &Path,
+    ignore: &[String],
+) -> Result<Vec<InputFile>, Box<dyn std::error::Error>> {
+    let patterns = ignore
+        .iter()
+        .map(|p| glob::Pattern::new(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    let is_ignored = |relative: &Path| {
+        let name = relative
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        name.starts_with('.')
+            || patterns
+                .iter()
+                .any(|p| p.matches_path(relative) || p.matches(&name))
+    };
+
+    let mut files = Vec::new();
+    let walker = walkdir::WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            entry.depth() == 0 || !is_ignored(relative)
+        });
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() && TargetFormat::from_path(entry.path()).is_some() {
+            let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            files.push(InputFile {
+                path: entry.path().to_string_lossy().into_owned(),
+                output_name: relative.to_path_buf(),
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// Annotate a single input and write it to its destination
+///
+/// When processing multiple inputs, `--output` names a directory that
+/// receives each annotated file under its original file name (or relative
+/// path in recursive mode), and stdout
+/// output is separated by `==> path <==` headers.
+fn process_input(
+    cli: &Cli,
+    schemas: &Mutex<SchemaResolver>,
+    config: &AnnotatorConfig,
+    input: &InputFile,
+    multiple: bool,
+    report: &mut FileReport,
+    out: &mut Vec<u8>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let output_name = &input.output_name;
+    let input = input.path.as_str();
+    let _span = tracing::info_span!("file", path = input).entered();
+
+    // Read input content
+    let (input_content, target_format) = if input == "-" {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        // Use --format or the output extension, then sniff the content, then default to YAML
+        let format = cli
+            .format
+            .map(TargetFormat::from)
+            .or_else(|| cli.output.as_ref().and_then(|p| TargetFormat::from_path(p)))
+            .or_else(|| TargetFormat::from_content(&content))
+            .unwrap_or(TargetFormat::Yaml);
+        (content, format)
+    } else {
+        let path = PathBuf::from(input);
+        let content = fs::read_to_string(&path)?;
+        let format = cli
+            .format
+            .map(TargetFormat::from)
+            .or_else(|| TargetFormat::from_path(&path))
+            .or_else(|| TargetFormat::from_content(&content))
+            .ok_or_else(|| {
+                CliError::usage(format!("Unknown file format: {} (use --format)", path.display()))
+            })?;
+        (content, format)
     };
 
     // Annotate
-    let annotated = annotate(&schema, &input_content, target_format, config)?;
+    let loaded = schemas.lock().unwrap().resolve(input, &input_content, target_format)?;
+    let schema = &loaded.schema;
+    tracing::debug!(format = ?target_format, "annotating");
+    let (annotated, annotation_report) = if cli.annotate.strip {
+        let stripped = strip_extracted(&loaded.annotations, &input_content, target_format, config.clone())?;
+        (stripped, AnnotationReport::default())
+    } else {
+        annotate_extracted(&loaded.annotations, &input_content, target_format, config.clone())?
+    };
+    report.annotations = annotation_report;
+    let (annotated, issues) = if cli.annotate.validation_comments && !cli.annotate.strip {
+        annotate_with_validation(schema, &input_content, target_format, config.clone())?
+    } else if cli.annotate.validate {
+        (annotated, validate(schema, &input_content, target_format)?)
+    } else {
+        (annotated, Vec::new())
+    };
+    report.validation_errors = issues.clone();
+    let name = if input == "-" { "<stdin>" } else { input };
+    // Checking modes don't write the annotations, so none are applied
+    if !cli.annotate.drift && !cli.annotate.check {
+        for path in &report.annotations.applied {
+            emit(&Event::AnnotationApplied { file: name, path });
+        }
+    }
+    for path in &report.annotations.redundant_defaults {
+        emit(&Event::RedundantDefault { file: name, path });
+    }
+    for path in &report.annotations.duplicate_keys {
+        emit(&Event::Warning {
+            file: Some(name),
+            message: format!("duplicate key `{}`", path),
+        });
+    }
+    for issue in &issues {
+        emit(&Event::ValidationError {
+            file: name,
+            line: issue.line,
+            path: &issue.path,
+            message: &issue.message,
+        });
+    }
+
+    if cli.annotate.drift {
+        report.drift = detect_drift(schema, &input_content, target_format, config.clone())?;
+        for drift in &report.drift {
+            emit(&Event::Drift {
+                file: name,
+                line: drift.line,
+                path: &drift.path,
+                kind: drift.kind,
+            });
+        }
+        check_issues(&issues)?;
+        return match report.drift.len() {
+            0 => Ok(()),
+            1 => Err(CliError::new(Failure::Check, "1 key with drifted documentation").into()),
+            n => {
+                let message = format!("{} keys with drifted documentation", n);
+                Err(CliError::new(Failure::Check, message).into())
+            }
+        };
+    }
+
+    if cli.annotate.check {
+        check_issues(&issues)?;
+        if annotated != input_content {
+            let diff = similar::TextDiff::from_lines(&input_content, &annotated);
+            for hunk in diff.grouped_ops(0) {
+                if let Some(op) = hunk.first() {
+                    report.out_of_date.push(op.old_range().start + 1);
+                }
+            }
+            for line in &report.out_of_date {
+                emit(&Event::OutOfDate { file: name, line: *line });
+            }
+            return Err(CliError::new(Failure::Check, "annotating would change the file").into());
+        }
+        return Ok(());
+    }
+
+    if cli.annotate.diff {
+        let diff = similar::TextDiff::from_lines(&input_content, &annotated);
+        let unified = diff
+            .unified_diff()
+            .header(name, name)
+            .to_string();
+        let unified = if use_color(cli.annotate.color) {
+            colorize_diff(&unified)
+        } else {
+            unified
+        };
+        out.write_all(unified.as_bytes())?;
+        return check_issues(&issues);
+    }
+
+    // Writing back to the input file is an in-place edit, which doesn't need --force
+    let input_path = PathBuf::from(input);
+    let output = if cli.annotate.in_place {
+        Some(input_path.clone())
+    } else if multiple {
+        match &cli.output {
+            Some(dir) => {
+                let output_path = dir.join(output_name);
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                Some(output_path)
+            }
+            None => None,
+        }
+    } else {
+        cli.output.clone()
+    };
+    let in_place = output.as_ref().is_some_and(|p| is_same_file(p, &input_path));
+    report.in_place = in_place;
 
     // Write output
-    if let Some(output_path) = cli.output {
-        if output_path.exists() && !cli.force {
-            return Err(format!(
+    if let Some(output_path) = output {
+        if output_path.exists() && !cli.force && !in_place {
+            return Err(CliError::usage(format!(
                 "Output file exists: {}. Use --force to overwrite.",
                 output_path.display()
-            ).into());
+            ))
+            .into());
+        }
+        if in_place && annotated == input_content {
+            emit(&Event::UpToDate {
+                output: &output_path,
+            });
+        } else {
+            fs::write(&output_path, &annotated)?;
+            emit(&Event::Written {
+                what: if cli.annotate.strip { "stripped config" } else { "annotated config" },
+                output: &output_path,
+            });
         }
-        fs::write(&output_path, &annotated)?;
-        eprintln!("Wrote annotated config to {}", output_path.display());
     } else {
-        io::stdout().write_all(annotated.as_bytes())?;
+        if multiple {
+            writeln!(out, "==> {} <==", input)?;
+        }
+        out.write_all(annotated.as_bytes())?;
     }
 
-    Ok(())
+    check_issues(&issues)
+}
+
+/// Describe an error, quoting the offending line of the input if known
+fn error_message(error: &(dyn std::error::Error + 'static)) -> String {
+    let annotator_error = error.downcast_ref::<AnnotatorError>();
+    let schema_error = error.downcast_ref::<SchemaError>();
+
+    #[cfg(feature = "miette")]
+    {
+        let diagnostic: Option<&dyn miette::Diagnostic> = match annotator_error {
+            Some(error) => Some(error),
+            None => schema_error.map(|error| error as _),
+        };
+        if let Some(diagnostic) = diagnostic {
+            let theme = if io::stderr().is_terminal() {
+                miette::GraphicalTheme::unicode()
+            } else {
+                miette::GraphicalTheme::unicode_nocolor()
+            };
+            let mut rendered = String::new();
+            let handler = miette::GraphicalReportHandler::new_themed(theme);
+            if handler.render_report(&mut rendered, diagnostic).is_ok() {
+                return rendered.trim_end().to_string();
+            }
+        }
+    }
+
+    let mut message = error.to_string();
+    let location = match annotator_error {
+        Some(error) => error.location(),
+        None => schema_error.and_then(SchemaError::location),
+    };
+    if let Some(location) = location {
+        let gutter = " ".repeat(location.line.to_string().len());
+        // Keep tabs so the marker lines up with the column below them
+        let pad: String = location
+            .excerpt
+            .chars()
+            .take(location.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        message += &format!("\n {} | {}\n {} | {}^", location.line, location.excerpt, gutter, pad);
+    }
+    message
+}
+
+/// Fail once output is written if validation found violations
+fn check_issues(issues: &[ValidationIssue]) -> Result<(), Box<dyn std::error::Error>> {
+    match issues.len() {
+        0 => Ok(()),
+        1 => Err(CliError::new(Failure::Validation, "1 validation error").into()),
+        n => {
+            let message = format!("{} validation errors", n);
+            Err(CliError::new(Failure::Validation, message).into())
+        }
+    }
+}
+
+/// Decide whether to color terminal output
+fn use_color(mode: ColorMode) -> bool {
+    match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    }
+}
+
+/// Add ANSI colors to a unified diff
+///
+/// Removals are red and additions green, with added comment lines (the
+/// annotations being inserted) in bold so they stand out from moved content.
+fn colorize_diff(diff: &str) -> String {
+    const RESET: &str = "\x1b[0m";
+    let mut out = String::with_capacity(diff.len() * 2);
+    for line in diff.split_inclusive('\n') {
+        let (content, newline) = match line.strip_suffix('\n') {
+            Some(content) => (content, "\n"),
+            None => (line, ""),
+        };
+        let style = if content.starts_with("---") || content.starts_with("+++") {
+            "\x1b[1m"
+        } else if content.starts_with("@@") {
+            "\x1b[36m"
+        } else if let Some(added) = content.strip_prefix('+') {
+            if added.trim_start().starts_with('#') {
+                "\x1b[1;32m"
+            } else {
+                "\x1b[32m"
+            }
+        } else if content.starts_with('-') {
+            "\x1b[31m"
+        } else {
+            ""
+        };
+        if style.is_empty() {
+            out.push_str(line);
+        } else {
+            out.push_str(&format!("{}{}{}{}", style, content, RESET, newline));
+        }
+    }
+    out
+}
+
+/// Check whether two paths refer to the same existing file
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(config: &str, args: &[&str]) -> Result<Cli, Box<dyn std::error::Error>> {
+        let path = Path::new("/work/.jsonschema-annotator.toml");
+        let command = apply_config(Cli::command(), path, config)?;
+        let args = std::iter::once("jsonschema-annotator").chain(args.iter().copied());
+        Ok(Cli::from_arg_matches(&command.try_get_matches_from(args)?)?)
+    }
+
+    #[test]
+    fn test_config_file() {
+        let config = "schema = \"schema.json\"\nignore = [\"fixtures\", \"*.bak\"]\nmax-width = 60\n";
+
+        let cli = parse(config, &["-i", "config.toml"]).unwrap();
+        assert_eq!(cli.schema.as_deref(), Some("/work/schema.json"));
+        assert_eq!(cli.annotate.ignore, vec!["fixtures", "*.bak"]);
+        assert!(matches!(cli.max_width, MaxWidth::Columns(60)));
+
+        // Flags take precedence, and subcommands get the options of annotating
+        let config = "max-width = 60\nlock = \"annotate.lock\"\n";
+        let mut cli = parse(config, &["--max-width", "none", "check", "-i", "a.toml"]).unwrap();
+        assert!(matches!(cli.max_width, MaxWidth::Unlimited));
+        let Some(Command::Check(args)) = cli.command.take() else {
+            panic!("expected the check subcommand");
+        };
+        assert_eq!(args.lock, Some(PathBuf::from("/work/annotate.lock")));
+
+        let cli = parse("schema = \"https://example.com/schema.json\"\n", &["-i", "a.toml"]).unwrap();
+        assert_eq!(cli.schema.as_deref(), Some("https://example.com/schema.json"));
+    }
+
+    #[test]
+    fn test_config_file_errors() {
+        let error = |config: &str| parse(config, &["-i", "config.toml"]).err().unwrap().to_string();
+
+        assert_eq!(error("colour = true\n"), "/work/.jsonschema-annotator.toml: unknown option `colour`");
+        // Lists are only taken by options with several values
+        for (config, key) in [
+            ("schema = []\n", "schema"),
+            ("schema = [\"a.json\", \"b.json\"]\n", "schema"),
+            ("max-width = []\n", "max-width"),
+            ("ignore = [1]\n", "ignore"),
+        ] {
+            let expected = format!("/work/.jsonschema-annotator.toml: invalid value for `{}`", key);
+            assert_eq!(error(config), expected);
+        }
+        assert_eq!(
+            error("include = \"everything\"\n"),
+            "/work/.jsonschema-annotator.toml: invalid value `everything` for `include`"
+        );
+    }
+
+    #[test]
+    fn test_coverage() {
+        let report = AnnotationReport {
+            applied: vec!["server".to_string(), "server.port".to_string()],
+            skipped_existing: vec!["name".to_string()],
+            unmatched: vec!["extra".to_string()],
+            ..Default::default()
+        };
+        let coverage = Coverage::new("config.toml".to_string(), report);
+        assert_eq!((coverage.documented, coverage.keys), (3, 4));
+        assert_eq!(coverage.to_string(), "config.toml: 3 of 4 keys documented (75%)\n  undocumented: extra");
+
+        let empty = Coverage::new("empty.toml".to_string(), AnnotationReport::default());
+        assert_eq!(empty.to_string(), "empty.toml: 0 of 0 keys documented");
+    }
+
+    #[test]
+    fn test_check_output_names() {
+        let inputs = [
+            InputFile::new("a/config.toml".to_string()),
+            InputFile::new("b/app.toml".to_string()),
+            InputFile::new("a/config.toml".to_string()),
+        ];
+        assert!(check_output_names(&inputs, Path::new("out")).is_ok());
+
+        let inputs = [
+            InputFile::new("a/config.toml".to_string()),
+            InputFile::new("b/config.toml".to_string()),
+        ];
+        let error = check_output_names(&inputs, Path::new("out")).unwrap_err();
+        let output = Path::new("out").join("config.toml");
+        assert_eq!(
+            error.message,
+            format!("a/config.toml and b/config.toml would both be written to {}", output.display())
+        );
+    }
+
+    #[test]
+    fn test_is_fresh() {
+        let path = std::env::temp_dir().join(format!("jsonschema-annotator-fresh-{}", std::process::id()));
+        fs::write(&path, "{}").unwrap();
+        assert!(is_fresh(&path, Duration::from_secs(60)));
+        assert!(!is_fresh(&path, Duration::ZERO));
+        fs::remove_file(&path).unwrap();
+        assert!(!is_fresh(&path, Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_schema_cache() {
+        let cache = |args: &[&str]| parse("", args).map(|cli| cli.schema_cache()).map_err(|e| e.to_string());
+        assert_eq!(cache(&["-i", "a.toml"]), Ok(SchemaCache::Fresh));
+        assert_eq!(cache(&["--refresh-schema", "-i", "a.toml"]), Ok(SchemaCache::Refresh));
+        assert_eq!(cache(&["--offline", "check", "-i", "a.toml"]), Ok(SchemaCache::Offline));
+        // An offline default from the configuration file yields to the flag
+        let cli = parse("offline = true\n", &["--refresh-schema", "-i", "a.toml"]).unwrap();
+        assert_eq!(cli.schema_cache(), SchemaCache::Refresh);
+        assert!(cache(&["--offline", "--refresh-schema", "-i", "a.toml"]).is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let path = Path::new("overrides.yaml");
+        // Keys may also be JSON Pointers
+        let content = "server.port: Port the server binds\n/server/host:\n  title: Hostname\n";
+        let overrides = parse_overrides(path, content).unwrap();
+        assert_eq!(overrides.get("server.host").unwrap().title.as_deref(), Some("Hostname"));
+
+        let schema = serde_json::json!({
+            "properties": {
+                "server": {
+                    "properties": {
+                        "port": { "title": "Port", "description": "Port to listen on", "default": 8080 },
+                        "host": { "title": "Host", "description": "Address to listen on" }
+                    }
+                }
+            }
+        });
+        let loaded = LoadedSchema::new(schema, Some(&overrides));
+        // A plain string only replaces the description
+        let port = loaded.annotations.get("server.port").unwrap();
+        assert_eq!(port.title.as_deref(), Some("Port"));
+        assert_eq!(port.description.as_deref(), Some("Port the server binds"));
+        assert_eq!(port.default_value, Some(serde_json::json!(8080)));
+        let host = loaded.annotations.get("server.host").unwrap();
+        assert_eq!(host.title.as_deref(), Some("Hostname"));
+        assert_eq!(host.description.as_deref(), Some("Address to listen on"));
+
+        for (content, error) in [
+            ("- port\n", "overrides.yaml: invalid type"),
+            ("1: Port\n", "overrides.yaml: keys must be dot-separated paths or JSON Pointers"),
+            ("port: 8080\n", "overrides.yaml: `port` must map to a description or to a `title` and `description`"),
+            ("port:\n  default: 80\n", "overrides.yaml: `port` must map to a description"),
+        ] {
+            let message = parse_overrides(path, content).unwrap_err().to_string();
+            assert!(message.starts_with(error), "{:?}: {}", content, message);
+        }
+    }
+
+    #[test]
+    fn test_max_width() {
+        assert_eq!(MaxWidth::Columns(60).resolve(true), Some(60));
+        assert_eq!(MaxWidth::Unlimited.resolve(true), None);
+        // Files get the default width, whatever the terminal or $COLUMNS
+        assert_eq!(MaxWidth::Auto.resolve(false), Some(80));
+    }
 }
@@ -0,0 +1,139 @@
+//! Node.js bindings, built as a native addon with napi-rs
+//!
+//! Build with `cargo rustc --release --features node --lib --crate-type cdylib`
+//! and copy the library to a `.node` file. Node-API symbols are resolved when
+//! the addon is loaded, so the feature doesn't break linking the binary.
+
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+
+use crate::annotator::{AnnotatorConfig, Include, ParseOptionError};
+use crate::format::TargetFormat;
+
+/// Options for [`annotate`], mirroring the command line flags
+///
+/// Fields left undefined keep their defaults. A `maxWidth` of 0 disables
+/// wrapping.
+#[napi(object)]
+#[derive(Default)]
+pub struct AnnotateOptions {
+    /// `"title"`, `"description"` or `"both"`
+    pub include: Option<String>,
+    pub max_width: Option<u32>,
+    pub include_default: Option<bool>,
+    pub include_examples: Option<bool>,
+    pub markdown: Option<bool>,
+    pub strip_html: Option<bool>,
+    pub dedupe: Option<bool>,
+    /// `"skip"`, `"prepend"`, `"append"`, `"replace"` or `"update"`
+    pub existing_comments: Option<String>,
+    pub only: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub max_depth: Option<u32>,
+    /// `"all"`, `"leaves"` or `"sections"`
+    pub nodes: Option<String>,
+    pub fail_on_unmatched: Option<bool>,
+    pub fail_on_unsupported: Option<bool>,
+    pub verify: Option<bool>,
+}
+
+/// Annotate a TOML or YAML document with comments from a JSON Schema
+///
+/// `format` is the document's format, `"toml"` or `"yaml"`.
+#[napi]
+pub fn annotate(
+    schema: serde_json::Value,
+    target: String,
+    format: String,
+    options: Option<AnnotateOptions>,
+) -> Result<String> {
+    let format = TargetFormat::from_extension(&format)
+        .ok_or_else(|| invalid_arg(format!("unknown format: {}", format)))?;
+    let config = config_from_options(options.unwrap_or_default())?;
+
+    crate::annotate(&schema, &target, format, config)
+        .map_err(|e| Error::new(Status::GenericFailure, e.to_string()))
+}
+
+/// Extract the annotations of a JSON Schema, keyed by property path
+#[napi]
+pub fn extract_annotations(schema: serde_json::Value) -> Result<serde_json::Value> {
+    serde_json::to_value(crate::extract_annotations(&schema))
+        .map_err(|e| Error::new(Status::GenericFailure, e.to_string()))
+}
+
+fn config_from_options(options: AnnotateOptions) -> Result<AnnotatorConfig> {
+    let mut config = AnnotatorConfig::default();
+    if let Some(include) = options.include {
+        let include: Include = include.parse().map_err(|e| invalid_value("include", e))?;
+        include.apply(&mut config);
+    }
+    if let Some(width) = options.max_width {
+        config.max_line_width = (width > 0).then_some(width as usize);
+    }
+    if let Some(mode) = options.existing_comments {
+        config.existing_comments = mode.parse().map_err(|e| invalid_value("existingComments", e))?;
+    }
+    if let Some(nodes) = options.nodes {
+        config.node_filter = nodes.parse().map_err(|e| invalid_value("nodes", e))?;
+    }
+    if let Some(depth) = options.max_depth {
+        config.max_depth = Some(depth as usize);
+    }
+    config.include_default = options.include_default.unwrap_or(config.include_default);
+    config.include_examples = options.include_examples.unwrap_or(config.include_examples);
+    config.convert_markdown = options.markdown.unwrap_or(config.convert_markdown);
+    config.strip_html = options.strip_html.unwrap_or(config.strip_html);
+    config.deduplicate = options.dedupe.unwrap_or(config.deduplicate);
+    config.include_paths = options.only.unwrap_or(config.include_paths);
+    config.exclude_paths = options.exclude.unwrap_or(config.exclude_paths);
+    config.strict = options.fail_on_unmatched.unwrap_or(config.strict);
+    config.fail_on_unsupported =
+        options.fail_on_unsupported.unwrap_or(config.fail_on_unsupported);
+    config.verify_round_trip = options.verify.unwrap_or(config.verify_round_trip);
+    Ok(config)
+}
+
+fn invalid_value(key: &str, error: ParseOptionError) -> Error {
+    invalid_arg(format!("{}: {}", key, error))
+}
+
+fn invalid_arg(message: String) -> Error {
+    Error::new(Status::InvalidArg, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_node_annotate() {
+        let schema = json!({"properties": {"port": {"title": "Port", "description": "Port"}}});
+        let options = AnnotateOptions { include: Some("title".into()), ..Default::default() };
+
+        let annotated =
+            annotate(schema.clone(), "port: 8080\n".into(), "yaml".into(), Some(options)).unwrap();
+        assert_eq!(annotated, "# Port\nport: 8080\n");
+
+        let options = AnnotateOptions { nodes: Some("some".into()), ..Default::default() };
+        let error = annotate(schema.clone(), "".into(), "yaml".into(), Some(options)).unwrap_err();
+        assert_eq!(error.status, Status::InvalidArg);
+        assert_eq!(error.reason, "nodes: invalid value \"some\", expected one of: all, leaves, sections");
+
+        let options = AnnotateOptions { max_width: Some(0), ..Default::default() };
+        assert_eq!(config_from_options(options).unwrap().max_line_width, None);
+
+        let error = annotate(schema, "port: [".into(), "yaml".into(), None).unwrap_err();
+        assert_eq!(error.status, Status::GenericFailure);
+    }
+
+    #[test]
+    fn test_node_extract_annotations() {
+        let schema = json!({"properties": {"port": {"title": "Port"}}});
+        assert_eq!(
+            extract_annotations(schema).unwrap(),
+            json!({"port": {"title": "Port"}})
+        );
+    }
+}
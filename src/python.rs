@@ -0,0 +1,126 @@
+//! Python bindings, built as the `jsonschema_annotator` extension module
+//!
+//! Build with [maturin](https://www.maturin.rs) from the repository root
+//! (`maturin develop` or `maturin build --release`), which picks up the
+//! features from `pyproject.toml`.
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::annotator::{AnnotatorConfig, Include, ParseOptionError};
+use crate::format::TargetFormat;
+
+/// Annotate a TOML or YAML document with comments from a JSON Schema
+///
+/// `schema` is the schema as a dict (or anything `json.dumps` accepts), and
+/// `fmt` the document's format, `"toml"` or `"yaml"`. Keyword options mirror
+/// the command line flags: `include` ("title", "description" or "both"),
+/// `max_width` (0 to disable wrapping), `include_default`,
+/// `include_examples`, `markdown`, `strip_html`, `dedupe`,
+/// `existing_comments`, `only`, `exclude`, `max_depth`, `nodes`,
+/// `fail_on_unmatched`, `fail_on_unsupported` and `verify`.
+#[pyfunction]
+#[pyo3(signature = (schema, target, fmt, **options))]
+fn annotate(
+    schema: &Bound<'_, PyAny>,
+    target: &str,
+    fmt: &str,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<String> {
+    let json = schema.py().import("json")?.call_method1("dumps", (schema,))?;
+    let schema: serde_json::Value = serde_json::from_str(&json.extract::<String>()?)
+        .map_err(|e| PyValueError::new_err(format!("invalid schema: {}", e)))?;
+    let format = TargetFormat::from_extension(fmt)
+        .ok_or_else(|| PyValueError::new_err(format!("unknown format: {}", fmt)))?;
+    let config = match options {
+        Some(options) => config_from_options(options)?,
+        None => AnnotatorConfig::default(),
+    };
+
+    crate::annotate(&schema, target, format, config)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn config_from_options(options: &Bound<'_, PyDict>) -> PyResult<AnnotatorConfig> {
+    let mut config = AnnotatorConfig::default();
+    for (key, value) in options {
+        let key: String = key.extract()?;
+        match key.as_str() {
+            "include" => {
+                let include: String = value.extract()?;
+                let include: Include = include.parse().map_err(|e| invalid_value(&key, e))?;
+                include.apply(&mut config);
+            }
+            "max_width" => config.max_line_width = Some(value.extract::<usize>()?).filter(|&width| width > 0),
+            "include_default" => config.include_default = value.extract()?,
+            "include_examples" => config.include_examples = value.extract()?,
+            "markdown" => config.convert_markdown = value.extract()?,
+            "strip_html" => config.strip_html = value.extract()?,
+            "dedupe" => config.deduplicate = value.extract()?,
+            "existing_comments" => {
+                let mode: String = value.extract()?;
+                config.existing_comments = mode.parse().map_err(|e| invalid_value(&key, e))?;
+            }
+            "only" => config.include_paths = value.extract()?,
+            "exclude" => config.exclude_paths = value.extract()?,
+            "max_depth" => config.max_depth = value.extract()?,
+            "nodes" => {
+                let nodes: String = value.extract()?;
+                config.node_filter = nodes.parse().map_err(|e| invalid_value(&key, e))?;
+            }
+            "fail_on_unmatched" => config.strict = value.extract()?,
+            "fail_on_unsupported" => config.fail_on_unsupported = value.extract()?,
+            "verify" => config.verify_round_trip = value.extract()?,
+            _ => return Err(PyTypeError::new_err(format!("unexpected option: {}", key))),
+        }
+    }
+    Ok(config)
+}
+
+fn invalid_value(key: &str, error: ParseOptionError) -> PyErr {
+    PyValueError::new_err(format!("{}: {}", key, error))
+}
+
+#[pymodule]
+fn jsonschema_annotator(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(annotate, module)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotator::NodeFilter;
+
+    #[test]
+    fn test_python_annotate() {
+        Python::initialize();
+        Python::attach(|py| {
+            let schema = c"{'properties': {'port': {'title': 'Port', 'description': 'Port'}}}";
+            let schema = py.eval(schema, None, None).unwrap();
+            let options = PyDict::new(py);
+            options.set_item("include", "title").unwrap();
+
+            let annotated = annotate(&schema, "port: 8080\n", "yaml", Some(&options)).unwrap();
+            assert_eq!(annotated, "# Port\nport: 8080\n");
+
+            options.set_item("max_width", 0).unwrap();
+            options.set_item("nodes", "leaves").unwrap();
+            let config = config_from_options(&options).unwrap();
+            assert_eq!(config.max_line_width, None);
+            assert_eq!(config.node_filter, NodeFilter::LeavesOnly);
+
+            options.set_item("nodes", "some").unwrap();
+            let error = annotate(&schema, "port: 8080\n", "yaml", Some(&options)).unwrap_err();
+            assert!(error.to_string().contains("nodes: invalid value \"some\""));
+
+            options.del_item("nodes").unwrap();
+            options.set_item("colour", true).unwrap();
+            let error = annotate(&schema, "port: 8080\n", "yaml", Some(&options)).unwrap_err();
+            assert!(error.is_instance_of::<PyTypeError>(py));
+
+            let error = annotate(&schema, "port: [", "yaml", None).unwrap_err();
+            assert!(error.is_instance_of::<PyValueError>(py));
+        });
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::schema::{Annotation, AnnotationMap};
+
+/// Styles of the reference page
+const STYLE: &str = r#"body { font-family: system-ui, sans-serif; max-width: 60rem; margin: 2rem auto; padding: 0 1rem; line-height: 1.5; color: #1f2328; }
+input[type=search] { width: 100%; padding: .5rem; font-size: 1rem; margin-bottom: 1rem; box-sizing: border-box; }
+.entry { border-left: 3px solid #d0d7de; padding: .25rem 0 .25rem 1rem; margin: .75rem 0; }
+summary { cursor: pointer; }
+.path { font-family: ui-monospace, monospace; font-weight: 600; }
+.anchor { color: #8c959f; text-decoration: none; margin-left: .5rem; }
+.title { font-weight: 600; margin-left: .5rem; }
+.meta { font-size: .9rem; color: #59636e; }
+.meta code, pre { background: #f6f8fa; border-radius: 4px; padding: .1rem .3rem; }
+[hidden] { display: none !important; }"#;
+
+/// Hides entries not matching the search, keeping the ancestors of matches
+const SCRIPT: &str = r#"const search = document.getElementById("search");
+const entries = Array.from(document.querySelectorAll(".entry"));
+search.addEventListener("input", () => {
+  const query = search.value.trim().toLowerCase();
+  for (const entry of entries) {
+    const own = entry.querySelectorAll(":scope > .own, :scope > details > .own");
+    const text = Array.from(own, (element) => element.textContent).join(" ");
+    entry.hidden = query !== "" && !text.toLowerCase().includes(query);
+  }
+  if (query === "") return;
+  for (const entry of entries) {
+    if (entry.hidden) continue;
+    for (let parent = entry.parentElement.closest(".entry"); parent; parent = parent.parentElement.closest(".entry")) {
+      parent.hidden = false;
+      const details = parent.querySelector(":scope > details");
+      if (details) details.open = true;
+    }
+  }
+});"#;
+
+/// Render the annotations as a self-contained HTML page documenting every
+/// path, for publishing a config reference
+///
+/// Each path gets an anchor to link to (its path, with characters other
+/// than letters, digits, `.`, `-` and `_` replaced by `-`), objects are
+/// collapsible sections nesting their properties, and a search box filters
+/// the paths by their path, title, description and `x-category`. The page is titled with
+/// the schema's root title, if any.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::{extract_annotations, html_reference};
+///
+/// let schema = serde_json::json!({
+///     "title": "Server",
+///     "properties": {"port": {"description": "Port to listen on", "default": 8080}},
+/// });
+/// let html = html_reference(&extract_annotations(&schema));
+/// assert!(html.contains(r#"<div class="entry" id="port">"#));
+/// ```
+pub fn html_reference(annotations: &AnnotationMap) -> String {
+    // Paths ordered so each comes before the paths below it, with the
+    // parents of annotated paths included even when they aren't annotated
+    let mut paths = BTreeSet::new();
+    for (path, _) in annotations.iter().filter(|(path, _)| !path.is_empty()) {
+        let segments: Vec<&str> = path.split('.').collect();
+        for end in 1..=segments.len() {
+            paths.insert(segments[..end].to_vec());
+        }
+    }
+    let paths: Vec<Vec<&str>> = paths.into_iter().collect();
+
+    let root = annotations.get("");
+    let title = root
+        .and_then(|root| root.title.as_deref())
+        .unwrap_or("Configuration Reference");
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    let _ = writeln!(out, "<title>{}</title>", escape(title));
+    let _ = writeln!(out, "<style>\n{}\n</style>\n</head>\n<body>", STYLE);
+    let _ = writeln!(out, "<h1>{}</h1>", escape(title));
+    if let Some(description) = root.and_then(|root| root.description.as_deref()) {
+        push_paragraphs(&mut out, description);
+    }
+    out.push_str("<input type=\"search\" id=\"search\" placeholder=\"Search paths and descriptions\">\n");
+
+    let mut open: Vec<&[&str]> = Vec::new();
+    for (i, segments) in paths.iter().enumerate() {
+        while open.last().is_some_and(|parent| !segments.starts_with(parent)) {
+            open.pop();
+            out.push_str("</details>\n</div>\n");
+        }
+        let path = segments.join(".");
+        let has_children = paths.get(i + 1).is_some_and(|next| next.starts_with(segments));
+        let annotation = annotations.get(&path);
+        let _ = writeln!(out, "<div class=\"entry\" id=\"{}\">", anchor(&path));
+        if has_children {
+            out.push_str("<details open>\n<summary class=\"own\">");
+            push_heading(&mut out, &path, annotation);
+            out.push_str("</summary>\n");
+            push_documentation(&mut out, annotation);
+            open.push(segments);
+        } else {
+            out.push_str("<div class=\"own\">");
+            push_heading(&mut out, &path, annotation);
+            out.push_str("</div>\n");
+            push_documentation(&mut out, annotation);
+            out.push_str("</div>\n");
+        }
+    }
+    for _ in open {
+        out.push_str("</details>\n</div>\n");
+    }
+
+    let _ = writeln!(out, "<script>\n{}\n</script>\n</body>\n</html>", SCRIPT);
+    out
+}
+
+/// Write a path with a link to it and its title
+fn push_heading(out: &mut String, path: &str, annotation: Option<&Annotation>) {
+    let _ = write!(
+        out,
+        "<span class=\"path\">{}</span><a class=\"anchor\" href=\"#{}\">#</a>",
+        escape(path),
+        anchor(path)
+    );
+    if let Some(title) = annotation.and_then(|annotation| annotation.title.as_ref()) {
+        let _ = write!(out, "<span class=\"title\">{}</span>", escape(title));
+    }
+}
+
+/// Write the description, default, format, content and examples of a path
+fn push_documentation(out: &mut String, annotation: Option<&Annotation>) {
+    let Some(annotation) = annotation else {
+        return;
+    };
+    let mut doc = String::new();
+    if let Some(description) = &annotation.description {
+        push_paragraphs(&mut doc, description);
+    }
+
+    let mut meta = Vec::new();
+    if let Some(category) = &annotation.category {
+        meta.push(format!("Category: {}", escape(category)));
+    }
+    if let Some(default) = &annotation.default {
+        meta.push(format!("Default: <code>{}</code>", escape(default)));
+    }
+    if let Some(format) = &annotation.format {
+        meta.push(format!("Format: <code>{}</code>", escape(format)));
+    }
+    if let Some(hint) = annotation.content_hint() {
+        meta.push(escape(&hint));
+    }
+    if !annotation.examples.is_empty() {
+        let examples: Vec<String> = annotation
+            .examples
+            .iter()
+            .map(|example| format!("<code>{}</code>", escape(&example.to_string())))
+            .collect();
+        meta.push(format!("Examples: {}", examples.join(", ")));
+    }
+    for line in meta {
+        let _ = writeln!(doc, "<div class=\"meta\">{}</div>", line);
+    }
+    if !doc.is_empty() {
+        let _ = write!(out, "<div class=\"own\">\n{}</div>\n", doc);
+    }
+}
+
+/// Write text as paragraphs, split at blank lines
+fn push_paragraphs(out: &mut String, text: &str) {
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let _ = writeln!(out, "<p>{}</p>", escape(paragraph).replace('\n', "<br>"));
+    }
+}
+
+/// Element id of a path, usable in a URL fragment as is
+fn anchor(path: &str) -> String {
+    path.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '-' })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract_annotations;
+    use insta::assert_snapshot;
+
+    #[test]
+    fn test_html_reference() {
+        let schema = serde_json::json!({
+            "title": "Service <Config>",
+            "description": "Settings of the service.\n\nRead at startup.",
+            "properties": {
+                "server": {
+                    "title": "Server",
+                    "properties": {
+                        "port": {"description": "Port to listen on", "default": 8080},
+                        "tls": {
+                            "properties": {
+                                "cert": {
+                                    "contentMediaType": "application/x-pem-file",
+                                    "examples": ["/etc/cert.pem"]
+                                }
+                            }
+                        }
+                    }
+                },
+                "log level": {"description": "One of `info` & `debug`", "format": "level", "x-category": "Logging"}
+            }
+        });
+
+        let result = html_reference(&extract_annotations(&schema));
+        assert_snapshot!(result);
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use crate::annotator::AnnotatorConfig;
+use crate::error::{AnnotatorError, Error, SchemaError, SchemaErrorKind};
+use crate::file::{parse_schema, schema_error};
+use crate::format::TargetFormat;
+
+/// Loads schemas from http(s) URLs and files without blocking the async runtime
+///
+/// Each schema is loaded once and then shared, so a resolver kept for the
+/// lifetime of a service only fetches every schema a single time. Concurrent
+/// first requests for the same schema may both fetch it.
+#[derive(Debug, Default)]
+pub struct AsyncSchemaResolver {
+    client: reqwest::Client,
+    schemas: Mutex<HashMap<String, Arc<Value>>>,
+}
+
+impl AsyncSchemaResolver {
+    /// Create a resolver with a default HTTP client
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a resolver fetching with the given client, e.g. one configured
+    /// with timeouts or proxies
+    pub fn with_client(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            schemas: Mutex::default(),
+        }
+    }
+
+    /// Load the schema at a URL or file path, as YAML if its extension is
+    /// `.yaml` or `.yml` and as JSON otherwise
+    pub async fn resolve(&self, source: &str) -> Result<Arc<Value>, SchemaError> {
+        if let Some(schema) = self.schemas.lock().unwrap().get(source) {
+            return Ok(schema.clone());
+        }
+
+        tracing::info!(source, "loading schema");
+        let content = if source.starts_with("http://") || source.starts_with("https://") {
+            self.fetch(source).await?
+        } else {
+            tokio::fs::read_to_string(source).await.map_err(|e| {
+                Error::new(SchemaErrorKind::Io)
+                    .add_context(format!("reading {}", source))
+                    .with_source(e)
+            })?
+        };
+        let schema = Arc::new(parse_schema(&content, source)?);
+
+        self.schemas.lock().unwrap().insert(source.to_string(), schema.clone());
+        Ok(schema)
+    }
+
+    async fn fetch(&self, url: &str) -> Result<String, SchemaError> {
+        tracing::debug!(url, "fetching schema");
+        let fetch_error =
+            || Error::new(SchemaErrorKind::Io).add_context(format!("fetching {}", url));
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| fetch_error().with_source(e))?;
+        response.text().await.map_err(|e| fetch_error().with_source(e))
+    }
+}
+
+/// Annotate a target document with the schema at a URL or file path
+///
+/// Like [`annotate`](crate::annotate), but loads the schema through an
+/// [`AsyncSchemaResolver`] so fetching it doesn't block the runtime.
+///
+/// # Example
+/// ```rust,no_run
+/// use jsonschema_annotator::{annotate_async, AnnotatorConfig, AsyncSchemaResolver, TargetFormat};
+///
+/// # async fn run() -> Result<(), jsonschema_annotator::AnnotatorError> {
+/// let resolver = AsyncSchemaResolver::new();
+/// let annotated = annotate_async(
+///     &resolver,
+///     "https://example.com/config.schema.json",
+///     "port = 8080",
+///     TargetFormat::Toml,
+///     AnnotatorConfig::default(),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn annotate_async(
+    resolver: &AsyncSchemaResolver,
+    schema_source: &str,
+    target: &str,
+    target_format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<String, AnnotatorError> {
+    let schema = resolver.resolve(schema_source).await.map_err(schema_error)?;
+    crate::annotate(schema.as_ref(), target, target_format, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_annotate_async_from_file() {
+        let schema_path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/schema.json");
+        let resolver = AsyncSchemaResolver::new();
+
+        let first = resolver.resolve(schema_path).await.unwrap();
+        let second = resolver.resolve(schema_path).await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let config = "[server]\nport = 8080\n";
+        let annotated = annotate_async(
+            &resolver,
+            schema_path,
+            config,
+            TargetFormat::Toml,
+            AnnotatorConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert!(annotated.contains("port = 8080"));
+        assert_ne!(annotated, config);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_missing_file() {
+        let resolver = AsyncSchemaResolver::new();
+        let error = resolver.resolve("does-not-exist.json").await.unwrap_err();
+        assert!(matches!(error.kind, SchemaErrorKind::Io));
+    }
+}
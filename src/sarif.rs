@@ -0,0 +1,193 @@
+use serde_json::{json, Value};
+
+use crate::annotator::AnnotationReport;
+use crate::drift::{CommentDrift, DriftKind};
+use crate::validate::ValidationIssue;
+
+/// Rules of the SARIF log: id, level and description
+const RULES: &[(&str, &str, &str)] = &[
+    ("validation-error", "error", "Value doesn't validate against the schema"),
+    ("missing-documentation", "warning", "Documentation from the schema is missing"),
+    ("stale-documentation", "warning", "Documentation differs from the schema"),
+    ("edited-documentation", "warning", "Generated documentation was edited by hand"),
+    ("out-of-date", "warning", "Comments are out of date with the schema"),
+    ("unsupported-key", "warning", "Key the schema documents can't be annotated"),
+    ("unset-path", "note", "Path documented in the schema isn't set"),
+];
+
+/// What was found in one document, for [`sarif_report`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SarifFile<'a> {
+    /// Path of the document, used as the location of its results
+    pub file: &'a str,
+    /// Outcome of annotating the document, whose
+    /// [`unsupported`](AnnotationReport::unsupported) and
+    /// [`missing`](AnnotationReport::missing) paths are reported
+    pub annotations: Option<&'a AnnotationReport>,
+    /// Values that don't validate against the schema
+    pub validation_errors: &'a [ValidationIssue],
+    /// Keys whose comments have drifted from the schema
+    pub drift: &'a [CommentDrift],
+    /// First lines of the places whose comments are out of date
+    pub out_of_date: &'a [usize],
+}
+
+/// Convert what was found in documents to a SARIF 2.1.0 log for code
+/// scanning dashboards
+///
+/// Validation errors are reported as errors, drift, out of date comments and
+/// keys that can't be annotated as warnings, and documented paths a document
+/// doesn't set as notes.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::{sarif_report, SarifFile};
+///
+/// let log = sarif_report(&[SarifFile {
+///     file: "config.toml",
+///     out_of_date: &[3],
+///     ..Default::default()
+/// }]);
+/// let result = &log["runs"][0]["results"][0];
+/// assert_eq!(result["ruleId"], "out-of-date");
+/// assert_eq!(result["locations"][0]["physicalLocation"]["region"]["startLine"], 3);
+/// ```
+pub fn sarif_report(files: &[SarifFile<'_>]) -> Value {
+    let mut results = Vec::new();
+    let mut result = |rule: &str, file: &str, line: Option<usize>, message: String| {
+        let (_, level, _) = RULES.iter().find(|(id, _, _)| *id == rule).unwrap();
+        let mut location = json!({
+            "physicalLocation": {
+                "artifactLocation": { "uri": file.replace('\\', "/") },
+            },
+        });
+        if let Some(line) = line {
+            location["physicalLocation"]["region"] = json!({ "startLine": line });
+        }
+        results.push(json!({
+            "ruleId": rule,
+            "level": level,
+            "message": { "text": message },
+            "locations": [location],
+        }));
+    };
+    for report in files {
+        let file = report.file;
+        let at = |path: &str| if path.is_empty() { "<root>".to_string() } else { format!("`{}`", path) };
+        for issue in report.validation_errors {
+            result("validation-error", file, issue.line, format!("{}: {}", at(&issue.path), issue.message));
+        }
+        for drift in report.drift {
+            let (rule, problem) = match drift.kind {
+                DriftKind::Missing => ("missing-documentation", "is missing documentation from the schema"),
+                DriftKind::Stale => ("stale-documentation", "has documentation differing from the schema"),
+                DriftKind::Edited => ("edited-documentation", "has generated documentation edited by hand"),
+            };
+            result(rule, file, drift.line, format!("{} {}", at(&drift.path), problem));
+        }
+        for line in report.out_of_date {
+            let message = "Comments are out of date with the schema, run jsonschema-annotator to update them";
+            result("out-of-date", file, Some(*line), message.to_string());
+        }
+        let Some(annotations) = report.annotations else {
+            continue;
+        };
+        for path in &annotations.unsupported {
+            let message = format!("{} can't be annotated where it's written", at(path));
+            result("unsupported-key", file, None, message);
+        }
+        for path in &annotations.missing {
+            result("unset-path", file, None, format!("{} is documented in the schema but not set", at(path)));
+        }
+    }
+
+    let rules: Vec<Value> = RULES
+        .iter()
+        .map(|(id, level, description)| {
+            json!({
+                "id": id,
+                "shortDescription": { "text": description },
+                "defaultConfiguration": { "level": level },
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/kellpossible/jsonschema-annotator",
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sarif_report() {
+        let issue = ValidationIssue {
+            path: "server.port".to_string(),
+            pointer: "/server/port".to_string(),
+            message: "70000 is greater than the maximum of 65535".to_string(),
+            keyword: "maximum".to_string(),
+            line: Some(2),
+        };
+        let drift = CommentDrift {
+            path: "server.host".to_string(),
+            line: Some(4),
+            kind: DriftKind::Stale,
+            expected: vec!["# Host".to_string()],
+            existing: vec!["# Hostname".to_string()],
+        };
+        let annotations = AnnotationReport {
+            missing: vec!["server.tls".to_string()],
+            ..Default::default()
+        };
+        let log = sarif_report(&[SarifFile {
+            file: "config\\app.toml",
+            annotations: Some(&annotations),
+            validation_errors: &[issue],
+            drift: &[drift],
+            out_of_date: &[7],
+        }]);
+
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), RULES.len());
+
+        let results = run["results"].as_array().unwrap();
+        let summary: Vec<_> = results
+            .iter()
+            .map(|result| {
+                let location = &result["locations"][0]["physicalLocation"];
+                assert_eq!(location["artifactLocation"]["uri"], "config/app.toml");
+                (
+                    result["ruleId"].as_str().unwrap(),
+                    result["level"].as_str().unwrap(),
+                    location["region"]["startLine"].as_u64(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("validation-error", "error", Some(2)),
+                ("stale-documentation", "warning", Some(4)),
+                ("out-of-date", "warning", Some(7)),
+                ("unset-path", "note", None),
+            ]
+        );
+        assert_eq!(results[0]["message"]["text"], "`server.port`: 70000 is greater than the maximum of 65535");
+        assert_eq!(results[3]["message"]["text"], "`server.tls` is documented in the schema but not set");
+    }
+}
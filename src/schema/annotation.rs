@@ -1,16 +1,65 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use super::parser::format_default_value;
 
 /// Annotation data extracted from a JSON Schema property
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Annotation {
     /// Dot-separated path (e.g., "server.port")
+    #[serde(skip_serializing)]
     pub path: String,
     /// Schema `title` field
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
     /// Schema `description` field
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
     /// Schema `default` field (as a string representation)
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub default: Option<String>,
+    /// Schema `default` field as given, for rendering in the syntax of the
+    /// target format
+    #[serde(skip_serializing)]
+    pub default_value: Option<Value>,
+    /// Schema `format` field (e.g. `date-time`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Schema `type` field as a list of type names, for rendering values in
+    /// the syntax of the target format
+    #[serde(skip_serializing)]
+    pub types: Vec<String>,
+    /// Schema `contentMediaType` field (e.g. `application/x-pem-file`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_media_type: Option<String>,
+    /// Schema `contentEncoding` field (e.g. `base64`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_encoding: Option<String>,
+    /// Schema `examples` values
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<Value>,
+    /// What the items of an array of scalars are, e.g. `List of strings,
+    /// each one of: "a", "b"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<String>,
+    /// Schema `x-category` field, grouping related keys (e.g. `Networking`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Extension keywords (`x-*`) of the property and their values, for
+    /// formatters rendering custom metadata such as `x-since`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub extensions: BTreeMap<String, Value>,
+    /// Whether the value is a secret, being `writeOnly`, of `format:
+    /// password` or marked `x-secret`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+    /// An example entry of a map, as a key and value derived from its
+    /// `propertyNames` and `additionalProperties`, shown above empty maps
+    /// with [`example_entries`](crate::AnnotatorConfig::example_entries)
+    #[serde(skip_serializing)]
+    pub example_entry: Option<(String, Value)>,
 }
 
 impl Annotation {
@@ -21,6 +70,17 @@ impl Annotation {
             title: None,
             description: None,
             default: None,
+            default_value: None,
+            format: None,
+            types: Vec::new(),
+            content_media_type: None,
+            content_encoding: None,
+            examples: Vec::new(),
+            items: None,
+            category: None,
+            extensions: BTreeMap::new(),
+            secret: false,
+            example_entry: None,
         }
     }
 
@@ -36,9 +96,103 @@ impl Annotation {
         self
     }
 
-    /// Set the default value
+    /// Set the default value, shown as given
     pub fn with_default(mut self, default: impl Into<String>) -> Self {
         self.default = Some(default.into());
+        self.default_value = None;
+        self
+    }
+
+    /// Set the default value from the schema
+    ///
+    /// Unlike [`with_default`](Self::with_default), the value is kept so
+    /// comments can show it the way it's written in the target format.
+    pub fn with_default_value(mut self, default: Value) -> Self {
+        self.default = Some(format_default_value(&default));
+        self.default_value = Some(default);
+        self
+    }
+
+    /// Set the schema format of the value
+    pub fn with_format(mut self, format: impl Into<String>) -> Self {
+        self.format = Some(format.into());
+        self
+    }
+
+    /// Set the types of the value
+    pub fn with_types(mut self, types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the media type of the string's content
+    pub fn with_content_media_type(mut self, media_type: impl Into<String>) -> Self {
+        self.content_media_type = Some(media_type.into());
+        self
+    }
+
+    /// Set the encoding of the string's content
+    pub fn with_content_encoding(mut self, encoding: impl Into<String>) -> Self {
+        self.content_encoding = Some(encoding.into());
+        self
+    }
+
+    /// Describe the string's content from its media type and encoding
+    /// (e.g., "Base64-encoded PEM certificate")
+    pub fn content_hint(&self) -> Option<String> {
+        let encoding = self.content_encoding.as_deref().and_then(encoding_name);
+        let media_type = self.content_media_type.as_deref().map(|media_type| {
+            let essence = media_type.split(';').next().unwrap_or_default().trim();
+            (essence, media_type_name(essence))
+        });
+        match (encoding, media_type) {
+            (Some(encoding), Some((_, Some(name)))) => Some(format!("{}-encoded {}", encoding, name)),
+            (Some(encoding), Some((essence, None))) => {
+                Some(format!("{}-encoded {} content", encoding, essence))
+            }
+            (Some(encoding), None) => Some(format!("{}-encoded data", encoding)),
+            (None, Some((_, Some(name)))) => {
+                let (first, rest) = name.split_at(1);
+                Some(format!("{}{}", first.to_uppercase(), rest))
+            }
+            (None, Some((essence, None))) => Some(format!("{} content", essence)),
+            (None, None) => None,
+        }
+    }
+
+    /// Set the example values
+    pub fn with_examples(mut self, examples: impl IntoIterator<Item = Value>) -> Self {
+        self.examples = examples.into_iter().collect();
+        self
+    }
+
+    /// Set the description of the array's items
+    pub fn with_items(mut self, items: impl Into<String>) -> Self {
+        self.items = Some(items.into());
+        self
+    }
+
+    /// Set the category of the key
+    pub fn with_category(mut self, category: impl Into<String>) -> Self {
+        self.category = Some(category.into());
+        self
+    }
+
+    /// Set the value of an extension keyword
+    pub fn with_extension(mut self, keyword: impl Into<String>, value: Value) -> Self {
+        self.extensions.insert(keyword.into(), value);
+        self
+    }
+
+    /// Set whether the value is a secret
+    pub fn with_secret(mut self, secret: bool) -> Self {
+        self.secret = secret;
+        self
+    }
+
+    /// Set the example entry of a map
+    pub fn with_example_entry(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.example_entry = Some((key.into(), value));
         self
     }
 
@@ -60,16 +214,114 @@ impl Annotation {
         lines
     }
 
+    /// Override the fields `other` sets, keeping the rest
+    ///
+    /// Extension keywords are overridden one by one, and a value is a secret
+    /// if either annotation says so.
+    fn override_with(&mut self, other: Annotation) {
+        if other.title.is_some() {
+            self.title = other.title;
+        }
+        if other.description.is_some() {
+            self.description = other.description;
+        }
+        if other.default.is_some() {
+            self.default = other.default;
+            self.default_value = other.default_value;
+        }
+        if other.format.is_some() {
+            self.format = other.format;
+        }
+        if !other.types.is_empty() {
+            self.types = other.types;
+        }
+        if other.content_media_type.is_some() {
+            self.content_media_type = other.content_media_type;
+        }
+        if other.content_encoding.is_some() {
+            self.content_encoding = other.content_encoding;
+        }
+        if !other.examples.is_empty() {
+            self.examples = other.examples;
+        }
+        if other.items.is_some() {
+            self.items = other.items;
+        }
+        if other.category.is_some() {
+            self.category = other.category;
+        }
+        self.extensions.extend(other.extensions);
+        self.secret |= other.secret;
+        if other.example_entry.is_some() {
+            self.example_entry = other.example_entry;
+        }
+    }
+
     /// Check if this annotation has any content
     pub fn is_empty(&self) -> bool {
-        self.title.is_none() && self.description.is_none() && self.default.is_none()
+        self.title.is_none()
+            && self.description.is_none()
+            && self.default.is_none()
+            && self.content_media_type.is_none()
+            && self.content_encoding.is_none()
+            && self.examples.is_empty()
+            && self.items.is_none()
+            && self.category.is_none()
+            && self.extensions.is_empty()
+            && !self.secret
     }
 }
 
+/// A readable name for a well-known media type
+fn media_type_name(essence: &str) -> Option<&'static str> {
+    let name = match essence.to_ascii_lowercase().as_str() {
+        "application/x-pem-file" | "application/pem-certificate-chain" => "PEM certificate",
+        "application/pkix-cert" | "application/x-x509-ca-cert" => "X.509 certificate",
+        "application/pkcs8" => "PKCS #8 private key",
+        "application/octet-stream" => "binary data",
+        "application/json" => "JSON",
+        "application/yaml" | "application/x-yaml" | "text/yaml" => "YAML",
+        "application/toml" => "TOML",
+        "application/xml" | "text/xml" => "XML",
+        "text/html" => "HTML",
+        "text/plain" => "text",
+        "image/png" => "PNG image",
+        "image/jpeg" => "JPEG image",
+        "image/svg+xml" => "SVG image",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// A readable name for a content encoding, or `None` for the identity
+/// encodings that don't change the content
+fn encoding_name(encoding: &str) -> Option<String> {
+    let name = match encoding.to_ascii_lowercase().as_str() {
+        "7bit" | "8bit" | "binary" => return None,
+        "base64" => "Base64",
+        "base64url" => "Base64url",
+        "base32" => "Base32",
+        "base16" => "Hex",
+        "quoted-printable" => "Quoted-printable",
+        _ => return Some(encoding.to_string()),
+    };
+    Some(name.to_string())
+}
+
 /// Collection of annotations indexed by path
 #[derive(Debug, Clone, Default)]
 pub struct AnnotationMap {
     inner: HashMap<String, Annotation>,
+    /// Paths of every property the schema defines
+    properties: HashSet<String>,
+    /// Paths of objects the schema lists properties for
+    listed: HashSet<String>,
+    /// Paths of objects that also allow other keys, through
+    /// `additionalProperties` or `patternProperties`
+    open: HashSet<String>,
+    /// Extension keywords (`x-*`) set to `false`, by the path of the
+    /// property setting them
+    disabled: HashMap<String, HashSet<String>>,
 }
 
 impl AnnotationMap {
@@ -83,6 +335,68 @@ impl AnnotationMap {
         self.inner.get(path)
     }
 
+    /// Get an annotation by the JSON Pointer to its value in a document,
+    /// e.g. `/server/port`
+    ///
+    /// Returns `None` for strings that aren't JSON Pointers, as well as for
+    /// paths without an annotation. See [`pointer_to_path`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use jsonschema_annotator::{Annotation, AnnotationMap};
+    ///
+    /// let mut map = AnnotationMap::new();
+    /// map.insert(Annotation::new("server.port").with_title("Port"));
+    /// assert_eq!(map.get_pointer("/server/port").unwrap().title.as_deref(), Some("Port"));
+    /// assert!(map.get_pointer("server.port").is_none());
+    /// ```
+    pub fn get_pointer(&self, pointer: &str) -> Option<&Annotation> {
+        self.get(&pointer_to_path(pointer)?)
+    }
+
+    /// Merge layers of annotations, later layers taking priority
+    ///
+    /// Each field of an annotation is overridden independently, so a layer
+    /// that only rewrites a title keeps the description below it. This suits
+    /// schema overlays and override files polishing the wording of a
+    /// schema's annotations. What the layers know about the schema's
+    /// properties is combined.
+    ///
+    /// # Example
+    /// ```rust
+    /// use jsonschema_annotator::{Annotation, AnnotationMap};
+    ///
+    /// let mut schema = AnnotationMap::new();
+    /// schema.insert(Annotation::new("port").with_title("port").with_description("Port to listen on"));
+    /// let mut overrides = AnnotationMap::new();
+    /// overrides.insert(Annotation::new("port").with_title("Port"));
+    ///
+    /// let merged = AnnotationMap::merge_with_priority(vec![schema, overrides]);
+    /// let port = merged.get("port").unwrap();
+    /// assert_eq!(port.title.as_deref(), Some("Port"));
+    /// assert_eq!(port.description.as_deref(), Some("Port to listen on"));
+    /// ```
+    pub fn merge_with_priority(layers: Vec<AnnotationMap>) -> AnnotationMap {
+        let mut merged = AnnotationMap::new();
+        for layer in layers {
+            for (path, annotation) in layer.inner {
+                match merged.inner.get_mut(&path) {
+                    Some(existing) => existing.override_with(annotation),
+                    None => {
+                        merged.inner.insert(path, annotation);
+                    }
+                }
+            }
+            merged.properties.extend(layer.properties);
+            merged.listed.extend(layer.listed);
+            merged.open.extend(layer.open);
+            for (path, keywords) in layer.disabled {
+                merged.disabled.entry(path).or_default().extend(keywords);
+            }
+        }
+        merged
+    }
+
     /// Insert an annotation
     pub fn insert(&mut self, annotation: Annotation) {
         if !annotation.is_empty() {
@@ -90,6 +404,68 @@ impl AnnotationMap {
         }
     }
 
+    /// Get a mutable annotation by path
+    pub(crate) fn get_mut(&mut self, path: &str) -> Option<&mut Annotation> {
+        self.inner.get_mut(path)
+    }
+
+    /// Insert an annotation even if it has no content, to mark a path that
+    /// needs rendering for other reasons
+    pub(crate) fn insert_unchecked(&mut self, annotation: Annotation) {
+        self.inner.insert(annotation.path.clone(), annotation);
+    }
+
+    /// Check whether the schema rules out a key
+    ///
+    /// A key is unknown if its parent's schema lists properties, the key
+    /// isn't one of them, and other keys aren't allowed through
+    /// `additionalProperties` or `patternProperties`. Keys below an unknown
+    /// key aren't unknown themselves, and a map built without a schema knows
+    /// no properties, so nothing is unknown to it.
+    pub fn is_unknown(&self, path: &str) -> bool {
+        let parent = path.rsplit_once('.').map_or("", |(parent, _)| parent);
+        self.listed.contains(parent) && !self.open.contains(parent) && !self.properties.contains(path)
+    }
+
+    /// Record the properties an object at `path` lists
+    pub(crate) fn record_properties<'a>(
+        &mut self,
+        path: &str,
+        keys: impl Iterator<Item = &'a String>,
+    ) {
+        self.listed.insert(path.to_string());
+        for key in keys {
+            let property = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+            self.properties.insert(property);
+        }
+    }
+
+    /// Paths of every property the schema defines
+    pub(crate) fn properties(&self) -> impl Iterator<Item = &String> {
+        self.properties.iter()
+    }
+
+    /// Record that an object at `path` allows keys besides its listed properties
+    pub(crate) fn record_open(&mut self, path: &str) {
+        self.open.insert(path.to_string());
+    }
+
+    /// Record that the property at `path` sets an extension keyword to `false`
+    pub(crate) fn record_disabled(&mut self, path: &str, keyword: &str) {
+        self.disabled.entry(path.to_string()).or_default().insert(keyword.to_string());
+    }
+
+    /// Check whether the schema sets `keyword` to `false` on the property at
+    /// `path` or any property above it
+    ///
+    /// Only extension keywords (starting with `x-`) are recorded, so schema
+    /// authors can opt properties out of things like annotation with
+    /// `"x-annotate": false`.
+    pub fn is_disabled(&self, path: &str, keyword: &str) -> bool {
+        let disabled = |path: &str| self.disabled.get(path).is_some_and(|keys| keys.contains(keyword));
+        disabled(path) || path.match_indices('.').any(|(end, _)| disabled(&path[..end]))
+    }
+
     /// Iterate over all annotations
     pub fn iter(&self) -> impl Iterator<Item = (&String, &Annotation)> {
         self.inner.iter()
@@ -106,6 +482,62 @@ impl AnnotationMap {
     }
 }
 
+/// Convert a dot-separated path, as used to key annotations, to a JSON
+/// Pointer to the value in a document
+///
+/// `~` and `/` in keys are escaped. The root path `""` is the pointer `""`.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::path_to_pointer;
+///
+/// assert_eq!(path_to_pointer("server.port"), "/server/port");
+/// assert_eq!(path_to_pointer("routes.api/v1"), "/routes/api~1v1");
+/// ```
+pub fn path_to_pointer(path: &str) -> String {
+    if path.is_empty() {
+        return String::new();
+    }
+    path.split('.')
+        .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// Convert a JSON Pointer to a value in a document to the dot-separated path
+/// annotations are keyed by
+///
+/// Escaped `~` and `/` are restored. Returns `None` if `pointer` isn't a JSON
+/// Pointer, i.e. neither empty nor starting with `/`. Since paths separate
+/// keys with dots, keys containing dots can't be told apart from nested keys
+/// after conversion.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::pointer_to_path;
+///
+/// assert_eq!(pointer_to_path("/server/port").as_deref(), Some("server.port"));
+/// assert_eq!(pointer_to_path("").as_deref(), Some(""));
+/// assert_eq!(pointer_to_path("server/port"), None);
+/// ```
+pub fn pointer_to_path(pointer: &str) -> Option<String> {
+    if pointer.is_empty() {
+        return Some(String::new());
+    }
+    let keys: Vec<String> = pointer
+        .strip_prefix('/')?
+        .split('/')
+        .map(|key| key.replace("~1", "/").replace("~0", "~"))
+        .collect();
+    Some(keys.join("."))
+}
+
+/// Serializes as an object from path to annotation, sorted by path
+impl Serialize for AnnotationMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.inner.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,6 +575,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_content_hint() {
+        let hint = |media_type: Option<&str>, encoding: Option<&str>| {
+            let mut ann = Annotation::new("test");
+            ann.content_media_type = media_type.map(str::to_string);
+            ann.content_encoding = encoding.map(str::to_string);
+            ann.content_hint()
+        };
+        assert_eq!(
+            hint(Some("application/x-pem-file"), Some("base64")).as_deref(),
+            Some("Base64-encoded PEM certificate")
+        );
+        assert_eq!(hint(None, Some("base64")).as_deref(), Some("Base64-encoded data"));
+        assert_eq!(hint(Some("application/octet-stream"), None).as_deref(), Some("Binary data"));
+        assert_eq!(
+            hint(Some("text/plain; charset=utf-8"), Some("base64")).as_deref(),
+            Some("Base64-encoded text")
+        );
+        assert_eq!(hint(Some("image/webp"), None).as_deref(), Some("image/webp content"));
+        assert_eq!(hint(None, Some("7bit")), None);
+        assert_eq!(hint(None, None), None);
+    }
+
     #[test]
     fn test_annotation_map() {
         let mut map = AnnotationMap::new();
@@ -156,6 +611,78 @@ mod tests {
         assert!(map.get("c").is_none());
     }
 
+    #[test]
+    fn test_annotation_map_serialize() {
+        let mut map = AnnotationMap::new();
+        map.insert(Annotation::new("server.port").with_title("Port").with_default("8080"));
+        map.insert(Annotation::new("server").with_description("Server settings"));
+
+        assert_eq!(
+            serde_json::to_string(&map).unwrap(),
+            r#"{"server":{"description":"Server settings"},"server.port":{"title":"Port","default":"8080"}}"#
+        );
+    }
+
+    #[test]
+    fn test_merge_with_priority() {
+        let mut schema = AnnotationMap::new();
+        schema.insert(
+            Annotation::new("server.port")
+                .with_title("Port")
+                .with_description("Port number")
+                .with_default_value(serde_json::json!(8080))
+                .with_extension("x-since", serde_json::json!("1.0")),
+        );
+        schema.insert(Annotation::new("server").with_title("Server"));
+        schema.record_properties("server", [String::from("port")].iter());
+
+        let mut overlay = AnnotationMap::new();
+        overlay.insert(
+            Annotation::new("server.port")
+                .with_description("Port to listen on")
+                .with_extension("x-owner", serde_json::json!("net")),
+        );
+        overlay.insert(Annotation::new("name").with_title("Name"));
+
+        let mut overrides = AnnotationMap::new();
+        overrides.insert(Annotation::new("server.port").with_title("Listen port").with_secret(true));
+
+        let merged = AnnotationMap::merge_with_priority(vec![schema, overlay, overrides]);
+        assert_eq!(merged.len(), 3);
+        let port = merged.get("server.port").unwrap();
+        assert_eq!(port.title.as_deref(), Some("Listen port"));
+        assert_eq!(port.description.as_deref(), Some("Port to listen on"));
+        assert_eq!(port.default.as_deref(), Some("8080"));
+        assert_eq!(port.default_value, Some(serde_json::json!(8080)));
+        assert_eq!(port.extensions.len(), 2);
+        assert!(port.secret);
+        assert_eq!(merged.get("server").unwrap().title.as_deref(), Some("Server"));
+        assert_eq!(merged.get("name").unwrap().title.as_deref(), Some("Name"));
+        assert!(merged.is_unknown("server.host"));
+        assert!(!merged.is_unknown("server.port"));
+    }
+
+    #[test]
+    fn test_pointer_conversion() {
+        for (path, pointer) in [
+            ("", ""),
+            ("port", "/port"),
+            ("server.port", "/server/port"),
+            ("paths./api", "/paths/~1api"),
+            ("home.~user", "/home/~0user"),
+            ("a.~1", "/a/~01"),
+        ] {
+            assert_eq!(path_to_pointer(path), pointer);
+            assert_eq!(pointer_to_path(pointer).as_deref(), Some(path));
+        }
+        assert_eq!(pointer_to_path("#/server"), None);
+
+        let mut map = AnnotationMap::new();
+        map.insert(Annotation::new("server.port").with_title("Port"));
+        assert_eq!(map.get_pointer("/server/port").unwrap().title.as_deref(), Some("Port"));
+        assert!(map.get_pointer("/server/host").is_none());
+    }
+
     #[test]
     fn test_empty_annotation_not_inserted() {
         let mut map = AnnotationMap::new();
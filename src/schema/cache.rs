@@ -0,0 +1,180 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+
+use super::{extract_annotations, AnnotationMap, SchemaSource};
+
+/// Extracted annotations shared between documents annotated against the same
+/// schema
+///
+/// Schemas are identified by their content, so equal schemas share one entry
+/// no matter where they were loaded from. Looking up a cached schema costs a
+/// hash and a comparison of the schema, instead of resolving its `$ref`s and
+/// walking it again. The cache can be shared between threads.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "toml")] {
+/// use jsonschema_annotator::{annotate_extracted, AnnotatorConfig, SchemaCache, TargetFormat};
+///
+/// let cache = SchemaCache::new();
+/// let schema = serde_json::json!({"properties": {"port": {"title": "Port"}}});
+///
+/// for document in ["port = 80", "port = 8080"] {
+///     let annotations = cache.annotations(&schema);
+///     let (annotated, _) =
+///         annotate_extracted(&annotations, document, TargetFormat::Toml, AnnotatorConfig::default())
+///             .unwrap();
+///     assert!(annotated.starts_with("# Port\n"));
+/// }
+/// assert_eq!(cache.len(), 1);
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct SchemaCache {
+    /// Entries by hash of their schema
+    entries: RwLock<HashMap<u64, Vec<CacheEntry>>>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    /// Kept to tell apart schemas with the same hash
+    schema: Value,
+    annotations: Arc<AnnotationMap>,
+}
+
+impl SchemaCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the annotations of a schema, extracting them on first use
+    pub fn annotations(&self, schema: &impl SchemaSource) -> Arc<AnnotationMap> {
+        let schema = schema.as_json();
+        let hash = hash_schema(schema);
+        if let Some(annotations) = self.find(hash, schema) {
+            return annotations;
+        }
+
+        // Extract without holding the lock, then keep whichever entry got in first
+        tracing::debug!(hash, "extracting annotations for uncached schema");
+        let annotations = Arc::new(extract_annotations(schema));
+        let mut entries = self.entries.write().unwrap();
+        let bucket = entries.entry(hash).or_default();
+        match bucket.iter().find(|entry| entry.schema == *schema) {
+            Some(entry) => entry.annotations.clone(),
+            None => {
+                bucket.push(CacheEntry {
+                    schema: schema.clone(),
+                    annotations: annotations.clone(),
+                });
+                annotations
+            }
+        }
+    }
+
+    /// Number of distinct schemas in the cache
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().values().map(Vec::len).sum()
+    }
+
+    /// Whether the cache holds no schemas
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop all cached schemas
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+    }
+
+    fn find(&self, hash: u64, schema: &Value) -> Option<Arc<AnnotationMap>> {
+        let entries = self.entries.read().unwrap();
+        let bucket = entries.get(&hash)?;
+        let entry = bucket.iter().find(|entry| entry.schema == *schema)?;
+        Some(entry.annotations.clone())
+    }
+}
+
+/// Hash a schema in a canonical form, with object keys sorted, so equal
+/// schemas hash the same whatever order their keys were written in
+fn hash_schema(schema: &Value) -> u64 {
+    fn hash_value(value: &Value, hasher: &mut DefaultHasher) {
+        match value {
+            Value::Null => hasher.write_u8(0),
+            Value::Bool(b) => {
+                hasher.write_u8(1);
+                b.hash(hasher);
+            }
+            Value::Number(n) => {
+                hasher.write_u8(2);
+                n.to_string().hash(hasher);
+            }
+            Value::String(s) => {
+                hasher.write_u8(3);
+                s.hash(hasher);
+            }
+            Value::Array(items) => {
+                hasher.write_u8(4);
+                hasher.write_usize(items.len());
+                for item in items {
+                    hash_value(item, hasher);
+                }
+            }
+            Value::Object(map) => {
+                hasher.write_u8(5);
+                hasher.write_usize(map.len());
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                for (key, value) in entries {
+                    key.hash(hasher);
+                    hash_value(value, hasher);
+                }
+            }
+        }
+    }
+
+    let mut hasher = DefaultHasher::new();
+    hash_value(schema, &mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_cache() {
+        let cache = SchemaCache::new();
+        let port = serde_json::json!({ "properties": { "port": { "title": "Port" } } });
+        let host = serde_json::json!({ "properties": { "host": { "title": "Host" } } });
+
+        let first = cache.annotations(&port);
+        // An equal schema from elsewhere hits the same entry
+        let second = cache.annotations(&port.clone());
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.get("port").unwrap().title.as_deref(), Some("Port"));
+
+        let other = cache.annotations(&host);
+        assert!(!Arc::ptr_eq(&first, &other));
+        assert_eq!(cache.len(), 2);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+    #[test]
+    fn test_hash_schema_ignores_key_order() {
+        let schema: Value = serde_json::from_str(r#"{"title": "A", "properties": {"b": {}, "a": {}}}"#).unwrap();
+        let reordered: Value = serde_json::from_str(r#"{"properties": {"a": {}, "b": {}}, "title": "A"}"#).unwrap();
+        assert_eq!(hash_schema(&schema), hash_schema(&reordered));
+        assert_ne!(hash_schema(&schema), hash_schema(&serde_json::json!({"title": "B"})));
+
+        let cache = SchemaCache::new();
+        assert!(Arc::ptr_eq(&cache.annotations(&schema), &cache.annotations(&reordered)));
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -1,6 +1,12 @@
 mod annotation;
+mod cache;
 mod parser;
 mod refs;
+mod source;
 
-pub use annotation::{Annotation, AnnotationMap};
+pub use annotation::{path_to_pointer, pointer_to_path, Annotation, AnnotationMap};
+pub use cache::SchemaCache;
 pub use parser::extract_annotations;
+pub(crate) use parser::{format_default_value, is_secret, schema_types};
+pub(crate) use refs::{percent_decode, Refs};
+pub use source::SchemaSource;
@@ -1,11 +1,11 @@
-use schemars::Schema;
 use serde_json::Value;
 
 use super::annotation::{Annotation, AnnotationMap};
-use super::refs::resolve_refs;
+use super::refs::Refs;
+use super::source::SchemaSource;
 
 /// Format a JSON value as a human-readable string for display in comments
-fn format_default_value(value: &Value) -> String {
+pub(crate) fn format_default_value(value: &Value) -> String {
     match value {
         Value::Null => "null".to_string(),
         Value::Bool(b) => b.to_string(),
@@ -27,30 +27,69 @@ fn format_default_value(value: &Value) -> String {
 
 /// Extract annotations from a JSON Schema
 ///
-/// This resolves $refs and walks the schema recursively,
-/// extracting title/description for each property path.
-pub fn extract_annotations(schema: &Schema) -> AnnotationMap {
-    let resolved = resolve_refs(schema);
+/// This walks the schema recursively, following $refs, and extracts the
+/// title/description for each property path.
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn extract_annotations(schema: &impl SchemaSource) -> AnnotationMap {
+    let schema = schema.as_json();
     let mut annotations = AnnotationMap::new();
     let mut path = Vec::new();
 
-    walk_schema(resolved.as_value(), &mut path, &mut annotations);
+    walk_schema(schema, &mut Refs::new(schema), &mut path, &mut annotations);
 
+    tracing::debug!(count = annotations.len(), "extracted annotations");
     annotations
 }
 
-fn walk_schema(value: &Value, current_path: &mut Vec<String>, annotations: &mut AnnotationMap) {
+fn walk_schema<'a>(
+    value: &'a Value,
+    refs: &mut Refs<'a>,
+    current_path: &mut Vec<String>,
+    annotations: &mut AnnotationMap,
+) {
+    refs.visit(value, |refs, value| walk_resolved(value, refs, current_path, annotations));
+}
+
+fn walk_resolved<'a>(
+    value: &'a Value,
+    refs: &mut Refs<'a>,
+    current_path: &mut Vec<String>,
+    annotations: &mut AnnotationMap,
+) {
     let Some(obj) = value.as_object() else {
         return;
     };
 
-    // Extract title/description/default at current level
+    // Extract title/description/default/examples at current level
+    let path = current_path.join(".");
     let title = obj.get("title").and_then(|v| v.as_str());
     let desc = obj.get("description").and_then(|v| v.as_str());
-    let default = obj.get("default").map(format_default_value);
-
-    if title.is_some() || desc.is_some() || default.is_some() {
-        let mut ann = Annotation::new(current_path.join("."));
+    let default = obj.get("default");
+    let examples = obj.get("examples").and_then(|v| v.as_array());
+
+    let media_type = obj.get("contentMediaType").and_then(|v| v.as_str());
+    let encoding = obj.get("contentEncoding").and_then(|v| v.as_str());
+
+    let has_examples = examples.is_some_and(|e| !e.is_empty());
+    let has_content = media_type.is_some() || encoding.is_some();
+    let secret = is_secret(obj);
+    let category = obj.get("x-category").and_then(|v| v.as_str());
+    let extensions: Vec<(&String, &Value)> = obj.iter().filter(|(key, _)| key.starts_with("x-")).collect();
+    let items = obj
+        .get("items")
+        .and_then(|items| refs.visit(items, |_, items| scalar_items(items)));
+
+    if title.is_some()
+        || desc.is_some()
+        || default.is_some()
+        || has_examples
+        || has_content
+        || secret
+        || items.is_some()
+        || category.is_some()
+        || !extensions.is_empty()
+    {
+        let mut ann = Annotation::new(path.clone());
         if let Some(t) = title {
             ann = ann.with_title(t);
         }
@@ -58,29 +97,75 @@ fn walk_schema(value: &Value, current_path: &mut Vec<String>, annotations: &mut
             ann = ann.with_description(d);
         }
         if let Some(d) = default {
-            ann = ann.with_default(d);
+            ann = ann.with_default_value(d.clone());
+        }
+        if let Some(format) = obj.get("format").and_then(|v| v.as_str()) {
+            ann = ann.with_format(format);
+        }
+        ann = ann.with_types(schema_types(value));
+        if let Some(media_type) = media_type {
+            ann = ann.with_content_media_type(media_type);
+        }
+        if let Some(encoding) = encoding {
+            ann = ann.with_content_encoding(encoding);
+        }
+        if let Some(examples) = examples {
+            ann = ann.with_examples(examples.iter().cloned());
         }
-        annotations.insert(ann);
+        if let Some(items) = items {
+            ann = ann.with_items(items);
+        }
+        if let Some(category) = category {
+            ann = ann.with_category(category);
+        }
+        for (keyword, value) in extensions {
+            ann = ann.with_extension(keyword, value.clone());
+        }
+        annotations.insert(ann.with_secret(secret));
+    }
+
+    // Record extension keywords turned off here, such as `x-annotate`
+    for (key, _) in obj.iter().filter(|(key, v)| key.starts_with("x-") && **v == Value::Bool(false)) {
+        annotations.record_disabled(&path, key);
+    }
+
+    // Record which keys the object allows, for spotting unknown keys
+    if let Some(props) = obj.get("properties").and_then(|v| v.as_object()) {
+        annotations.record_properties(&path, props.keys());
+    }
+    let additional = obj.get("additionalProperties");
+    if additional.is_some_and(|v| v.is_object() || v == &Value::Bool(true))
+        || obj.get("patternProperties").is_some_and(Value::is_object)
+    {
+        annotations.record_open(&path);
     }
 
     // Recurse into properties
     if let Some(props) = obj.get("properties").and_then(|v| v.as_object()) {
         for (key, val) in props {
             current_path.push(key.clone());
-            walk_schema(val, current_path, annotations);
+            walk_schema(val, refs, current_path, annotations);
             current_path.pop();
         }
     }
 
     // Handle array items (annotation applies to the array key itself)
     if let Some(items) = obj.get("items") {
-        walk_schema(items, current_path, annotations);
+        walk_schema(items, refs, current_path, annotations);
     }
 
-    // Handle additionalProperties if it's a schema object
-    if let Some(additional) = obj.get("additionalProperties") {
-        if additional.is_object() {
-            walk_schema(additional, current_path, annotations);
+    // Handle additionalProperties if it's a schema object. It describes the
+    // map's entries, so its annotation only stands in for the map's own
+    if let Some(additional) = additional.filter(|v| v.is_object()) {
+        let own = annotations.get(&path).cloned();
+        walk_schema(additional, refs, current_path, annotations);
+        if let Some(own) = own {
+            annotations.insert(own);
+        }
+        let key = entry_key(obj, refs);
+        let value = crate::generate::sample_value(additional, refs);
+        if let Some(annotation) = annotations.get_mut(&path) {
+            annotation.example_entry = Some((key, value));
         }
     }
 
@@ -88,12 +173,83 @@ fn walk_schema(value: &Value, current_path: &mut Vec<String>, annotations: &mut
     for keyword in ["oneOf", "allOf", "anyOf"] {
         if let Some(schemas) = obj.get(keyword).and_then(|v| v.as_array()) {
             for schema in schemas {
-                walk_schema(schema, current_path, annotations);
+                walk_schema(schema, refs, current_path, annotations);
             }
         }
     }
 }
 
+/// The key of a map's example entry: the first example or allowed value of
+/// its `propertyNames`, or else `name`
+fn entry_key<'a>(obj: &'a serde_json::Map<String, Value>, refs: &mut Refs<'a>) -> String {
+    let key = obj.get("propertyNames").and_then(|names| {
+        refs.visit(names, |_, names| {
+            names
+                .get("examples")
+                .or_else(|| names.get("enum"))
+                .and_then(|v| v.as_array()?.first())
+                .or_else(|| names.get("const"))
+                .and_then(Value::as_str)
+        })
+    });
+    key.unwrap_or("name").to_string()
+}
+
+/// Describe the items of an array of scalars from their schema, e.g. `List
+/// of integers, each between 1 and 65535`
+///
+/// Arrays of objects and arrays have their items annotated by path instead,
+/// and items with neither a type nor constraints have nothing to describe.
+fn scalar_items(items: &Value) -> Option<String> {
+    let types = schema_types(items);
+    if !items.is_object()
+        || items.get("properties").is_some()
+        || types.iter().any(|name| matches!(*name, "object" | "array"))
+    {
+        return None;
+    }
+
+    let each = if let Some(value) = items.get("const") {
+        Some(format!("each {}", format_default_value(value)))
+    } else if let Some(values) = items.get("enum").and_then(Value::as_array) {
+        let values: Vec<String> = values.iter().map(format_default_value).collect();
+        Some(format!("each one of: {}", values.join(", ")))
+    } else {
+        let constraints = crate::validate::constraints(items);
+        (!constraints.is_empty()).then(|| format!("each {}", constraints.join(", ")))
+    };
+    if types.is_empty() && each.is_none() {
+        return None;
+    }
+
+    let noun = if types.is_empty() {
+        "values".to_string()
+    } else {
+        types.iter().map(|name| format!("{}s", name)).collect::<Vec<_>>().join(" or ")
+    };
+    Some(match each {
+        Some(each) => format!("List of {}, {}", noun, each),
+        None => format!("List of {}", noun),
+    })
+}
+
+/// The type names of a schema's `type`, a single name or a list of them,
+/// none for an untyped schema
+pub(crate) fn schema_types(schema: &Value) -> Vec<&str> {
+    match schema.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether a schema describes a secret: `writeOnly`, of `format: password`
+/// or marked `x-secret`
+pub(crate) fn is_secret(obj: &serde_json::Map<String, Value>) -> bool {
+    let flag = |keyword: &str| obj.get(keyword) == Some(&Value::Bool(true));
+    flag("writeOnly") || flag("x-secret") || obj.get("format").and_then(Value::as_str) == Some("password")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,8 +269,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         assert_eq!(annotations.len(), 2);
 
@@ -147,8 +302,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         assert_eq!(annotations.len(), 3);
 
@@ -183,8 +337,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         // Both home and work should have annotations from the Address $def
         let home = annotations.get("home").unwrap();
@@ -197,6 +350,35 @@ mod tests {
         assert_eq!(work.title, Some("Address".to_string()));
     }
 
+    #[test]
+    fn test_extract_nested_and_recursive_refs() {
+        let schema_json = json!({
+            "$defs": {
+                "City": { "title": "City" },
+                "Address": {
+                    "title": "Address",
+                    "properties": {
+                        "city": {"$ref": "#/$defs/City"},
+                        "previous": {"$ref": "#/$defs/Address"}
+                    }
+                }
+            },
+            "properties": {
+                "home": {"$ref": "#/$defs/Address"}
+            }
+        });
+
+        let annotations = extract_annotations(&schema_json);
+
+        // References inside a definition are followed too
+        let city = annotations.get("home.city").unwrap();
+        assert_eq!(city.title, Some("City".to_string()));
+
+        // A definition referring to itself is only expanded once
+        assert!(annotations.get("home.previous").is_none());
+        assert_eq!(annotations.len(), 2);
+    }
+
     #[test]
     fn test_extract_root_annotation() {
         let schema_json = json!({
@@ -209,8 +391,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         // Root level annotation has empty path
         let root = annotations.get("").unwrap();
@@ -221,6 +402,28 @@ mod tests {
         assert_eq!(debug.title, Some("Debug Mode".to_string()));
     }
 
+    #[test]
+    fn test_extract_disabled_keywords() {
+        let schema_json = json!({
+            "properties": {
+                "internal": {
+                    "x-annotate": false,
+                    "x-secret": true,
+                    "properties": {"token": {"title": "Token"}}
+                },
+                "name": {"title": "Name", "readOnly": false}
+            }
+        });
+
+        let annotations = extract_annotations(&schema_json);
+
+        assert!(annotations.is_disabled("internal", "x-annotate"));
+        assert!(annotations.is_disabled("internal.token", "x-annotate"));
+        assert!(!annotations.is_disabled("internal", "x-secret"));
+        assert!(!annotations.is_disabled("name", "readOnly"));
+        assert!(!annotations.is_disabled("name", "x-annotate"));
+    }
+
     #[test]
     fn test_extract_no_annotations() {
         let schema_json = json!({
@@ -230,8 +433,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         assert!(annotations.is_empty());
     }
@@ -254,8 +456,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         let users = annotations.get("users").unwrap();
         assert_eq!(users.title, Some("Users".to_string()));
@@ -265,6 +466,34 @@ mod tests {
         assert_eq!(user_name.title, Some("User Name".to_string()));
     }
 
+    #[test]
+    fn test_extract_scalar_array_items() {
+        let schema_json = json!({
+            "$defs": {"Port": {"type": "integer", "minimum": 1, "maximum": 65535}},
+            "properties": {
+                "levels": {"type": "array", "items": {"type": "string", "enum": ["a", "b"]}},
+                "ports": {"type": "array", "items": {"$ref": "#/$defs/Port"}},
+                "tags": {"title": "Tags", "items": {"type": "string", "maxLength": 8, "pattern": "^[a-z]+$"}},
+                "ids": {"items": {"type": ["integer", "string"]}},
+                "users": {"items": {"type": "object", "properties": {"name": {"type": "string"}}}},
+                "any": {"items": {}}
+            }
+        });
+
+        let annotations = extract_annotations(&schema_json);
+        let items = |path: &str| annotations.get(path).and_then(|a| a.items.clone());
+
+        assert_eq!(items("levels").as_deref(), Some(r#"List of strings, each one of: "a", "b""#));
+        assert_eq!(items("ports").as_deref(), Some("List of integers, each between 1 and 65535"));
+        assert_eq!(
+            items("tags").as_deref(),
+            Some("List of strings, each of at most 8 characters, matching `^[a-z]+$`")
+        );
+        assert_eq!(items("ids").as_deref(), Some("List of integers or strings"));
+        assert_eq!(items("users"), None);
+        assert_eq!(items("any"), None);
+    }
+
     #[test]
     fn test_extract_oneof() {
         let schema_json = json!({
@@ -292,8 +521,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         let value = annotations.get("value").unwrap();
         assert_eq!(value.title, Some("Value".to_string()));
@@ -327,8 +555,7 @@ mod tests {
             ]
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         let base = annotations.get("base").unwrap();
         assert_eq!(base.title, Some("Base Property".to_string()));
@@ -364,8 +591,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         let config = annotations.get("config").unwrap();
         assert_eq!(config.title, Some("Config".to_string()));
@@ -402,8 +628,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         let port = annotations.get("port").unwrap();
         assert_eq!(port.title, Some("Port".to_string()));
@@ -430,12 +655,83 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let annotations = extract_annotations(&schema);
+        let annotations = extract_annotations(&schema_json);
 
         let timeout = annotations.get("timeout").unwrap();
         assert_eq!(timeout.title, None);
         assert_eq!(timeout.description, None);
         assert_eq!(timeout.default, Some("30".to_string()));
     }
+
+    #[test]
+    fn test_extract_examples() {
+        let schema_json = json!({
+            "properties": {
+                "host": {
+                    "examples": ["localhost", "0.0.0.0"]
+                },
+                "port": {
+                    "title": "Port",
+                    "examples": []
+                }
+            }
+        });
+
+        let annotations = extract_annotations(&schema_json);
+
+        let host = annotations.get("host").unwrap();
+        assert_eq!(host.examples, vec![json!("localhost"), json!("0.0.0.0")]);
+        assert!(annotations.get("port").unwrap().examples.is_empty());
+    }
+
+    #[test]
+    fn test_extract_content_hints() {
+        let schema_json = json!({
+            "properties": {
+                "cert": {
+                    "type": "string",
+                    "contentMediaType": "application/x-pem-file",
+                    "contentEncoding": "base64"
+                }
+            }
+        });
+
+        let annotations = extract_annotations(&schema_json);
+
+        let cert = annotations.get("cert").unwrap();
+        assert_eq!(cert.content_media_type.as_deref(), Some("application/x-pem-file"));
+        assert_eq!(cert.content_encoding.as_deref(), Some("base64"));
+    }
+
+    #[test]
+    fn test_extract_additional_properties() {
+        let schema_json = json!({
+            "$defs": {
+                "Level": { "type": "string", "description": "Log level", "examples": ["debug"] }
+            },
+            "properties": {
+                "env": {
+                    "title": "Environment",
+                    "additionalProperties": { "type": "string", "description": "Value of the variable" }
+                },
+                "levels": {
+                    "propertyNames": { "examples": ["my_crate"] },
+                    "additionalProperties": { "$ref": "#/$defs/Level" }
+                }
+            }
+        });
+
+        let annotations = extract_annotations(&schema_json);
+
+        // The map's own annotation wins over the one of its entries
+        let env = annotations.get("env").unwrap();
+        assert_eq!(env.title.as_deref(), Some("Environment"));
+        assert_eq!(env.description, None);
+        assert_eq!(env.example_entry, Some(("name".to_string(), json!(""))));
+
+        // Without one, the entries' annotation stands in for it
+        let levels = annotations.get("levels").unwrap();
+        assert_eq!(levels.description.as_deref(), Some("Log level"));
+        assert_eq!(levels.example_entry, Some(("my_crate".to_string(), json!("debug"))));
+    }
 }
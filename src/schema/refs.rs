@@ -1,42 +1,88 @@
-use schemars::Schema;
+use std::borrow::Cow;
+
 use serde_json::Value;
 
-/// Resolve all local $ref pointers in a Schema
+/// Looks through local `$ref` pointers while walking a schema
 ///
-/// This only handles local references starting with "#" (e.g., "#/$defs/Address").
-/// External file or URL references are not supported.
-pub fn resolve_refs(schema: &Schema) -> Schema {
-    let value = schema.as_value().clone();
-    let resolved = resolve_refs_value(value, schema);
-    // The resolved value should always be an object (from a valid schema)
-    resolved.try_into().unwrap_or_else(|_| schema.clone())
+/// References are followed where they're encountered instead of being
+/// substituted into a copy of the schema, so definitions reused many times
+/// are never copied. Only local references starting with "#" (e.g.,
+/// "#/$defs/Address") are followed; external file or URL references are not
+/// supported.
+pub(crate) struct Refs<'a> {
+    root: &'a Value,
+    /// References currently being followed, innermost last
+    expanding: Vec<&'a str>,
 }
 
-fn resolve_refs_value(mut value: Value, root: &Schema) -> Value {
-    match &mut value {
-        Value::Object(map) => {
-            if let Some(Value::String(ref_path)) = map.get("$ref") {
-                // Only handle local references starting with #
-                if ref_path.starts_with('#') {
-                    // Use schemars' built-in pointer method (handles percent-decoding)
-                    if let Some(resolved) = root.pointer(ref_path) {
-                        return resolved.clone();
-                    }
-                }
-            }
-            // Recurse into all values
-            for v in map.values_mut() {
-                *v = resolve_refs_value(v.clone(), root);
-            }
+impl<'a> Refs<'a> {
+    pub fn new(root: &'a Value) -> Self {
+        Self {
+            root,
+            expanding: Vec::new(),
+        }
+    }
+
+    /// Call `f` with the schema `value` refers to, or `value` itself if it
+    /// isn't a resolvable reference
+    ///
+    /// A reference back into a definition that's already being followed is
+    /// left alone, so walks over recursive schemas terminate.
+    pub fn visit<R>(&mut self, value: &'a Value, f: impl FnOnce(&mut Self, &'a Value) -> R) -> R {
+        let Some((ref_path, target)) = self.target(value) else {
+            return f(self, value);
+        };
+        tracing::trace!(ref_path, "resolved $ref");
+        self.expanding.push(ref_path);
+        // The target may itself be a reference
+        let result = self.visit(target, f);
+        self.expanding.pop();
+        result
+    }
+
+    fn target(&self, value: &'a Value) -> Option<(&'a str, &'a Value)> {
+        let ref_path = value.get("$ref")?.as_str()?;
+        // Only handle local references starting with #
+        let Some(fragment) = ref_path.strip_prefix('#') else {
+            tracing::debug!(ref_path, "skipping non-local $ref");
+            return None;
+        };
+        if self.expanding.contains(&ref_path) {
+            tracing::debug!(ref_path, "skipping recursive $ref");
+            return None;
         }
-        Value::Array(arr) => {
-            for item in arr.iter_mut() {
-                *item = resolve_refs_value(item.clone(), root);
+        let Some(target) = percent_decode(fragment).and_then(|p| self.root.pointer(&p)) else {
+            tracing::debug!(ref_path, "unresolvable $ref");
+            return None;
+        };
+        Some((ref_path, target))
+    }
+}
+
+/// Percent-decode a URI fragment into a JSON Pointer
+///
+/// Returns `None` if the result isn't valid UTF-8. A `%` not followed by two
+/// hex digits is kept as is.
+pub(crate) fn percent_decode(fragment: &str) -> Option<Cow<'_, str>> {
+    if !fragment.contains('%') {
+        return Some(Cow::Borrowed(fragment));
+    }
+    let mut bytes = Vec::with_capacity(fragment.len());
+    let mut segments = fragment.split('%');
+    bytes.extend(segments.next().unwrap_or_default().as_bytes());
+    for segment in segments {
+        match segment.get(0..2).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                bytes.push(byte);
+                bytes.extend(&segment.as_bytes()[2..]);
+            }
+            None => {
+                bytes.push(b'%');
+                bytes.extend(segment.as_bytes());
             }
         }
-        _ => {}
     }
-    value
+    String::from_utf8(bytes).ok().map(Cow::Owned)
 }
 
 #[cfg(test)]
@@ -44,6 +90,11 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    /// Follow the value at `pointer` if it's a reference
+    fn resolve<'a>(root: &'a Value, pointer: &str) -> &'a Value {
+        Refs::new(root).visit(root.pointer(pointer).unwrap(), |_, value| value)
+    }
+
     #[test]
     fn test_resolve_refs_simple() {
         let schema_json = json!({
@@ -60,16 +111,12 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let resolved = resolve_refs(&schema);
-        let value = resolved.as_value();
-
         // The $ref should be replaced with the actual definition
-        let first_name = &value["properties"]["firstName"];
+        let first_name = resolve(&schema_json, "/properties/firstName");
         assert_eq!(first_name["type"], "string");
         assert_eq!(first_name["title"], "Name");
 
-        let last_name = &value["properties"]["lastName"];
+        let last_name = resolve(&schema_json, "/properties/lastName");
         assert_eq!(last_name["type"], "string");
         assert_eq!(last_name["title"], "Name");
     }
@@ -92,11 +139,7 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let resolved = resolve_refs(&schema);
-        let value = resolved.as_value();
-
-        let home = &value["properties"]["home"];
+        let home = resolve(&schema_json, "/properties/home");
         assert_eq!(home["type"], "object");
         assert_eq!(home["title"], "Address");
         assert_eq!(home["properties"]["city"]["type"], "string");
@@ -111,13 +154,9 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let resolved = resolve_refs(&schema);
-        let value = resolved.as_value();
-
         // External $ref should remain unchanged
         assert_eq!(
-            value["properties"]["external"]["$ref"],
+            resolve(&schema_json, "/properties/external")["$ref"],
             "http://example.com/schema.json"
         );
     }
@@ -131,14 +170,55 @@ mod tests {
             }
         });
 
-        let schema: Schema = schema_json.try_into().unwrap();
-        let resolved = resolve_refs(&schema);
-        let value = resolved.as_value();
-
         // Unresolvable $ref should remain unchanged
         assert_eq!(
-            value["properties"]["missing"]["$ref"],
+            resolve(&schema_json, "/properties/missing")["$ref"],
             "#/$defs/DoesNotExist"
         );
     }
+
+    #[test]
+    fn test_resolve_refs_percent_encoded() {
+        let schema_json = json!({
+            "$defs": { "Port number": { "title": "Port" } },
+            "properties": { "port": {"$ref": "#/$defs/Port%20number"} }
+        });
+
+        assert_eq!(resolve(&schema_json, "/properties/port")["title"], "Port");
+    }
+
+    #[test]
+    fn test_resolve_refs_chained() {
+        let schema_json = json!({
+            "$defs": {
+                "Port": { "$ref": "#/$defs/Number" },
+                "Number": { "title": "Number" }
+            },
+            "properties": { "port": {"$ref": "#/$defs/Port"} }
+        });
+
+        assert_eq!(resolve(&schema_json, "/properties/port")["title"], "Number");
+    }
+
+    #[test]
+    fn test_resolve_refs_recursive() {
+        let schema_json = json!({
+            "$defs": {
+                "Node": {
+                    "title": "Node",
+                    "properties": { "child": {"$ref": "#/$defs/Node"} }
+                }
+            },
+            "properties": { "root": {"$ref": "#/$defs/Node"} }
+        });
+
+        let mut refs = Refs::new(&schema_json);
+        let root = schema_json.pointer("/properties/root").unwrap();
+        let child = refs.visit(root, |refs, node| {
+            assert_eq!(node["title"], "Node");
+            refs.visit(&node["properties"]["child"], |_, child| child)
+        });
+        // The reference back into Node isn't followed again
+        assert_eq!(child["$ref"], "#/$defs/Node");
+    }
 }
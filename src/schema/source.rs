@@ -0,0 +1,23 @@
+use serde_json::Value;
+
+/// A JSON Schema document to take annotations from
+///
+/// Implemented for [`serde_json::Value`] and, with the `schemars` feature
+/// (enabled by default), for [`schemars::Schema`].
+pub trait SchemaSource {
+    /// The schema as a JSON value
+    fn as_json(&self) -> &Value;
+}
+
+impl SchemaSource for Value {
+    fn as_json(&self) -> &Value {
+        self
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl SchemaSource for schemars::Schema {
+    fn as_json(&self) -> &Value {
+        self.as_value()
+    }
+}
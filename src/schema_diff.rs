@@ -0,0 +1,240 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::schema::{extract_annotations, Annotation, AnnotationMap, SchemaSource};
+
+/// A difference between two versions of a schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum SchemaChange {
+    /// A property only the new schema defines
+    Added { path: String },
+    /// A property only the old schema defines
+    Removed { path: String },
+    /// A property that moved to another path, recognized by its unchanged
+    /// title, description and default
+    Renamed { from: String, to: String },
+    /// A property whose default changed, or was added or removed
+    DefaultChanged {
+        path: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// A property whose description changed, or was added or removed
+    DescriptionChanged {
+        path: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+/// Written as a line of migration notes
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added { path } => write!(f, "Added `{}`", path),
+            Self::Removed { path } => write!(f, "Removed `{}`", path),
+            Self::Renamed { from, to } => write!(f, "Renamed `{}` to `{}`", from, to),
+            Self::DefaultChanged { path, old, new } => match (old, new) {
+                (Some(old), Some(new)) => {
+                    write!(f, "Changed the default of `{}` from {} to {}", path, old, new)
+                }
+                (None, Some(new)) => write!(f, "Added default {} for `{}`", new, path),
+                (Some(old), None) => write!(f, "Removed default {} of `{}`", old, path),
+                (None, None) => write!(f, "Changed the default of `{}`", path),
+            },
+            Self::DescriptionChanged { path, .. } => {
+                write!(f, "Changed the description of `{}`", path)
+            }
+        }
+    }
+}
+
+/// Compare two versions of a schema by the properties they define and the
+/// documentation of each
+///
+/// See [`diff_annotations`] for what is reported.
+///
+/// # Example
+/// ```rust
+/// use jsonschema_annotator::{diff_schemas, SchemaChange};
+///
+/// let old = serde_json::json!({"properties": {"port": {"default": 80}}});
+/// let new = serde_json::json!({"properties": {"port": {"default": 8080}, "host": {}}});
+///
+/// let changes = diff_schemas(&old, &new);
+/// assert_eq!(changes[0], SchemaChange::Added { path: "host".to_string() });
+/// assert_eq!(changes[1].to_string(), "Changed the default of `port` from 80 to 8080");
+/// ```
+pub fn diff_schemas(old: &impl SchemaSource, new: &impl SchemaSource) -> Vec<SchemaChange> {
+    diff_annotations(&extract_annotations(old), &extract_annotations(new))
+}
+
+/// Compare the annotations of two versions of a schema
+///
+/// A property removed from one path and added at another is reported as
+/// renamed when it kept its title, description and default, and the
+/// properties below a renamed object are renamed along with it without
+/// being reported themselves. Changes are listed renames first, then
+/// removals, additions and changed documentation, each sorted by path.
+pub fn diff_annotations(old: &AnnotationMap, new: &AnnotationMap) -> Vec<SchemaChange> {
+    let old_paths = paths(old);
+    let new_paths = paths(new);
+    let removed: Vec<&str> = old_paths.difference(&new_paths).copied().collect();
+    let mut added: Vec<&str> = new_paths.difference(&old_paths).copied().collect();
+
+    // Removed paths are sorted, so objects are matched before their properties
+    let mut renamed: Vec<(&str, String)> = Vec::new();
+    let mut moved: Vec<(&str, String)> = Vec::new();
+    let mut still_removed = Vec::new();
+    for from in removed {
+        let below = renamed.iter().find_map(|(parent, to)| {
+            let rest = from.strip_prefix(parent)?.strip_prefix('.')?;
+            Some(format!("{}.{}", to, rest))
+        });
+        if let Some(i) = below.and_then(|to| added.iter().position(|path| *path == to)) {
+            moved.push((from, added.remove(i).to_string()));
+            continue;
+        }
+        let same = |to: &&str| match (old.get(from), new.get(to)) {
+            (Some(old), Some(new)) => same_documentation(old, new),
+            _ => false,
+        };
+        match added.iter().position(same) {
+            Some(i) => renamed.push((from, added.remove(i).to_string())),
+            None => still_removed.push(from),
+        }
+    }
+
+    let mut changes: Vec<SchemaChange> = renamed
+        .iter()
+        .map(|(from, to)| SchemaChange::Renamed {
+            from: from.to_string(),
+            to: to.clone(),
+        })
+        .collect();
+    changes.extend(still_removed.iter().map(|path| SchemaChange::Removed { path: path.to_string() }));
+    changes.extend(added.iter().map(|path| SchemaChange::Added { path: path.to_string() }));
+
+    // Documentation is compared at the new path of renamed properties
+    let mut kept: Vec<(&str, &str)> = old_paths
+        .intersection(&new_paths)
+        .map(|path| (*path, *path))
+        .collect();
+    kept.extend(renamed.iter().chain(&moved).map(|(from, to)| (*from, to.as_str())));
+    kept.sort_by_key(|(_, to)| *to);
+    let documentation = |map: &AnnotationMap, path: &str| {
+        let annotation = map.get(path);
+        (
+            annotation.and_then(|a| a.default.clone()),
+            annotation.and_then(|a| a.description.clone()),
+        )
+    };
+    let mut description_changes = Vec::new();
+    for (from, to) in kept {
+        let (old_default, old_description) = documentation(old, from);
+        let (new_default, new_description) = documentation(new, to);
+        if old_default != new_default {
+            changes.push(SchemaChange::DefaultChanged {
+                path: to.to_string(),
+                old: old_default,
+                new: new_default,
+            });
+        }
+        if old_description != new_description {
+            description_changes.push(SchemaChange::DescriptionChanged {
+                path: to.to_string(),
+                old: old_description,
+                new: new_description,
+            });
+        }
+    }
+    changes.extend(description_changes);
+    changes
+}
+
+/// Paths of the properties a schema defines or documents, without the root
+fn paths(annotations: &AnnotationMap) -> BTreeSet<&str> {
+    annotations
+        .properties()
+        .chain(annotations.iter().map(|(path, _)| path))
+        .map(String::as_str)
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+/// Whether two annotations document the same property, ignoring ones with
+/// nothing to recognize them by
+fn same_documentation(old: &Annotation, new: &Annotation) -> bool {
+    let documented = old.title.is_some() || old.description.is_some() || old.default.is_some();
+    documented
+        && old.title == new.title
+        && old.description == new.description
+        && old.default == new.default
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_schemas() {
+        let old = json!({
+            "properties": {
+                "ssl": {
+                    "description": "TLS settings",
+                    "properties": {
+                        "cert": {"description": "Certificate path", "default": "cert.pem"},
+                        "verify": {}
+                    }
+                },
+                "port": {"description": "Port", "default": 80},
+                "legacy": {"description": "No longer used"},
+                "host": {}
+            }
+        });
+        let new = json!({
+            "properties": {
+                "tls": {
+                    "description": "TLS settings",
+                    "properties": {
+                        "cert": {"description": "Certificate path", "default": "tls.pem"},
+                        "verify": {}
+                    }
+                },
+                "port": {"description": "Port to listen on", "default": 8080},
+                "host": {},
+                "workers": {"default": 4}
+            }
+        });
+
+        let notes: Vec<String> = diff_schemas(&old, &new).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            notes,
+            vec![
+                "Renamed `ssl` to `tls`",
+                "Removed `legacy`",
+                "Added `workers`",
+                "Changed the default of `port` from 80 to 8080",
+                "Changed the default of `tls.cert` from \"cert.pem\" to \"tls.pem\"",
+                "Changed the description of `port`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undocumented_properties_are_not_renamed() {
+        let old = json!({"properties": {"a": {}}});
+        let new = json!({"properties": {"b": {}}});
+        assert_eq!(
+            diff_schemas(&old, &new),
+            vec![
+                SchemaChange::Removed { path: "a".to_string() },
+                SchemaChange::Added { path: "b".to_string() },
+            ]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use crate::annotator::AnnotatorConfig;
+use crate::drift::render_comments;
+use crate::error::AnnotatorError;
+use crate::format::TargetFormat;
+use crate::schema::{extract_annotations, AnnotationMap, SchemaSource};
+
+/// Remove the comments annotating would add from a document
+///
+/// For each documented key, the lines `config` would render for it are
+/// removed from the block of comments directly above it, along with the
+/// marker line of [`ExistingCommentBehavior::Update`] blocks, so stripping
+/// with the options a document was annotated with undoes the annotation.
+/// Hand-written comments, and generated ones that were edited since or come
+/// from different options, are kept.
+///
+/// [`ExistingCommentBehavior::Update`]: crate::ExistingCommentBehavior::Update
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "toml")] {
+/// use jsonschema_annotator::{strip_annotations, AnnotatorConfig, TargetFormat};
+///
+/// let schema = serde_json::json!({"properties": {"port": {"title": "Port"}}});
+/// let stripped = strip_annotations(
+///     &schema,
+///     "# Port\n# Keep this note\nport = 8080\n",
+///     TargetFormat::Toml,
+///     AnnotatorConfig::default(),
+/// )
+/// .unwrap();
+/// assert_eq!(stripped, "# Keep this note\nport = 8080\n");
+/// # }
+/// ```
+pub fn strip_annotations(
+    schema: &impl SchemaSource,
+    target: &str,
+    target_format: TargetFormat,
+    config: AnnotatorConfig,
+) -> Result<String, AnnotatorError> {
+    strip_extracted(&extract_annotations(schema), target, target_format, config)
+}
+
+/// Remove the comments annotating would add, with previously extracted
+/// annotations
+///
+/// Like [`strip_annotations`], but takes the result of
+/// [`extract_annotations`] so a schema shared by many documents only has to
+/// be processed once.
+pub fn strip_extracted(
+    annotations: &AnnotationMap,
+    target: &str,
+    target_format: TargetFormat,
+    mut config: AnnotatorConfig,
+) -> Result<String, AnnotatorError> {
+    let marker = format!("# {}", config.generated_marker);
+    // Only the comments of the schema's annotations are looked for
+    config.warn_unknown_keys = false;
+    config.redundant_defaults = Default::default();
+    let rendered = render_comments(annotations, target, target_format, config)?;
+
+    let lines: Vec<&str> = target.split_inclusive('\n').collect();
+    let mut removed = vec![false; lines.len()];
+    for comment in rendered {
+        let Some(key) = comment.line.map(|line| line - 1).filter(|&key| key < lines.len()) else {
+            continue;
+        };
+        let start = lines[..key]
+            .iter()
+            .rposition(|line| !line.trim_start().starts_with('#'))
+            .map_or(0, |pos| pos + 1);
+        let block: Vec<&str> = lines[start..key].iter().map(|line| line.trim()).collect();
+        let run = comment.lines.len();
+        let Some(pos) = (run > 0 && run <= block.len())
+            .then(|| block.windows(run).rposition(|window| window == comment.lines))
+            .flatten()
+        else {
+            continue;
+        };
+        let first = match pos.checked_sub(1) {
+            Some(above) if block[above] == marker => start + above,
+            _ => start + pos,
+        };
+        removed[first..start + pos + run].fill(true);
+    }
+
+    Ok(lines
+        .iter()
+        .zip(removed)
+        .filter(|(_, removed)| !removed)
+        .map(|(line, _)| *line)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "yaml")]
+    use crate::annotator::ExistingCommentBehavior;
+
+    fn schema() -> serde_json::Value {
+        serde_json::json!({
+            "properties": {
+                "server": {
+                    "title": "Server",
+                    "properties": {
+                        "port": { "title": "Port", "description": "Port to listen on" },
+                        "host": { "title": "Host" }
+                    }
+                }
+            }
+        })
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_strip_toml() {
+        let content = "[server]\n# Hand-written\nport = 8080\nhost = \"::\"\n";
+        let annotated = crate::annotate(&schema(), content, TargetFormat::Toml, AnnotatorConfig::default()).unwrap();
+        assert_ne!(annotated, content);
+
+        let stripped = strip_annotations(&schema(), &annotated, TargetFormat::Toml, AnnotatorConfig::default());
+        assert_eq!(stripped.unwrap(), content);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_strip_yaml() {
+        let content = "server:\n  # Hand-written\n  port: 8080\n  host: \"::\"\n";
+        for existing_comments in [ExistingCommentBehavior::Append, ExistingCommentBehavior::Update] {
+            let config = || AnnotatorConfig {
+                existing_comments,
+                ..Default::default()
+            };
+            let annotated = crate::annotate(&schema(), content, TargetFormat::Yaml, config()).unwrap();
+            assert_ne!(annotated, content);
+
+            let stripped = strip_annotations(&schema(), &annotated, TargetFormat::Yaml, config());
+            assert_eq!(stripped.unwrap(), content);
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_strip_keeps_edited_comments() {
+        let content = "server:\n  # Port\n  # Port to listen on, unless behind a proxy\n  port: 8080\n";
+        let stripped = strip_annotations(&schema(), content, TargetFormat::Yaml, AnnotatorConfig::default());
+        assert_eq!(stripped.unwrap(), content);
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::annotator::{AnnotatorConfig, CommentContext, CommentFormatter};
+#[cfg(feature = "toml")]
+use crate::error::toml_parse_error;
+#[cfg(feature = "yaml")]
+use crate::error::yaml_parse_error;
+use crate::error::{AnnotatorError, AnnotatorErrorKind, Error};
+use crate::format::TargetFormat;
+use crate::schema::{extract_annotations, schema_types, Annotation, SchemaSource};
+
+/// A schema violation found in a target document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationIssue {
+    /// Dot-separated path of the offending field (e.g., "server.port"),
+    /// empty for the document root
+    pub path: String,
+    /// JSON Pointer to the offending value (e.g., "/server/port")
+    pub pointer: String,
+    /// Description of the violation
+    pub message: String,
+    /// Schema keyword the value violates (e.g., "maximum")
+    pub keyword: String,
+    /// One-based line of the offending key, or of its nearest ancestor in the
+    /// document, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+/// Validate a target document against a schema
+///
+/// Returns every violation found; an empty list means the document is valid.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "toml")] {
+/// use jsonschema_annotator::{validate, TargetFormat};
+///
+/// let schema: serde_json::Value = serde_json::from_str(
+///     r#"{"properties": {"port": {"type": "integer"}}}"#,
+/// ).unwrap();
+///
+/// let issues = validate(&schema, "port = \"80\"", TargetFormat::Toml).unwrap();
+/// assert_eq!(issues[0].path, "port");
+/// # }
+/// ```
+pub fn validate(
+    schema: &impl SchemaSource,
+    target: &str,
+    target_format: TargetFormat,
+) -> Result<Vec<ValidationIssue>, AnnotatorError> {
+    let validator = jsonschema::validator_for(schema.as_json()).map_err(|e| {
+        Error::new(AnnotatorErrorKind::InvalidSchema).add_context(e.to_string())
+    })?;
+    let instance = parse_target(target, target_format)?;
+
+    let mut issues: Vec<ValidationIssue> = validator
+        .iter_errors(&instance)
+        .map(|error| {
+            let pointer = error.instance_path().as_str().to_string();
+            ValidationIssue {
+                path: pointer_to_path(&pointer),
+                pointer,
+                message: error.to_string(),
+                keyword: error.kind().keyword().to_string(),
+                line: None,
+            }
+        })
+        .collect();
+    if !issues.is_empty() {
+        let lines = key_line_map(target, target_format);
+        for issue in &mut issues {
+            let mut path = issue.path.as_str();
+            while !path.is_empty() && issue.line.is_none() {
+                issue.line = lines.get(path).copied();
+                path = path.rsplit_once('.').map_or("", |(parent, _)| parent);
+            }
+        }
+    }
+    Ok(issues)
+}
+
+/// Annotate a target document and embed its schema violations as comments
+///
+/// Each violation is rendered as an `# INVALID: …` comment above the
+/// offending key, after its regular annotation, producing a review copy of
+/// a broken document. A value breaking a constraint of its own schema (its
+/// type, range, length, pattern or allowed values) is described by what the
+/// schema expects, such as `expected integer between 1 and 65535`, and
+/// other violations by the validator's message. Violations at the document
+/// root have no key to attach to and are only returned.
+pub fn annotate_with_validation(
+    schema: &impl SchemaSource,
+    target: &str,
+    target_format: TargetFormat,
+    mut config: AnnotatorConfig,
+) -> Result<(String, Vec<ValidationIssue>), AnnotatorError> {
+    let issues = validate(schema, target, target_format)?;
+    let mut annotations = extract_annotations(schema);
+
+    let mut errors: HashMap<String, Vec<String>> = HashMap::new();
+    for issue in issues.iter().filter(|issue| !issue.path.is_empty()) {
+        let expected = VALUE_KEYWORDS
+            .contains(&issue.keyword.as_str())
+            .then(|| value_schema(schema.as_json(), &issue.pointer))
+            .flatten()
+            .and_then(|value_schema| expectation(value_schema, target_format));
+        let message = expected.unwrap_or_else(|| issue.message.clone());
+        let messages = errors.entry(issue.path.clone()).or_default();
+        if !messages.contains(&message) {
+            messages.push(message);
+        }
+        // Fields without an annotation still need an entry to carry the error
+        if annotations.get(&issue.path).is_none() {
+            annotations.insert_unchecked(Annotation::new(issue.path.clone()));
+        }
+    }
+    config.formatter = Arc::new(ValidationCommentFormatter {
+        inner: config.formatter.clone(),
+        errors,
+    });
+
+    let (annotated, _) = crate::annotate_extracted(&annotations, target, target_format, config)?;
+    Ok((annotated, issues))
+}
+
+/// Appends validation errors to the comments of another formatter
+#[derive(Debug)]
+struct ValidationCommentFormatter {
+    inner: Arc<dyn CommentFormatter>,
+    errors: HashMap<String, Vec<String>>,
+}
+
+impl CommentFormatter for ValidationCommentFormatter {
+    fn format(&self, annotation: &Annotation, context: &CommentContext<'_>) -> Vec<String> {
+        let mut lines = self.inner.format(annotation, context);
+        if let Some(errors) = self.errors.get(context.path) {
+            lines.extend(errors.iter().map(|e| format!("# INVALID: {}", e)));
+        }
+        lines
+    }
+}
+
+/// The line of each key of a document, or none if they can't be found
+pub(crate) fn key_line_map(target: &str, target_format: TargetFormat) -> HashMap<String, usize> {
+    let lines: Result<HashMap<String, usize>, AnnotatorError> = match target_format {
+        #[cfg(feature = "toml")]
+        TargetFormat::Toml => crate::annotator::toml_key_line_map(target),
+        #[cfg(feature = "yaml")]
+        TargetFormat::Yaml => crate::annotator::yaml_key_line_map(target),
+        _ => Ok(HashMap::new()),
+    };
+    lines.unwrap_or_default()
+}
+
+/// Parse a target document into a JSON value for validation
+pub(crate) fn parse_target(target: &str, target_format: TargetFormat) -> Result<Value, AnnotatorError> {
+    match target_format {
+        #[cfg(feature = "toml")]
+        TargetFormat::Toml => {
+            let doc: toml_edit::DocumentMut = target
+                .parse()
+                .map_err(|e| toml_parse_error(target, e))?;
+            Ok(toml_table_to_json(doc.as_table()))
+        }
+        #[cfg(feature = "yaml")]
+        TargetFormat::Yaml => {
+            let value: Option<Value> =
+                serde_yaml::from_str(target).map_err(|e| yaml_parse_error(target, e))?;
+            // An empty document is an empty mapping rather than null
+            Ok(value.unwrap_or_else(|| Value::Object(serde_json::Map::new())))
+        }
+        TargetFormat::Custom(name) => Err(Error::new(AnnotatorErrorKind::UnsupportedFormat)
+            .add_context(format!("parsing {} documents isn't supported", name))),
+        #[cfg(not(all(feature = "toml", feature = "yaml")))]
+        format => Err(crate::format::feature_disabled(format)),
+    }
+}
+
+/// Convert a TOML table to JSON, leaving out the formatting
+#[cfg(feature = "toml")]
+pub(crate) fn toml_table_to_json(table: &dyn toml_edit::TableLike) -> Value {
+    let map = table
+        .iter()
+        .filter_map(|(key, item)| Some((key.to_string(), toml_item_to_json(item)?)))
+        .collect();
+    Value::Object(map)
+}
+
+#[cfg(feature = "toml")]
+fn toml_item_to_json(item: &toml_edit::Item) -> Option<Value> {
+    match item {
+        toml_edit::Item::None => None,
+        toml_edit::Item::Value(value) => Some(toml_value_to_json(value)),
+        toml_edit::Item::Table(table) => Some(toml_table_to_json(table)),
+        toml_edit::Item::ArrayOfTables(array) => Some(Value::Array(
+            array.iter().map(|table| toml_table_to_json(table)).collect(),
+        )),
+    }
+}
+
+#[cfg(feature = "toml")]
+fn toml_value_to_json(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(s) => Value::String(s.value().clone()),
+        toml_edit::Value::Integer(i) => Value::from(*i.value()),
+        toml_edit::Value::Float(f) => Value::from(*f.value()),
+        toml_edit::Value::Boolean(b) => Value::Bool(*b.value()),
+        toml_edit::Value::Datetime(d) => Value::String(d.value().to_string()),
+        toml_edit::Value::Array(array) => Value::Array(array.iter().map(toml_value_to_json).collect()),
+        toml_edit::Value::InlineTable(table) => toml_table_to_json(table),
+    }
+}
+
+/// Keywords constraining a value by itself, whose violations are described
+/// by what the value's schema expects
+const VALUE_KEYWORDS: &[&str] = &[
+    "type",
+    "const",
+    "enum",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "multipleOf",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "format",
+    "minItems",
+    "maxItems",
+];
+
+/// Find the schema of the value at a JSON Pointer into the document
+fn value_schema<'a>(root: &'a Value, pointer: &str) -> Option<&'a Value> {
+    let path = pointer_to_path(pointer);
+    let mut location = match path.as_str() {
+        "" => String::new(),
+        path => crate::enrich::locate(root, path)?,
+    };
+    // Items of arrays at the end of the pointer have the array's `items` schema
+    let indices = pointer
+        .rsplit('/')
+        .take_while(|segment| segment.parse::<usize>().is_ok())
+        .count();
+    for _ in 0..indices {
+        location = crate::enrich::resolve(root, format!("{}/items", location))?;
+    }
+    root.pointer(&location)
+}
+
+/// Describe the values a schema allows, e.g. "expected integer between 1
+/// and 65535", or `None` if it has no constraints to describe
+fn expectation(schema: &Value, format: TargetFormat) -> Option<String> {
+    let literal = |value: &Value| {
+        crate::annotator::render_literal(value, format, None).unwrap_or_else(|| value.to_string())
+    };
+    if let Some(value) = schema.get("const") {
+        return Some(format!("expected {}", literal(value)));
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        let values: Vec<String> = values.iter().map(literal).collect();
+        return Some(format!("expected one of {}", values.join(", ")));
+    }
+
+    let constraints = constraints(schema);
+    let types = schema_types(schema);
+    if types.is_empty() && constraints.is_empty() {
+        return None;
+    }
+    let noun = if types.is_empty() { "value".to_string() } else { types.join(" or ") };
+    let mut description = format!("expected {}", noun);
+    for (i, constraint) in constraints.iter().enumerate() {
+        description.push_str(if i == 0 { " " } else { ", " });
+        description.push_str(constraint);
+    }
+    Some(description)
+}
+
+/// Describe the constraints a schema puts on a value besides its type and
+/// allowed values, e.g. "between 1 and 65535" and "matching `^[a-z]+$`"
+pub(crate) fn constraints(schema: &Value) -> Vec<String> {
+    let number = |key: &str| schema.get(key).filter(|value| value.is_number());
+    let count = |key: &str| schema.get(key).and_then(Value::as_u64);
+    let mut constraints = Vec::new();
+    match (number("minimum"), number("maximum")) {
+        (Some(min), Some(max)) => constraints.push(format!("between {} and {}", min, max)),
+        (min, max) => {
+            let bounds = [
+                min.map(|min| format!("at least {}", min)),
+                number("exclusiveMinimum").map(|min| format!("greater than {}", min)),
+                max.map(|max| format!("at most {}", max)),
+                number("exclusiveMaximum").map(|max| format!("less than {}", max)),
+            ];
+            let bounds: Vec<String> = bounds.into_iter().flatten().collect();
+            if !bounds.is_empty() {
+                constraints.push(bounds.join(" and "));
+            }
+        }
+    }
+    if let Some(step) = number("multipleOf") {
+        constraints.push(format!("a multiple of {}", step));
+    }
+    if let Some(range) = count_range(count("minLength"), count("maxLength"), "character") {
+        constraints.push(format!("of {}", range));
+    }
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        constraints.push(format!("matching `{}`", pattern));
+    }
+    if let Some(value_format) = schema.get("format").and_then(Value::as_str) {
+        constraints.push(format!("in {} format", value_format));
+    }
+    if let Some(range) = count_range(count("minItems"), count("maxItems"), "item") {
+        constraints.push(format!("with {}", range));
+    }
+    constraints
+}
+
+/// Describe a range of counts, e.g. "1 to 64 characters"
+fn count_range(min: Option<u64>, max: Option<u64>, unit: &str) -> Option<String> {
+    let units = |n: u64| if n == 1 { unit.to_string() } else { format!("{}s", unit) };
+    match (min, max) {
+        (Some(min), Some(max)) if min == max => Some(format!("exactly {} {}", min, units(min))),
+        (Some(min), Some(max)) => Some(format!("{} to {} {}", min, max, units(max))),
+        (Some(min), None) => Some(format!("at least {} {}", min, units(min))),
+        (None, Some(max)) => Some(format!("at most {} {}", max, units(max))),
+        (None, None) => None,
+    }
+}
+
+/// Convert a JSON Pointer to the dot-separated path used for annotations
+///
+/// Array indices are dropped, since annotations for array items live on the
+/// array's own key.
+fn pointer_to_path(pointer: &str) -> String {
+    let keys: Vec<&str> = pointer.split('/').filter(|segment| segment.parse::<usize>().is_err()).collect();
+    crate::schema::pointer_to_path(&keys.join("/")).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "yaml")]
+    use insta::assert_snapshot;
+
+    fn schema() -> Value {
+        serde_json::json!({
+            "properties": {
+                "server": {
+                    "title": "Server",
+                    "properties": {
+                        "port": { "title": "Port", "type": "integer", "maximum": 65535 },
+                        "host": { "type": "string" }
+                    }
+                }
+            }
+        })
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_validate_reports_paths() {
+        let toml = "[server]\nport = 70000\nhost = 1\n";
+        let mut issues = validate(&schema(), toml, TargetFormat::Toml).unwrap();
+        issues.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].path, "server.host");
+        assert_eq!(issues[0].pointer, "/server/host");
+        assert_eq!(issues[0].line, Some(3));
+        assert_eq!(issues[1].path, "server.port");
+        assert_eq!(issues[1].line, Some(2));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_validate_lines_fall_back_to_parent() {
+        let schema = serde_json::json!({
+            "properties": {
+                "server": { "type": "object", "required": ["port"] },
+                "tags": { "items": { "type": "string" } }
+            }
+        });
+        let yaml = "server:\n  host: a\ntags:\n  - a\n  - 1\n";
+        let mut issues = validate(&schema, yaml, TargetFormat::Yaml).unwrap();
+        issues.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let lines: Vec<_> = issues.iter().map(|issue| (issue.path.as_str(), issue.line)).collect();
+        assert_eq!(lines, vec![("server", Some(1)), ("tags", Some(3))]);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_validate_valid_yaml() {
+        let yaml = "server:\n  port: 8080\n";
+        assert!(validate(&schema(), yaml, TargetFormat::Yaml).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_annotate_with_validation_comments() {
+        let yaml = "server:\n  port: 70000\n  host: 1\n";
+        let (result, issues) =
+            annotate_with_validation(&schema(), yaml, TargetFormat::Yaml, AnnotatorConfig::default())
+                .unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_snapshot!(result);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_invalid_comments_describe_expectations() {
+        let schema = serde_json::json!({
+            "properties": {
+                "port": { "type": "integer", "minimum": 1, "maximum": 65535 },
+                "level": { "enum": ["info", "debug"] },
+                "name": { "type": "string", "maxLength": 8, "pattern": "^[a-z]+$" },
+                "tags": { "items": { "type": "string", "minLength": 1 } },
+                "server": { "type": "object", "required": ["host"] }
+            }
+        });
+        let toml = "port = 0\nlevel = \"trace\"\nname = \"Too-long-name\"\ntags = [\"\"]\n[server]\n";
+        let (result, _) =
+            annotate_with_validation(&schema, toml, TargetFormat::Toml, AnnotatorConfig::default())
+                .unwrap();
+        let comments: Vec<&str> = result.lines().filter(|line| line.starts_with('#')).collect();
+        assert_eq!(
+            comments,
+            vec![
+                "# INVALID: expected integer between 1 and 65535",
+                "# INVALID: expected one of \"info\", \"debug\"",
+                "# INVALID: expected string of at most 8 characters, matching `^[a-z]+$`",
+                "# List of strings, each of at least 1 character",
+                "# INVALID: expected string of at least 1 character",
+                "# INVALID: \"host\" is a required property",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pointer_to_path() {
+        assert_eq!(pointer_to_path(""), "");
+        assert_eq!(pointer_to_path("/server/port"), "server.port");
+        assert_eq!(pointer_to_path("/servers/0/name"), "servers.name");
+        assert_eq!(pointer_to_path("/a~1b"), "a/b");
+    }
+}